@@ -8,22 +8,29 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 
 use crate::backend::{create_backend, StopReason, ToolResult};
 use crate::coding;
-use crate::config::Config;
+use crate::config::{CodingConfig, Config};
 use crate::desires::DesireState;
 use crate::feedback;
-use crate::permissions::{check_permission, PermCheck};
-use crate::tools::ToolRegistry;
+use crate::instructions;
+use crate::permissions::{
+    check_permission, grant_rules, Grant, PermCheck, PermDecision, PermRule, PermScope, ToolCategory,
+};
+use crate::safety::{EmergencyStop, SafetyGovernor, Verdict};
+use crate::subagent;
+use crate::tools::cancel::CancelToken;
+use crate::tools::checkpoint::Checkpoints;
+use crate::tools::progress::{Progress, Stream};
+use crate::tools::{fs, tts_engine, ToolRegistry};
 
 /// A pending permission request waiting for user response.
 pub struct PermRequest {
     pub id: String,
-    pub tx: oneshot::Sender<bool>,
+    pub tx: oneshot::Sender<PermDecision>,
 }
 
 const MAX_ITERATIONS: usize = 50;
@@ -34,8 +41,19 @@ const MAX_ITERATIONS: usize = 50;
 pub enum AgentEvent {
     /// Partial text chunk (streaming)
     Text { chunk: String },
-    /// A tool is being called
-    Action { name: String, label: String },
+    /// A tool is being called; `id` ties its progress and result to it
+    Action { id: String, name: String, label: String },
+    /// A line of output from the running tool
+    ToolProgress { id: String, stream: Stream, line: String },
+    /// A tool finished: its result, plus the camera frame (base64 JPEG) or
+    /// the unified diff of a file edit, if any
+    ToolResult {
+        id: String,
+        name: String,
+        text: String,
+        image: Option<String>,
+        diff: Option<String>,
+    },
     /// Permission confirmation needed before executing a tool
     PermRequest { id: String, tool: String, detail: String },
     /// Agent finished (end_turn)
@@ -46,33 +64,174 @@ pub enum AgentEvent {
     Error { message: String },
 }
 
+/// The formatted project context and the project instructions, if any.
+type ProjectPrompt = (String, Option<String>);
+
 pub struct Agent {
     config: Config,
     history: Vec<Value>,
     desires: DesireState,
     /// Cached world-model string, built on first run and persisted across turns.
     world_model: Option<String>,
+    /// Tools live as long as the agent so their state (e.g. the Tuya token)
+    /// carries over between turns.
+    tools: Arc<ToolRegistry>,
+    /// Limits on body actuators; its rate-limit history spans turns.
+    safety: SafetyGovernor,
+    /// Trust mode and remembered answers, shared with sub-agents.
+    perms: Arc<std::sync::Mutex<Permissions>>,
     /// Pending permission requests: id → oneshot sender
-    pub pending_perms: Arc<std::sync::Mutex<std::collections::HashMap<String, oneshot::Sender<bool>>>>,
+    pub pending_perms: Arc<std::sync::Mutex<std::collections::HashMap<String, oneshot::Sender<PermDecision>>>>,
+    /// Set on a sub-agent: its task, tools and step budget (see `subagent`).
+    brief: Option<subagent::Brief>,
+    /// Text of the latest reply; a sub-agent's summary for `delegate`.
+    last_reply: String,
+}
+
+/// The permission state an agent shares with its sub-agents, so a "session"
+/// or "always" answer given to one applies to all of them, and saved rules go
+/// into the one config.
+struct Permissions {
+    /// Trust mode and saved ("always") rules; supersedes `config.coding`'s.
+    coding: CodingConfig,
+    /// "Allow/deny for this session" answers.
+    session: Vec<PermRule>,
+}
+
+impl Permissions {
+    /// Keep `rules` for `scope`; true if they belong in the saved config.
+    /// The trust mode is left as the user set it: in prompt mode the saved
+    /// rules settle prompts without turning on custom mode.
+    fn remember(&mut self, scope: PermScope, rules: Vec<PermRule>) -> bool {
+        match scope {
+            PermScope::Once => false,
+            PermScope::Session => {
+                self.session.extend(rules);
+                false
+            }
+            PermScope::Always => {
+                self.coding.rules.extend(rules);
+                true
+            }
+        }
+    }
+}
+
+/// What a turn's tool calls pass on to the sub-agents they start.
+#[derive(Clone)]
+struct TurnHandles {
+    cancel: CancelToken,
+    estop: EmergencyStop,
+    pending_perms: Arc<std::sync::Mutex<std::collections::HashMap<String, oneshot::Sender<PermDecision>>>>,
+    unattended: bool,
 }
 
 impl Agent {
     pub fn new(config: Config) -> Self {
         Self {
+            tools: Arc::new(ToolRegistry::new(&config)),
+            safety: SafetyGovernor::new(config.safety.clone()),
+            perms: Arc::new(std::sync::Mutex::new(Permissions {
+                coding: config.coding.clone(),
+                session: Vec::new(),
+            })),
             config,
             history: Vec::new(),
             desires: DesireState::default(),
             world_model: None,
             pending_perms: Arc::new(std::sync::Mutex::new(std::collections::HashMap::new())),
+            brief: None,
+            last_reply: String::new(),
+        }
+    }
+
+    /// A sub-agent for `brief`: same config, tools and permissions, its own
+    /// history.
+    fn sub_agent(&self, brief: subagent::Brief) -> Self {
+        Self {
+            config: self.config.clone(),
+            history: Vec::new(),
+            desires: DesireState::default(),
+            world_model: None,
+            tools: self.tools.clone(),
+            safety: SafetyGovernor::new(self.config.safety.clone()),
+            perms: self.perms.clone(),
+            pending_perms: self.pending_perms.clone(),
+            brief: Some(brief),
+            last_reply: String::new(),
         }
     }
 
     /// Respond to a pending permission request.
-    pub fn respond_permission(&self, id: &str, allowed: bool) {
+    pub fn respond_permission(&self, id: &str, decision: PermDecision) {
         let mut lock = self.pending_perms.lock().unwrap();
         if let Some(tx) = lock.remove(id) {
-            let _ = tx.send(allowed);
+            let _ = tx.send(decision);
+        }
+    }
+
+    /// Snapshots of files as they were before each turn changed them.
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.tools.checkpoints
+    }
+
+    /// Remembered permission decisions: this session's grants, then saved rules.
+    pub fn grants(&self) -> Vec<Grant> {
+        let perms = self.perms.lock().unwrap();
+        let session = perms.session.iter().map(|rule| (PermScope::Session, rule));
+        let saved = perms.coding.rules.iter().map(|rule| (PermScope::Always, rule));
+        session
+            .chain(saved)
+            .map(|(scope, rule)| Grant {
+                scope,
+                rule: rule.clone(),
+            })
+            .collect()
+    }
+
+    /// Forget a grant; revoking a saved rule rewrites the config.
+    /// Returns false if there was no such grant.
+    pub fn revoke_grant(&mut self, grant: &Grant) -> Result<bool> {
+        let mut perms = self.perms.lock().unwrap();
+        let rules = match grant.scope {
+            PermScope::Session => &mut perms.session,
+            PermScope::Always => &mut perms.coding.rules,
+            PermScope::Once => return Ok(false),
+        };
+        let Some(i) = rules.iter().position(|r| *r == grant.rule) else {
+            return Ok(false);
+        };
+        rules.remove(i);
+        if grant.scope == PermScope::Always {
+            self.save_rules(&perms)?;
         }
+        Ok(true)
+    }
+
+    /// Save the config with `perms`' trust mode and rules.
+    fn save_rules(&self, perms: &Permissions) -> Result<()> {
+        let mut config = self.config.clone();
+        config.coding = perms.coding.clone();
+        config.save()
+    }
+
+    /// Remember a decision beyond this call, per its scope.
+    fn remember_decision(&self, decision: PermDecision, tool: &str, input: &Value) {
+        let work_dir = self.config.coding.effective_work_dir();
+        let rules = grant_rules(decision.allowed, tool, input, &work_dir);
+        let mut perms = self.perms.lock().unwrap();
+        // Saved while still locked, so concurrent sub-agents can't save an
+        // older copy over this one.
+        if perms.remember(decision.scope, rules) {
+            if let Err(e) = self.save_rules(&perms) {
+                tracing::warn!("Failed to save permission rule for {tool}: {e}");
+            }
+        }
+    }
+
+    /// Shared handle on the tools, e.g. to halt the body from outside a turn.
+    pub fn tools(&self) -> Arc<ToolRegistry> {
+        self.tools.clone()
     }
 
     /// Returns true if any desire is above the action threshold.
@@ -89,48 +248,55 @@ impl Agent {
 
     // ── World model ────────────────────────────────────────────────
 
-    /// Build (or return cached) world model string from config.
+    /// Build the world model string. The hardware line is cached from config;
+    /// known locations and the estimated position change as the body moves.
     ///
     /// Phase 2: this will call memory::recall() for episodic context.
-    fn world_model(&mut self) -> &str {
+    fn world_model(&mut self) -> String {
         if self.world_model.is_none() {
             let camera_status = if self.config.camera.host.is_empty() {
                 "no camera".to_string()
             } else {
                 format!("ONVIF camera @ {}", self.config.camera.host)
             };
-            let robot_status = if self.config.mobility.tuya_device_id.is_empty() {
-                "no robot".to_string()
-            } else {
-                "Tuya robot vacuum (mobility enabled)".to_string()
-            };
-            let tts_status = if self.config.tts.elevenlabs_api_key.is_empty() {
+            let robot_status = self.tools.mobility.hardware_status();
+            let tts_engines = tts_engine::configured_engines(&self.config.tts);
+            let tts_status = if tts_engines.is_empty() {
                 "no voice".to_string()
             } else {
-                "ElevenLabs TTS (voice enabled)".to_string()
+                format!("{} TTS (voice enabled)", tts_engines.join(" + "))
             };
 
             self.world_model = Some(format!(
-                "Hardware: {camera_status} | {robot_status} | {tts_status}\n\
-                 Known locations: (none recalled yet)\n\
-                 Recent interactions: (episodic memory not yet available — Phase 2)"
+                "Hardware: {camera_status} | {robot_status} | {tts_status}"
             ));
         }
-        self.world_model.as_deref().unwrap()
+        format!(
+            "{}\n\
+             Known locations: {}\n\
+             Recent interactions: (episodic memory not yet available — Phase 2)",
+            self.world_model.as_deref().unwrap(),
+            self.tools.mobility.locations_summary()
+        )
     }
 
     // ── System prompt ──────────────────────────────────────────────
 
     /// Build the full system prompt, injecting world model, memory context,
     /// and desire context when relevant.
-    fn system_prompt(&mut self, episodic_context: String, desire_context: Option<String>) -> String {
+    fn system_prompt(
+        &mut self,
+        episodic_context: String,
+        desire_context: Option<String>,
+        project_context: Option<ProjectPrompt>,
+    ) -> String {
         let persona = load_me_md()
             .or_else(|| (!self.config.persona.is_empty()).then(|| self.config.persona.clone()))
             .unwrap_or_else(|| {
                 format!("You are {}, a helpful AI companion.", self.config.agent_name)
             });
 
-        let world_model = self.world_model().to_string();
+        let world_model = self.world_model();
 
         // episodic_context is passed in from run() via tools.memory_recall_for_context()
 
@@ -161,18 +327,14 @@ impl Agent {
         };
 
         // Inject coding context when work_dir is configured
-        let coding_section = {
-            let wd = self.config.coding.effective_work_dir();
-            if wd.is_empty() {
-                String::new()
-            } else {
-                let ctx = coding::scan_project(&wd);
-                format!(
-                    "\n{}\n\n{}\n",
-                    coding::format_context(&ctx),
-                    coding::CODING_WORKFLOW
-                )
+        let coding_section = match project_context {
+            Some((ctx, instructions)) => {
+                let instructions = instructions
+                    .map(|s| format!("\n{s}\n"))
+                    .unwrap_or_default();
+                format!("\n{ctx}\n\n{}\n{instructions}", coding::CODING_WORKFLOW)
             }
+            None => String::new(),
         };
 
         format!(
@@ -186,7 +348,9 @@ impl Agent {
             [Body Parts and What They Do]\n\
             - Eyes (see): This IS your vision. Calling see() means YOU ARE LOOKING.\n\
             - Neck (look): Rotate your gaze left/right/up/down.\n\
-            - Legs (walk): Move the robot vacuum. NOTE: walking does NOT change what the camera sees.\n\
+            - Legs (walk): Move the robot body. NOTE: walking does NOT change what the camera sees.\n\
+            - Body sense (body_status): Battery, charging and errors of your robot body. Low battery = tired legs.\n\
+            - Sense of place (mark_location, go_to): Name where your body is, and walk back to named places later.\n\
             - Voice (say): Your ONLY way to make sound. Text is SILENT — only say() is heard.\n\n\
             [Core Loop]\n\
             1. THINK: What do I need to do?\n\
@@ -208,42 +372,95 @@ impl Agent {
         )
     }
 
+    /// The project context and instructions for work_dir, or `None` when no
+    /// work_dir is set. Scanning walks the tree, so it runs off the runtime.
+    async fn project_context(&self) -> Option<ProjectPrompt> {
+        let wd = self.config.coding.effective_work_dir();
+        if wd.is_empty() {
+            return None;
+        }
+        let scan = tokio::task::spawn_blocking(move || {
+            let ctx = coding::format_context(&coding::scan_project(&wd));
+            (ctx, instructions::load(&wd))
+        });
+        scan.await.ok()
+    }
+
+    /// A sub-agent's system prompt, with the project context when a work_dir is set.
+    fn brief_prompt(
+        &self,
+        brief: &subagent::Brief,
+        project_context: Option<ProjectPrompt>,
+    ) -> String {
+        let context = project_context.map(|(ctx, instructions)| match instructions {
+            Some(instructions) => format!("{ctx}\n\n{instructions}"),
+            None => ctx,
+        });
+        subagent::system_prompt(brief, &self.config.agent_name, context.as_deref())
+    }
+
     // ── Main run loop ──────────────────────────────────────────────
 
     /// Run one user turn. Streams events via the sender.
-    /// `cancel_flag`: set to true externally to abort the loop early.
+    /// `cancel`: tripped externally to abort the turn, including a running tool.
+    /// `estop`: while engaged, body actuators are refused (see `safety`).
+    /// `unattended`: no one is there to answer permission prompts (heartbeat).
     pub async fn run(
         &mut self,
         user_input: String,
         tx: mpsc::Sender<AgentEvent>,
-        cancel_flag: Arc<AtomicBool>,
-        pending_perms: Arc<std::sync::Mutex<std::collections::HashMap<String, oneshot::Sender<bool>>>>,
+        cancel: CancelToken,
+        estop: EmergencyStop,
+        pending_perms: Arc<std::sync::Mutex<std::collections::HashMap<String, oneshot::Sender<PermDecision>>>>,
+        unattended: bool,
     ) -> Result<()> {
         let backend = create_backend(&self.config);
-        let tools = ToolRegistry::new(&self.config);
+        let tools = self.tools.clone();
+        let project_context = self.project_context().await;
+
+        let (system, tool_defs, max_steps, active_desire) = match &self.brief {
+            // A sub-agent has no desires, memories or checkpoints of its own.
+            Some(brief) => {
+                let defs = tools
+                    .tool_defs()
+                    .into_iter()
+                    .filter(|d| brief.tools.contains(&d.name))
+                    .collect();
+                (self.brief_prompt(brief, project_context), defs, brief.max_steps, None)
+            }
+            None => {
+                // Advance desires (time-based decay/growth)
+                self.desires.decay();
+
+                // Check for strong desires → generate intentionality context
+                let desire_context = self.desires.context_string();
 
-        // Advance desires (time-based decay/growth)
-        self.desires.decay();
+                // If a desire is active, note which one so we can partially satisfy it after
+                let active_desire = self.desires.strongest().map(|(name, _)| name);
 
-        // Check for strong desires → generate intentionality context
-        let desire_context = self.desires.context_string();
+                // Recall recent episodic memories to inject into system prompt
+                let episodic_context = tools.memory_recall_for_context(5);
 
-        // If a desire is active, note which one so we can partially satisfy it after
-        let active_desire = self.desires.strongest().map(|(name, _)| name);
+                // Files this turn modifies are checkpointed under a new turn id.
+                tools.checkpoints.begin_turn(&user_input);
 
-        // Recall recent episodic memories to inject into system prompt
-        let episodic_context = tools.memory_recall_for_context(5);
+                let mut defs = tools.tool_defs();
+                defs.push(subagent::tool_def());
+                let system = self.system_prompt(episodic_context, desire_context, project_context);
+                (system, defs, MAX_ITERATIONS, active_desire)
+            }
+        };
 
         // Add user message to history
         let user_msg = backend.make_user_message(&user_input);
         self.history.push(user_msg);
 
-        let system = self.system_prompt(episodic_context, desire_context);
-        let tool_defs = tools.tool_defs();
-
-        for _iteration in 0..MAX_ITERATIONS {
+        for _iteration in 0..max_steps {
             // Check for cancellation before each step
-            if cancel_flag.load(Ordering::Relaxed) {
+            if cancel.is_cancelled() {
+                if estop.is_engaged() {
+                    tools.mobility.halt().await;
+                }
                 let _ = tx.send(AgentEvent::Cancelled).await;
                 return Ok(());
             }
@@ -263,6 +480,9 @@ impl Agent {
                 .await?;
 
             self.history.push(raw_assistant);
+            if !result.text.is_empty() {
+                self.last_reply = result.text.clone();
+            }
 
             if result.stop_reason == StopReason::EndTurn {
                 // Satisfy the active desire now that we responded
@@ -276,16 +496,59 @@ impl Agent {
             // Execute tool calls
             let mut tool_results = Vec::new();
             for tc in &result.tool_calls {
-                let label = format_action_label(&tc.name, &tc.input);
+                // Every tool call still needs a result, even after cancelling.
+                if cancel.is_cancelled() {
+                    tool_results.push(ToolResult {
+                        call_id: tc.id.clone(),
+                        text: format!("(Skipped: {} was cancelled before it started)", tc.name),
+                        image_b64: None,
+                    });
+                    continue;
+                }
+
+                // A sub-agent only has the tools it was given.
+                if !tool_defs.iter().any(|d| d.name == tc.name) && self.brief.is_some() {
+                    tool_results.push(ToolResult {
+                        call_id: tc.id.clone(),
+                        text: format!("Tool '{}' is not available to this sub-agent", tc.name),
+                        image_b64: None,
+                    });
+                    continue;
+                }
+
+                // ── Body safety ─────────────────────────────────────
+                let input = match self.safety.check(&tc.name, &tc.input, &estop) {
+                    Verdict::Allow => tc.input.clone(),
+                    Verdict::Adjust(adjusted) => adjusted,
+                    Verdict::Refuse(reason) => {
+                        tracing::info!("safety refused {}: {reason}", tc.name);
+                        tool_results.push(ToolResult {
+                            call_id: tc.id.clone(),
+                            text: reason,
+                            image_b64: None,
+                        });
+                        continue;
+                    }
+                };
+                let label = format_action_label(&tc.name, &input);
 
                 // ── Permission check ────────────────────────────────
-                let arg = tc.input.to_string();
-                let perm = check_permission(
-                    &self.config.coding.trust_mode,
-                    &self.config.coding.rules,
-                    &tc.name,
-                    &arg,
-                );
+                // Unknown tools get the strictest category.
+                let category = tool_defs
+                    .iter()
+                    .find(|d| d.name == tc.name)
+                    .map_or(ToolCategory::Exec, |d| d.category);
+                let perm = {
+                    let perms = self.perms.lock().unwrap();
+                    check_permission(
+                        &perms.coding,
+                        &perms.session,
+                        unattended,
+                        &tc.name,
+                        category,
+                        &input,
+                    )
+                };
 
                 match perm {
                     PermCheck::Deny => {
@@ -298,7 +561,7 @@ impl Agent {
                     }
                     PermCheck::NeedsPrompt => {
                         let req_id = uuid::Uuid::new_v4().to_string();
-                        let (perm_tx, perm_rx) = oneshot::channel::<bool>();
+                        let (perm_tx, perm_rx) = oneshot::channel::<PermDecision>();
                         {
                             let mut lock = pending_perms.lock().unwrap();
                             lock.insert(req_id.clone(), perm_tx);
@@ -310,12 +573,18 @@ impl Agent {
                         }).await;
 
                         // Wait for user response (or cancellation)
-                        let allowed = tokio::select! {
-                            res = perm_rx => res.unwrap_or(false),
-                            _ = tokio::time::sleep(std::time::Duration::from_secs(120)) => false,
+                        let no = PermDecision {
+                            allowed: false,
+                            scope: PermScope::Once,
+                        };
+                        let decision = tokio::select! {
+                            res = perm_rx => res.unwrap_or(no),
+                            () = cancel.cancelled() => no,
+                            _ = tokio::time::sleep(std::time::Duration::from_secs(120)) => no,
                         };
+                        self.remember_decision(decision, &tc.name, &input);
 
-                        if !allowed {
+                        if !decision.allowed {
                             tool_results.push(ToolResult {
                                 call_id: tc.id.clone(),
                                 text: format!("User denied permission for tool '{}'", tc.name),
@@ -330,6 +599,7 @@ impl Agent {
 
                 let _ = tx
                     .send(AgentEvent::Action {
+                        id: tc.id.clone(),
                         name: tc.name.clone(),
                         label,
                     })
@@ -342,16 +612,63 @@ impl Agent {
                     self.desires.boost("look_outside", 0.1);
                 }
 
-                let (text, image_b64) =
-                    tools.execute(&tc.name, &tc.input).await.unwrap_or_else(|e| {
-                        (format!("Tool error: {e}"), None)
-                    });
+                // Only a call that runs counts against cooldowns and rate limits.
+                self.safety.record(&tc.name, &input);
+
+                let before: Vec<_> = tools
+                    .fs
+                    .edit_targets(&tc.name, &input)
+                    .into_iter()
+                    .map(|(raw, path)| {
+                        let old = std::fs::read_to_string(&path).unwrap_or_default();
+                        (raw, path, old)
+                    })
+                    .collect();
+                let (progress, progress_relay) = relay_progress(&tx, &tc.id);
+                let (text, image_b64) = if tc.name == "delegate" && self.brief.is_none() {
+                    let turn = TurnHandles {
+                        cancel: cancel.clone(),
+                        estop: estop.clone(),
+                        pending_perms: pending_perms.clone(),
+                        unattended,
+                    };
+                    (self.delegate(&input, &tx, turn, &progress).await, None)
+                } else {
+                    tools
+                        .execute(&tc.name, &input, &cancel, &progress)
+                        .await
+                        .unwrap_or_else(|e| (format!("Tool error: {e}"), None))
+                };
+                // Every progress line goes out before the result.
+                drop(progress);
+                progress_relay.await.ok();
+
+                let diffs: Vec<String> = before
+                    .into_iter()
+                    .filter_map(|(raw, path, old)| {
+                        let new = std::fs::read_to_string(&path).unwrap_or_default();
+                        fs::unified_diff(&raw, &old, &new)
+                    })
+                    .collect();
+                let diff = (!diffs.is_empty()).then(|| diffs.concat());
+                let _ = tx
+                    .send(AgentEvent::ToolResult {
+                        id: tc.id.clone(),
+                        name: tc.name.clone(),
+                        text: text.clone(),
+                        image: image_b64.clone(),
+                        diff,
+                    })
+                    .await;
 
                 // Self-feedback: append structured feedback to tool result
                 let feedback_suffix = match tc.name.as_str() {
                     "bash" => feedback::bash_feedback(&text)
                         .map(|fb| format!("\n\n{fb}"))
                         .unwrap_or_default(),
+                    "run_tests" => feedback::tests_feedback(&text)
+                        .map(|fb| format!("\n\n{fb}"))
+                        .unwrap_or_default(),
                     "write_file" => {
                         let path = tc.input["path"].as_str().unwrap_or("");
                         let fb = feedback::write_feedback(path);
@@ -388,6 +705,75 @@ impl Agent {
         let _ = tx.send(AgentEvent::Done).await;
         Ok(())
     }
+
+    /// Runs a `delegate` call's tasks as concurrent sub-agents; their summaries.
+    async fn delegate(
+        &self,
+        input: &Value,
+        tx: &mpsc::Sender<AgentEvent>,
+        turn: TurnHandles,
+        progress: &Progress,
+    ) -> String {
+        let briefs = match subagent::briefs(input) {
+            Ok(briefs) => briefs,
+            Err(e) => return e,
+        };
+        let runs: Vec<_> = briefs
+            .iter()
+            .enumerate()
+            .map(|(i, brief)| {
+                let mut child = self.sub_agent(brief.clone());
+                let task = brief.task.clone();
+                let turn = turn.clone();
+                async move {
+                    let (child_tx, child_rx) = mpsc::channel(64);
+                    // Boxed: this future is part of `run`'s own.
+                    let run = Box::pin(child.run(
+                        task,
+                        child_tx,
+                        turn.cancel,
+                        turn.estop,
+                        turn.pending_perms,
+                        turn.unattended,
+                    ));
+                    let follow = subagent::follow(i + 1, child_rx, tx, progress);
+                    let (result, mut outcome) = tokio::join!(run, follow);
+                    if let Err(e) = result {
+                        outcome.note = Some(e.to_string());
+                    }
+                    outcome.summary = child.last_reply;
+                    outcome
+                }
+            })
+            .collect();
+        let outcomes = futures::future::join_all(runs).await;
+        subagent::render(&briefs, &outcomes)
+    }
+}
+
+/// A `Progress` whose lines become `ToolProgress` events for tool call `id`,
+/// and the task relaying them, which ends once the `Progress` is dropped.
+fn relay_progress(
+    tx: &mpsc::Sender<AgentEvent>,
+    id: &str,
+) -> (Progress, tokio::task::JoinHandle<()>) {
+    let (line_tx, mut line_rx) = mpsc::unbounded_channel();
+    let progress = Progress::new(move |stream, line| {
+        let _ = line_tx.send((stream, line));
+    });
+    let tx = tx.clone();
+    let id = id.to_string();
+    let relay = tokio::spawn(async move {
+        while let Some((stream, line)) = line_rx.recv().await {
+            let event = AgentEvent::ToolProgress {
+                id: id.clone(),
+                stream,
+                line,
+            };
+            let _ = tx.send(event).await;
+        }
+    });
+    (progress, relay)
 }
 
 /// Load persona from ME.md — same lookup order as the Python version:
@@ -414,6 +800,81 @@ mod tests {
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
 
+    use super::*;
+    use crate::permissions::TrustMode;
+
+    #[tokio::test]
+    async fn progress_is_relayed_in_order_and_ends_with_the_tool() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let (progress, relay) = relay_progress(&tx, "call_1");
+        for i in 0..10 {
+            progress.line(Stream::Stdout, &format!("line {i}"));
+        }
+        let reader = tokio::spawn(async move {
+            let mut lines = Vec::new();
+            while let Some(AgentEvent::ToolProgress { id, line, .. }) = rx.recv().await {
+                assert_eq!(id, "call_1");
+                lines.push(line);
+            }
+            lines
+        });
+        drop(progress);
+        relay.await.unwrap();
+        drop(tx);
+        let lines = reader.await.unwrap();
+        let expected: Vec<String> = (0..10).map(|i| format!("line {i}")).collect();
+        assert_eq!(lines, expected);
+    }
+
+    #[test]
+    fn tool_events_serialize_for_the_frontend() {
+        let event = AgentEvent::ToolProgress {
+            id: "c".into(),
+            stream: Stream::Stderr,
+            line: "warn".into(),
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "type": "tool_progress",
+                "id": "c",
+                "stream": "stderr",
+                "line": "warn",
+            })
+        );
+        let event = AgentEvent::ToolResult {
+            id: "c".into(),
+            name: "edit_file".into(),
+            text: "Edited".into(),
+            image: None,
+            diff: Some("--- a/x".into()),
+        };
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["type"], "tool_result");
+        assert_eq!(value["diff"], "--- a/x");
+        assert!(value["image"].is_null());
+    }
+
+    #[test]
+    fn always_grant_keeps_prompt_mode() {
+        let mut perms = Permissions {
+            coding: CodingConfig {
+                work_dir: "/tmp".to_string(),
+                trust_mode: TrustMode::Prompt,
+                ..Default::default()
+            },
+            session: Vec::new(),
+        };
+        let input = serde_json::json!({"command": "cargo test"});
+        let rules = grant_rules(true, "bash", &input, "/tmp");
+        assert!(perms.remember(PermScope::Always, rules));
+        assert_eq!(perms.coding.trust_mode, TrustMode::Prompt);
+        assert!(perms.session.is_empty());
+        let check =
+            check_permission(&perms.coding, &[], false, "bash", ToolCategory::Exec, &input);
+        assert_eq!(check, PermCheck::Allow);
+    }
+
     #[test]
     fn cancel_flag_starts_false() {
         let flag = Arc::new(AtomicBool::new(false));
@@ -474,6 +935,16 @@ fn format_action_label(name: &str, input: &Value) -> String {
             };
             t(key).to_string()
         }
+        "body_status" => t("action_body_status").to_string(),
+        "delegate" => {
+            let tasks = subagent::briefs(input).map_or(0, |b| b.len());
+            format!("🧭 delegate ({tasks} task(s))...")
+        }
+        "mark_location" => t("action_mark_location").to_string(),
+        "go_to" => {
+            let place = input["name"].as_str().unwrap_or("");
+            format!("{} {place}", t("action_go_to"))
+        }
         _ => format!("⚙️ {name}..."),
     }
 }
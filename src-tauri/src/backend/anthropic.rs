@@ -323,6 +323,7 @@ mod tests {
     fn convert_tools_uses_input_schema_key() {
         let tool = ToolDef {
             name: "test".to_string(),
+            category: crate::permissions::ToolCategory::Perception,
             description: "desc".to_string(),
             input_schema: serde_json::json!({"type": "object"}),
         };
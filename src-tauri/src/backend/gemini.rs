@@ -368,6 +368,7 @@ mod tests {
     fn convert_tools_wraps_in_function_declarations() {
         let tool = ToolDef {
             name: "search".to_string(),
+            category: crate::permissions::ToolCategory::Perception,
             description: "Search things".to_string(),
             input_schema: serde_json::json!({"type": "object"}),
         };
@@ -380,6 +381,7 @@ mod tests {
     fn convert_tools_declaration_has_name_and_parameters() {
         let tool = ToolDef {
             name: "my_tool".to_string(),
+            category: crate::permissions::ToolCategory::Perception,
             description: "desc".to_string(),
             input_schema: serde_json::json!({"type": "object"}),
         };
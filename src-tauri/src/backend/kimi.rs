@@ -321,6 +321,7 @@ mod tests {
     fn convert_tools_uses_function_wrapper() {
         let tool = ToolDef {
             name: "search".to_string(),
+            category: crate::permissions::ToolCategory::Perception,
             description: "Search something".to_string(),
             input_schema: serde_json::json!({"type": "object"}),
         };
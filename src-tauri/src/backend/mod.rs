@@ -7,11 +7,15 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use crate::config::Config;
+use crate::permissions::ToolCategory;
 
 /// A single tool definition passed to the LLM.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolDef {
     pub name: String,
+    /// What kind of access the tool needs; trust policies apply per category.
+    /// Not sent to the LLM.
+    pub category: ToolCategory,
     pub description: String,
     pub input_schema: serde_json::Value,
 }
@@ -28,7 +32,6 @@ pub struct ToolCall {
 #[derive(Debug)]
 pub struct TurnResult {
     pub stop_reason: StopReason,
-    #[allow(dead_code)]
     pub text: String,
     pub tool_calls: Vec<ToolCall>,
 }
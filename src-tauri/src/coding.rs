@@ -6,34 +6,58 @@
 
 use std::path::Path;
 
+use crate::repo_map;
+use crate::tools::git;
+
 /// Summary of a project for injection into the system prompt.
 #[derive(Debug, Clone)]
 pub struct ProjectContext {
     pub work_dir: String,
     pub project_type: ProjectType,
+    /// Every project found, root first, each with its own commands
+    pub projects: Vec<Project>,
     /// Key files found (Cargo.toml, package.json, README, etc.)
     pub key_files: Vec<String>,
     /// Brief description parsed from manifest
     pub description: Option<String>,
     /// Detected language(s)
     pub languages: Vec<String>,
+    /// Branch and working-tree state, when work_dir is in a git repository
+    pub git: Option<git::Status>,
+    /// Ranked files and their top-level symbols (see `repo_map`)
+    pub repo_map: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProjectType {
     Rust,
     Node,
+    Bun,
+    Deno,
     Python,
+    Go,
+    Maven,
+    Gradle,
+    CMake,
     Mixed,
     Unknown,
 }
 
 impl ProjectType {
+    /// The usual test command; a `Project` may refine it (wrapper, workspace, uv, ...).
     pub fn test_command(&self) -> Option<&'static str> {
         match self {
             ProjectType::Rust => Some("cargo test"),
             ProjectType::Node => Some("npm test"),
+            ProjectType::Bun => Some("bun test"),
+            ProjectType::Deno => Some("deno test"),
             ProjectType::Python => Some("python -m pytest"),
+            ProjectType::Go => Some("go test ./..."),
+            ProjectType::Maven => Some("mvn test"),
+            ProjectType::Gradle => Some("gradle test"),
+            ProjectType::CMake => {
+                Some("cmake -B build && cmake --build build && ctest --test-dir build")
+            }
             ProjectType::Mixed => None,
             ProjectType::Unknown => None,
         }
@@ -43,35 +67,391 @@ impl ProjectType {
         match self {
             ProjectType::Rust => Some("cargo build"),
             ProjectType::Node => Some("npm run build"),
+            ProjectType::Bun => Some("bun run build"),
+            ProjectType::Deno => None,
             ProjectType::Python => None,
+            ProjectType::Go => Some("go build ./..."),
+            ProjectType::Maven => Some("mvn package -DskipTests"),
+            ProjectType::Gradle => Some("gradle build -x test"),
+            ProjectType::CMake => Some("cmake -B build && cmake --build build"),
             ProjectType::Mixed => None,
             ProjectType::Unknown => None,
         }
     }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProjectType::Rust => "Rust",
+            ProjectType::Node => "Node.js / TypeScript",
+            ProjectType::Bun => "Bun",
+            ProjectType::Deno => "Deno",
+            ProjectType::Python => "Python",
+            ProjectType::Go => "Go",
+            ProjectType::Maven => "Java (Maven)",
+            ProjectType::Gradle => "Java/Kotlin (Gradle)",
+            ProjectType::CMake => "C/C++ (CMake)",
+            ProjectType::Mixed => "Mixed",
+            ProjectType::Unknown => "Unknown",
+        }
+    }
+
+    fn language(&self) -> Option<&'static str> {
+        match self {
+            ProjectType::Rust => Some("Rust"),
+            ProjectType::Node | ProjectType::Bun | ProjectType::Deno => {
+                Some("JavaScript/TypeScript")
+            }
+            ProjectType::Python => Some("Python"),
+            ProjectType::Go => Some("Go"),
+            ProjectType::Maven => Some("Java"),
+            ProjectType::Gradle => Some("Java/Kotlin"),
+            ProjectType::CMake => Some("C/C++"),
+            ProjectType::Mixed | ProjectType::Unknown => None,
+        }
+    }
+
+    /// Kinds whose root command also covers nested projects of a sibling kind
+    /// (an npm workspace can hold Bun packages, a Gradle build Maven modules).
+    fn family(&self) -> &'static str {
+        match self {
+            ProjectType::Node | ProjectType::Bun | ProjectType::Deno => "js",
+            ProjectType::Maven | ProjectType::Gradle => "jvm",
+            other => other.label(),
+        }
+    }
 }
 
-/// Scan a work_dir and build a ProjectContext.
-pub fn scan_project(work_dir: &str) -> ProjectContext {
-    let base = Path::new(work_dir);
+/// One project in work_dir: the root or a sub-directory with its own manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    /// Relative to work_dir, `""` for work_dir itself
+    pub dir: String,
+    pub kind: ProjectType,
+    /// Tooling beyond the kind, e.g. "uv", "pnpm", "workspace of 3 crates"
+    pub tooling: Vec<String>,
+    /// Run in `dir`
+    pub test_command: Option<String>,
+    /// Run in `dir`
+    pub build_command: Option<String>,
+}
 
-    let has_cargo = base.join("Cargo.toml").exists();
-    let has_package_json = base.join("package.json").exists();
-    let has_pyproject = base.join("pyproject.toml").exists() || base.join("setup.py").exists();
+impl Project {
+    fn new(kind: ProjectType) -> Self {
+        Project {
+            dir: String::new(),
+            test_command: kind.test_command().map(str::to_string),
+            build_command: kind.build_command().map(str::to_string),
+            kind,
+            tooling: Vec::new(),
+        }
+    }
+
+    /// `dir/`, or `.` for work_dir itself.
+    pub fn display_dir(&self) -> String {
+        if self.dir.is_empty() {
+            ".".to_string()
+        } else {
+            format!("{}/", self.dir)
+        }
+    }
 
-    let project_type = match (has_cargo, has_package_json, has_pyproject) {
-        (true, false, false) => ProjectType::Rust,
-        (false, true, false) => ProjectType::Node,
-        (false, false, true) => ProjectType::Python,
-        (false, false, false) => ProjectType::Unknown,
+    /// The kind, with its tooling in parentheses.
+    pub fn label(&self) -> String {
+        if self.tooling.is_empty() {
+            self.kind.label().to_string()
+        } else {
+            format!("{} ({})", self.kind.label(), self.tooling.join(", "))
+        }
+    }
+}
+
+/// Directories never searched for sub-projects.
+const SKIP_DIRS: &[&str] = &[
+    "node_modules",
+    "target",
+    "vendor",
+    "dist",
+    "build",
+    "out",
+    "venv",
+    "__pycache__",
+];
+/// work_dir itself and two levels below it.
+const MAX_DEPTH: usize = 2;
+const MAX_PROJECTS: usize = 8;
+
+/// Every project in work_dir, shallowest first. A project nested in another of
+/// the same family is left out: the outer one's commands cover it (workspaces,
+/// multi-module builds).
+pub fn detect_projects(work_dir: &str) -> Vec<Project> {
+    let base = Path::new(work_dir);
+    let mut found: Vec<Project> = Vec::new();
+    for rel in project_dirs(base, MAX_DEPTH) {
+        for project in projects_in(&base.join(&rel), &rel) {
+            let covered = found.iter().any(|outer| {
+                outer.kind.family() == project.kind.family() && is_within(&rel, &outer.dir)
+            });
+            if !covered && found.len() < MAX_PROJECTS {
+                found.push(project);
+            }
+        }
+    }
+    found
+}
+
+/// The kind of project in work_dir: `Mixed` when it holds several.
+fn overall_type(projects: &[Project]) -> ProjectType {
+    match projects {
+        [] => ProjectType::Unknown,
+        [only] => only.kind.clone(),
         _ => ProjectType::Mixed,
+    }
+}
+
+fn is_within(dir: &str, outer: &str) -> bool {
+    outer.is_empty() || dir == outer || dir.starts_with(&format!("{outer}/"))
+}
+
+/// `base` and its sub-directories down to `depth`, as `/`-separated relative
+/// paths sorted shallowest first. Hidden, gitignored and `SKIP_DIRS` are left out.
+fn project_dirs(base: &Path, depth: usize) -> Vec<String> {
+    let mut dirs: Vec<String> = ignore::WalkBuilder::new(base)
+        .max_depth(Some(depth))
+        .require_git(false)
+        .filter_entry(|e| {
+            e.depth() == 0 || !SKIP_DIRS.contains(&e.file_name().to_string_lossy().as_ref())
+        })
+        .build()
+        .flatten()
+        .filter(|e| e.file_type().is_some_and(|t| t.is_dir()))
+        .filter_map(|e| {
+            let rel = e.path().strip_prefix(base).ok()?;
+            Some(rel.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    dirs.sort_by(|a, b| {
+        (a.matches('/').count(), !a.is_empty(), a).cmp(&(b.matches('/').count(), !b.is_empty(), b))
+    });
+    dirs
+}
+
+/// The projects whose manifests sit directly in `dir`.
+fn projects_in(dir: &Path, rel: &str) -> Vec<Project> {
+    let has = |name: &str| dir.join(name).is_file();
+    let mut found = Vec::new();
+    if has("Cargo.toml") {
+        found.push(rust_project(dir));
+    }
+    if has("deno.json") || has("deno.jsonc") {
+        found.push(Project::new(ProjectType::Deno));
+    } else if has("package.json") {
+        found.push(node_project(dir));
+    }
+    if has("pyproject.toml") || has("setup.py") {
+        found.push(python_project(dir));
+    }
+    if has("go.mod") {
+        found.push(Project::new(ProjectType::Go));
+    }
+    if has("pom.xml") {
+        found.push(with_wrapper(dir, ProjectType::Maven, "mvn", "mvnw"));
+    } else if ["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts"]
+        .iter()
+        .any(|name| has(name))
+    {
+        found.push(with_wrapper(dir, ProjectType::Gradle, "gradle", "gradlew"));
+    }
+    if has("CMakeLists.txt") {
+        found.push(Project::new(ProjectType::CMake));
+    }
+    for project in &mut found {
+        project.dir = rel.to_string();
+    }
+    found
+}
+
+fn read_toml(path: &Path) -> toml::Table {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .unwrap_or_default()
+}
+
+fn rust_project(dir: &Path) -> Project {
+    let mut project = Project::new(ProjectType::Rust);
+    let manifest = read_toml(&dir.join("Cargo.toml"));
+    if let Some(workspace) = manifest.get("workspace") {
+        let members: Vec<&str> = workspace
+            .get("members")
+            .and_then(|m| m.as_array())
+            .map(|m| m.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+        let count = count_members(dir, &members, "Cargo.toml");
+        project.tooling.push(format!("workspace of {count} crate(s)"));
+        project.test_command = Some("cargo test --workspace".to_string());
+        project.build_command = Some("cargo build --workspace".to_string());
+    }
+    project
+}
+
+fn node_project(dir: &Path) -> Project {
+    let has = |name: &str| dir.join(name).is_file();
+    let manifest: serde_json::Value = std::fs::read_to_string(dir.join("package.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+
+    let mut project;
+    let manager = if has("bun.lockb") || has("bun.lock") {
+        project = Project::new(ProjectType::Bun);
+        "bun"
+    } else {
+        project = Project::new(ProjectType::Node);
+        let manager = if has("pnpm-lock.yaml") {
+            "pnpm"
+        } else if has("yarn.lock") {
+            "yarn"
+        } else {
+            "npm"
+        };
+        if manager != "npm" {
+            project.tooling.push(manager.to_string());
+            project.test_command = Some(format!("{manager} test"));
+            project.build_command = Some(format!("{manager} run build"));
+        }
+        manager
     };
 
+    // npm/yarn/bun list them in package.json, pnpm in pnpm-workspace.yaml.
+    let listed = match &manifest["workspaces"] {
+        serde_json::Value::Array(list) => list,
+        other => other["packages"].as_array().map(Vec::as_slice).unwrap_or_default(),
+    };
+    let mut workspaces: Vec<String> =
+        listed.iter().filter_map(|v| v.as_str()).map(str::to_string).collect();
+    if manager == "pnpm" && workspaces.is_empty() {
+        workspaces = pnpm_workspace_globs(dir);
+    }
+    if !workspaces.is_empty() {
+        let globs: Vec<&str> = workspaces.iter().map(String::as_str).collect();
+        let count = count_members(dir, &globs, "package.json");
+        project.tooling.push(format!("workspace of {count} package(s)"));
+        // Without a root script, run the members' own.
+        let scripts = &manifest["scripts"];
+        let per_member = |script: &str| match manager {
+            "npm" => Some(format!("npm run {script} --workspaces --if-present")),
+            "pnpm" => Some(format!("pnpm -r --if-present {script}")),
+            _ => None,
+        };
+        if scripts["test"].is_null() {
+            if let Some(command) = per_member("test") {
+                project.test_command = Some(command);
+            }
+        }
+        if scripts["build"].is_null() {
+            if let Some(command) = per_member("build") {
+                project.build_command = Some(command);
+            }
+        }
+    }
+    project
+}
+
+/// The `packages:` list of a `pnpm-workspace.yaml`.
+fn pnpm_workspace_globs(dir: &Path) -> Vec<String> {
+    let Ok(text) = std::fs::read_to_string(dir.join("pnpm-workspace.yaml")) else {
+        return Vec::new();
+    };
+    let mut globs = Vec::new();
+    let mut in_packages = false;
+    for line in text.lines() {
+        if !line.starts_with([' ', '-']) {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+        if let Some(item) = line.trim().strip_prefix('-').filter(|_| in_packages) {
+            globs.push(item.trim().trim_matches(['\'', '"']).to_string());
+        }
+    }
+    globs
+}
+
+fn python_project(dir: &Path) -> Project {
+    let mut project = Project::new(ProjectType::Python);
+    let manifest = read_toml(&dir.join("pyproject.toml"));
+    let tool = manifest.get("tool");
+    let uses = |name: &str, lock: &str| {
+        dir.join(lock).is_file() || tool.is_some_and(|t| t.get(name).is_some())
+    };
+    let runner = if uses("uv", "uv.lock") {
+        Some("uv")
+    } else if uses("poetry", "poetry.lock") {
+        Some("poetry")
+    } else {
+        None
+    };
+    if let Some(runner) = runner {
+        project.tooling.push(runner.to_string());
+        project.test_command = Some(format!("{runner} run pytest"));
+        project.build_command = Some(format!("{runner} build"));
+    }
+    project
+}
+
+/// A Maven or Gradle project, run through its wrapper script when it has one.
+fn with_wrapper(dir: &Path, kind: ProjectType, tool: &str, wrapper: &str) -> Project {
+    let mut project = Project::new(kind);
+    if dir.join(wrapper).is_file() {
+        project.tooling.push(wrapper.to_string());
+        let local = format!("./{wrapper}");
+        project.test_command = project.test_command.map(|c| c.replacen(tool, &local, 1));
+        project.build_command = project.build_command.map(|c| c.replacen(tool, &local, 1));
+    }
+    project
+}
+
+/// How many sub-directories of `dir` match one of the workspace `globs` and
+/// hold a `manifest`.
+fn count_members(dir: &Path, globs: &[&str], manifest: &str) -> usize {
+    let mut set = globset::GlobSetBuilder::new();
+    for glob in globs.iter().filter(|g| !g.starts_with('!')) {
+        let glob = glob.trim_start_matches("./").trim_end_matches('/');
+        if let Ok(glob) = globset::GlobBuilder::new(glob).literal_separator(true).build() {
+            set.add(glob);
+        }
+    }
+    let Ok(set) = set.build() else {
+        return 0;
+    };
+    project_dirs(dir, 3)
+        .iter()
+        .filter(|rel| !rel.is_empty() && set.is_match(rel.as_str()))
+        .filter(|rel| dir.join(rel).join(manifest).is_file())
+        .count()
+}
+
+/// Scan a work_dir and build a ProjectContext.
+pub fn scan_project(work_dir: &str) -> ProjectContext {
+    let base = Path::new(work_dir);
+    let projects = detect_projects(work_dir);
+
     let mut key_files = Vec::new();
     let mut description = None;
-    let mut languages = Vec::new();
+    let mut languages: Vec<String> = Vec::new();
 
     // Collect manifest files
-    for name in &["Cargo.toml", "package.json", "pyproject.toml", "setup.py", "go.mod"] {
+    for name in &[
+        "Cargo.toml",
+        "package.json",
+        "deno.json",
+        "pyproject.toml",
+        "setup.py",
+        "go.mod",
+        "pom.xml",
+        "build.gradle",
+        "build.gradle.kts",
+        "CMakeLists.txt",
+    ] {
         if base.join(name).exists() {
             key_files.push(name.to_string());
         }
@@ -85,27 +465,28 @@ pub fn scan_project(work_dir: &str) -> ProjectContext {
         }
     }
 
-    // Parse description from manifests
-    if has_cargo {
-        if let Ok(text) = std::fs::read_to_string(base.join("Cargo.toml")) {
-            if let Some(desc) = extract_toml_field(&text, "description") {
-                description = Some(desc);
+    // Parse description from the root manifests
+    if let Some(desc) = read_toml(&base.join("Cargo.toml"))
+        .get("package")
+        .and_then(|p| p.get("description"))
+        .and_then(|d| d.as_str())
+    {
+        description = Some(desc.to_string());
+    }
+    if let Ok(text) = std::fs::read_to_string(base.join("package.json")) {
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+            if let Some(desc) = json["description"].as_str() {
+                description = Some(desc.to_string());
             }
         }
-        languages.push("Rust".to_string());
     }
-    if has_package_json {
-        if let Ok(text) = std::fs::read_to_string(base.join("package.json")) {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
-                if let Some(desc) = json["description"].as_str() {
-                    description = Some(desc.to_string());
-                }
+
+    for project in &projects {
+        if let Some(language) = project.kind.language() {
+            if !languages.iter().any(|l| l == language) {
+                languages.push(language.to_string());
             }
         }
-        languages.push("JavaScript/TypeScript".to_string());
-    }
-    if has_pyproject {
-        languages.push("Python".to_string());
     }
 
     // Detect source directories
@@ -115,23 +496,31 @@ pub fn scan_project(work_dir: &str) -> ProjectContext {
         }
     }
 
+    // Only a project gets a map: a work_dir like $HOME would be walked whole
+    // and its file names sent to the model.
+    let repo_map = if !projects.is_empty() || base.join(".git").exists() {
+        repo_map::current(work_dir, repo_map::DEFAULT_TOKENS)
+    } else {
+        None
+    };
+
     ProjectContext {
         work_dir: work_dir.to_string(),
-        project_type,
+        project_type: overall_type(&projects),
+        projects,
         key_files,
         description,
         languages,
+        git: git::status_blocking(work_dir),
+        repo_map,
     }
 }
 
 /// Format the project context as a system-prompt section.
 pub fn format_context(ctx: &ProjectContext) -> String {
-    let proj_type = match &ctx.project_type {
-        ProjectType::Rust => "Rust",
-        ProjectType::Node => "Node.js / TypeScript",
-        ProjectType::Python => "Python",
-        ProjectType::Mixed => "Mixed",
-        ProjectType::Unknown => "Unknown",
+    let proj_type = match ctx.projects.as_slice() {
+        [only] if only.dir.is_empty() => only.label(),
+        _ => ctx.project_type.label().to_string(),
     };
 
     let mut lines = vec![
@@ -152,8 +541,38 @@ pub fn format_context(ctx: &ProjectContext) -> String {
         lines.push(format!("Key files : {}", ctx.key_files.join(", ")));
     }
 
-    if let Some(test_cmd) = ctx.project_type.test_command() {
-        lines.push(format!("Test cmd  : {test_cmd}"));
+    if let Some(status) = &ctx.git {
+        lines.push(format!("Git       : {}", status.summary()));
+    }
+
+    match ctx.projects.as_slice() {
+        [] => {}
+        [only] if only.dir.is_empty() => {
+            if let Some(test_cmd) = &only.test_command {
+                lines.push(format!("Test cmd  : {test_cmd}"));
+            }
+            if let Some(build_cmd) = &only.build_command {
+                lines.push(format!("Build cmd : {build_cmd}"));
+            }
+        }
+        projects => {
+            lines.push("Projects  :".to_string());
+            for project in projects {
+                let mut line = format!("  - {} {}", project.display_dir(), project.label());
+                if let Some(test_cmd) = &project.test_command {
+                    line.push_str(&format!("; test: {test_cmd}"));
+                }
+                if let Some(build_cmd) = &project.build_command {
+                    line.push_str(&format!("; build: {build_cmd}"));
+                }
+                lines.push(line);
+            }
+        }
+    }
+
+    if let Some(map) = &ctx.repo_map {
+        lines.push(String::new());
+        lines.push(map.clone());
     }
 
     lines.join("\n")
@@ -166,17 +585,31 @@ pub const CODING_WORKFLOW: &str = r#"[Coding Workflow — follow this strictly]
 2. PLAN — State your plan in 2-3 sentences before writing any code.
 3. WRITE SMALL — Make the smallest possible change that moves toward the goal.
    Prefer edit_file over write_file to avoid clobbering existing code.
-4. VERIFY — After every write_file or edit_file, read the file back to confirm.
-5. TEST — After any code change, run the project's test command.
+4. VERIFY — After every file change, read the file back to confirm.
+5. TEST — After any code change, run the tests with run_tests.
    Do not declare success until tests pass.
 6. ONE THING AT A TIME — Complete one step fully before moving to the next.
 
 [Tool Usage Rules]
 - read_file   : Always use line ranges for large files (> 200 lines).
 - edit_file   : old_string must be unique. Add surrounding context if needed.
+- apply_patch : Use for changes spanning several places or files; all or nothing.
 - bash        : Prefer short-lived commands. Always check the exit code.
+                Pass session="main" to keep cd / exported variables between calls.
+- bash_background : Start servers and watchers here, then read them with
+                bash_output and stop them with bash_kill when done.
+- git_status / git_diff / git_log : Use these instead of running git in bash.
+- git_commit  : Commit only when asked; write a message that says what changed.
+- run_tests   : Runs the project's test command and lists each failure with
+                file:line. Pass filter to re-run only the failing tests.
+- delegate    : Hand broad explorations ("where and how is X handled?") to
+                sub-agents; only their summaries come back. Tasks in one call
+                run in parallel. Sub-agents don't edit files; make the changes
+                yourself.
 - list_files  : Use to orient yourself at the start of a task.
 - grep        : Use to find definitions and usages before editing.
+                Use output_mode="files_with_matches" first on broad searches,
+                and context=N to see the code around a match.
 
 [What NOT to do]
 - Do NOT write code without reading first.
@@ -184,18 +617,6 @@ pub const CODING_WORKFLOW: &str = r#"[Coding Workflow — follow this strictly]
 - Do NOT declare done without running tests.
 - Do NOT make sweeping changes across many files in one step."#;
 
-fn extract_toml_field(text: &str, field: &str) -> Option<String> {
-    for line in text.lines() {
-        if let Some(rest) = line.trim().strip_prefix(&format!("{field} =")) {
-            let val = rest.trim().trim_matches('"').to_string();
-            if !val.is_empty() {
-                return Some(val);
-            }
-        }
-    }
-    None
-}
-
 // ── Tests ─────────────────────────────────────────────────────────
 
 #[cfg(test)]
@@ -256,6 +677,120 @@ mod tests {
         assert_eq!(ctx.project_type, ProjectType::Mixed);
     }
 
+    #[test]
+    fn detects_sub_projects() {
+        // The Tauri layout: a web app at the root, the Rust backend below it.
+        let dir = make_dir();
+        write(&dir, "package.json", r#"{"name":"app"}"#);
+        write(&dir, "src-tauri/Cargo.toml", "[package]\nname = \"app\"\n");
+        write(&dir, "node_modules/dep/package.json", "{}");
+        let ctx = scan_project(dir.path().to_str().unwrap());
+        assert_eq!(ctx.project_type, ProjectType::Mixed);
+        let dirs: Vec<_> = ctx.projects.iter().map(|p| (p.dir.as_str(), &p.kind)).collect();
+        assert_eq!(dirs, [("", &ProjectType::Node), ("src-tauri", &ProjectType::Rust)]);
+        assert_eq!(ctx.languages, ["JavaScript/TypeScript", "Rust"]);
+        let s = format_context(&ctx);
+        assert!(
+            s.contains(
+                "Projects  :\n\
+                 \x20 - . Node.js / TypeScript; test: npm test; build: npm run build\n\
+                 \x20 - src-tauri/ Rust; test: cargo test; build: cargo build"
+            ),
+            "{s}"
+        );
+    }
+
+    #[test]
+    fn workspace_members_are_covered_by_the_root() {
+        let dir = make_dir();
+        write(&dir, "Cargo.toml", "[workspace]\nmembers = [\"crates/*\", \"cli\"]\n");
+        write(&dir, "crates/core/Cargo.toml", "[package]\n");
+        write(&dir, "crates/net/Cargo.toml", "[package]\n");
+        write(&dir, "cli/Cargo.toml", "[package]\n");
+        let projects = detect_projects(dir.path().to_str().unwrap());
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].tooling, ["workspace of 3 crate(s)"]);
+        assert_eq!(projects[0].test_command.as_deref(), Some("cargo test --workspace"));
+
+        let dir = make_dir();
+        write(&dir, "package.json", r#"{"workspaces":["packages/*"]}"#);
+        write(&dir, "packages/ui/package.json", "{}");
+        write(&dir, "packages/bun-tool/package.json", "{}");
+        write(&dir, "packages/bun-tool/bun.lock", "");
+        let projects = detect_projects(dir.path().to_str().unwrap());
+        assert_eq!(projects.len(), 1);
+        assert_eq!(
+            projects[0].test_command.as_deref(),
+            Some("npm run test --workspaces --if-present")
+        );
+        assert_eq!(projects[0].tooling, ["workspace of 2 package(s)"]);
+
+        let dir = make_dir();
+        write(&dir, "package.json", r#"{"scripts":{"test":"turbo test"}}"#);
+        write(&dir, "pnpm-lock.yaml", "");
+        write(&dir, "pnpm-workspace.yaml", "packages:\n  - 'apps/*'\n");
+        write(&dir, "apps/web/package.json", "{}");
+        let projects = detect_projects(dir.path().to_str().unwrap());
+        assert_eq!(projects[0].tooling, ["pnpm", "workspace of 1 package(s)"]);
+        assert_eq!(projects[0].test_command.as_deref(), Some("pnpm test"));
+        assert_eq!(projects[0].build_command.as_deref(), Some("pnpm -r --if-present build"));
+    }
+
+    #[test]
+    fn detects_each_ecosystem() {
+        let detect = |files: &[(&str, &str)]| {
+            let dir = make_dir();
+            for (name, content) in files {
+                write(&dir, name, content);
+            }
+            let mut projects = detect_projects(dir.path().to_str().unwrap());
+            assert_eq!(projects.len(), 1, "{projects:?}");
+            let p = projects.remove(0);
+            (p.label(), p.test_command, p.build_command)
+        };
+        let some = |s: &str| Some(s.to_string());
+
+        let (label, test, build) = detect(&[("go.mod", "module x\n")]);
+        assert_eq!(label, "Go");
+        assert_eq!((test, build), (some("go test ./..."), some("go build ./...")));
+
+        let (label, test, _) = detect(&[("pom.xml", "<project/>"), ("mvnw", "")]);
+        assert_eq!((label.as_str(), test), ("Java (Maven) (mvnw)", some("./mvnw test")));
+
+        let (label, test, build) = detect(&[("build.gradle.kts", "")]);
+        assert_eq!(label, "Java/Kotlin (Gradle)");
+        assert_eq!((test, build), (some("gradle test"), some("gradle build -x test")));
+
+        let (label, test, _) = detect(&[("CMakeLists.txt", "project(x)\n")]);
+        assert_eq!(label, "C/C++ (CMake)");
+        assert!(test.unwrap().ends_with("ctest --test-dir build"));
+
+        let (label, test, build) = detect(&[("deno.json", "{}"), ("package.json", "{}")]);
+        assert_eq!((label.as_str(), test, build), ("Deno", some("deno test"), None));
+
+        let (label, test, _) = detect(&[("package.json", "{}"), ("bun.lockb", "")]);
+        assert_eq!((label.as_str(), test), ("Bun", some("bun test")));
+
+        let (label, test, _) = detect(&[("package.json", "{}"), ("yarn.lock", "")]);
+        assert_eq!((label.as_str(), test), ("Node.js / TypeScript (yarn)", some("yarn test")));
+
+        let (label, test, build) = detect(&[("pyproject.toml", "[project]\n"), ("uv.lock", "")]);
+        assert_eq!(label, "Python (uv)");
+        assert_eq!((test, build), (some("uv run pytest"), some("uv build")));
+
+        let (label, test, _) = detect(&[("pyproject.toml", "[tool.poetry]\nname = \"x\"\n")]);
+        assert_eq!((label.as_str(), test), ("Python (poetry)", some("poetry run pytest")));
+    }
+
+    #[test]
+    fn format_context_shows_tooling_and_build_for_a_single_project() {
+        let dir = make_dir();
+        write(&dir, "pyproject.toml", "[tool.uv]\n");
+        let s = format_context(&scan_project(dir.path().to_str().unwrap()));
+        assert!(s.contains("Type      : Python (uv)\n"), "{s}");
+        assert!(s.contains("Test cmd  : uv run pytest\nBuild cmd : uv build"), "{s}");
+    }
+
     #[test]
     fn unknown_for_empty_dir() {
         let dir = make_dir();
@@ -324,6 +859,41 @@ mod tests {
         assert!(s.contains("my crate"));
     }
 
+    #[test]
+    fn format_context_includes_git_state() {
+        let dir = make_dir();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap()
+        };
+        let ctx = scan_project(dir.path().to_str().unwrap());
+        assert!(ctx.git.is_none());
+        assert!(!format_context(&ctx).contains("Git"));
+
+        git(&["init", "-q", "-b", "trunk"]);
+        write(&dir, "notes.txt", "todo\n");
+        let ctx = scan_project(dir.path().to_str().unwrap());
+        let s = format_context(&ctx);
+        assert!(s.contains("Git       : trunk, 1 untracked"), "{s}");
+    }
+
+    #[test]
+    fn format_context_includes_repo_map() {
+        let dir = make_dir();
+        write(&dir, "src/main.rs", "fn main() {}\n");
+        // Not a project yet: no manifest, no .git.
+        let s = format_context(&scan_project(dir.path().to_str().unwrap()));
+        assert!(!s.contains("[Repo Map]"), "{s}");
+
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        let s = format_context(&scan_project(dir.path().to_str().unwrap()));
+        assert!(s.contains("\n\n[Repo Map] 1 file(s)"), "{s}");
+        assert!(s.contains("src/main.rs (13 B, entry): fn main"), "{s}");
+    }
+
     // ── CODING_WORKFLOW content ───────────────────────────────────
 
     #[test]
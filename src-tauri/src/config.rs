@@ -1,15 +1,21 @@
 use anyhow::Result;
 use dirs::config_dir;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-use crate::permissions::{PermRule, TrustMode};
+use crate::permissions::{CategoryPolicy, PermRule, TrustMode};
+use crate::tools::sandbox::SandboxMode;
 
-fn config_path() -> PathBuf {
+/// Where config.toml lives, e.g. `~/.config/familiar-ai`.
+pub fn app_config_dir() -> PathBuf {
     config_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("familiar-ai")
-        .join("config.toml")
+}
+
+fn config_path() -> PathBuf {
+    app_config_dir().join("config.toml")
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -30,6 +36,58 @@ pub struct TtsConfig {
     pub elevenlabs_api_key: String,
     #[serde(default = "default_voice_id")]
     pub voice_id: String,
+    /// Default engine: "elevenlabs" | "openai" | "voicevox" | "piper".
+    /// Empty = "elevenlabs".
+    #[serde(default)]
+    pub engine: String,
+    #[serde(default)]
+    pub openai_api_key: String,
+    /// OpenAI-compatible base URL. Empty = https://api.openai.com/v1
+    #[serde(default)]
+    pub openai_base_url: String,
+    /// Empty = gpt-4o-mini-tts
+    #[serde(default)]
+    pub openai_model: String,
+    /// VOICEVOX engine URL. Empty = http://127.0.0.1:50021
+    #[serde(default)]
+    pub voicevox_url: String,
+    /// Piper executable. Empty = "piper" on PATH.
+    #[serde(default)]
+    pub piper_path: String,
+    /// Default Piper voice model (.onnx).
+    #[serde(default)]
+    pub piper_model: String,
+    /// Per-language voice overrides keyed by language code ("ja", "en", ...).
+    #[serde(default)]
+    pub languages: HashMap<String, TtsVoiceConfig>,
+}
+
+impl TtsConfig {
+    pub fn effective_engine(&self) -> &str {
+        if self.engine.is_empty() {
+            "elevenlabs"
+        } else {
+            &self.engine
+        }
+    }
+}
+
+/// Engine + voice + style for one language.
+///
+/// `voice`/`style` meaning depends on the engine:
+///   elevenlabs: voice_id / style exaggeration (0.0–1.0)
+///   openai:     voice name / delivery instructions
+///   voicevox:   style id or speaker name / style name
+///   piper:      model path (.onnx) / speaker id
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct TtsVoiceConfig {
+    /// Empty = the default `tts.engine`.
+    #[serde(default)]
+    pub engine: String,
+    #[serde(default)]
+    pub voice: String,
+    #[serde(default)]
+    pub style: String,
 }
 
 fn default_voice_id() -> String {
@@ -38,10 +96,161 @@ fn default_voice_id() -> String {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MobilityConfig {
+    /// Robot body: "tuya" | "http" | "sim". Empty = tuya.
+    #[serde(default)]
+    pub robot: String,
     pub tuya_region: String,
     pub tuya_api_key: String,
     pub tuya_api_secret: String,
     pub tuya_device_id: String,
+    /// LAN address of the robot (port 6668 unless given). Together with
+    /// `tuya_local_key` this sends movement directly, skipping the cloud.
+    #[serde(default)]
+    pub tuya_local_ip: String,
+    /// 16-character device local key (from the Tuya IoT platform / tinytuya wizard).
+    #[serde(default)]
+    pub tuya_local_key: String,
+    /// Local protocol version: "3.3" | "3.4" | "3.5". Empty = 3.3.
+    #[serde(default)]
+    pub tuya_protocol_version: String,
+    /// Data point id of `direction_control`. Empty = "4".
+    #[serde(default)]
+    pub tuya_direction_dp: String,
+    /// Base URL of an HTTP/JSON robot (`robot = "http"`).
+    #[serde(default)]
+    pub http_url: String,
+    /// Bearer token for the HTTP robot, if it wants one.
+    #[serde(default)]
+    pub http_token: String,
+    /// ASCII floor plan for the simulator (`robot = "sim"`). Empty = built-in room.
+    #[serde(default)]
+    pub sim_map: String,
+    /// Forward speed used for dead reckoning, m/s. 0 = 0.3.
+    #[serde(default)]
+    pub speed: f64,
+    /// Turn-in-place rate used for dead reckoning, degrees/s. 0 = 90.
+    #[serde(default)]
+    pub turn_rate: f64,
+}
+
+impl MobilityConfig {
+    pub fn effective_robot(&self) -> &str {
+        if self.robot.is_empty() {
+            "tuya"
+        } else {
+            &self.robot
+        }
+    }
+
+    pub fn effective_speed(&self) -> f64 {
+        if self.speed > 0.0 {
+            self.speed
+        } else {
+            crate::tools::navigation::NOMINAL_SPEED
+        }
+    }
+
+    pub fn effective_turn_rate(&self) -> f64 {
+        if self.turn_rate > 0.0 {
+            self.turn_rate
+        } else {
+            crate::tools::navigation::NOMINAL_TURN_RATE
+        }
+    }
+}
+
+/// Limits on what the model may do with the body (see `safety`).
+/// 0 / empty means the default.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SafetyConfig {
+    /// Longest single `walk`, seconds. 0 = 10. Untimed walks are capped to it too.
+    #[serde(default)]
+    pub max_walk_secs: f64,
+    /// Largest single `look` turn, degrees. 0 = 45.
+    #[serde(default)]
+    pub max_look_degrees: u32,
+    /// `walk` / `go_to` commands per minute. 0 = 12.
+    #[serde(default)]
+    pub walk_per_minute: u32,
+    /// `look` commands per minute. 0 = 12.
+    #[serde(default)]
+    pub look_per_minute: u32,
+    /// `say` utterances per minute. 0 = 10.
+    #[serde(default)]
+    pub say_per_minute: u32,
+    /// Minimum gap between two commands to the same actuator, seconds. 0 = 1.
+    #[serde(default)]
+    pub cooldown_secs: f64,
+    /// Local time range when `say` is muted, e.g. "22:00-07:00". Empty = never.
+    #[serde(default)]
+    pub quiet_hours: String,
+}
+
+impl SafetyConfig {
+    pub fn effective_max_walk_secs(&self) -> f64 {
+        if self.max_walk_secs > 0.0 {
+            self.max_walk_secs
+        } else {
+            10.0
+        }
+    }
+
+    pub fn effective_max_look_degrees(&self) -> u32 {
+        if self.max_look_degrees > 0 {
+            self.max_look_degrees
+        } else {
+            45
+        }
+    }
+
+    /// Per-minute limit for an actuator ("walk" | "look" | "say").
+    pub fn effective_per_minute(&self, actuator: &str) -> u32 {
+        let (set, default) = match actuator {
+            "walk" => (self.walk_per_minute, 12),
+            "look" => (self.look_per_minute, 12),
+            _ => (self.say_per_minute, 10),
+        };
+        if set > 0 {
+            set
+        } else {
+            default
+        }
+    }
+
+    pub fn effective_cooldown_secs(&self) -> f64 {
+        if self.cooldown_secs > 0.0 {
+            self.cooldown_secs
+        } else {
+            1.0
+        }
+    }
+}
+
+/// OS-level confinement for `bash` (see `tools::sandbox`). 0 / empty means the default.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SandboxConfig {
+    /// "off" | "on" | "offline". Rules can override it per command.
+    #[serde(default)]
+    pub mode: SandboxMode,
+    /// CPU time per command, seconds. 0 = 300.
+    #[serde(default)]
+    pub max_cpu_secs: u64,
+    /// Address-space limit per process, MB. 0 = none.
+    #[serde(default)]
+    pub max_memory_mb: u64,
+    /// Extra environment variables passed through, e.g. "CARGO_HOME".
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+impl SandboxConfig {
+    pub fn effective_max_cpu_secs(&self) -> u64 {
+        if self.max_cpu_secs > 0 {
+            self.max_cpu_secs
+        } else {
+            300
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -49,12 +258,29 @@ pub struct CodingConfig {
     /// Working directory for file/shell tools. Defaults to home dir.
     #[serde(default)]
     pub work_dir: String,
+    /// Extra directories the file tools may touch besides work_dir.
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    /// Extra paths the file tools must never write, on top of the config
+    /// dir, ~/.familiar_ai and .git directories.
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
     /// Permission mode: "prompt" | "full" | "custom"
     #[serde(default)]
     pub trust_mode: TrustMode,
     /// Custom allow/deny rules (used when trust_mode = "custom").
     #[serde(default)]
     pub rules: Vec<PermRule>,
+    /// Per-category overrides: perception/actuation/memory/fs-read default to
+    /// allow, fs-write/exec/network to prompt.
+    #[serde(default)]
+    pub policy: CategoryPolicy,
+    /// Category policy for unattended (heartbeat) turns; never more lenient
+    /// than `policy`, and anything that would prompt is denied.
+    #[serde(default)]
+    pub unattended: CategoryPolicy,
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
 }
 
 impl CodingConfig {
@@ -93,6 +319,8 @@ pub struct Config {
     pub mobility: MobilityConfig,
     #[serde(default)]
     pub coding: CodingConfig,
+    #[serde(default)]
+    pub safety: SafetyConfig,
 }
 
 fn default_platform() -> String {
@@ -112,6 +340,7 @@ impl Default for Config {
             tts: TtsConfig::default(),
             mobility: MobilityConfig::default(),
             coding: CodingConfig::default(),
+            safety: SafetyConfig::default(),
         }
     }
 }
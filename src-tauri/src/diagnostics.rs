@@ -0,0 +1,727 @@
+/// Structured failures parsed from test and build output.
+///
+/// Recognises `cargo build`/`cargo test` (human and `--message-format=json`),
+/// pytest, jest, vitest and tsc. Every parser runs over the whole output and
+/// adds what it finds, so a command that both compiles and tests reports both.
+/// The self-feedback step and `run_tests` use this to point the agent at exact
+/// locations instead of a wall of log.
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Failures listed in feedback; the rest are counted.
+const MAX_LISTED: usize = 15;
+/// Failure messages are cut to this many characters.
+const MAX_MESSAGE_CHARS: usize = 300;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Failure {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub test: Option<String>,
+    pub message: String,
+}
+
+impl Failure {
+    fn location(&self) -> Option<String> {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => Some(format!("{file}:{line}")),
+            (Some(file), None) => Some(file.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub failures: Vec<Failure>,
+    /// Totals from test runners' summary lines, when one was seen.
+    pub passed: Option<usize>,
+    pub failed: Option<usize>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty() && self.passed.is_none() && self.failed.is_none()
+    }
+
+    /// "12 passed, 1 failed", when a test summary was seen.
+    pub fn summary(&self) -> Option<String> {
+        match (self.passed, self.failed) {
+            (None, None) => None,
+            (passed, failed) => Some(format!(
+                "{} passed, {} failed",
+                passed.unwrap_or(0),
+                failed.unwrap_or(0)
+            )),
+        }
+    }
+
+    /// One failure per line: "- src/lib.rs:10 tests::adds: message".
+    pub fn describe(&self) -> String {
+        let mut lines: Vec<String> = self
+            .failures
+            .iter()
+            .take(MAX_LISTED)
+            .map(|f| {
+                let mut line = String::from("-");
+                for part in [f.location(), f.test.clone()].into_iter().flatten() {
+                    line.push(' ');
+                    line.push_str(&part);
+                }
+                format!("{line}: {}", f.message)
+            })
+            .collect();
+        if self.failures.len() > MAX_LISTED {
+            lines.push(format!("- … and {} more", self.failures.len() - MAX_LISTED));
+        }
+        lines.join("\n")
+    }
+
+    fn push(&mut self, failure: Failure) {
+        let message = cut(failure.message.trim());
+        let failure = Failure { message, ..failure };
+        if !self.failures.contains(&failure) {
+            self.failures.push(failure);
+        }
+    }
+
+    fn count(&mut self, passed: Option<usize>, failed: Option<usize>) {
+        if let Some(n) = passed {
+            self.passed = Some(self.passed.unwrap_or(0) + n);
+        }
+        if let Some(n) = failed {
+            self.failed = Some(self.failed.unwrap_or(0) + n);
+        }
+    }
+}
+
+/// Everything recognisable in a command's combined output.
+pub fn parse(output: &str) -> Report {
+    let clean = ansi().replace_all(output, "");
+    let lines: Vec<&str> = clean.lines().collect();
+    let mut report = Report::default();
+    cargo_json(&lines, &mut report);
+    rustc(&lines, &mut report);
+    cargo_test(&lines, &mut report);
+    pytest(&lines, &mut report);
+    jest(&lines, &mut report);
+    vitest(&lines, &mut report);
+    tsc(&lines, &mut report);
+    report
+}
+
+fn re(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid diagnostics regex"))
+}
+
+fn ansi() -> &'static Regex {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    re(&ANSI, r"\x1b\[[0-9;]*[A-Za-z]")
+}
+
+fn cut(message: &str) -> String {
+    match message.char_indices().nth(MAX_MESSAGE_CHARS) {
+        Some((end, _)) => format!("{}…", &message[..end]),
+        None => message.to_string(),
+    }
+}
+
+/// `(\d+) word` anywhere in `line`.
+fn number_before(line: &str, word: &str) -> Option<usize> {
+    let mut last = None;
+    for (i, _) in line.match_indices(word) {
+        let digits: String = line[..i]
+            .trim_end()
+            .chars()
+            .rev()
+            .take_while(char::is_ascii_digit)
+            .collect();
+        if !digits.is_empty() {
+            last = digits.chars().rev().collect::<String>().parse().ok();
+        }
+    }
+    last
+}
+
+// ── Rust ──────────────────────────────────────────────────────────
+
+/// `cargo build --message-format=json`: compiler errors.
+fn cargo_json(lines: &[&str], report: &mut Report) {
+    for line in lines
+        .iter()
+        .filter(|l| l.starts_with("{\"reason\":\"compiler-message\""))
+    {
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let msg = &msg["message"];
+        if msg["level"] != "error" {
+            continue;
+        }
+        let span = msg["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|s| s["is_primary"] == true));
+        let code = msg["code"]["code"].as_str();
+        let text = msg["message"].as_str().unwrap_or("");
+        report.push(Failure {
+            file: span
+                .and_then(|s| s["file_name"].as_str())
+                .map(str::to_string),
+            line: span
+                .and_then(|s| s["line_start"].as_u64())
+                .map(|n| n as u32),
+            test: None,
+            message: match code {
+                Some(code) => format!("[{code}] {text}"),
+                None => text.to_string(),
+            },
+        });
+    }
+}
+
+/// Human-readable rustc errors: `error[E0425]: …` then ` --> file:line:col`.
+fn rustc(lines: &[&str], report: &mut Report) {
+    static HEADER: OnceLock<Regex> = OnceLock::new();
+    static ARROW: OnceLock<Regex> = OnceLock::new();
+    let header = re(&HEADER, r"^error(?:\[(E\d+)\])?: (.+)$");
+    let arrow = re(&ARROW, r"^\s*--> (.+?):(\d+):\d+$");
+    for (i, line) in lines.iter().enumerate() {
+        let Some(c) = header.captures(line) else {
+            continue;
+        };
+        let text = &c[2];
+        if [
+            "could not compile",
+            "aborting due to",
+            "test failed",
+            "build failed",
+        ]
+        .iter()
+        .any(|skip| text.starts_with(skip))
+        {
+            continue;
+        }
+        let at = lines[i + 1..]
+            .iter()
+            .take(3)
+            .find_map(|l| arrow.captures(l));
+        report.push(Failure {
+            file: at.as_ref().map(|a| a[1].to_string()),
+            line: at.as_ref().and_then(|a| a[2].parse().ok()),
+            test: None,
+            message: match c.get(1) {
+                Some(code) => format!("[{}] {text}", code.as_str()),
+                None => text.to_string(),
+            },
+        });
+    }
+}
+
+/// `cargo test`: panics with their location, tests that failed without one,
+/// and the `test result:` totals of every test binary.
+fn cargo_test(lines: &[&str], report: &mut Report) {
+    static PANIC: OnceLock<Regex> = OnceLock::new();
+    static OLD_PANIC: OnceLock<Regex> = OnceLock::new();
+    // Rust 1.73+: location, then the message on the following lines.
+    let panic = re(
+        &PANIC,
+        r"^thread '([^']+)'(?: \(\d+\))? panicked at (.+?):(\d+):\d+:$",
+    );
+    // Before: the message in quotes, then the location.
+    let old_panic = re(
+        &OLD_PANIC,
+        r"^thread '([^']+)'(?: \(\d+\))? panicked at '(.*?)'?, (.+?):(\d+):\d+$",
+    );
+    let mut panicked = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(c) = panic.captures(line) {
+            let message: Vec<&str> = lines[i + 1..]
+                .iter()
+                .map(|l| l.trim())
+                .take_while(|l| {
+                    !l.is_empty() && !l.starts_with("note:") && !l.starts_with("stack backtrace")
+                })
+                .take(4)
+                .collect();
+            panicked.push(c[1].to_string());
+            report.push(Failure {
+                file: Some(c[2].to_string()),
+                line: c[3].parse().ok(),
+                test: Some(c[1].to_string()),
+                message: message.join(" "),
+            });
+        } else if let Some(c) = old_panic.captures(line) {
+            panicked.push(c[1].to_string());
+            report.push(Failure {
+                file: Some(c[3].to_string()),
+                line: c[4].parse().ok(),
+                test: Some(c[1].to_string()),
+                message: c[2].to_string(),
+            });
+        } else if let Some(rest) = line.strip_prefix("test result: ") {
+            report.count(
+                number_before(rest, " passed"),
+                number_before(rest, " failed"),
+            );
+        }
+    }
+    // The closing `failures:` list names every failed test.
+    let mut in_list = false;
+    for line in lines {
+        if *line == "failures:" {
+            in_list = true;
+            continue;
+        }
+        if !in_list {
+            continue;
+        }
+        match line.strip_prefix("    ") {
+            Some(name) if !name.contains(' ') && !name.is_empty() => {
+                if !panicked.iter().any(|p| p == name) {
+                    panicked.push(name.to_string());
+                    report.push(Failure {
+                        test: Some(name.to_string()),
+                        message: "failed (no panic message; see its output)".to_string(),
+                        ..Default::default()
+                    });
+                }
+            }
+            _ if line.trim().is_empty() => {}
+            _ => in_list = false,
+        }
+    }
+}
+
+// ── Python ────────────────────────────────────────────────────────
+
+/// A pytest traceback section.
+struct Section {
+    /// As in the `____ name ____` header: "test_add", "TestApi.test_get".
+    name: String,
+    /// The last `path:line:` in the traceback.
+    place: Option<(String, u32)>,
+    /// The first `E` line.
+    error: Option<String>,
+}
+
+/// pytest: `FAILED path::test - message` summary lines, with the line number
+/// from the test's traceback section (`path:12: AssertionError`).
+fn pytest(lines: &[&str], report: &mut Report) {
+    static SECTION: OnceLock<Regex> = OnceLock::new();
+    static WHERE: OnceLock<Regex> = OnceLock::new();
+    static SHORT: OnceLock<Regex> = OnceLock::new();
+    static TOTALS: OnceLock<Regex> = OnceLock::new();
+    let section = re(&SECTION, r"^_{3,} (.+?) _{3,}$");
+    let at = re(&WHERE, r"^([^\s:]+\.py):(\d+): (\w+)");
+    let short = re(&SHORT, r"^(FAILED|ERROR) (\S+?)(?: - (.*))?$");
+    let totals = re(&TOTALS, r"^=+ .*\bin [\d.]+s.* =+$");
+
+    let mut sections: Vec<Section> = Vec::new();
+    for line in lines {
+        if let Some(c) = section.captures(line) {
+            sections.push(Section {
+                name: c[1].to_string(),
+                place: None,
+                error: None,
+            });
+        } else if let Some(current) = sections.last_mut() {
+            if let Some(c) = at.captures(line) {
+                current.place = c[2].parse().ok().map(|n| (c[1].to_string(), n));
+            } else if let Some(e) = line.strip_prefix("E ") {
+                current.error.get_or_insert_with(|| e.trim().to_string());
+            }
+        }
+    }
+
+    let mut listed = false;
+    for line in lines {
+        if let Some(c) = short.captures(line) {
+            listed = true;
+            let id = &c[2];
+            let file = id.split("::").next().unwrap_or(id).to_string();
+            let dotted = id.replace("::", ".");
+            let found = sections
+                .iter()
+                .find(|s| dotted.ends_with(&s.name) || id.ends_with(&s.name));
+            let message = c
+                .get(3)
+                .map(|m| m.as_str().to_string())
+                .or_else(|| found.and_then(|s| s.error.clone()))
+                .unwrap_or_else(|| c[1].to_lowercase());
+            let line = found
+                .and_then(|s| s.place.as_ref())
+                .filter(|(f, _)| *f == file)
+                .map(|(_, n)| *n);
+            report.push(Failure {
+                file: Some(file),
+                line,
+                test: id.contains("::").then(|| id.to_string()),
+                message,
+            });
+        } else if totals.is_match(line) && !line.contains("warnings summary") {
+            let failed = number_before(line, " failed").or(number_before(line, " error"));
+            report.count(number_before(line, " passed"), failed);
+        }
+    }
+    // Without the short summary (`-rN`), fall back to the sections.
+    if !listed {
+        for s in sections {
+            let Some((file, line)) = s.place else {
+                continue;
+            };
+            report.push(Failure {
+                file: Some(file),
+                line: Some(line),
+                test: Some(s.name),
+                message: s.error.unwrap_or_else(|| "failed".to_string()),
+            });
+        }
+    }
+}
+
+// ── JavaScript / TypeScript ───────────────────────────────────────
+
+/// A stack frame outside node_modules: `at fn (file:line:col)` or `at file:line:col`.
+fn frame(line: &str) -> Option<(String, u32)> {
+    static FRAME: OnceLock<Regex> = OnceLock::new();
+    let frame = re(&FRAME, r"^\s*at (?:.*? \()?([^\s()]+?):(\d+):\d+\)?$");
+    let c = frame.captures(line)?;
+    if c[1].contains("node_modules") {
+        return None;
+    }
+    Some((c[1].to_string(), c[2].parse().ok()?))
+}
+
+/// jest: `● Suite › test` blocks, the message after it and its first own frame.
+fn jest(lines: &[&str], report: &mut Report) {
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        if let Some(rest) = line.strip_prefix("Tests:") {
+            report.count(
+                number_before(rest, " passed"),
+                number_before(rest, " failed"),
+            );
+        }
+        let Some(name) = line.strip_prefix("● ") else {
+            i += 1;
+            continue;
+        };
+        let end = lines[i + 1..]
+            .iter()
+            .position(|l| {
+                let t = l.trim();
+                t.starts_with("● ")
+                    || t.starts_with("Tests:")
+                    || t.starts_with("FAIL ")
+                    || t.starts_with("PASS ")
+            })
+            .map_or(lines.len(), |n| i + 1 + n);
+        let block = &lines[i + 1..end];
+        let message = block
+            .iter()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty())
+            .unwrap_or("failed");
+        let place = block.iter().find_map(|l| frame(l));
+        report.push(Failure {
+            file: place.as_ref().map(|p| p.0.clone()),
+            line: place.map(|p| p.1),
+            test: Some(name.to_string()),
+            message: message.to_string(),
+        });
+        i = end;
+    }
+}
+
+/// vitest: ` FAIL  file > suite > test`, the error line, then ` ❯ file:line:col`.
+fn vitest(lines: &[&str], report: &mut Report) {
+    static FAIL: OnceLock<Regex> = OnceLock::new();
+    static ARROW: OnceLock<Regex> = OnceLock::new();
+    let fail = re(&FAIL, r"^\s*FAIL\s+(\S+) > (.+)$");
+    let arrow = re(&ARROW, r"^\s*❯ (.+?):(\d+):\d+");
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("Tests ") {
+            report.count(
+                number_before(line, " passed"),
+                number_before(line, " failed"),
+            );
+            continue;
+        }
+        let Some(c) = fail.captures(line) else {
+            continue;
+        };
+        let rest = &lines[i + 1..];
+        let block_end = rest
+            .iter()
+            .position(|l| fail.is_match(l))
+            .unwrap_or(rest.len());
+        let block = &rest[..block_end];
+        let message = block
+            .iter()
+            .map(|l| l.trim())
+            .find(|l| !l.is_empty())
+            .unwrap_or("failed");
+        let place = block.iter().find_map(|l| arrow.captures(l));
+        report.push(Failure {
+            file: Some(
+                place
+                    .as_ref()
+                    .map_or(c[1].to_string(), |p| p[1].to_string()),
+            ),
+            line: place.as_ref().and_then(|p| p[2].parse().ok()),
+            test: Some(c[2].to_string()),
+            message: message.to_string(),
+        });
+    }
+}
+
+/// tsc: `file(line,col): error TS1234: …` or, with `--pretty`, `file:line:col - error TS1234: …`.
+fn tsc(lines: &[&str], report: &mut Report) {
+    static PLAIN: OnceLock<Regex> = OnceLock::new();
+    static PRETTY: OnceLock<Regex> = OnceLock::new();
+    let plain = re(&PLAIN, r"^(.+?)\((\d+),\d+\): error (TS\d+): (.+)$");
+    let pretty = re(&PRETTY, r"^(.+?):(\d+):\d+ - error (TS\d+): (.+)$");
+    for line in lines {
+        if let Some(c) = plain.captures(line).or_else(|| pretty.captures(line)) {
+            report.push(Failure {
+                file: Some(c[1].to_string()),
+                line: c[2].parse().ok(),
+                test: None,
+                message: format!("[{}] {}", &c[3], &c[4]),
+            });
+        }
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(file: &str, line: u32, test: Option<&str>, message: &str) -> Failure {
+        Failure {
+            file: Some(file.to_string()),
+            line: Some(line),
+            test: test.map(str::to_string),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn cargo_test_panics_and_totals() {
+        let out = "running 3 tests
+test tests::ok ... ok
+test tests::adds ... FAILED
+test tests::errs ... FAILED
+
+failures:
+
+---- tests::adds stdout ----
+
+thread 'tests::adds' (24584) panicked at src/lib.rs:10:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+---- tests::errs stdout ----
+Error: \"boom\"
+
+failures:
+    tests::adds
+    tests::errs
+
+test result: FAILED. 1 passed; 2 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+";
+        let report = parse(out);
+        assert_eq!(
+            report.failures[0],
+            failure(
+                "src/lib.rs",
+                10,
+                Some("tests::adds"),
+                "assertion `left == right` failed left: 1 right: 2"
+            )
+        );
+        assert_eq!(report.failures[1].test.as_deref(), Some("tests::errs"));
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.summary().as_deref(), Some("1 passed, 2 failed"));
+    }
+
+    #[test]
+    fn old_style_panics() {
+        let out = "thread 'it_works' panicked at 'assertion failed: x', tests/it.rs:4:5\n";
+        assert_eq!(
+            parse(out).failures,
+            vec![failure(
+                "tests/it.rs",
+                4,
+                Some("it_works"),
+                "assertion failed: x"
+            )]
+        );
+    }
+
+    #[test]
+    fn rustc_errors_human_and_json() {
+        let human = "   Compiling demo v0.1.0\n\
+            error[E0425]: cannot find value `foo` in this scope\n \
+             --> src/main.rs:2:13\n  |\n\
+            error: could not compile `demo` (bin \"demo\") due to 1 previous error\n";
+        assert_eq!(
+            parse(human).failures,
+            vec![failure(
+                "src/main.rs",
+                2,
+                None,
+                "[E0425] cannot find value `foo` in this scope"
+            )]
+        );
+
+        let json = r#"{"reason":"compiler-artifact","target":{}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused","spans":[],"code":null}}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","code":{"code":"E0308"},"spans":[{"file_name":"src/a.rs","line_start":7,"is_primary":true}]}}
+{"reason":"build-finished","success":false}"#;
+        assert_eq!(
+            parse(json).failures,
+            vec![failure("src/a.rs", 7, None, "[E0308] mismatched types")]
+        );
+    }
+
+    #[test]
+    fn pytest_failures_with_lines() {
+        let out = "\
+=================================== FAILURES ===================================
+___________________________________ test_add ___________________________________
+
+    def test_add():
+>       assert add(1, 1) == 3
+E       assert 2 == 3
+
+tests/test_math.py:5: AssertionError
+_____________________________ TestApi.test_get _____________________________
+
+tests/test_api.py:20: in test_get
+    raise KeyError('x')
+E   KeyError: 'x'
+tests/test_api.py:20: KeyError
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_add - assert 2 == 3
+FAILED tests/test_api.py::TestApi::test_get - KeyError: 'x'
+ERROR tests/test_db.py - ModuleNotFoundError: No module named 'psycopg'
+==================== 2 failed, 5 passed, 1 error in 0.12s =====================
+";
+        let report = parse(out);
+        assert_eq!(
+            report.failures,
+            vec![
+                failure(
+                    "tests/test_math.py",
+                    5,
+                    Some("tests/test_math.py::test_add"),
+                    "assert 2 == 3"
+                ),
+                failure(
+                    "tests/test_api.py",
+                    20,
+                    Some("tests/test_api.py::TestApi::test_get"),
+                    "KeyError: 'x'"
+                ),
+                Failure {
+                    file: Some("tests/test_db.py".to_string()),
+                    line: None,
+                    test: None,
+                    message: "ModuleNotFoundError: No module named 'psycopg'".to_string(),
+                },
+            ]
+        );
+        assert_eq!(report.summary().as_deref(), Some("5 passed, 2 failed"));
+    }
+
+    #[test]
+    fn jest_failures() {
+        let out = "\
+ FAIL  src/sum.test.js
+  ● math › adds
+
+    expect(received).toBe(expected) // Object.is equality
+
+    Expected: 3
+    Received: 2
+
+      at Object.toBe (node_modules/expect/build/index.js:1:1)
+      at Object.toBe (src/sum.test.js:4:21)
+
+Tests:       1 failed, 1 passed, 2 total
+";
+        let report = parse(out);
+        assert_eq!(
+            report.failures,
+            vec![failure(
+                "src/sum.test.js",
+                4,
+                Some("math › adds"),
+                "expect(received).toBe(expected) // Object.is equality"
+            )]
+        );
+        assert_eq!(report.summary().as_deref(), Some("1 passed, 1 failed"));
+    }
+
+    #[test]
+    fn vitest_failures() {
+        let out = "\
+ \x1b[31mFAIL\x1b[39m  src/sum.test.ts > math > adds
+AssertionError: expected 2 to be 3 // Object.is equality
+
+ ❯ src/sum.test.ts:4:23
+
+ Test Files  1 failed (1)
+      Tests  1 failed | 1 passed (2)
+";
+        let report = parse(out);
+        assert_eq!(
+            report.failures,
+            vec![failure(
+                "src/sum.test.ts",
+                4,
+                Some("math > adds"),
+                "AssertionError: expected 2 to be 3 // Object.is equality"
+            )]
+        );
+        assert_eq!(report.summary().as_deref(), Some("1 passed, 1 failed"));
+    }
+
+    #[test]
+    fn tsc_errors_plain_and_pretty() {
+        let out =
+            "src/a.ts(10,5): error TS2322: Type 'string' is not assignable to type 'number'.\n\
+                   src/b.tsx:3:1 - error TS2304: Cannot find name 'foo'.\n";
+        assert_eq!(
+            parse(out).failures,
+            vec![
+                failure(
+                    "src/a.ts",
+                    10,
+                    None,
+                    "[TS2322] Type 'string' is not assignable to type 'number'."
+                ),
+                failure("src/b.tsx", 3, None, "[TS2304] Cannot find name 'foo'."),
+            ]
+        );
+    }
+
+    #[test]
+    fn describe_lists_locations() {
+        let mut report = Report::default();
+        for i in 0..MAX_LISTED + 2 {
+            report.push(failure("src/a.rs", i as u32, Some("t"), "boom"));
+        }
+        let text = report.describe();
+        assert!(text.starts_with("- src/a.rs:0 t: boom\n"));
+        assert!(text.ends_with("- … and 2 more"));
+        assert!(parse("all good\n").is_empty());
+    }
+}
@@ -8,6 +8,7 @@
 ///   - ReAct (Reason+Act) loop (Yao et al., 2022)
 ///   - Reflexion (Shinn et al., 2023)
 ///   - Self-RAG (Asai et al., 2023)
+use crate::{coding, diagnostics};
 
 /// Analyse a bash tool result and decide whether the agent needs to reflect.
 ///
@@ -26,6 +27,21 @@ pub fn bash_feedback(output: &str) -> Option<String> {
         return None;
     }
 
+    // Compiler errors and test failures: point at exact locations.
+    let report = diagnostics::parse(output);
+    if !report.failures.is_empty() {
+        let totals = report
+            .summary()
+            .map(|s| format!(" ({s})"))
+            .unwrap_or_default();
+        return Some(format!(
+            "[Self-Feedback] The command exited with code {exit_code}{totals}.\n\
+             Failures:\n{}\n\
+             Open each location, fix the root cause, and retry.",
+            report.describe()
+        ));
+    }
+
     // Extract the most relevant error lines (prefer stderr)
     let error_section = if let Some(start) = output.find("--- stderr ---\n") {
         &output[start + "--- stderr ---\n".len()..]
@@ -57,17 +73,35 @@ pub fn write_feedback(path: &str) -> String {
 
 /// Generate a reminder to run tests after writing code.
 pub fn test_reminder(work_dir: &str) -> Option<String> {
-    // Check if there's a test command available
-    let has_cargo = std::path::Path::new(work_dir).join("Cargo.toml").exists();
-    let has_package_json = std::path::Path::new(work_dir).join("package.json").exists();
-
-    if has_cargo {
-        Some("[Self-Feedback] Run `cargo test` to verify correctness.".to_string())
-    } else if has_package_json {
-        Some("[Self-Feedback] Run tests (e.g. `npm test`) to verify correctness.".to_string())
-    } else {
-        None
+    let commands: Vec<String> = coding::detect_projects(work_dir)
+        .iter()
+        .filter_map(|p| {
+            let command = p.test_command.as_ref()?;
+            Some(if p.dir.is_empty() {
+                format!("`{command}`")
+            } else {
+                format!("`{command}` in {}", p.display_dir())
+            })
+        })
+        .collect();
+    if commands.is_empty() {
+        return None;
     }
+    Some(format!(
+        "[Self-Feedback] Run the tests with `run_tests` ({}) to verify correctness.",
+        commands.join("; ")
+    ))
+}
+
+/// After `run_tests`: nothing when they passed, else a nudge to fix the
+/// failures it listed.
+pub fn tests_feedback(output: &str) -> Option<String> {
+    bash_feedback(output)?;
+    Some(
+        "[Self-Feedback] Tests failed. Fix the failures listed above, then run \
+         `run_tests` again (use `filter` to re-run just those tests)."
+            .to_string(),
+    )
 }
 
 // ── Tests ─────────────────────────────────────────────────────────
@@ -119,6 +153,27 @@ mod tests {
         assert!(fb.is_none());
     }
 
+    #[test]
+    fn bash_feedback_points_at_failure_locations() {
+        let output = "Exit: 101\n--- stdout ---\n\
+            thread 'tests::adds' panicked at src/lib.rs:10:9:\nassertion failed: ok\n\n\
+            test result: FAILED. 3 passed; 1 failed; 0 ignored\n";
+        let fb = bash_feedback(output).unwrap();
+        assert!(fb.contains("code 101 (3 passed, 1 failed)"), "{fb}");
+        assert!(
+            fb.contains("- src/lib.rs:10 tests::adds: assertion failed: ok"),
+            "{fb}"
+        );
+    }
+
+    #[test]
+    fn tests_feedback_only_on_failure() {
+        assert!(tests_feedback("Exit: 0\n13 passed").is_none());
+        assert!(tests_feedback("Exit: 1\nFAILED")
+            .unwrap()
+            .contains("run_tests"));
+    }
+
     #[test]
     fn write_feedback_includes_path() {
         let fb = write_feedback("src/main.rs");
@@ -142,6 +197,19 @@ mod tests {
         assert!(reminder.contains("npm test"));
     }
 
+    #[test]
+    fn test_reminder_names_each_sub_project() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+        std::fs::create_dir(dir.path().join("server")).unwrap();
+        std::fs::write(dir.path().join("server/go.mod"), "module x\n").unwrap();
+        let reminder = test_reminder(dir.path().to_str().unwrap()).unwrap();
+        assert!(
+            reminder.contains("(`npm test`; `go test ./...` in server/)"),
+            "{reminder}"
+        );
+    }
+
     #[test]
     fn test_reminder_none_for_unknown_project() {
         let dir = tempfile::TempDir::new().unwrap();
@@ -161,6 +161,27 @@ fn lookup(key: &'static str, lang: Lang) -> &'static str {
         ("action_walk_stop", Lang::De) => "🛑 Hält an...",
         ("action_walk_stop", _) => "🛑 Stopping...",
 
+        ("action_body_status", Lang::Ja) => "🔋 体の調子を確認中...",
+        ("action_body_status", Lang::Zh) => "🔋 检查身体状态...",
+        ("action_body_status", Lang::ZhTw) => "🔋 檢查身體狀態...",
+        ("action_body_status", Lang::Fr) => "🔋 Vérifie son corps...",
+        ("action_body_status", Lang::De) => "🔋 Prüft den Körper...",
+        ("action_body_status", _) => "🔋 Checking body...",
+
+        ("action_mark_location", Lang::Ja) => "📍 場所を覚えています...",
+        ("action_mark_location", Lang::Zh) => "📍 记住这个地方...",
+        ("action_mark_location", Lang::ZhTw) => "📍 記住這個地方...",
+        ("action_mark_location", Lang::Fr) => "📍 Mémorise cet endroit...",
+        ("action_mark_location", Lang::De) => "📍 Merkt sich den Ort...",
+        ("action_mark_location", _) => "📍 Remembering this spot...",
+
+        ("action_go_to", Lang::Ja) => "🧭 移動中:",
+        ("action_go_to", Lang::Zh) => "🧭 前往:",
+        ("action_go_to", Lang::ZhTw) => "🧭 前往:",
+        ("action_go_to", Lang::Fr) => "🧭 Se rend à",
+        ("action_go_to", Lang::De) => "🧭 Geht zu",
+        ("action_go_to", _) => "🧭 Heading to",
+
         // ── Intensity adverbs ──────────────────────────────────────────────
         ("intensity_slightly", Lang::Ja) => "少し",
         ("intensity_slightly", Lang::Zh) => "有点",
@@ -320,6 +341,7 @@ mod tests {
             "action_look_up", "action_look_down", "action_look_around",
             "action_walk_forward", "action_walk_backward",
             "action_walk_left", "action_walk_right", "action_walk_stop",
+            "action_body_status", "action_mark_location", "action_go_to",
             "intensity_slightly", "intensity_moderately", "intensity_strongly",
             "desire_observe_room_why", "desire_observe_room_action",
             "desire_look_outside_why", "desire_look_outside_action",
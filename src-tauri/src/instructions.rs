@@ -0,0 +1,303 @@
+/// Project instruction files for the coding system prompt.
+///
+/// Projects state their conventions in `AGENTS.md`, `FAMILIAR.md` or
+/// `CLAUDE.md`. These are collected from `~/.familiar_ai/` (the user's own,
+/// for every project) and then from the repository root down to work_dir, so
+/// the most specific file comes last and wins where they disagree. Each file
+/// is capped; when the whole section is too large, the most general files are
+/// left out first.
+///
+/// The prompt is rebuilt every turn. Files are stat'ed each time and re-read
+/// only when their mtime or size changed, so edits show up on the next turn.
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Looked for in every directory, in this order.
+pub const FILE_NAMES: &[&str] = &["AGENTS.md", "FAMILIAR.md", "CLAUDE.md"];
+/// Longer files are cut.
+const MAX_FILE_BYTES: usize = 8_000;
+/// The whole section, before files are left out.
+const MAX_TOTAL_BYTES: usize = 24_000;
+
+/// One instruction file.
+#[derive(Debug, Clone, PartialEq)]
+struct Source {
+    /// `~/.familiar_ai/AGENTS.md`, or relative to the repository root
+    label: String,
+    text: String,
+}
+
+// ── Cache ─────────────────────────────────────────────────────────
+
+struct Cached {
+    mtime: Option<SystemTime>,
+    size: u64,
+    text: String,
+}
+
+fn cache() -> &'static Mutex<HashMap<PathBuf, Cached>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, Cached>>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+/// The file's text, from the cache unless it changed since it was read.
+fn read(path: &Path) -> Option<String> {
+    let mut cache = cache().lock().unwrap();
+    let Some(meta) = path.metadata().ok().filter(|m| m.is_file()) else {
+        cache.remove(path);
+        return None;
+    };
+    let (mtime, size) = (meta.modified().ok(), meta.len());
+    if let Some(hit) = cache
+        .get(path)
+        .filter(|c| c.mtime == mtime && c.size == size)
+    {
+        return Some(hit.text.clone());
+    }
+    let text = std::fs::read_to_string(path).ok()?;
+    cache.insert(
+        path.to_path_buf(),
+        Cached {
+            mtime,
+            size,
+            text: text.clone(),
+        },
+    );
+    Some(text)
+}
+
+// ── Discovery ─────────────────────────────────────────────────────
+
+/// The directories to search, most general first: the repository root (the
+/// nearest ancestor with `.git`) down to `work_dir`, or `work_dir` alone
+/// outside a repository.
+fn project_dirs(work_dir: &Path) -> Vec<PathBuf> {
+    let work_dir = work_dir
+        .canonicalize()
+        .unwrap_or_else(|_| work_dir.to_path_buf());
+    let root = work_dir
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(&work_dir)
+        .to_path_buf();
+    let mut dirs: Vec<PathBuf> = work_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&root))
+        .map(Path::to_path_buf)
+        .collect();
+    dirs.reverse();
+    dirs
+}
+
+/// Every non-empty instruction file for `work_dir`, most general first.
+/// Symlinked and identical copies (a `CLAUDE.md` mirroring `AGENTS.md`)
+/// appear once.
+fn sources(global: Option<&Path>, work_dir: &Path) -> Vec<Source> {
+    let dirs = project_dirs(work_dir);
+    let root = dirs.first().cloned();
+    let mut candidates: Vec<(PathBuf, String)> = Vec::new();
+    if let Some(global) = global {
+        for name in FILE_NAMES {
+            candidates.push((global.join(name), format!("~/.familiar_ai/{name}")));
+        }
+    }
+    for dir in &dirs {
+        let rel = root
+            .as_deref()
+            .and_then(|root| dir.strip_prefix(root).ok())
+            .unwrap_or(Path::new(""));
+        for name in FILE_NAMES {
+            let label = rel.join(name).to_string_lossy().replace('\\', "/");
+            candidates.push((dir.join(name), label));
+        }
+    }
+
+    let mut seen_paths = HashSet::new();
+    let mut seen_texts = HashSet::new();
+    let mut found = Vec::new();
+    for (path, label) in candidates {
+        let Some(text) = read(&path) else {
+            continue;
+        };
+        let text = text.trim().to_string();
+        let real = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if text.is_empty() || !seen_paths.insert(real) || !seen_texts.insert(text.clone()) {
+            continue;
+        }
+        found.push(Source { label, text });
+    }
+    found
+}
+
+// ── Merging ───────────────────────────────────────────────────────
+
+/// `text` cut to `MAX_FILE_BYTES` at a line break, with a note of what was cut.
+fn cap(text: &str) -> String {
+    if text.len() <= MAX_FILE_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_FILE_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').unwrap_or(end);
+    format!(
+        "{}\n[… truncated, {} more bytes]",
+        text[..end].trim_end(),
+        text.len() - end
+    )
+}
+
+/// The prompt section for `sources`, or `None` when there are none.
+fn render(sources: &[Source]) -> Option<String> {
+    let mut blocks: Vec<(String, String)> = sources
+        .iter()
+        .map(|s| {
+            (
+                s.label.clone(),
+                format!("── {} ──\n{}", s.label, cap(&s.text)),
+            )
+        })
+        .collect();
+    let mut omitted = Vec::new();
+    while blocks.len() > 1 && blocks.iter().map(|(_, b)| b.len()).sum::<usize>() > MAX_TOTAL_BYTES {
+        omitted.push(blocks.remove(0).0);
+    }
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let mut lines = vec![
+        "[Project Instructions]".to_string(),
+        "Conventions from the user and this project. Follow them; where they disagree, later \
+         (more specific) files win, and all of them win over the workflow defaults above."
+            .to_string(),
+    ];
+    if !omitted.is_empty() {
+        lines.push(format!("(Left out for size: {})", omitted.join(", ")));
+    }
+    for (_, block) in blocks {
+        lines.push(String::new());
+        lines.push(block);
+    }
+    Some(lines.join("\n"))
+}
+
+/// The instructions section for `work_dir`, or `None` when no instruction
+/// file applies.
+pub fn load(work_dir: &str) -> Option<String> {
+    let global = dirs::home_dir().map(|home| home.join(".familiar_ai"));
+    render(&sources(global.as_deref(), Path::new(work_dir)))
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, text: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, text).unwrap();
+    }
+
+    fn labels(global: Option<&Path>, work_dir: &Path) -> Vec<String> {
+        sources(global, work_dir)
+            .into_iter()
+            .map(|s| s.label)
+            .collect()
+    }
+
+    #[test]
+    fn collects_from_the_repo_root_down_to_work_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = tmp.path();
+        let repo = outside.join("repo");
+        let global = outside.join("home/.familiar_ai");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        write(&outside.join("AGENTS.md"), "not part of the repo");
+        write(&global.join("FAMILIAR.md"), "Answer briefly.");
+        write(&repo.join("AGENTS.md"), "Use tabs.");
+        write(&repo.join("app/CLAUDE.md"), "App rules.");
+        write(&repo.join("app/api/AGENTS.md"), "API rules.");
+        write(&repo.join("app/api/FAMILIAR.md"), "  \n");
+
+        assert_eq!(
+            labels(Some(&global), &repo.join("app/api")),
+            [
+                "~/.familiar_ai/FAMILIAR.md",
+                "AGENTS.md",
+                "app/CLAUDE.md",
+                "app/api/AGENTS.md"
+            ]
+        );
+        // Outside a repository only work_dir itself is searched.
+        assert_eq!(labels(None, &outside.join("home")), Vec::<String>::new());
+        assert_eq!(labels(None, outside), ["AGENTS.md"]);
+    }
+
+    #[test]
+    fn identical_copies_appear_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        write(&tmp.path().join("AGENTS.md"), "Run cargo fmt.\n");
+        write(&tmp.path().join("CLAUDE.md"), "Run cargo fmt.");
+        assert_eq!(labels(None, tmp.path()), ["AGENTS.md"]);
+    }
+
+    #[test]
+    fn renders_general_first_and_caps_sizes() {
+        let source = |label: &str, text: String| Source {
+            label: label.to_string(),
+            text,
+        };
+        let s = render(&[
+            source("~/.familiar_ai/AGENTS.md", "Be kind.".into()),
+            source("AGENTS.md", "Use tabs.".into()),
+        ])
+        .unwrap();
+        assert!(s.starts_with("[Project Instructions]\n"), "{s}");
+        assert!(
+            s.ends_with("── ~/.familiar_ai/AGENTS.md ──\nBe kind.\n\n── AGENTS.md ──\nUse tabs."),
+            "{s}"
+        );
+
+        let long = "rule\n".repeat(3_000);
+        let s = render(&[source("AGENTS.md", long)]).unwrap();
+        assert!(s.len() < MAX_FILE_BYTES + 300, "{}", s.len());
+        assert!(s.ends_with(&format!("[… truncated, {} more bytes]", 15_000 - 7_999)));
+
+        // Four files of 7 KB are too many; the most general one goes.
+        let medium = "rule\n".repeat(1_400);
+        let s = render(&[
+            source("~/.familiar_ai/AGENTS.md", medium.clone()),
+            source("AGENTS.md", medium.clone()),
+            source("a/AGENTS.md", medium.clone()),
+            source("a/b/AGENTS.md", medium),
+        ])
+        .unwrap();
+        assert!(
+            s.contains("(Left out for size: ~/.familiar_ai/AGENTS.md)"),
+            "{s}"
+        );
+        assert!(s.contains("── a/b/AGENTS.md ──"));
+        assert!(render(&[]).is_none());
+    }
+
+    #[test]
+    fn picks_up_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("AGENTS.md");
+        let dir = tmp.path().to_str().unwrap();
+        assert!(load(dir).is_none_or(|s| !s.contains("── AGENTS.md ──")));
+        write(&file, "Use tabs.");
+        assert!(load(dir).unwrap().contains("── AGENTS.md ──\nUse tabs."));
+        write(&file, "Use four spaces.");
+        assert!(load(dir)
+            .unwrap()
+            .contains("── AGENTS.md ──\nUse four spaces."));
+        std::fs::remove_file(&file).unwrap();
+        assert!(load(dir).is_none_or(|s| !s.contains("── AGENTS.md ──")));
+    }
+}
@@ -3,26 +3,40 @@ mod backend;
 mod coding;
 mod config;
 mod desires;
+mod diagnostics;
 mod feedback;
 mod i18n;
+mod instructions;
 mod permissions;
+mod repo_map;
+mod safety;
+mod subagent;
 mod tools;
+mod workspace;
 
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use agent::{Agent, AgentEvent};
 use config::Config;
+use permissions::{Grant, PermDecision, PermScope};
+use safety::EmergencyStop;
 use tauri::{AppHandle, Emitter, State};
 use tokio::sync::mpsc;
+use tools::cancel::CancelToken;
+use tools::checkpoint::Checkpoint;
+use tools::ToolRegistry;
 
 /// Shared app state — Arc so the heartbeat thread can hold a reference too.
 struct AppState {
     agent: Arc<Mutex<Option<Agent>>>,
-    /// Set to true to abort the current agent run.
-    cancel_flag: Arc<AtomicBool>,
+    /// The agent's tools, reachable while a turn has the agent checked out.
+    tools: Mutex<Option<Arc<ToolRegistry>>>,
+    /// Cancelled to abort the current agent run, including any running tool.
+    cancel: CancelToken,
+    /// Latched by `emergency_stop`; body actuators are refused until released.
+    estop: EmergencyStop,
     /// Pending permission requests shared across agent turns.
-    pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<bool>>>>,
+    pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<PermDecision>>>>,
 }
 
 // ── Tauri commands ────────────────────────────────────────────────
@@ -38,6 +52,7 @@ fn get_config() -> Result<Config, String> {
 fn save_config(config: Config, state: State<AppState>) -> Result<(), String> {
     config.save().map_err(|e| e.to_string())?;
     let agent = Agent::new(config);
+    *state.tools.lock().unwrap() = Some(agent.tools());
     *state.agent.lock().unwrap() = Some(agent);
     Ok(())
 }
@@ -48,21 +63,111 @@ fn is_configured(state: State<AppState>) -> bool {
     state.agent.lock().unwrap().is_some()
 }
 
-/// Abort the currently running agent turn.
+/// Abort the currently running agent turn and whatever tool it is running.
 #[tauri::command]
 fn cancel_message(state: State<AppState>) {
-    state.cancel_flag.store(true, Ordering::Relaxed);
+    state.cancel.cancel();
 }
 
-/// Respond to a pending permission request (allow/deny).
+/// Emergency stop: abort the turn, halt the robot, and refuse every body
+/// actuator until `release_emergency_stop`. Fails if the robot didn't stop.
 #[tauri::command]
-fn respond_permission(id: String, allowed: bool, state: State<AppState>) {
+async fn emergency_stop(state: State<'_, AppState>) -> Result<(), String> {
+    state.estop.engage();
+    state.cancel.cancel();
+    // A running turn halts on its way out, but the model may still be
+    // thinking — stop the hardware right now too, through the same robot.
+    let tools = state.tools.lock().unwrap().clone();
+    if let Some(tools) = tools {
+        if let Err(e) = tools.mobility.try_halt().await {
+            tracing::warn!("Emergency stop could not stop the robot: {e}");
+            return Err(format!("Could not stop the robot: {e}"));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn release_emergency_stop(state: State<AppState>) {
+    state.estop.release();
+}
+
+#[tauri::command]
+fn emergency_stop_engaged(state: State<AppState>) -> bool {
+    state.estop.is_engaged()
+}
+
+/// Respond to a pending permission request (allow/deny), optionally
+/// remembering the answer for the session or saving it as a rule.
+#[tauri::command]
+fn respond_permission(
+    id: String,
+    allowed: bool,
+    scope: Option<PermScope>,
+    state: State<AppState>,
+) {
     let mut lock = state.pending_perms.lock().unwrap();
     if let Some(tx) = lock.remove(&id) {
-        let _ = tx.send(allowed);
+        let _ = tx.send(PermDecision {
+            allowed,
+            scope: scope.unwrap_or_default(),
+        });
     }
 }
 
+/// Session grants and saved permission rules.
+#[tauri::command]
+fn list_permission_grants(state: State<AppState>) -> Result<Vec<Grant>, String> {
+    let lock = state.agent.lock().unwrap();
+    let agent = lock.as_ref().ok_or("Agent is busy or not initialized")?;
+    Ok(agent.grants())
+}
+
+#[tauri::command]
+fn revoke_permission_grant(grant: Grant, state: State<AppState>) -> Result<bool, String> {
+    let mut lock = state.agent.lock().unwrap();
+    let agent = lock.as_mut().ok_or("Agent is busy or not initialized")?;
+    agent.revoke_grant(&grant).map_err(|e| e.to_string())
+}
+
+/// File checkpoints, newest first.
+#[tauri::command]
+fn list_checkpoints(state: State<AppState>) -> Result<Vec<Checkpoint>, String> {
+    let lock = state.agent.lock().unwrap();
+    let agent = lock.as_ref().ok_or("Agent is busy or not initialized")?;
+    Ok(agent.checkpoints().list())
+}
+
+/// Unified diff of the files now against checkpoint `turn` (one file with `path`).
+#[tauri::command]
+fn diff_checkpoint(
+    turn: String,
+    path: Option<String>,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let lock = state.agent.lock().unwrap();
+    let agent = lock.as_ref().ok_or("Agent is busy or not initialized")?;
+    agent
+        .checkpoints()
+        .diff(&turn, path.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Put files back as they were before turn `turn` (one file with `path`).
+#[tauri::command]
+fn restore_checkpoint(
+    turn: String,
+    path: Option<String>,
+    state: State<AppState>,
+) -> Result<Vec<String>, String> {
+    let lock = state.agent.lock().unwrap();
+    let agent = lock.as_ref().ok_or("Agent is busy or not initialized")?;
+    agent
+        .checkpoints()
+        .restore_as_turn(&turn, path.as_deref())
+        .map_err(|e| e.to_string())
+}
+
 /// Send a user message. Events are emitted to the frontend via `agent-event`.
 #[tauri::command]
 async fn send_message(
@@ -70,14 +175,16 @@ async fn send_message(
     app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    // Reset cancel flag before each new turn
-    state.cancel_flag.store(false, Ordering::Relaxed);
+    // Re-arm the cancel token before each new turn
+    state.cancel.reset();
     run_agent_turn(
         message,
         app,
         state.agent.clone(),
-        state.cancel_flag.clone(),
+        state.cancel.clone(),
+        state.estop.clone(),
         state.pending_perms.clone(),
+        false,
     )
     .await
 }
@@ -115,13 +222,15 @@ fn save_me_md(content: String) -> Result<(), String> {
 // ── Shared agent runner ───────────────────────────────────────────
 
 /// Take the agent, run one turn, put it back. Used by both send_message and
-/// the heartbeat thread so the logic lives in one place.
+/// the heartbeat thread (`unattended`) so the logic lives in one place.
 async fn run_agent_turn(
     message: String,
     app: AppHandle,
     agent_arc: Arc<Mutex<Option<Agent>>>,
-    cancel_flag: Arc<AtomicBool>,
-    pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<bool>>>>,
+    cancel: CancelToken,
+    estop: EmergencyStop,
+    pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<PermDecision>>>>,
+    unattended: bool,
 ) -> Result<(), String> {
     let mut agent = {
         let mut lock = agent_arc.lock().unwrap();
@@ -144,7 +253,7 @@ async fn run_agent_turn(
     });
 
     agent
-        .run(message, tx, cancel_flag, pending_perms)
+        .run(message, tx, cancel, estop, pending_perms, unattended)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -161,8 +270,9 @@ async fn run_agent_turn(
 fn spawn_heartbeat(
     agent_arc: Arc<Mutex<Option<Agent>>>,
     app: AppHandle,
-    cancel_flag: Arc<AtomicBool>,
-    pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<bool>>>>,
+    cancel: CancelToken,
+    estop: EmergencyStop,
+    pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<PermDecision>>>>,
     interval_secs: u64,
 ) {
     tauri::async_runtime::spawn(async move {
@@ -173,6 +283,11 @@ fn spawn_heartbeat(
         loop {
             interval.tick().await;
 
+            // No autonomous turns while the emergency stop is engaged.
+            if estop.is_engaged() {
+                continue;
+            }
+
             // Check: is agent free AND does it have a strong desire?
             let should_tick = {
                 let lock = agent_arc.lock().unwrap();
@@ -184,13 +299,15 @@ fn spawn_heartbeat(
 
             if should_tick {
                 tracing::debug!("heartbeat: firing idle tick");
-                cancel_flag.store(false, Ordering::Relaxed);
+                cancel.reset();
                 let _ = run_agent_turn(
                     "(idle — your desires are active, act on them naturally)".to_string(),
                     app.clone(),
                     agent_arc.clone(),
-                    cancel_flag.clone(),
+                    cancel.clone(),
+                    estop.clone(),
                     pending_perms.clone(),
+                    true, // no one to answer prompts: deny instead
                 )
                 .await;
             }
@@ -207,22 +324,26 @@ pub fn run() {
         .filter(|c| c.is_configured())
         .map(Agent::new);
 
+    let initial_tools = initial_agent.as_ref().map(Agent::tools);
     let agent_arc = Arc::new(Mutex::new(initial_agent));
 
-    let cancel_flag = Arc::new(AtomicBool::new(false));
-    let pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<bool>>>> =
+    let cancel = CancelToken::new();
+    let estop = EmergencyStop::default();
+    let pending_perms: Arc<Mutex<std::collections::HashMap<String, tokio::sync::oneshot::Sender<PermDecision>>>> =
         Arc::new(Mutex::new(std::collections::HashMap::new()));
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(AppState {
             agent: agent_arc.clone(),
-            cancel_flag: cancel_flag.clone(),
+            tools: Mutex::new(initial_tools),
+            cancel: cancel.clone(),
+            estop: estop.clone(),
             pending_perms: pending_perms.clone(),
         })
         .setup(move |app| {
             // Heartbeat: check desires every 60 seconds
-            spawn_heartbeat(agent_arc.clone(), app.handle().clone(), cancel_flag.clone(), pending_perms.clone(), 60);
+            spawn_heartbeat(agent_arc.clone(), app.handle().clone(), cancel.clone(), estop.clone(), pending_perms.clone(), 60);
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -231,7 +352,15 @@ pub fn run() {
             is_configured,
             send_message,
             cancel_message,
+            emergency_stop,
+            release_emergency_stop,
+            emergency_stop_engaged,
             respond_permission,
+            list_permission_grants,
+            revoke_permission_grant,
+            list_checkpoints,
+            diff_checkpoint,
+            restore_checkpoint,
             clear_history,
             get_me_md,
             save_me_md,
@@ -0,0 +1,578 @@
+/// Repository map for the coding system prompt.
+///
+/// Lists the project's source files with their sizes and top-level symbols
+/// (Rust, TypeScript/JavaScript, Python), most relevant first, cut to a token
+/// budget; the rest is summarised per directory. Files rank higher when they
+/// are entry points, when other files use their symbols, and when they sit
+/// near the root. The walk skips `.gitignore`d files like `list_files` does,
+/// and also hidden files and directories, anything the workspace won't let
+/// the model read, and everything past `MAX_FILES`.
+///
+/// Symbols are cached per file and only re-extracted when its mtime or size
+/// changes; an unchanged tree reuses the last map as is. `current` reuses a
+/// recent map without walking at all, so a turn doesn't pay for the walk.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use crate::workspace::{Access, Workspace};
+
+/// Default budget for the map in the system prompt.
+pub const DEFAULT_TOKENS: usize = 1_500;
+/// Rough size of a token, for budgeting.
+const CHARS_PER_TOKEN: usize = 4;
+/// Larger files are listed but not parsed.
+const MAX_PARSE_BYTES: u64 = 512_000;
+/// Files walked at most; a larger tree is mapped from its first files.
+const MAX_FILES: usize = 5_000;
+/// `current` reuses a map this recent.
+const REUSE_FOR: Duration = Duration::from_secs(60);
+/// Symbols shown per file.
+const MAX_SYMBOLS: usize = 10;
+/// Directories named in the "not shown" summary.
+const MAX_SUMMARY_DIRS: usize = 8;
+/// Names defined in more files than this are too common to count as references.
+const MAX_DEFINERS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lang {
+    Rust,
+    TypeScript,
+    Python,
+}
+
+fn lang(path: &Path) -> Option<Lang> {
+    match path.extension()?.to_str()? {
+        "rs" => Some(Lang::Rust),
+        "ts" | "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(Lang::TypeScript),
+        "py" => Some(Lang::Python),
+        _ => None,
+    }
+}
+
+// ── Symbol extraction ─────────────────────────────────────────────
+
+/// What one source file defines and uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Parsed {
+    /// For display: "fn scan_project", "class Agent".
+    symbols: Vec<String>,
+    /// The bare names of `symbols`, for counting references.
+    names: Vec<String>,
+    /// Identifiers appearing in the file.
+    idents: HashSet<String>,
+    /// Has a program entry: `fn main`, `if __name__ == "__main__"`.
+    main: bool,
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid symbol regex"))
+}
+
+/// Top-level (unindented) definitions only; Rust items must be `pub`.
+fn parse(lang: Lang, text: &str) -> Parsed {
+    static RUST_ITEM: OnceLock<Regex> = OnceLock::new();
+    static RUST_IMPL: OnceLock<Regex> = OnceLock::new();
+    static RUST_MAIN: OnceLock<Regex> = OnceLock::new();
+    static TS_EXPORT: OnceLock<Regex> = OnceLock::new();
+    static TS_LOCAL: OnceLock<Regex> = OnceLock::new();
+    static PY_DEF: OnceLock<Regex> = OnceLock::new();
+    static PY_MAIN: OnceLock<Regex> = OnceLock::new();
+    static IDENT: OnceLock<Regex> = OnceLock::new();
+
+    let mut parsed = Parsed::default();
+    let mut add = |kind: &str, name: &str| {
+        parsed.symbols.push(format!("{kind} {name}"));
+        parsed.names.push(name.to_string());
+    };
+    match lang {
+        Lang::Rust => {
+            let item = regex(
+                &RUST_ITEM,
+                r#"(?m)^pub(?:\([^)]*\))?\s+(?:(?:async|unsafe|const|extern\s+"[^"]*")\s+)*(fn|struct|enum|trait|type|mod|const|static)\s+([A-Za-z_]\w*)"#,
+            );
+            for c in item.captures_iter(text) {
+                add(&c[1], &c[2]);
+            }
+            for c in regex(&RUST_IMPL, r"(?m)^(?:unsafe\s+)?(impl\b[^{;]*)").captures_iter(text) {
+                let header = c[1].split_whitespace().collect::<Vec<_>>().join(" ");
+                parsed.symbols.push(header);
+            }
+            parsed.main =
+                regex(&RUST_MAIN, r"(?m)^(?:pub\s+)?(?:async\s+)?fn\s+main\s*\(").is_match(text);
+            if parsed.main && !parsed.names.iter().any(|n| n == "main") {
+                parsed.symbols.push("fn main".to_string());
+            }
+        }
+        Lang::TypeScript => {
+            let export = regex(
+                &TS_EXPORT,
+                r"(?m)^export\s+(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(function\*?|class|interface|type|enum|const|let|var)\s+([A-Za-z_$][\w$]*)",
+            );
+            for c in export.captures_iter(text) {
+                add(&c[1], &c[2]);
+            }
+            let local = regex(
+                &TS_LOCAL,
+                r"(?m)^(?:async\s+)?(function|class)\s+([A-Za-z_$][\w$]*)",
+            );
+            for c in local.captures_iter(text) {
+                add(&c[1], &c[2]);
+            }
+        }
+        Lang::Python => {
+            let def = regex(&PY_DEF, r"(?m)^(?:async\s+)?(def|class)\s+([A-Za-z]\w*)");
+            for c in def.captures_iter(text) {
+                add(&c[1], &c[2]);
+            }
+            parsed.main =
+                regex(&PY_MAIN, r#"(?m)^if\s+__name__\s*==\s*['"]__main__['"]"#).is_match(text);
+        }
+    }
+    parsed.idents = regex(&IDENT, r"[A-Za-z_]\w{3,}")
+        .find_iter(text)
+        .map(|m| m.as_str().to_string())
+        .collect();
+    parsed
+}
+
+// ── Cache ─────────────────────────────────────────────────────────
+
+struct CachedFile {
+    mtime: Option<SystemTime>,
+    size: u64,
+    parsed: Parsed,
+}
+
+#[derive(Default)]
+struct Cache {
+    files: HashMap<PathBuf, CachedFile>,
+    /// The last map, keyed by a fingerprint of every file's path, mtime and
+    /// size plus the budget.
+    last: Option<(u64, String)>,
+    /// Maps from `current` by work_dir and budget, with when they were built.
+    recent: HashMap<(String, usize), (Instant, Option<String>)>,
+}
+
+fn cache() -> &'static Mutex<Cache> {
+    static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+// ── Building the map ──────────────────────────────────────────────
+
+struct Entry {
+    rel: PathBuf,
+    size: u64,
+    parsed: Option<Parsed>,
+    score: i64,
+}
+
+/// The files to map under `base` with their mtime and size, sorted.
+fn files(base: &Path) -> Vec<(PathBuf, (Option<SystemTime>, u64))> {
+    let workspace = Workspace::new(&base.to_string_lossy(), &[], &[]);
+    // Links aren't followed, so the canonical base plus the relative path is
+    // the file's canonical path.
+    let root = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    WalkBuilder::new(base)
+        .require_git(false)
+        .follow_links(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+        .flatten()
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .filter(|e| {
+            let rel = e.path().strip_prefix(base).unwrap_or(e.path());
+            workspace.check(&root.join(rel), Access::Read).is_ok()
+        })
+        .take(MAX_FILES)
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some((e.path().to_path_buf(), (meta.modified().ok(), meta.len())))
+        })
+        .collect()
+}
+
+/// Like `build`, but reuses the map from the last call for the same
+/// `work_dir` and budget if it is younger than `REUSE_FOR`.
+pub fn current(work_dir: &str, tokens: usize) -> Option<String> {
+    let key = (work_dir.to_string(), tokens);
+    if let Some((at, map)) = cache().lock().unwrap().recent.get(&key) {
+        if at.elapsed() < REUSE_FOR {
+            return map.clone();
+        }
+    }
+    let map = build(work_dir, tokens);
+    let mut cache = cache().lock().unwrap();
+    cache.recent.retain(|_, (at, _)| at.elapsed() < REUSE_FOR);
+    cache.recent.insert(key, (Instant::now(), map.clone()));
+    map
+}
+
+/// The map for `work_dir` within about `tokens` tokens, or `None` when it
+/// has no files.
+pub fn build(work_dir: &str, tokens: usize) -> Option<String> {
+    let base = Path::new(work_dir);
+    let files = files(base);
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    tokens.hash(&mut hasher);
+    files.hash(&mut hasher);
+    let fingerprint = hasher.finish();
+
+    let mut cache = cache().lock().unwrap();
+    if let Some((seen, map)) = &cache.last {
+        if *seen == fingerprint {
+            return Some(map.clone());
+        }
+    }
+
+    let mut entries = Vec::with_capacity(files.len());
+    let mut live = HashSet::new();
+    for (path, (mtime, size)) in &files {
+        let parsed = lang(path)
+            .filter(|_| *size <= MAX_PARSE_BYTES)
+            .and_then(|lang| {
+                live.insert(path.clone());
+                let fresh = cache
+                    .files
+                    .get(path)
+                    .is_some_and(|c| c.mtime == *mtime && c.size == *size);
+                if !fresh {
+                    let text = std::fs::read_to_string(path).ok()?;
+                    let parsed = parse(lang, &text);
+                    cache.files.insert(
+                        path.clone(),
+                        CachedFile {
+                            mtime: *mtime,
+                            size: *size,
+                            parsed,
+                        },
+                    );
+                }
+                cache.files.get(path).map(|c| c.parsed.clone())
+            });
+        entries.push(Entry {
+            rel: path.strip_prefix(base).unwrap_or(path).to_path_buf(),
+            size: *size,
+            parsed,
+            score: 0,
+        });
+    }
+    cache.files.retain(|path, _| live.contains(path));
+
+    rank(&mut entries);
+    let map = render(&entries, tokens * CHARS_PER_TOKEN);
+    cache.last = Some((fingerprint, map.clone()));
+    Some(map)
+}
+
+fn is_entry(rel: &Path, parsed: &Parsed) -> bool {
+    let stem = rel.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let near_root = rel.components().count() <= 4;
+    parsed.main
+        || (near_root
+            && matches!(
+                stem,
+                "main" | "lib" | "index" | "app" | "App" | "cli" | "__main__" | "manage"
+            ))
+}
+
+fn is_test(rel: &Path) -> bool {
+    let path = rel.to_string_lossy();
+    let name = rel.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    path.split('/')
+        .any(|c| matches!(c, "test" | "tests" | "__tests__" | "spec"))
+        || name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+}
+
+/// Scores every entry: referenced by other files, entry point, has symbols,
+/// shallow, not a test.
+fn rank(entries: &mut [Entry]) {
+    let mut definers: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, e) in entries.iter().enumerate() {
+        for name in e.parsed.iter().flat_map(|p| &p.names) {
+            definers.entry(name.as_str()).or_default().push(i);
+        }
+    }
+    definers.retain(|_, files| files.len() <= MAX_DEFINERS);
+
+    let mut referrers: Vec<HashSet<usize>> = vec![HashSet::new(); entries.len()];
+    for (i, e) in entries.iter().enumerate() {
+        for ident in e.parsed.iter().flat_map(|p| &p.idents) {
+            for &d in definers.get(ident.as_str()).into_iter().flatten() {
+                if d != i {
+                    referrers[d].insert(i);
+                }
+            }
+        }
+    }
+
+    for (e, refs) in entries.iter_mut().zip(&referrers) {
+        let Some(parsed) = &e.parsed else {
+            continue;
+        };
+        let depth = e.rel.components().count() as i64;
+        let mut score = 3 * refs.len() as i64 + parsed.symbols.len().min(10) as i64 - 2 * depth;
+        if is_entry(&e.rel, parsed) {
+            score += 30;
+        }
+        if is_test(&e.rel) {
+            score -= 20;
+        }
+        e.score = score;
+    }
+}
+
+fn render(entries: &[Entry], budget: usize) -> String {
+    let total: u64 = entries.iter().map(|e| e.size).sum();
+    let header = format!(
+        "[Repo Map] {} file(s), {}; most relevant first",
+        entries.len(),
+        size(total)
+    );
+    let mut order: Vec<&Entry> = entries
+        .iter()
+        .filter(|e| e.parsed.as_ref().is_some_and(|p| !p.symbols.is_empty()))
+        .collect();
+    order.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.rel.cmp(&b.rel)));
+
+    let mut lines = vec![header];
+    let mut used = lines[0].len();
+    let mut shown = HashSet::new();
+    for e in order {
+        let parsed = e.parsed.as_ref().expect("filtered above");
+        let mut line = format!("{} ({}", e.rel.display(), size(e.size));
+        if is_entry(&e.rel, parsed) {
+            line.push_str(", entry");
+        }
+        line.push_str("): ");
+        let mut symbols = parsed
+            .symbols
+            .iter()
+            .take(MAX_SYMBOLS)
+            .cloned()
+            .collect::<Vec<_>>();
+        if parsed.symbols.len() > MAX_SYMBOLS {
+            symbols.push(format!("+{} more", parsed.symbols.len() - MAX_SYMBOLS));
+        }
+        line.push_str(&symbols.join(" · "));
+        if used + line.len() + 1 > budget {
+            break;
+        }
+        used += line.len() + 1;
+        shown.insert(e.rel.as_path());
+        lines.push(line);
+    }
+
+    // Everything else, summed per directory (two levels deep).
+    let mut rest: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    for e in entries.iter().filter(|e| !shown.contains(e.rel.as_path())) {
+        let dir: Vec<_> = e
+            .rel
+            .parent()
+            .into_iter()
+            .flat_map(|p| p.iter())
+            .take(2)
+            .collect();
+        let key = if dir.is_empty() {
+            "./".to_string()
+        } else {
+            format!("{}/", PathBuf::from_iter(dir).display())
+        };
+        let slot = rest.entry(key).or_default();
+        slot.0 += 1;
+        slot.1 += e.size;
+    }
+    if !rest.is_empty() {
+        let mut dirs: Vec<_> = rest.into_iter().collect();
+        dirs.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(&b.0)));
+        let more = dirs.len().saturating_sub(MAX_SUMMARY_DIRS);
+        let mut parts: Vec<String> = dirs
+            .iter()
+            .take(MAX_SUMMARY_DIRS)
+            .map(|(dir, (n, bytes))| format!("{dir} ({n}, {})", size(*bytes)))
+            .collect();
+        if more > 0 {
+            parts.push(format!("{more} more dirs"));
+        }
+        lines.push(format!("Other files: {}", parts.join(", ")));
+    }
+    lines.join("\n")
+}
+
+fn size(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{b} B"),
+        b if b < 1024 * 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn map(dir: &tempfile::TempDir, tokens: usize) -> String {
+        build(dir.path().to_str().unwrap(), tokens).unwrap()
+    }
+
+    #[test]
+    fn extracts_rust_symbols() {
+        let text = "use x;\npub struct Agent {}\npub(crate) async fn run() {}\nfn private() {}\n\
+                    pub const LIMIT: usize = 1;\nimpl<T> Display for Agent<T> {}\n\
+                    mod inner {\n    pub fn nested() {}\n}\nfn main() {}\n";
+        let parsed = parse(Lang::Rust, text);
+        assert_eq!(
+            parsed.symbols,
+            vec![
+                "struct Agent",
+                "fn run",
+                "const LIMIT",
+                "impl<T> Display for Agent<T>",
+                "fn main"
+            ]
+        );
+        assert!(parsed.main);
+    }
+
+    #[test]
+    fn extracts_typescript_and_python_symbols() {
+        let ts = "import x from 'y';\nexport default function App() {}\nexport const api = {};\n\
+                  export interface Props {}\nclass Local {}\n  function inner() {}\n";
+        let parsed = parse(Lang::TypeScript, ts);
+        assert_eq!(
+            parsed.symbols,
+            vec![
+                "function App",
+                "const api",
+                "interface Props",
+                "class Local"
+            ]
+        );
+
+        let py = "class Robot:\n    def walk(self): pass\n\ndef _helper(): pass\nasync def main(): pass\n\
+                  if __name__ == \"__main__\":\n    main()\n";
+        let parsed = parse(Lang::Python, py);
+        assert_eq!(parsed.symbols, vec!["class Robot", "def main"]);
+        assert!(parsed.main);
+    }
+
+    #[test]
+    fn ranks_entry_points_and_shared_code_first() {
+        let dir = tree(&[
+            ("src/main.rs", "fn main() { let c = Config::load(); }\n"),
+            ("src/config.rs", "pub struct Config;\n"),
+            ("src/agent.rs", "pub fn think(c: Config) {}\n"),
+            ("src/unused.rs", "pub fn lonely() {}\n"),
+            ("tests/it.rs", "pub fn check(c: Config) {}\n"),
+            ("README.md", "# hi\n"),
+            ("target/out.rs", "pub fn ignored() {}\n"),
+            (".gitignore", "target/\n"),
+        ]);
+        let out = map(&dir, DEFAULT_TOKENS);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("[Repo Map] 6 file(s)"), "{out}");
+        assert!(lines[1].starts_with("src/main.rs ("), "{out}");
+        assert!(lines[1].ends_with(", entry): fn main"), "{out}");
+        assert!(
+            lines[2].starts_with("src/config.rs (19 B): struct Config"),
+            "{out}"
+        );
+        assert!(lines[5].starts_with("tests/it.rs"), "{out}");
+        assert!(!out.contains("ignored"), "{out}");
+        assert!(out.ends_with("Other files: ./ (1, 5 B)"), "{out}");
+    }
+
+    #[test]
+    fn truncates_to_the_budget_and_summarises_the_rest() {
+        let files: Vec<(String, String)> = (0..50)
+            .map(|i| {
+                (
+                    format!("pkg/mod{i:02}.py"),
+                    format!("def function_number_{i}(): pass\n"),
+                )
+            })
+            .collect();
+        let refs: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(a, b)| (a.as_str(), b.as_str()))
+            .collect();
+        let dir = tree(&refs);
+        let out = map(&dir, 100);
+        assert!(out.len() <= 100 * CHARS_PER_TOKEN + 40, "{}", out.len());
+        assert!(out.lines().count() > 3);
+        assert!(
+            out.lines()
+                .last()
+                .unwrap()
+                .starts_with("Other files: pkg/ ("),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn changed_files_are_parsed_again() {
+        let dir = tree(&[("lib.py", "def first(): pass\n")]);
+        assert!(map(&dir, DEFAULT_TOKENS).contains("def first"));
+        assert_eq!(map(&dir, DEFAULT_TOKENS), map(&dir, DEFAULT_TOKENS));
+        std::fs::write(dir.path().join("lib.py"), "def second_version(): pass\n").unwrap();
+        let out = map(&dir, DEFAULT_TOKENS);
+        assert!(
+            out.contains("def second_version") && !out.contains("def first"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn hidden_files_are_left_out() {
+        let dir = tree(&[
+            ("main.py", "def main(): pass\n"),
+            (".secrets/keys.py", "def api_key(): pass\n"),
+            (".hidden.py", "def hidden(): pass\n"),
+        ]);
+        let out = map(&dir, DEFAULT_TOKENS);
+        assert!(out.starts_with("[Repo Map] 1 file(s)"), "{out}");
+        assert!(!out.contains("api_key") && !out.contains("hidden"), "{out}");
+    }
+
+    #[test]
+    fn recent_maps_are_reused() {
+        let dir = tree(&[("lib.py", "def first(): pass\n")]);
+        let wd = dir.path().to_str().unwrap();
+        assert!(current(wd, DEFAULT_TOKENS).unwrap().contains("def first"));
+        std::fs::write(dir.path().join("lib.py"), "def second(): pass\n").unwrap();
+        assert!(current(wd, DEFAULT_TOKENS).unwrap().contains("def first"));
+        assert!(build(wd, DEFAULT_TOKENS).unwrap().contains("def second"));
+    }
+
+    #[test]
+    fn empty_dirs_have_no_map() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(build(dir.path().to_str().unwrap(), DEFAULT_TOKENS).is_none());
+    }
+}
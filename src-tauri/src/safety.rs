@@ -0,0 +1,486 @@
+/// Body-safety governor — limits on what the model may do with the body.
+///
+/// Sits between `Agent::run` and the actuators (`walk`, `go_to`, `look`, `say`):
+///   - emergency stop: every actuator is refused until it is released
+///   - quiet hours: `say` is muted during a local time range
+///   - max durations: `walk` seconds, each `go_to` leg and `look` degrees
+///     are bounded
+///   - cooldowns and per-minute rate limits per actuator
+///
+/// A refused call is not an error — the reason goes back to the model as the
+/// tool result, so it can adapt. `walk` with `direction = "stop"` is always
+/// allowed. A call counts against cooldowns and rate limits only once it runs
+/// (`record`), so one refused here or by the permission check costs nothing.
+use chrono::NaiveTime;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::SafetyConfig;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// ── Emergency stop ────────────────────────────────────────────────────────────
+
+/// Latching emergency stop shared between the UI command and running turns.
+#[derive(Clone, Default)]
+pub struct EmergencyStop(Arc<AtomicBool>);
+
+impl EmergencyStop {
+    pub fn engage(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn release(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// ── Governor ──────────────────────────────────────────────────────────────────
+
+/// Outcome of a safety check.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Allow,
+    /// Allowed with adjusted input (e.g. an untimed walk given a time limit).
+    Adjust(Value),
+    /// Refused; the text is the tool result.
+    Refuse(String),
+}
+
+pub struct SafetyGovernor {
+    config: SafetyConfig,
+    quiet_hours: Option<(NaiveTime, NaiveTime)>,
+    /// Recent commands that ran, per actuator, oldest first.
+    recent: Mutex<HashMap<&'static str, VecDeque<Instant>>>,
+}
+
+impl SafetyGovernor {
+    pub fn new(config: SafetyConfig) -> Self {
+        let quiet_hours = if config.quiet_hours.trim().is_empty() {
+            None
+        } else {
+            let parsed = parse_quiet_hours(&config.quiet_hours);
+            if parsed.is_none() {
+                tracing::warn!(
+                    "Ignoring quiet_hours {:?} — expected \"HH:MM-HH:MM\"",
+                    config.quiet_hours
+                );
+            }
+            parsed
+        };
+        Self {
+            config,
+            quiet_hours,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check a tool call against the limits at the current time.
+    pub fn check(&self, tool: &str, input: &Value, estop: &EmergencyStop) -> Verdict {
+        let now = chrono::Local::now().time();
+        self.check_at(tool, input, estop.is_engaged(), Instant::now(), now)
+    }
+
+    /// `check` with the clock passed in.
+    pub fn check_at(
+        &self,
+        tool: &str,
+        input: &Value,
+        estop: bool,
+        now: Instant,
+        local_time: NaiveTime,
+    ) -> Verdict {
+        let Some(actuator) = actuator(tool) else {
+            return Verdict::Allow;
+        };
+        // Stopping is always safe, and never counts against a limit.
+        if is_stop(tool, input) {
+            return Verdict::Allow;
+        }
+        if estop {
+            return Verdict::Refuse(format!(
+                "(Safety: emergency stop is engaged — {tool} is disabled until your companion releases it)"
+            ));
+        }
+
+        if actuator == "say" {
+            if let Some((from, to)) = self.quiet_hours {
+                if in_range(local_time, from, to) {
+                    return Verdict::Refuse(format!(
+                        "(Safety: quiet hours {}-{} — say is muted, stay silent)",
+                        from.format("%H:%M"),
+                        to.format("%H:%M")
+                    ));
+                }
+            }
+        }
+
+        let mut verdict = Verdict::Allow;
+        match tool {
+            "walk" => {
+                let max = self.config.effective_max_walk_secs();
+                match input["duration"].as_f64() {
+                    Some(secs) if secs > max => {
+                        return Verdict::Refuse(format!(
+                            "(Safety: walk of {secs}s is over the {max}s limit — walk in shorter steps)"
+                        ));
+                    }
+                    Some(_) => {}
+                    None => {
+                        let mut capped = input.clone();
+                        capped["duration"] = max.into();
+                        verdict = Verdict::Adjust(capped);
+                    }
+                }
+            }
+            // The route is planned by the tool, so it gets the limit to cut
+            // each leg to. Whatever the model passed is overwritten.
+            "go_to" => {
+                let mut capped = input.clone();
+                capped["max_leg_secs"] = self.config.effective_max_walk_secs().into();
+                verdict = Verdict::Adjust(capped);
+            }
+            "look" => {
+                let max = self.config.effective_max_look_degrees();
+                let degrees = input["degrees"].as_u64().unwrap_or(30);
+                if degrees > max as u64 {
+                    return Verdict::Refuse(format!(
+                        "(Safety: look of {degrees}° is over the {max}° limit — turn in smaller steps)"
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        let mut recent = self.recent.lock().unwrap();
+        let history = recent.entry(actuator).or_default();
+        while history
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= RATE_WINDOW)
+        {
+            history.pop_front();
+        }
+
+        let cooldown = Duration::from_secs_f64(self.config.effective_cooldown_secs());
+        if let Some(last) = history.back() {
+            let since = now.duration_since(*last);
+            if since < cooldown {
+                return Verdict::Refuse(format!(
+                    "(Safety: {actuator} is cooling down — wait {:.1}s before the next one)",
+                    (cooldown - since).as_secs_f64()
+                ));
+            }
+        }
+
+        let limit = self.config.effective_per_minute(actuator) as usize;
+        if history.len() >= limit {
+            let retry = RATE_WINDOW - now.duration_since(history[0]);
+            return Verdict::Refuse(format!(
+                "(Safety: {actuator} limit of {limit} per minute reached — try again in {:.0}s)",
+                retry.as_secs_f64().ceil()
+            ));
+        }
+
+        verdict
+    }
+
+    /// Count a call that passed `check` and the permission check, as it runs.
+    pub fn record(&self, tool: &str, input: &Value) {
+        self.record_at(tool, input, Instant::now());
+    }
+
+    /// `record` with the clock passed in.
+    pub fn record_at(&self, tool: &str, input: &Value, now: Instant) {
+        let Some(actuator) = actuator(tool) else {
+            return;
+        };
+        if is_stop(tool, input) {
+            return;
+        }
+        let mut recent = self.recent.lock().unwrap();
+        recent.entry(actuator).or_default().push_back(now);
+    }
+}
+
+fn is_stop(tool: &str, input: &Value) -> bool {
+    tool == "walk" && input["direction"].as_str().unwrap_or("stop") == "stop"
+}
+
+/// Rate-limited actuator a tool drives, if any.
+fn actuator(tool: &str) -> Option<&'static str> {
+    match tool {
+        "walk" | "go_to" => Some("walk"),
+        "look" => Some("look"),
+        "say" => Some("say"),
+        _ => None,
+    }
+}
+
+/// "22:00-07:00" → (22:00, 07:00).
+fn parse_quiet_hours(s: &str) -> Option<(NaiveTime, NaiveTime)> {
+    let (from, to) = s.split_once('-')?;
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").ok();
+    Some((parse(from)?, parse(to)?))
+}
+
+/// Whether `t` falls in [from, to), wrapping past midnight when `from > to`.
+fn in_range(t: NaiveTime, from: NaiveTime, to: NaiveTime) -> bool {
+    if from <= to {
+        from <= t && t < to
+    } else {
+        t >= from || t < to
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn governor(config: SafetyConfig) -> SafetyGovernor {
+        SafetyGovernor::new(config)
+    }
+
+    fn noon() -> NaiveTime {
+        NaiveTime::from_hms_opt(12, 0, 0).unwrap()
+    }
+
+    fn at(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    /// A call that runs: `check_at`, then `record_at` unless refused.
+    fn run(g: &SafetyGovernor, tool: &str, input: &Value, t: Instant) -> Verdict {
+        let verdict = g.check_at(tool, input, false, t, noon());
+        if !matches!(verdict, Verdict::Refuse(_)) {
+            g.record_at(tool, input, t);
+        }
+        verdict
+    }
+
+    fn refused(verdict: Verdict) -> String {
+        match verdict {
+            Verdict::Refuse(text) => text,
+            other => panic!("expected a refusal, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn non_actuators_are_not_governed() {
+        let g = governor(SafetyConfig::default());
+        let t = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(
+                g.check_at("see", &json!({}), true, t, noon()),
+                Verdict::Allow
+            );
+        }
+    }
+
+    #[test]
+    fn long_walk_is_refused() {
+        let g = governor(SafetyConfig::default());
+        let input = json!({"direction": "forward", "duration": 30.0});
+        let text = refused(g.check_at("walk", &input, false, Instant::now(), noon()));
+        assert!(text.contains("over the 10s limit"), "{text}");
+    }
+
+    #[test]
+    fn untimed_walk_is_capped() {
+        let g = governor(SafetyConfig {
+            max_walk_secs: 4.0,
+            ..Default::default()
+        });
+        let input = json!({"direction": "forward"});
+        assert_eq!(
+            g.check_at("walk", &input, false, Instant::now(), noon()),
+            Verdict::Adjust(json!({"direction": "forward", "duration": 4.0}))
+        );
+    }
+
+    #[test]
+    fn wide_look_is_refused() {
+        let g = governor(SafetyConfig::default());
+        let t = Instant::now();
+        let text = refused(g.check_at(
+            "look",
+            &json!({"direction": "left", "degrees": 90}),
+            false,
+            t,
+            noon(),
+        ));
+        assert!(text.contains("45°"), "{text}");
+        assert_eq!(
+            g.check_at(
+                "look",
+                &json!({"direction": "left", "degrees": 45}),
+                false,
+                t,
+                noon()
+            ),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn cooldown_between_commands() {
+        let g = governor(SafetyConfig {
+            cooldown_secs: 2.0,
+            ..Default::default()
+        });
+        let input = json!({"direction": "left"});
+        let t = Instant::now();
+        assert_eq!(run(&g, "look", &input, t), Verdict::Allow);
+        let text = refused(run(&g, "look", &input, t + Duration::from_millis(500)));
+        assert!(text.contains("wait 1.5s"), "{text}");
+        // Other actuators have their own cooldown.
+        assert_eq!(run(&g, "say", &json!({"text": "hi"}), t), Verdict::Allow);
+        let later = t + Duration::from_secs(2);
+        assert_eq!(run(&g, "look", &input, later), Verdict::Allow);
+    }
+
+    #[test]
+    fn rate_limit_per_minute() {
+        let g = governor(SafetyConfig {
+            look_per_minute: 3,
+            ..Default::default()
+        });
+        let input = json!({"direction": "up"});
+        let t = Instant::now();
+        for i in 0..3 {
+            let when = t + Duration::from_secs(5 * i);
+            assert_eq!(run(&g, "look", &input, when), Verdict::Allow);
+        }
+        let text = refused(run(&g, "look", &input, t + Duration::from_secs(20)));
+        assert!(text.contains("limit of 3 per minute"), "{text}");
+        assert!(text.contains("try again in 40s"), "{text}");
+        // The oldest command leaves the window after a minute.
+        let later = t + Duration::from_secs(60);
+        assert_eq!(run(&g, "look", &input, later), Verdict::Allow);
+    }
+
+    #[test]
+    fn go_to_shares_the_walk_limit() {
+        let g = governor(SafetyConfig {
+            walk_per_minute: 1,
+            ..Default::default()
+        });
+        let t = Instant::now();
+        let walk = json!({"direction": "forward", "duration": 1.0});
+        assert_eq!(run(&g, "walk", &walk, t), Verdict::Allow);
+        let later = t + Duration::from_secs(5);
+        refused(run(&g, "go_to", &json!({"name": "dock"}), later));
+    }
+
+    #[test]
+    fn go_to_legs_get_the_walk_limit() {
+        let g = governor(SafetyConfig {
+            max_walk_secs: 4.0,
+            ..Default::default()
+        });
+        let input = json!({"name": "kitchen", "max_leg_secs": 60.0});
+        assert_eq!(
+            g.check_at("go_to", &input, false, Instant::now(), noon()),
+            Verdict::Adjust(json!({"name": "kitchen", "max_leg_secs": 4.0}))
+        );
+    }
+
+    #[test]
+    fn refused_calls_do_not_count() {
+        let g = governor(SafetyConfig {
+            walk_per_minute: 1,
+            ..Default::default()
+        });
+        let t = Instant::now();
+        let too_long = json!({"direction": "forward", "duration": 99.0});
+        refused(run(&g, "walk", &too_long, t));
+        let ok = json!({"direction": "forward", "duration": 1.0});
+        assert_eq!(run(&g, "walk", &ok, t), Verdict::Allow);
+    }
+
+    #[test]
+    fn calls_that_never_ran_do_not_count() {
+        let g = governor(SafetyConfig {
+            walk_per_minute: 1,
+            cooldown_secs: 5.0,
+            ..Default::default()
+        });
+        let t = Instant::now();
+        let walk = json!({"direction": "forward", "duration": 1.0});
+        // Allowed here, then denied by the permission check: never recorded.
+        assert_eq!(g.check_at("walk", &walk, false, t, noon()), Verdict::Allow);
+        assert_eq!(run(&g, "walk", &walk, t), Verdict::Allow);
+        refused(run(&g, "walk", &walk, t + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn quiet_hours_mute_say_across_midnight() {
+        let g = governor(SafetyConfig {
+            quiet_hours: "22:00-07:00".into(),
+            ..Default::default()
+        });
+        let say = json!({"text": "good night"});
+        let t = Instant::now();
+        let text = refused(g.check_at("say", &say, false, t, at(23, 30)));
+        assert!(text.contains("quiet hours 22:00-07:00"), "{text}");
+        refused(g.check_at("say", &say, false, t, at(6, 59)));
+        assert_eq!(g.check_at("say", &say, false, t, at(7, 0)), Verdict::Allow);
+        // Walking is not affected by quiet hours.
+        let walk = json!({"direction": "left", "duration": 1.0});
+        assert_eq!(
+            g.check_at("walk", &walk, false, t, at(23, 30)),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn bad_quiet_hours_are_ignored() {
+        let g = governor(SafetyConfig {
+            quiet_hours: "late".into(),
+            ..Default::default()
+        });
+        let say = json!({"text": "hi"});
+        assert_eq!(
+            g.check_at("say", &say, false, Instant::now(), at(3, 0)),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn emergency_stop_refuses_everything_but_stop() {
+        let g = governor(SafetyConfig::default());
+        let t = Instant::now();
+        for (tool, input) in [
+            ("walk", json!({"direction": "forward", "duration": 1.0})),
+            ("go_to", json!({"name": "dock"})),
+            ("look", json!({"direction": "left"})),
+            ("say", json!({"text": "hi"})),
+        ] {
+            let text = refused(g.check_at(tool, &input, true, t, noon()));
+            assert!(text.contains("emergency stop"), "{text}");
+        }
+        assert_eq!(
+            g.check_at("walk", &json!({"direction": "stop"}), true, t, noon()),
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn emergency_stop_latches_until_released() {
+        let estop = EmergencyStop::default();
+        let shared = estop.clone();
+        shared.engage();
+        assert!(estop.is_engaged());
+        shared.release();
+        assert!(!estop.is_engaged());
+    }
+}
@@ -0,0 +1,387 @@
+/// `delegate`: hand self-contained tasks to sub-agents.
+///
+/// A sub-agent is a child `Agent` on the same backend and tools, with its own
+/// empty history, a task prompt, a subset of the tools (the read-only file,
+/// search and git tools unless the caller picks others) and its own step
+/// budget. Its calls pass the same safety and permission checks; permission
+/// prompts reach the user as usual. Its tool calls stream into the `delegate`
+/// call's progress, and only its final answer comes back as the result, so a
+/// broad exploration stays out of the main history. The tasks of one call run
+/// concurrently.
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::agent::AgentEvent;
+use crate::backend::ToolDef;
+use crate::permissions::ToolCategory;
+use crate::tools::progress::{Progress, Stream};
+
+/// Tasks per `delegate` call.
+pub const MAX_TASKS: usize = 4;
+pub const DEFAULT_STEPS: usize = 15;
+pub const MAX_STEPS: usize = 30;
+/// Longer answers are cut before they reach the main history.
+const MAX_SUMMARY_BYTES: usize = 8_000;
+
+/// What a sub-agent gets when the task names no tools.
+pub const DEFAULT_TOOLS: &[&str] = &[
+    "read_file",
+    "list_files",
+    "grep",
+    "git_status",
+    "git_diff",
+    "git_log",
+];
+/// Everything a sub-agent may be given. No file edit tools (edits belong in the
+/// main history, checkpointed and diffed), no body or voice, no further
+/// delegation. `bash` can still write files, uncheckpointed, so the prompt
+/// tells it not to.
+pub const ALLOWED_TOOLS: &[&str] = &[
+    "read_file",
+    "list_files",
+    "grep",
+    "git_status",
+    "git_diff",
+    "git_log",
+    "run_tests",
+    "bash",
+    "recall",
+    "search_memories",
+];
+
+/// One task for a sub-agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brief {
+    pub task: String,
+    /// Names from `ALLOWED_TOOLS`
+    pub tools: Vec<String>,
+    pub max_steps: usize,
+}
+
+/// How a sub-agent's run ended.
+#[derive(Debug, Default, PartialEq)]
+pub struct Outcome {
+    /// Its last reply
+    pub summary: String,
+    /// Tool calls it made
+    pub steps: usize,
+    /// Why it stopped early, if it did
+    pub note: Option<String>,
+}
+
+pub fn tool_def() -> ToolDef {
+    ToolDef {
+        name: "delegate".to_string(),
+        // Reads by default; each sub-agent call is checked on its own.
+        category: ToolCategory::FsRead,
+        description: format!(
+            "Hand a self-contained task (e.g. \"find where X is handled and how\") to a \
+             sub-agent with a fresh context. Only its final summary comes back, so use it \
+             for broad exploration that would flood this conversation. Pass up to \
+             {MAX_TASKS} tasks to run them concurrently. Sub-agents have no file edit tools \
+             (`bash` is allowed if you list it, and its changes are not checkpointed), \
+             can't move the body and can't ask you questions: write each task so it \
+             stands alone."
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "tasks": {
+                    "type": "array",
+                    "description": "Tasks to run concurrently",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "task": {
+                                "type": "string",
+                                "description": "What to do and what to report back"
+                            },
+                            "tools": {
+                                "type": "array",
+                                "items": { "type": "string", "enum": ALLOWED_TOOLS },
+                                "description": format!("Tools it may use (default: {})", DEFAULT_TOOLS.join(", "))
+                            },
+                            "max_steps": {
+                                "type": "integer",
+                                "description": format!("Step budget: model turns, each with any number of tool calls (default {DEFAULT_STEPS}, max {MAX_STEPS})")
+                            }
+                        },
+                        "required": ["task"]
+                    }
+                }
+            },
+            "required": ["tasks"]
+        }),
+    }
+}
+
+/// The tasks of a `delegate` call, or what is wrong with it, for the model.
+pub fn briefs(input: &Value) -> Result<Vec<Brief>, String> {
+    // A lone task object is taken as a list of one.
+    let tasks = match &input["tasks"] {
+        Value::Array(tasks) => tasks.clone(),
+        _ if input["task"].is_string() => vec![input.clone()],
+        _ => return Err("delegate needs `tasks`: a list of {task, tools?, max_steps?}".into()),
+    };
+    if tasks.is_empty() || tasks.len() > MAX_TASKS {
+        return Err(format!("Pass 1 to {MAX_TASKS} tasks, not {}", tasks.len()));
+    }
+    tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let task = t["task"].as_str().map(str::trim).unwrap_or("");
+            if task.is_empty() {
+                return Err(format!("Task {} has no `task` text", i + 1));
+            }
+            let tools: Vec<String> = match t["tools"].as_array() {
+                Some(names) => names
+                    .iter()
+                    .filter_map(|n| n.as_str())
+                    .map(str::to_string)
+                    .collect(),
+                None => DEFAULT_TOOLS.iter().map(|s| s.to_string()).collect(),
+            };
+            if let Some(bad) = tools.iter().find(|n| !ALLOWED_TOOLS.contains(&n.as_str())) {
+                return Err(format!(
+                    "Sub-agents can't use `{bad}`. Allowed: {}",
+                    ALLOWED_TOOLS.join(", ")
+                ));
+            }
+            let max_steps = t["max_steps"]
+                .as_u64()
+                .map_or(DEFAULT_STEPS, |n| n as usize)
+                .clamp(1, MAX_STEPS);
+            Ok(Brief {
+                task: task.to_string(),
+                tools,
+                max_steps,
+            })
+        })
+        .collect()
+}
+
+/// The system prompt of a sub-agent working for `agent_name`. `context` is the
+/// project context and instructions, when a work_dir is set.
+pub fn system_prompt(brief: &Brief, agent_name: &str, context: Option<&str>) -> String {
+    let mut prompt = format!(
+        "You are a sub-agent of {agent_name}. {agent_name} handed you the task in the next \
+         message. Do it with your tools, then reply with what you found.\n\n\
+         [Rules]\n\
+         - Work on your own: no one will answer questions. Make reasonable assumptions and \
+           say which.\n\
+         - You have {steps} steps. Stop exploring once you can answer.\n\
+         - Don't change files, not even with bash: your changes would not be \
+           checkpointed. Say what should change and where (file:line) instead.\n\
+         - Your final reply is all {agent_name} will see. Make it a concise, self-contained \
+           summary: file paths, line numbers, names and facts, not a story of your steps.",
+        steps = brief.max_steps
+    );
+    if let Some(context) = context {
+        prompt.push_str("\n\n");
+        prompt.push_str(context);
+    }
+    prompt
+}
+
+/// Follows sub-agent `n` until its events end: reports its tool calls on
+/// `progress` and passes its permission prompts on to `parent`.
+pub async fn follow(
+    n: usize,
+    mut events: mpsc::Receiver<AgentEvent>,
+    parent: &mpsc::Sender<AgentEvent>,
+    progress: &Progress,
+) -> Outcome {
+    let mut outcome = Outcome::default();
+    while let Some(event) = events.recv().await {
+        match event {
+            AgentEvent::Action { label, .. } => {
+                outcome.steps += 1;
+                progress.line(Stream::Stdout, &format!("[{n}] {label}"));
+            }
+            AgentEvent::PermRequest { .. } => {
+                let _ = parent.send(event).await;
+            }
+            AgentEvent::Error { message } => {
+                progress.line(Stream::Stderr, &format!("[{n}] {message}"));
+                outcome.note = Some(message);
+            }
+            AgentEvent::Cancelled => outcome.note = Some("Cancelled.".to_string()),
+            AgentEvent::Text { .. }
+            | AgentEvent::ToolProgress { .. }
+            | AgentEvent::ToolResult { .. }
+            | AgentEvent::Done => {}
+        }
+    }
+    outcome
+}
+
+/// The `delegate` result: each task's summary, in order.
+pub fn render(briefs: &[Brief], outcomes: &[Outcome]) -> String {
+    let sections: Vec<String> = briefs
+        .iter()
+        .zip(outcomes)
+        .enumerate()
+        .map(|(i, (brief, outcome))| {
+            let title: String = brief
+                .task
+                .lines()
+                .next()
+                .unwrap_or("")
+                .chars()
+                .take(80)
+                .collect();
+            let mut summary = outcome.summary.trim().to_string();
+            if summary.len() > MAX_SUMMARY_BYTES {
+                let mut end = MAX_SUMMARY_BYTES;
+                while !summary.is_char_boundary(end) {
+                    end -= 1;
+                }
+                summary.truncate(end);
+                summary.push_str("\n[… summary truncated]");
+            }
+            if summary.is_empty() {
+                summary = "(No summary.)".to_string();
+            }
+            let mut section = format!(
+                "## Sub-agent {}: {title}\n{summary}\n({} tool call(s)",
+                i + 1,
+                outcome.steps
+            );
+            if let Some(note) = &outcome.note {
+                section.push_str(&format!("; stopped early: {note}"));
+            }
+            section.push(')');
+            section
+        })
+        .collect();
+    sections.join("\n\n")
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn briefs_default_to_read_only_tools() {
+        let briefs = briefs(&json!({"tasks": [{"task": " Find the parser "}]})).unwrap();
+        assert_eq!(
+            briefs,
+            [Brief {
+                task: "Find the parser".to_string(),
+                tools: DEFAULT_TOOLS.iter().map(|s| s.to_string()).collect(),
+                max_steps: DEFAULT_STEPS,
+            }]
+        );
+        // A single task without the list.
+        let one = super::briefs(&json!({"task": "x", "tools": ["grep"], "max_steps": 99})).unwrap();
+        assert_eq!(
+            (one[0].tools.as_slice(), one[0].max_steps),
+            (&["grep".to_string()][..], MAX_STEPS)
+        );
+    }
+
+    #[test]
+    fn briefs_refuse_other_tools_and_too_many_tasks() {
+        let err = briefs(&json!({"tasks": [{"task": "x", "tools": ["grep", "edit_file"]}]}));
+        assert!(err
+            .unwrap_err()
+            .starts_with("Sub-agents can't use `edit_file`"));
+        let err = briefs(&json!({"tasks": [{"task": "x", "tools": ["delegate"]}]}));
+        assert!(err.is_err());
+        let five = vec![json!({"task": "x"}); 5];
+        assert_eq!(
+            briefs(&json!({"tasks": five})).unwrap_err(),
+            "Pass 1 to 4 tasks, not 5"
+        );
+        assert_eq!(
+            briefs(&json!({"tasks": [{"task": ""}]})).unwrap_err(),
+            "Task 1 has no `task` text"
+        );
+        assert!(briefs(&json!({})).is_err());
+    }
+
+    #[test]
+    fn system_prompt_states_the_budget_and_context() {
+        let brief = Brief {
+            task: "t".to_string(),
+            tools: vec![],
+            max_steps: 7,
+        };
+        let prompt = system_prompt(&brief, "Kota", Some("[Project Context]"));
+        assert!(prompt.starts_with("You are a sub-agent of Kota."));
+        assert!(prompt.contains("You have 7 steps."));
+        assert!(prompt.contains("Don't change files, not even with bash"));
+        assert!(prompt.ends_with("\n\n[Project Context]"));
+    }
+
+    #[tokio::test]
+    async fn follow_reports_calls_and_forwards_permission_prompts() {
+        let (tx, rx) = mpsc::channel(16);
+        let (parent_tx, mut parent_rx) = mpsc::channel(16);
+        let lines = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let progress = Progress::new(move |_, line| sink.lock().unwrap().push(line));
+        for event in [
+            AgentEvent::Text {
+                chunk: "Looking".into(),
+            },
+            AgentEvent::Action {
+                id: "a".into(),
+                name: "grep".into(),
+                label: "⚙️ grep...".into(),
+            },
+            AgentEvent::PermRequest {
+                id: "p".into(),
+                tool: "bash".into(),
+                detail: "ls".into(),
+            },
+            AgentEvent::Error {
+                message: "Reached maximum steps.".into(),
+            },
+            AgentEvent::Done,
+        ] {
+            tx.send(event).await.unwrap();
+        }
+        drop(tx);
+
+        let outcome = follow(2, rx, &parent_tx, &progress).await;
+        assert_eq!(outcome.steps, 1);
+        assert_eq!(outcome.note.as_deref(), Some("Reached maximum steps."));
+        assert_eq!(
+            *lines.lock().unwrap(),
+            ["[2] ⚙️ grep...", "[2] Reached maximum steps."]
+        );
+        assert!(
+            matches!(parent_rx.try_recv(), Ok(AgentEvent::PermRequest { id, .. }) if id == "p")
+        );
+    }
+
+    #[test]
+    fn render_lists_each_summary() {
+        let brief = |task: &str| Brief {
+            task: task.to_string(),
+            tools: vec![],
+            max_steps: 5,
+        };
+        let outcomes = [
+            Outcome {
+                summary: "Parsing is in src/parse.rs:40.\n".into(),
+                steps: 3,
+                note: None,
+            },
+            Outcome {
+                summary: String::new(),
+                steps: 5,
+                note: Some("Reached maximum steps.".into()),
+            },
+        ];
+        assert_eq!(
+            render(&[brief("Find the parser\nand its tests"), brief("Check CI")], &outcomes),
+            "## Sub-agent 1: Find the parser\nParsing is in src/parse.rs:40.\n(3 tool call(s))\n\n\
+             ## Sub-agent 2: Check CI\n(No summary.)\n(5 tool call(s); stopped early: Reached maximum steps.)"
+        );
+    }
+}
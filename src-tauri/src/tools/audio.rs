@@ -0,0 +1,628 @@
+/// Native audio pipeline — decoding, resampling, G.711 A-law and local playback.
+///
+/// Everything runs in-process so speech does not depend on mpv/ffplay/ffmpeg
+/// being installed:
+///   - MP3/WAV → mono PCM via symphonia (streamed as bytes arrive)
+///   - integer resampler (moving-average anti-alias + linear interpolation)
+///   - G.711 A-law encoder for the Tapo speaker
+///   - `LocalSpeaker` on the default output device via cpal
+use anyhow::{anyhow, bail, Result};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream, ReadOnlySource};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tokio::sync::mpsc;
+
+use super::tts_engine::{AudioFormat, AudioPiece};
+
+/// Mono 16-bit PCM at `sample_rate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pcm {
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+// ── Decoding ──────────────────────────────────────────────────────────────────
+
+/// Start a decoder for a clip whose bytes are still arriving.
+///
+/// Send compressed chunks into the returned sender and drop it at the end of
+/// the clip; PCM comes out of the receiver as soon as each frame decodes.
+pub fn spawn_decoder(
+    format: AudioFormat,
+) -> (std::sync::mpsc::Sender<Vec<u8>>, mpsc::UnboundedReceiver<Result<Pcm>>) {
+    let (input_tx, input_rx) = std::sync::mpsc::channel();
+    let (output_tx, output_rx) = mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || {
+        let source = Box::new(ReadOnlySource::new(ChannelReader::new(input_rx)));
+        let result = decode_source(source, format, |pcm| output_tx.send(Ok(pcm)).is_ok());
+        if let Err(e) = result {
+            let _ = output_tx.send(Err(e));
+        }
+    });
+    (input_tx, output_rx)
+}
+
+/// Decode one sentence whose `Begin(format)` was just received from `pieces`.
+///
+/// Returns a future that forwards the sentence's `Data` into the decoder (and
+/// completes at its `End`) plus the decoded PCM; drive both concurrently.
+pub fn decode_sentence(
+    pieces: &mut mpsc::UnboundedReceiver<AudioPiece>,
+    format: AudioFormat,
+) -> (impl Future<Output = ()> + '_, mpsc::UnboundedReceiver<Result<Pcm>>) {
+    let (input, decoded) = spawn_decoder(format);
+    let feed = async move {
+        while let Some(AudioPiece::Data(bytes)) = pieces.recv().await {
+            let _ = input.send(bytes);
+        }
+        // `input` drops here, which ends the clip for the decoder.
+    };
+    (feed, decoded)
+}
+
+/// Decode `source` packet by packet, handing mono PCM to `sink` until it returns false.
+fn decode_source(
+    source: Box<dyn MediaSource>,
+    format: AudioFormat,
+    mut sink: impl FnMut(Pcm) -> bool,
+) -> Result<()> {
+    let mut hint = Hint::new();
+    hint.with_extension(format.extension());
+    let stream = MediaSourceStream::new(source, Default::default());
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| anyhow!("unrecognized {} audio: {e}", format.extension()))?;
+    let mut reader = probed.format;
+
+    let track = reader
+        .default_track()
+        .ok_or_else(|| anyhow!("no audio track"))?;
+    let track_id = track.id;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut buffer: Option<SampleBuffer<i16>> = None;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt frame is skipped, as players do.
+            Err(SymphoniaError::DecodeError(e)) => {
+                tracing::debug!("audio decode: {e}");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let needed = decoded.capacity() * channels;
+        if buffer.as_ref().is_none_or(|b| b.capacity() < needed) {
+            buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+        let buffer = buffer.as_mut().expect("buffer just allocated");
+        buffer.copy_interleaved_ref(decoded);
+
+        let pcm = Pcm {
+            sample_rate: spec.rate,
+            samples: downmix(buffer.samples(), channels),
+        };
+        if !pcm.samples.is_empty() && !sink(pcm) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Average interleaved channels into one.
+fn downmix(interleaved: &[i16], channels: usize) -> Vec<i16> {
+    if channels == 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| {
+            let sum: i32 = frame.iter().map(|&s| s as i32).sum();
+            (sum / channels as i32) as i16
+        })
+        .collect()
+}
+
+/// Blocking `Read` over chunks from a channel; EOF once the sender is dropped.
+struct ChannelReader {
+    // Mutex only to make the reader `Sync`, which symphonia requires.
+    rx: Mutex<std::sync::mpsc::Receiver<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: std::sync::mpsc::Receiver<Vec<u8>>) -> Self {
+        Self { rx: Mutex::new(rx), chunk: Vec::new(), pos: 0 }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            let next = self.rx.lock().unwrap_or_else(|e| e.into_inner()).recv();
+            match next {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// ── Resampling ────────────────────────────────────────────────────────────────
+
+/// Streaming sample-rate converter with integer arithmetic, so output is
+/// bit-for-bit reproducible.
+///
+/// When downsampling, input first goes through a moving average as wide as the
+/// rate ratio (a cheap anti-alias filter); output samples are then linearly
+/// interpolated at exact rational positions `n * from / to`.
+pub struct Resampler {
+    from: u32,
+    to: u32,
+    /// Last `taps` raw input samples and their sum.
+    window: VecDeque<i16>,
+    window_sum: i32,
+    /// Filtered input not yet consumed; `buf[0]` is input sample `base`.
+    buf: Vec<i16>,
+    base: u64,
+    /// Index of the next output sample.
+    next: u64,
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32) -> Self {
+        let taps = if from > to { from.div_ceil(to) as usize } else { 1 };
+        Self {
+            from,
+            to,
+            window: std::iter::repeat_n(0, taps).collect(),
+            window_sum: 0,
+            buf: Vec::new(),
+            base: 0,
+            next: 0,
+        }
+    }
+
+    /// Convert the next block of input; may return fewer samples than the ratio
+    /// suggests while waiting for the neighbour needed for interpolation.
+    pub fn process(&mut self, input: &[i16]) -> Vec<i16> {
+        if self.from == self.to {
+            return input.to_vec();
+        }
+
+        let taps = self.window.len() as i32;
+        for &sample in input {
+            self.window_sum += sample as i32 - self.window.pop_front().unwrap_or(0) as i32;
+            self.window.push_back(sample);
+            self.buf.push((self.window_sum / taps) as i16);
+        }
+
+        let (from, to) = (self.from as u64, self.to as u64);
+        let available = self.base + self.buf.len() as u64;
+        let mut out = Vec::new();
+        loop {
+            let pos = self.next * from;
+            let (i, frac) = (pos / to, pos % to);
+            let last_needed = if frac == 0 { i } else { i + 1 };
+            if last_needed >= available {
+                break;
+            }
+            let a = self.buf[(i - self.base) as usize] as i64;
+            let sample = if frac == 0 {
+                a
+            } else {
+                let b = self.buf[(i + 1 - self.base) as usize] as i64;
+                a + (b - a) * frac as i64 / to as i64
+            };
+            out.push(sample as i16);
+            self.next += 1;
+        }
+
+        // Drop input that no future output sample can reference.
+        let keep_from = (self.next * from / to).min(available);
+        let consumed = (keep_from - self.base) as usize;
+        self.buf.drain(..consumed);
+        self.base = keep_from;
+        out
+    }
+}
+
+// ── G.711 A-law ───────────────────────────────────────────────────────────────
+
+/// Segment end points for 13-bit magnitudes (ITU-T G.711).
+const ALAW_SEG_END: [i16; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+/// Encode one 16-bit linear sample to A-law (ITU-T G.711, even bits inverted).
+pub fn linear_to_alaw(sample: i16) -> u8 {
+    let mut pcm = sample >> 3; // G.711 works on 13-bit samples
+    let mask = if pcm >= 0 {
+        0xD5 // sign bit set, even bits inverted
+    } else {
+        pcm = -pcm - 1;
+        0x55
+    };
+    let Some(seg) = ALAW_SEG_END.iter().position(|&end| pcm <= end) else {
+        return 0x7F ^ mask; // out of range: clip to the largest code
+    };
+    let shift = if seg < 2 { 1 } else { seg };
+    let aval = ((seg as u8) << 4) | ((pcm >> shift) & 0x0F) as u8;
+    aval ^ mask
+}
+
+pub fn encode_alaw(samples: &[i16]) -> Vec<u8> {
+    samples.iter().map(|&s| linear_to_alaw(s)).collect()
+}
+
+// ── Local playback ────────────────────────────────────────────────────────────
+
+/// The default output device, fed with PCM from any thread.
+///
+/// cpal streams are not `Send`, so the stream lives on its own thread; samples
+/// reach its callback through a shared queue.
+pub struct LocalSpeaker {
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    failed: Arc<AtomicBool>,
+    device_rate: u32,
+    resampler: Option<(u32, Resampler)>,
+    stop: std::sync::mpsc::Sender<()>,
+}
+
+impl LocalSpeaker {
+    /// Open the default output device.
+    pub async fn open() -> Result<Self> {
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let failed = Arc::new(AtomicBool::new(false));
+        let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+
+        let (q, f) = (queue.clone(), failed.clone());
+        std::thread::spawn(move || match start_output(q, f) {
+            Ok((_stream, rate)) => {
+                let _ = ready_tx.send(Ok(rate));
+                // Keep the stream alive until the speaker is finished or dropped.
+                let _ = stop_rx.recv();
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+            }
+        });
+
+        let device_rate = ready_rx
+            .await
+            .map_err(|_| anyhow!("audio output thread exited"))??;
+        Ok(Self { queue, failed, device_rate, resampler: None, stop: stop_tx })
+    }
+
+    /// Queue PCM for playback; it starts sounding immediately.
+    pub fn push(&mut self, pcm: &Pcm) {
+        let converted = match &mut self.resampler {
+            Some((rate, r)) if *rate == pcm.sample_rate => r.process(&pcm.samples),
+            _ => {
+                let mut r = Resampler::new(pcm.sample_rate, self.device_rate);
+                let out = r.process(&pcm.samples);
+                self.resampler = Some((pcm.sample_rate, r));
+                out
+            }
+        };
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.extend(converted.into_iter().map(|s| s as f32 / 32768.0));
+    }
+
+    /// Wait until everything queued has been played, then close the device.
+    pub async fn finish(self) -> Result<()> {
+        loop {
+            if self.failed.load(Ordering::Relaxed) {
+                bail!("audio output device stopped");
+            }
+            if self.queue.lock().unwrap_or_else(|e| e.into_inner()).is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        // Let the device drain its own buffer before the stream is dropped.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let _ = self.stop.send(());
+        Ok(())
+    }
+}
+
+/// Build and start the output stream; returns it with the device sample rate.
+fn start_output(
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    failed: Arc<AtomicBool>,
+) -> Result<(cpal::Stream, u32)> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| anyhow!("no audio output device"))?;
+    let supported = device.default_output_config()?;
+    let config: cpal::StreamConfig = supported.config();
+    let rate = config.sample_rate.0;
+
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_output::<f32>(&device, &config, queue, failed)?,
+        cpal::SampleFormat::I16 => build_output::<i16>(&device, &config, queue, failed)?,
+        cpal::SampleFormat::U16 => build_output::<u16>(&device, &config, queue, failed)?,
+        other => bail!("unsupported output sample format {other:?}"),
+    };
+    stream.play()?;
+    Ok((stream, rate))
+}
+
+fn build_output<T: cpal::SizedSample + cpal::FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    failed: Arc<AtomicBool>,
+) -> Result<cpal::Stream> {
+    let channels = config.channels.max(1) as usize;
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+            for frame in data.chunks_mut(channels) {
+                // Mono source → same sample on every channel; silence on underrun.
+                let value = T::from_sample(queue.pop_front().unwrap_or(0.0));
+                frame.fill(value);
+            }
+        },
+        move |e| {
+            tracing::warn!("audio output: {e}");
+            failed.store(true, Ordering::Relaxed);
+        },
+        None,
+    )?;
+    Ok(stream)
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ITU-T G.711 A-law expansion, used to check the encoder round-trips.
+    fn alaw_to_linear(code: u8) -> i16 {
+        let a = code ^ 0x55;
+        let mut t = ((a & 0x0F) as i16) << 4;
+        let seg = (a & 0x70) >> 4;
+        match seg {
+            0 => t += 8,
+            1 => t += 0x108,
+            _ => {
+                t += 0x108;
+                t <<= seg - 1;
+            }
+        }
+        if a & 0x80 != 0 {
+            t
+        } else {
+            -t
+        }
+    }
+
+    /// Decode a complete clip.
+    fn decode(bytes: Vec<u8>, format: AudioFormat) -> Result<Pcm> {
+        let mut out = Pcm { sample_rate: 0, samples: Vec::new() };
+        let source = Box::new(std::io::Cursor::new(bytes));
+        decode_source(source, format, |pcm| {
+            out.sample_rate = pcm.sample_rate;
+            out.samples.extend_from_slice(&pcm.samples);
+            true
+        })?;
+        if out.samples.is_empty() {
+            bail!("no audio decoded");
+        }
+        Ok(out)
+    }
+
+    /// Minimal 16-bit PCM WAV file.
+    fn wav(sample_rate: u32, channels: u16, samples: &[i16]) -> Vec<u8> {
+        let data_len = (samples.len() * 2) as u32;
+        let block_align = channels * 2;
+        let mut out = Vec::new();
+        out.extend_from_slice(b"RIFF");
+        out.extend_from_slice(&(36 + data_len).to_le_bytes());
+        out.extend_from_slice(b"WAVEfmt ");
+        out.extend_from_slice(&16u32.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        out.extend_from_slice(&channels.to_le_bytes());
+        out.extend_from_slice(&sample_rate.to_le_bytes());
+        out.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        out.extend_from_slice(&block_align.to_le_bytes());
+        out.extend_from_slice(&16u16.to_le_bytes());
+        out.extend_from_slice(b"data");
+        out.extend_from_slice(&data_len.to_le_bytes());
+        for s in samples {
+            out.extend_from_slice(&s.to_le_bytes());
+        }
+        out
+    }
+
+    /// `frames` silent MPEG-1 Layer III frames (mono, 32kbps, 32kHz).
+    /// All-zero side info means no spectral data, which decodes to silence.
+    fn silent_mp3(frames: usize) -> Vec<u8> {
+        // 144 * 32000 / 32000 = 144 bytes per frame, no padding, no CRC
+        let mut frame = [0u8; 144];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x18, 0xC0]);
+        frame.repeat(frames)
+    }
+
+    // ── A-law ─────────────────────────────────────────────────────
+
+    #[test]
+    fn alaw_matches_g711_reference_vectors() {
+        // Values from the ITU-T G.711 / Sun reference implementation.
+        let vectors: &[(i16, u8)] = &[
+            (0, 0xD5),
+            (-1, 0x55),
+            (8, 0xD5),
+            (16, 0xD4),
+            (-16, 0x55),
+            (-17, 0x54),
+            (256, 0xC5),
+            (512, 0xF5),
+            (1024, 0xE5),
+            (4096, 0x85),
+            (-4096, 0x1A),
+            (32767, 0xAA),
+            (-32768, 0x2A),
+        ];
+        for &(linear, code) in vectors {
+            assert_eq!(linear_to_alaw(linear), code, "linear {linear}");
+        }
+    }
+
+    #[test]
+    fn alaw_round_trip_stays_within_quantization_step() {
+        for linear in (i16::MIN..=i16::MAX).step_by(7) {
+            let decoded = alaw_to_linear(linear_to_alaw(linear)) as i32;
+            let magnitude = (linear as i32).unsigned_abs();
+            // Step size is 16 below 512 and doubles with every segment above.
+            let step = ((magnitude + 1).next_power_of_two() / 32).max(16) as i32;
+            assert!(
+                (decoded - linear as i32).abs() <= step,
+                "{linear} → {decoded} (step {step})"
+            );
+        }
+    }
+
+    // ── Resampler ─────────────────────────────────────────────────
+
+    #[test]
+    fn resampler_same_rate_is_identity() {
+        let mut r = Resampler::new(8000, 8000);
+        assert_eq!(r.process(&[1, -2, 3]), vec![1, -2, 3]);
+    }
+
+    #[test]
+    fn resampler_halves_with_two_tap_average() {
+        let mut r = Resampler::new(16000, 8000);
+        // filtered: 50, 150, 250, 350, 450, 550 → every second one
+        assert_eq!(r.process(&[100, 200, 300, 400, 500, 600]), vec![50, 250, 450]);
+    }
+
+    #[test]
+    fn resampler_24k_to_8k_reference_vector() {
+        let mut r = Resampler::new(24000, 8000);
+        let input: Vec<i16> = (0..12).map(|i| i * 300).collect();
+        // 3-tap average (zero history) sampled at 0, 3, 6, 9
+        assert_eq!(r.process(&input), vec![0, 600, 1500, 2400]);
+    }
+
+    #[test]
+    fn resampler_interpolates_fractional_positions() {
+        let mut r = Resampler::new(8000, 12000);
+        // positions 0, 2/3, 4/3, 2, 8/3 (truncated toward zero)
+        assert_eq!(r.process(&[0, 300, 600, 900]), vec![0, 200, 400, 600, 800]);
+    }
+
+    #[test]
+    fn resampler_is_independent_of_block_boundaries() {
+        let input: Vec<i16> = (0..441).map(|i| ((i * 37) % 2000 - 1000) as i16).collect();
+        let whole = Resampler::new(44100, 8000).process(&input);
+
+        let mut r = Resampler::new(44100, 8000);
+        let mut pieces = Vec::new();
+        for block in input.chunks(17) {
+            pieces.extend(r.process(block));
+        }
+        assert_eq!(pieces, whole);
+        assert_eq!(whole.len(), 80);
+    }
+
+    // ── Decoding ──────────────────────────────────────────────────
+
+    #[test]
+    fn decodes_mono_wav() {
+        let pcm = decode(wav(16000, 1, &[1, -2, 300, i16::MIN]), AudioFormat::Wav).unwrap();
+        assert_eq!(pcm, Pcm { sample_rate: 16000, samples: vec![1, -2, 300, i16::MIN] });
+    }
+
+    #[test]
+    fn decodes_stereo_wav_to_mono() {
+        let pcm = decode(wav(8000, 2, &[100, 300, -100, -300]), AudioFormat::Wav).unwrap();
+        assert_eq!(pcm.samples, vec![200, -200]);
+    }
+
+    #[test]
+    fn decodes_mp3_frames() {
+        let pcm = decode(silent_mp3(10), AudioFormat::Mp3).unwrap();
+        assert_eq!(pcm.sample_rate, 32000);
+        assert!(!pcm.samples.is_empty());
+        assert_eq!(pcm.samples.len() % 1152, 0);
+        assert!(pcm.samples.iter().all(|&s| s == 0));
+    }
+
+    #[test]
+    fn garbage_is_an_error_not_silence() {
+        assert!(decode(b"definitely not audio".to_vec(), AudioFormat::Wav).is_err());
+    }
+
+    #[test]
+    fn wav_to_pcma_is_bit_exact() {
+        // 16kHz → 8kHz (2-tap average, every second sample) → A-law
+        let samples = [0, 0, 1000, 1000, -1000, -1000, 8000, 8000, -32768, -32768];
+        let pcm = decode(wav(16000, 1, &samples), AudioFormat::Wav).unwrap();
+        let narrow = Resampler::new(pcm.sample_rate, 8000).process(&pcm.samples);
+        assert_eq!(narrow, vec![0, 500, 0, 3500, -12384]);
+        assert_eq!(encode_alaw(&narrow), vec![0xD5, 0xCA, 0xD5, 0x9E, 0x3D]);
+    }
+
+    #[tokio::test]
+    async fn streaming_decoder_emits_pcm_before_input_ends() {
+        let bytes = wav(8000, 1, &vec![7i16; 4000]);
+        let (input, mut output) = spawn_decoder(AudioFormat::Wav);
+
+        // Send only the first half; PCM must already come out.
+        input.send(bytes[..bytes.len() / 2].to_vec()).unwrap();
+        let first = tokio::time::timeout(Duration::from_secs(5), output.recv())
+            .await
+            .expect("decoder waited for the whole clip")
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.sample_rate, 8000);
+        assert!(first.samples.iter().all(|&s| s == 7));
+
+        input.send(bytes[bytes.len() / 2..].to_vec()).unwrap();
+        drop(input);
+        let mut total = first.samples.len();
+        while let Some(pcm) = output.recv().await {
+            total += pcm.unwrap().samples.len();
+        }
+        assert_eq!(total, 4000);
+    }
+}
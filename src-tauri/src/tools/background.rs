@@ -0,0 +1,330 @@
+/// Background processes for `bash_background` / `bash_output` / `bash_kill`.
+///
+/// A job's stdout and stderr (lines prefixed `[stderr]`) are collected into a
+/// buffer as they arrive; `bash_output` hands back whatever is new since the
+/// last poll. Jobs outlive the turn that started them, and are killed with
+/// their whole process group by `bash_kill` or when the tool is dropped.
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::oneshot;
+
+use super::shell::KillOnDrop;
+
+/// Unread output kept per job; older output is dropped first.
+const MAX_BUFFER_BYTES: usize = 256 * 1024;
+
+#[derive(Default)]
+struct Output {
+    unread: String,
+    dropped: usize,
+    status: Option<i32>,
+}
+
+struct Job {
+    command: String,
+    started: Instant,
+    output: Arc<Mutex<Output>>,
+    kill: Option<oneshot::Sender<()>>,
+    /// Killed with the job when it is dropped.
+    group: KillOnDrop,
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    jobs: Mutex<HashMap<String, Job>>,
+    next_id: AtomicUsize,
+}
+
+impl Jobs {
+    /// Start `cmd` (already set up with its sandbox and cwd); returns the job id.
+    pub fn start(&self, mut cmd: tokio::process::Command, command: &str) -> Result<String> {
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let mut child = cmd.spawn().context("failed to start background job")?;
+        let group = KillOnDrop(child.id());
+
+        let output = Arc::new(Mutex::new(Output::default()));
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(collect(stdout, "", output.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(collect(stderr, "[stderr] ", output.clone()));
+        }
+        let (kill_tx, kill_rx) = oneshot::channel::<()>();
+        let waiter_output = output.clone();
+        tokio::spawn(async move {
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = kill_rx => {
+                    let _ = child.start_kill();
+                    child.wait().await
+                }
+            };
+            let code = status.ok().and_then(|s| s.code()).unwrap_or(-1);
+            waiter_output.lock().unwrap().status = Some(code);
+        });
+
+        let id = format!("bg{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+        self.jobs.lock().unwrap().insert(
+            id.clone(),
+            Job {
+                command: command.to_string(),
+                started: Instant::now(),
+                output,
+                kill: Some(kill_tx),
+                group,
+            },
+        );
+        Ok(id)
+    }
+
+    /// New output since the last poll, waiting up to `wait` for some to arrive.
+    pub async fn poll(&self, id: &str, wait: Duration) -> String {
+        let Some(output) = self.output_of(id) else {
+            return self.unknown(id);
+        };
+        let deadline = Instant::now() + wait;
+        loop {
+            {
+                let out = output.lock().unwrap();
+                if !out.unread.is_empty() || out.status.is_some() || Instant::now() >= deadline {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        // Let the readers catch up with output written just before exit.
+        if output.lock().unwrap().status.is_some() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.report(id, &output)
+    }
+
+    /// Kill the job's process group; returns its final output.
+    pub async fn kill(&self, id: &str) -> String {
+        let Some(job) = self.jobs.lock().unwrap().remove(id) else {
+            return self.unknown(id);
+        };
+        let Job {
+            command,
+            started,
+            output,
+            kill,
+            group,
+        } = job;
+        if output.lock().unwrap().status.is_none() {
+            #[cfg(unix)]
+            if let Some(pgid) = group.0 {
+                // SAFETY: plain syscall on the group created for this job.
+                unsafe {
+                    libc::killpg(pgid as libc::pid_t, libc::SIGTERM);
+                }
+            }
+            // Give it a moment to shut down cleanly, then force it.
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while output.lock().unwrap().status.is_none() && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+        if let Some(kill) = kill {
+            let _ = kill.send(());
+        }
+        drop(group);
+        // Let the readers drain what the job printed on its way out.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut out = output.lock().unwrap();
+        format!(
+            "Killed {id} (`{command}`, ran {}s){}",
+            started.elapsed().as_secs(),
+            unread_text(&mut out)
+        )
+    }
+
+    /// One line per job, for when the model asks about an unknown id.
+    pub fn list(&self) -> String {
+        let jobs = self.jobs.lock().unwrap();
+        if jobs.is_empty() {
+            return "No background jobs.".to_string();
+        }
+        let mut ids: Vec<&String> = jobs.keys().collect();
+        ids.sort_by_key(|id| id[2..].parse::<usize>().unwrap_or(0));
+        ids.into_iter()
+            .map(|id| {
+                let job = &jobs[id];
+                format!(
+                    "{id}: {} — `{}`",
+                    state(&job.output.lock().unwrap(), job.started),
+                    job.command
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn output_of(&self, id: &str) -> Option<Arc<Mutex<Output>>> {
+        self.jobs.lock().unwrap().get(id).map(|j| j.output.clone())
+    }
+
+    fn unknown(&self, id: &str) -> String {
+        format!("No background job '{id}'. Jobs:\n{}", self.list())
+    }
+
+    fn report(&self, id: &str, output: &Mutex<Output>) -> String {
+        let started = match self.jobs.lock().unwrap().get(id) {
+            Some(job) => job.started,
+            None => return self.unknown(id),
+        };
+        let mut out = output.lock().unwrap();
+        format!("{id}: {}{}", state(&out, started), unread_text(&mut out))
+    }
+}
+
+fn state(out: &Output, started: Instant) -> String {
+    match out.status {
+        Some(code) => format!("exited with {code}"),
+        None => format!("running for {}s", started.elapsed().as_secs()),
+    }
+}
+
+/// Takes the unread output, as the rest of a report.
+fn unread_text(out: &mut Output) -> String {
+    let mut text = String::new();
+    if out.dropped > 0 {
+        text.push_str(&format!("\n[… {} earlier bytes dropped …]", out.dropped));
+        out.dropped = 0;
+    }
+    if out.unread.is_empty() {
+        text.push_str("\n(no new output)");
+    } else {
+        text.push('\n');
+        text.push_str(out.unread.trim_end_matches('\n'));
+        out.unread.clear();
+    }
+    text
+}
+
+async fn collect(stream: impl AsyncRead + Unpin, prefix: &'static str, output: Arc<Mutex<Output>>) {
+    let mut lines = BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut out = output.lock().unwrap();
+        out.unread.push_str(prefix);
+        out.unread.push_str(&line);
+        out.unread.push('\n');
+        if out.unread.len() > MAX_BUFFER_BYTES {
+            let mut cut = out.unread.len() - MAX_BUFFER_BYTES;
+            while !out.unread.is_char_boundary(cut) {
+                cut += 1;
+            }
+            out.unread.drain(..cut);
+            out.dropped += cut;
+        }
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bash(command: &str) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new("bash");
+        cmd.arg("-c").arg(command).current_dir("/tmp");
+        cmd
+    }
+
+    #[tokio::test]
+    async fn output_is_returned_incrementally() {
+        let jobs = Jobs::default();
+        let id = jobs
+            .start(bash("echo one; echo warn >&2; sleep 0.5; echo two"), "demo")
+            .unwrap();
+        assert_eq!(id, "bg1");
+
+        let first = jobs.poll(&id, Duration::from_secs(5)).await;
+        assert!(first.contains("running"), "{first}");
+        assert!(first.contains("one"), "{first}");
+
+        let mut rest = String::new();
+        for _ in 0..50 {
+            rest.push_str(&jobs.poll(&id, Duration::from_secs(1)).await);
+            if rest.contains("exited") {
+                break;
+            }
+        }
+        assert!(rest.contains("exited with 0"), "{rest}");
+        assert!(rest.contains("two"), "{rest}");
+        assert!(!rest.contains("one"), "old output repeated: {rest}");
+        let all = format!("{first}{rest}");
+        assert!(all.contains("[stderr] warn"), "{all}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn kill_stops_the_whole_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("survived");
+        let jobs = Jobs::default();
+        let command = format!(
+            "echo started; (sleep 1; touch {}) & sleep 60",
+            marker.display()
+        );
+        let id = jobs.start(bash(&command), "server").unwrap();
+        assert!(jobs
+            .poll(&id, Duration::from_secs(5))
+            .await
+            .contains("started"));
+
+        let text = jobs.kill(&id).await;
+        assert!(text.starts_with("Killed bg1"), "{text}");
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists(), "child outlived bash_kill");
+        assert!(jobs
+            .poll(&id, Duration::ZERO)
+            .await
+            .starts_with("No background job"));
+    }
+
+    #[tokio::test]
+    async fn unknown_ids_list_the_jobs() {
+        let jobs = Jobs::default();
+        assert!(jobs
+            .poll("bg9", Duration::ZERO)
+            .await
+            .contains("No background jobs."));
+        jobs.start(bash("sleep 5"), "sleep 5").unwrap();
+        let text = jobs.kill("nope").await;
+        assert!(text.contains("bg1: running for 0s — `sleep 5`"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn buffer_keeps_only_the_newest_output() {
+        let jobs = Jobs::default();
+        let id = jobs
+            .start(
+                bash("head -c 400000 /dev/zero | tr '\\0' 'x' | fold -w 100"),
+                "flood",
+            )
+            .unwrap();
+        // Let it finish before the first poll so the buffer overflows.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let text = jobs.poll(&id, Duration::from_secs(5)).await;
+        assert!(text.contains("exited"), "{}", &text[..200.min(text.len())]);
+        assert!(
+            text.contains("earlier bytes dropped"),
+            "{}",
+            &text[..200.min(text.len())]
+        );
+        assert!(text.len() <= MAX_BUFFER_BYTES + 200);
+    }
+}
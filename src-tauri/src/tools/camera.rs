@@ -7,6 +7,7 @@ use serde_json::json;
 use tokio::process::Command;
 
 use crate::backend::ToolDef;
+use crate::permissions::ToolCategory;
 
 use super::ToolOutput;
 
@@ -37,11 +38,13 @@ impl CameraTool {
         vec![
             ToolDef {
                 name: "see".to_string(),
+                category: ToolCategory::Perception,
                 description: "Take a photo with your camera (your eyes). Call this after looking around to actually see what is there.".to_string(),
                 input_schema: json!({"type": "object", "properties": {}, "required": []}),
             },
             ToolDef {
                 name: "look".to_string(),
+                category: ToolCategory::Actuation,
                 description: "Move your camera neck. direction: left|right|up|down|around. degrees: how far (default 30).".to_string(),
                 input_schema: json!({
                     "type": "object",
@@ -95,6 +98,7 @@ impl CameraTool {
                 "-y",
                 tmp.to_str().unwrap_or("/tmp/familiar_cap.jpg"),
             ])
+            .kill_on_drop(true)
             .output()
             .await?;
 
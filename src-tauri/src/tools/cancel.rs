@@ -0,0 +1,102 @@
+/// Cooperative cancellation for agent turns.
+///
+/// `cancel_message` trips the token; the agent loop checks it between steps and
+/// `ToolRegistry::execute` races every tool against it, so a running `bash`,
+/// `walk` or `say` is interrupted instead of finishing on its own. Interrupted
+/// tools are dropped mid-flight: child processes are killed on drop, audio
+/// stops when its output stream is dropped, and the robot is told to stop.
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+#[derive(Clone)]
+pub struct CancelToken {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Arm the token again for the next turn.
+    pub fn reset(&self) {
+        self.tx.send_replace(false);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Resolves once the token is cancelled (immediately if it already is).
+    pub async fn cancelled(&self) {
+        let mut rx = self.tx.subscribe();
+        // The sender lives as long as `self`, so this only returns on cancel.
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+
+    /// Run `fut` unless cancelled first; `None` means it was interrupted and dropped.
+    pub async fn run<F: Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            () = self.cancelled() => None,
+            out = fut => Some(out),
+        }
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn run_completes_when_not_cancelled() {
+        let token = CancelToken::new();
+        assert_eq!(token.run(async { 7 }).await, Some(7));
+    }
+
+    #[tokio::test]
+    async fn cancel_interrupts_running_future() {
+        let token = CancelToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+        let out = tokio::time::timeout(
+            Duration::from_secs(5),
+            token.run(tokio::time::sleep(Duration::from_secs(60))),
+        )
+        .await
+        .expect("cancel did not interrupt");
+        assert_eq!(out, None);
+    }
+
+    #[tokio::test]
+    async fn already_cancelled_never_starts() {
+        let token = CancelToken::new();
+        token.cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(token.run(async { 7 }).await, None);
+
+        token.reset();
+        assert!(!token.is_cancelled());
+        assert_eq!(token.run(async { 7 }).await, Some(7));
+    }
+}
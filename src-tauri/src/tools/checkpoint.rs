@@ -0,0 +1,536 @@
+/// File checkpoints: undo for the agent's edits, with or without git.
+///
+/// Each turn gets a checkpoint the first time a tool modifies a file in it;
+/// every file is copied once, before its first change in that turn. A
+/// checkpoint lives in `~/.familiar_ai/checkpoints/<turn>/` as the copies
+/// plus a `manifest.json`. Restoring puts a file (or every file of a turn)
+/// back the way it was before that turn and deletes files the turn created.
+/// A restore is itself checkpointed when it happens during a turn, so it
+/// can be undone too.
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::fs::unified_diff;
+use super::ToolOutput;
+use crate::permissions::{canonical_path, ToolCategory};
+use crate::workspace::{Access, Workspace};
+
+/// Older checkpoints are deleted once there are more than this.
+const MAX_CHECKPOINTS: usize = 100;
+/// Checkpoints shown by the `checkpoints` tool.
+const LISTED: usize = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    /// Sortable turn id, e.g. `20261018-142501`.
+    pub id: String,
+    /// Start of the message that began the turn.
+    pub prompt: String,
+    pub started: String,
+    pub files: Vec<CheckpointFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CheckpointFile {
+    pub path: PathBuf,
+    /// False if the turn created the file; restoring deletes it.
+    pub existed: bool,
+}
+
+pub struct Checkpoints {
+    root: PathBuf,
+    workspace: Workspace,
+    /// The running turn; written to disk on its first snapshot.
+    current: Mutex<Option<Checkpoint>>,
+}
+
+pub fn default_checkpoints_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".familiar_ai")
+        .join("checkpoints")
+}
+
+impl Checkpoints {
+    pub fn new(root: PathBuf, workspace: Workspace) -> Self {
+        Self {
+            root,
+            workspace,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Start a new turn; files it modifies are snapshotted into a fresh checkpoint.
+    pub fn begin_turn(&self, prompt: &str) {
+        let now = chrono::Local::now();
+        let mut id = now.format("%Y%m%d-%H%M%S").to_string();
+        // Two turns in the same second get distinct ids.
+        let mut n = 1;
+        while self.root.join(&id).exists() {
+            n += 1;
+            id = format!("{}-{n}", now.format("%Y%m%d-%H%M%S"));
+        }
+        *self.current.lock().unwrap() = Some(Checkpoint {
+            id,
+            prompt: prompt.chars().take(120).collect(),
+            started: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            files: Vec::new(),
+        });
+    }
+
+    /// Copy `path` before the turn first modifies it. Outside a turn, or for
+    /// a file already copied this turn, it does nothing.
+    pub fn snapshot(&self, path: &Path) -> Result<()> {
+        let mut current = self.current.lock().unwrap();
+        let Some(turn) = current.as_mut() else {
+            return Ok(());
+        };
+        if turn.files.iter().any(|f| f.path == path) {
+            return Ok(());
+        }
+        let dir = self.root.join(&turn.id);
+        let first = !dir.exists();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create checkpoint dir {}", dir.display()))?;
+        let existed = path.is_file();
+        if existed {
+            std::fs::copy(path, dir.join(blob_name(turn.files.len())))
+                .with_context(|| format!("cannot checkpoint {}", path.display()))?;
+        }
+        turn.files.push(CheckpointFile {
+            path: path.to_path_buf(),
+            existed,
+        });
+        std::fs::write(
+            dir.join("manifest.json"),
+            serde_json::to_string_pretty(turn)?,
+        )?;
+        if first {
+            self.prune();
+        }
+        Ok(())
+    }
+
+    /// Checkpoints on disk, newest first.
+    pub fn list(&self) -> Vec<Checkpoint> {
+        let Ok(entries) = std::fs::read_dir(&self.root) else {
+            return Vec::new();
+        };
+        let mut all: Vec<Checkpoint> = entries
+            .flatten()
+            .filter_map(|e| {
+                let manifest = std::fs::read_to_string(e.path().join("manifest.json")).ok()?;
+                serde_json::from_str(&manifest).ok()
+            })
+            .collect();
+        all.sort_by(|a, b| b.id.cmp(&a.id));
+        all
+    }
+
+    /// How the files differ now from checkpoint `id`, as unified diffs;
+    /// `path` narrows it to one file.
+    pub fn diff(&self, id: &str, path: Option<&str>) -> Result<String> {
+        let checkpoint = self.load(id)?;
+        let mut out = Vec::new();
+        for (i, file) in self.select(&checkpoint, path)? {
+            let label = self.label(&file.path);
+            let before = if file.existed {
+                std::fs::read(self.root.join(id).join(blob_name(i)))?
+            } else {
+                Vec::new()
+            };
+            let after = std::fs::read(&file.path).unwrap_or_default();
+            let text = match (String::from_utf8(before), String::from_utf8(after)) {
+                (Ok(before), Ok(after)) => unified_diff(&label, &before, &after)
+                    .unwrap_or_else(|| format!("{label}: unchanged since the checkpoint\n")),
+                (Err(before), Err(after)) if before.as_bytes() == after.as_bytes() => {
+                    format!("{label}: unchanged since the checkpoint\n")
+                }
+                _ => format!("Binary file {label} differs\n"),
+            };
+            out.push(text);
+        }
+        Ok(out.concat())
+    }
+
+    /// Put files back as they were before turn `id`: one file with `path`,
+    /// else all of them. Returns a line per file.
+    pub fn restore(&self, id: &str, path: Option<&str>) -> Result<Vec<String>> {
+        let checkpoint = self.load(id)?;
+        let mut done = Vec::new();
+        for (i, file) in self.select(&checkpoint, path)? {
+            let label = self.label(&file.path);
+            if let Err(e) = self.workspace.check(&file.path, Access::Write) {
+                done.push(format!("skipped {label}: {e}"));
+                continue;
+            }
+            self.snapshot(&file.path)?;
+            if file.existed {
+                if let Some(parent) = file.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(self.root.join(id).join(blob_name(i)), &file.path)
+                    .with_context(|| format!("cannot restore {label}"))?;
+                done.push(format!("restored {label}"));
+            } else if file.path.is_file() {
+                std::fs::remove_file(&file.path)?;
+                done.push(format!("removed {label} (created in that turn)"));
+            } else {
+                done.push(format!("{label}: already gone"));
+            }
+        }
+        Ok(done)
+    }
+
+    /// `restore` from outside a turn (the UI): it gets a checkpoint of its
+    /// own, so it can be undone like an agent turn.
+    pub fn restore_as_turn(&self, id: &str, path: Option<&str>) -> Result<Vec<String>> {
+        let target = path.map_or_else(|| format!("turn {id}"), |p| format!("{p} from turn {id}"));
+        self.begin_turn(&format!("Restore {target}"));
+        self.restore(id, path)
+    }
+
+    fn load(&self, id: &str) -> Result<Checkpoint> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("Invalid checkpoint id '{id}'");
+        }
+        let manifest = std::fs::read_to_string(self.root.join(id).join("manifest.json"))
+            .with_context(|| format!("No checkpoint '{id}'"))?;
+        Ok(serde_json::from_str(&manifest)?)
+    }
+
+    /// The checkpoint's files with their blob index, or just the one at `path`.
+    fn select<'a>(
+        &self,
+        checkpoint: &'a Checkpoint,
+        path: Option<&str>,
+    ) -> Result<Vec<(usize, &'a CheckpointFile)>> {
+        let files = checkpoint.files.iter().enumerate();
+        let Some(raw) = path else {
+            return Ok(files.collect());
+        };
+        let wanted = canonical_path(raw, self.workspace.work_dir());
+        let found: Vec<_> = files.filter(|(_, f)| f.path == wanted).collect();
+        if found.is_empty() {
+            bail!("{raw} is not in checkpoint {}", checkpoint.id);
+        }
+        Ok(found)
+    }
+
+    /// A path relative to the work dir when it's inside it.
+    fn label(&self, path: &Path) -> String {
+        let work_dir = canonical_path(".", self.workspace.work_dir());
+        path.strip_prefix(&work_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    fn prune(&self) {
+        for old in self.list().iter().skip(MAX_CHECKPOINTS) {
+            if let Err(e) = std::fs::remove_dir_all(self.root.join(&old.id)) {
+                tracing::warn!("could not prune checkpoint {}: {e}", old.id);
+            }
+        }
+    }
+
+    // ── Tools ────────────────────────────────────────────────────
+
+    pub fn tool_defs() -> Vec<crate::backend::ToolDef> {
+        use serde_json::json;
+        vec![
+            crate::backend::ToolDef {
+                name: "checkpoints".to_string(),
+                category: ToolCategory::FsRead,
+                description: "List file checkpoints, newest first: one per turn that changed \
+                    files, taken before the first change. With `turn`, show how the files \
+                    differ now from that checkpoint; `path` narrows it to one file."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "turn": { "type": "string", "description": "Checkpoint id to diff against" },
+                        "path": { "type": "string", "description": "Only this file" }
+                    }
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "restore_checkpoint".to_string(),
+                category: ToolCategory::FsWrite,
+                description: "Undo edits: put files back the way they were before a turn. \
+                    Restores one file with `path`, else every file that turn changed; files \
+                    the turn created are deleted. The restore itself can be undone."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "turn": { "type": "string", "description": "Checkpoint id from `checkpoints`" },
+                        "path": { "type": "string", "description": "Only this file" }
+                    },
+                    "required": ["turn"]
+                }),
+            },
+        ]
+    }
+
+    pub fn execute(&self, name: &str, input: &Value) -> Result<ToolOutput> {
+        let turn = input["turn"].as_str();
+        let path = input["path"].as_str();
+        let text = match (name, turn) {
+            ("checkpoints", None) => self.describe_list(),
+            ("checkpoints", Some(turn)) => {
+                let diff = self.diff(turn, path)?;
+                if diff.is_empty() {
+                    format!("Checkpoint {turn} has no files")
+                } else {
+                    diff
+                }
+            }
+            ("restore_checkpoint", Some(turn)) => self.restore(turn, path)?.join("\n"),
+            _ => bail!("missing turn"),
+        };
+        Ok((text, None))
+    }
+
+    fn describe_list(&self) -> String {
+        let all = self.list();
+        if all.is_empty() {
+            return "No checkpoints yet.".to_string();
+        }
+        let mut text = String::new();
+        for checkpoint in all.iter().take(LISTED) {
+            text.push_str(&format!(
+                "{} ({}) \"{}\"\n",
+                checkpoint.id, checkpoint.started, checkpoint.prompt
+            ));
+            for file in &checkpoint.files {
+                let new = if file.existed { "" } else { " (new)" };
+                text.push_str(&format!("  {}{new}\n", self.label(&file.path)));
+            }
+        }
+        if all.len() > LISTED {
+            text.push_str(&format!("… {} older checkpoints\n", all.len() - LISTED));
+        }
+        text
+    }
+}
+
+fn blob_name(index: usize) -> String {
+    format!("{index}.snap")
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Fixture {
+        checkpoints: Checkpoints,
+        work: PathBuf,
+        _dirs: (tempfile::TempDir, tempfile::TempDir),
+    }
+
+    fn fixture() -> Fixture {
+        let work_dir = tempfile::tempdir().unwrap();
+        let store = tempfile::tempdir().unwrap();
+        let work = work_dir.path().canonicalize().unwrap();
+        let workspace = Workspace::new(work.to_str().unwrap(), &[], &[]);
+        Fixture {
+            checkpoints: Checkpoints::new(store.path().to_path_buf(), workspace),
+            work,
+            _dirs: (work_dir, store),
+        }
+    }
+
+    #[test]
+    fn snapshots_once_per_turn_and_restores_the_turn() {
+        let f = fixture();
+        let a = f.work.join("a.txt");
+        let b = f.work.join("sub/b.txt");
+        std::fs::write(&a, "original\n").unwrap();
+
+        f.checkpoints.begin_turn("refactor things");
+        f.checkpoints.snapshot(&a).unwrap();
+        std::fs::write(&a, "first edit\n").unwrap();
+        f.checkpoints.snapshot(&a).unwrap();
+        std::fs::write(&a, "second edit\n").unwrap();
+        f.checkpoints.snapshot(&b).unwrap();
+        std::fs::create_dir_all(b.parent().unwrap()).unwrap();
+        std::fs::write(&b, "new file\n").unwrap();
+
+        let list = f.checkpoints.list();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].prompt, "refactor things");
+        assert_eq!(
+            list[0].files,
+            vec![
+                CheckpointFile {
+                    path: a.clone(),
+                    existed: true
+                },
+                CheckpointFile {
+                    path: b.clone(),
+                    existed: false
+                },
+            ]
+        );
+
+        let done = f.checkpoints.restore(&list[0].id, None).unwrap();
+        assert_eq!(
+            done,
+            ["restored a.txt", "removed sub/b.txt (created in that turn)"]
+        );
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "original\n");
+        assert!(!b.exists());
+    }
+
+    #[test]
+    fn diff_and_single_file_restore() {
+        let f = fixture();
+        let a = f.work.join("a.txt");
+        let b = f.work.join("b.txt");
+        std::fs::write(&a, "one\ntwo\n").unwrap();
+        std::fs::write(&b, "keep\n").unwrap();
+        f.checkpoints.begin_turn("edit");
+        for path in [&a, &b] {
+            f.checkpoints.snapshot(path).unwrap();
+        }
+        std::fs::write(&a, "one\n2\n").unwrap();
+        std::fs::write(&b, "changed\n").unwrap();
+        let id = f.checkpoints.list()[0].id.clone();
+
+        let diff = f.checkpoints.diff(&id, Some("a.txt")).unwrap();
+        assert!(
+            diff.contains("--- a/a.txt") && diff.contains("-two\n+2\n"),
+            "{diff}"
+        );
+        assert!(!diff.contains("b.txt"), "{diff}");
+
+        f.checkpoints.restore(&id, Some("a.txt")).unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "one\ntwo\n");
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "changed\n");
+        assert!(f
+            .checkpoints
+            .diff(&id, Some("a.txt"))
+            .unwrap()
+            .contains("unchanged"));
+        assert!(f.checkpoints.restore(&id, Some("c.txt")).is_err());
+    }
+
+    #[test]
+    fn a_restore_during_a_turn_can_be_undone() {
+        let f = fixture();
+        let a = f.work.join("a.txt");
+        std::fs::write(&a, "v1").unwrap();
+        f.checkpoints.begin_turn("first");
+        f.checkpoints.snapshot(&a).unwrap();
+        std::fs::write(&a, "v2").unwrap();
+        let first = f.checkpoints.list()[0].id.clone();
+
+        f.checkpoints.begin_turn("undo");
+        f.checkpoints.restore(&first, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "v1");
+
+        let undo = f.checkpoints.list()[0].clone();
+        assert_ne!(undo.id, first);
+        f.checkpoints.restore(&undo.id, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "v2");
+    }
+
+    #[test]
+    fn restores_from_the_ui_get_their_own_checkpoint() {
+        let f = fixture();
+        let a = f.work.join("a.txt");
+        std::fs::write(&a, "v1").unwrap();
+        f.checkpoints.begin_turn("agent turn");
+        f.checkpoints.snapshot(&a).unwrap();
+        std::fs::write(&a, "v2").unwrap();
+        let turn = f.checkpoints.list()[0].id.clone();
+
+        f.checkpoints.restore_as_turn(&turn, Some("a.txt")).unwrap();
+        let list = f.checkpoints.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].prompt, format!("Restore a.txt from turn {turn}"));
+        f.checkpoints.restore(&list[0].id, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "v2");
+    }
+
+    #[test]
+    fn nothing_is_recorded_outside_a_turn_or_for_bad_ids() {
+        let f = fixture();
+        let a = f.work.join("a.txt");
+        std::fs::write(&a, "x").unwrap();
+        f.checkpoints.snapshot(&a).unwrap();
+        assert!(f.checkpoints.list().is_empty());
+        for id in ["", "../etc", "a/b"] {
+            assert!(f.checkpoints.diff(id, None).is_err(), "{id}");
+        }
+    }
+
+    #[test]
+    fn tools_list_diff_and_restore() {
+        let f = fixture();
+        let a = f.work.join("a.txt");
+        std::fs::write(&a, "before\n").unwrap();
+        assert_eq!(
+            f.checkpoints.execute("checkpoints", &json!({})).unwrap().0,
+            "No checkpoints yet."
+        );
+        f.checkpoints.begin_turn("make a change");
+        f.checkpoints.snapshot(&a).unwrap();
+        std::fs::write(&a, "after\n").unwrap();
+        let id = f.checkpoints.list()[0].id.clone();
+
+        let list = f.checkpoints.execute("checkpoints", &json!({})).unwrap().0;
+        assert!(
+            list.contains(&id) && list.contains("\"make a change\"") && list.contains("  a.txt")
+        );
+        let diff = f
+            .checkpoints
+            .execute("checkpoints", &json!({ "turn": id }))
+            .unwrap()
+            .0;
+        assert!(diff.contains("-before\n+after\n"), "{diff}");
+        let out = f
+            .checkpoints
+            .execute("restore_checkpoint", &json!({ "turn": id }))
+            .unwrap()
+            .0;
+        assert_eq!(out, "restored a.txt");
+        assert!(f
+            .checkpoints
+            .execute("restore_checkpoint", &json!({}))
+            .is_err());
+    }
+
+    #[test]
+    fn old_checkpoints_are_pruned() {
+        let f = fixture();
+        for i in 0..MAX_CHECKPOINTS + 3 {
+            let dir = f.checkpoints.root.join(format!("20200101-{i:06}"));
+            std::fs::create_dir_all(&dir).unwrap();
+            let checkpoint = Checkpoint {
+                id: format!("20200101-{i:06}"),
+                prompt: String::new(),
+                started: String::new(),
+                files: Vec::new(),
+            };
+            std::fs::write(
+                dir.join("manifest.json"),
+                serde_json::to_string(&checkpoint).unwrap(),
+            )
+            .unwrap();
+        }
+        f.checkpoints.begin_turn("new");
+        f.checkpoints.snapshot(&f.work.join("x.txt")).unwrap();
+        let list = f.checkpoints.list();
+        assert_eq!(list.len(), MAX_CHECKPOINTS);
+        assert_eq!(list.last().unwrap().id, "20200101-000004");
+    }
+}
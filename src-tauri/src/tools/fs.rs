@@ -1,27 +1,61 @@
-/// Coding tools: read_file, write_file, edit_file, list_files, grep
+/// Coding tools: read_file, write_file, edit_file, apply_patch, list_files, grep
 ///
-/// Inspired by opencode / Claude Code tool design.
+/// Inspired by opencode / Claude Code tool design. Every path goes through
+/// the `Workspace`, so nothing outside the allowed roots is touched and
+/// protected paths are never written.
 use anyhow::{bail, Result};
 use serde_json::Value;
 
-use super::ToolOutput;
+use super::{patch, search, ToolOutput};
+use crate::workspace::{Access, Workspace};
 
 pub struct FsTool {
     pub work_dir: String,
+    workspace: Workspace,
 }
 
 impl FsTool {
+    /// Confined to `work_dir` alone; see `with_workspace`.
     pub fn new(work_dir: String) -> Self {
-        Self { work_dir }
+        let workspace = Workspace::new(&work_dir, &[], &[]);
+        Self { work_dir, workspace }
     }
 
-    fn resolve_path(&self, raw: &str) -> std::path::PathBuf {
-        let p = std::path::Path::new(raw);
-        if p.is_absolute() {
-            p.to_path_buf()
-        } else {
-            std::path::Path::new(&self.work_dir).join(p)
-        }
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = workspace;
+        self
+    }
+
+    fn resolve_path(&self, raw: &str, access: Access) -> Result<std::path::PathBuf> {
+        self.workspace.resolve(raw, access).map_err(|e| anyhow::anyhow!("Access denied: {e}"))
+    }
+
+    /// Where listed paths are shown relative to.
+    fn root(&self) -> std::path::PathBuf {
+        self.resolve_path(&self.work_dir, Access::Read)
+            .unwrap_or_else(|_| self.work_dir.clone().into())
+    }
+
+    /// Whether a path found while walking a directory may be shown.
+    fn readable(&self, path: &std::path::Path) -> bool {
+        path.canonicalize()
+            .is_ok_and(|p| self.workspace.check(&p, Access::Read).is_ok())
+    }
+
+    /// The files a write/edit/patch call is about to change, as given and
+    /// resolved; paths that may not be written are left out.
+    pub fn edit_targets(&self, name: &str, input: &Value) -> Vec<(String, std::path::PathBuf)> {
+        let raw: Vec<String> = match name {
+            "write_file" | "edit_file" => input["path"].as_str().map(str::to_string).into_iter().collect(),
+            "apply_patch" => patch::touched_paths(input),
+            _ => Vec::new(),
+        };
+        raw.into_iter()
+            .filter_map(|r| {
+                let path = self.resolve_path(&r, Access::Write).ok()?;
+                Some((r, path))
+            })
+            .collect()
     }
 
     // ── Tool definitions ─────────────────────────────────────────
@@ -31,6 +65,7 @@ impl FsTool {
         vec![
             crate::backend::ToolDef {
                 name: "read_file".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
                 description: "Read a file. Optionally specify line range.".to_string(),
                 input_schema: json!({
                     "type": "object",
@@ -44,6 +79,7 @@ impl FsTool {
             },
             crate::backend::ToolDef {
                 name: "write_file".to_string(),
+                category: crate::permissions::ToolCategory::FsWrite,
                 description: "Write (overwrite) a file with given content.".to_string(),
                 input_schema: json!({
                     "type": "object",
@@ -56,6 +92,7 @@ impl FsTool {
             },
             crate::backend::ToolDef {
                 name: "edit_file".to_string(),
+                category: crate::permissions::ToolCategory::FsWrite,
                 description: "Replace an exact string in a file. old_string must be unique.".to_string(),
                 input_schema: json!({
                     "type": "object",
@@ -67,26 +104,74 @@ impl FsTool {
                     "required": ["path", "old_string", "new_string"]
                 }),
             },
+            crate::backend::ToolDef {
+                name: "apply_patch".to_string(),
+                category: crate::permissions::ToolCategory::FsWrite,
+                description: "Change several places or files in one call: a unified diff in \
+                    `patch` (--- a/path, +++ b/path, @@ hunks; /dev/null creates or deletes), \
+                    or a list of `edits`. Hunks are matched by context, tolerating shifted \
+                    line numbers and whitespace. All or nothing: if any hunk or edit fails, no \
+                    file is changed. Reports each hunk."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "patch": { "type": "string", "description": "Unified diff; paths relative to work_dir" },
+                        "edits": {
+                            "type": "array",
+                            "description": "Exact replacements, applied in order",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "path": { "type": "string" },
+                                    "old_string": { "type": "string", "description": "Text to replace; empty creates a new file" },
+                                    "new_string": { "type": "string" },
+                                    "replace_all": { "type": "boolean", "description": "Replace every occurrence (default: must be unique)" }
+                                },
+                                "required": ["path", "old_string", "new_string"]
+                            }
+                        }
+                    }
+                }),
+            },
             crate::backend::ToolDef {
                 name: "list_files".to_string(),
-                description: "List files matching a glob pattern under a directory.".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
+                description: "List files under a directory, sorted, skipping .gitignore'd files and .git/. \
+                    Paths are relative to work_dir.".to_string(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
                         "path": { "type": "string", "description": "Directory to search (default: work_dir)" },
-                        "pattern": { "type": "string", "description": "Glob pattern (default: **/*)" }
+                        "pattern": { "type": "string", "description": "Glob, e.g. *.rs (any depth) or src/**/*.ts (default: all files)" },
+                        "offset": { "type": "integer", "description": "Skip this many files (for the next page)" },
+                        "limit": { "type": "integer", "description": "Files per page (default: 200)" }
                     }
                 }),
             },
             crate::backend::ToolDef {
                 name: "grep".to_string(),
-                description: "Search file contents with a regex pattern.".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
+                description: "Search file contents with a regex, skipping .gitignore'd files and .git/. \
+                    Binary files are only reported as matching.".to_string(),
                 input_schema: json!({
                     "type": "object",
                     "properties": {
                         "pattern": { "type": "string", "description": "Regex pattern" },
                         "path": { "type": "string", "description": "File or directory to search" },
-                        "include": { "type": "string", "description": "File glob filter e.g. *.rs" }
+                        "include": { "type": "string", "description": "File glob filter e.g. *.rs" },
+                        "case_insensitive": { "type": "boolean" },
+                        "fixed_strings": { "type": "boolean", "description": "Treat pattern as literal text, not a regex" },
+                        "context": { "type": "integer", "description": "Lines of context around each match" },
+                        "before_context": { "type": "integer", "description": "Lines before each match (like grep -B)" },
+                        "after_context": { "type": "integer", "description": "Lines after each match (like grep -A)" },
+                        "output_mode": {
+                            "type": "string",
+                            "enum": ["content", "files_with_matches", "count"],
+                            "description": "Matching lines (default), matching file paths, or match counts per file"
+                        },
+                        "offset": { "type": "integer", "description": "Skip this many matches (files in other modes)" },
+                        "limit": { "type": "integer", "description": "Matches per page (default: 100; 200 files in other modes)" }
                     },
                     "required": ["pattern"]
                 }),
@@ -98,7 +183,7 @@ impl FsTool {
 
     pub fn read_file(&self, input: &Value) -> Result<ToolOutput> {
         let raw = input["path"].as_str().ok_or_else(|| anyhow::anyhow!("missing path"))?;
-        let path = self.resolve_path(raw);
+        let path = self.resolve_path(raw, Access::Read)?;
 
         if !path.exists() {
             bail!("File not found: {}", path.display());
@@ -134,7 +219,7 @@ impl FsTool {
     pub fn write_file(&self, input: &Value) -> Result<ToolOutput> {
         let raw = input["path"].as_str().ok_or_else(|| anyhow::anyhow!("missing path"))?;
         let content = input["content"].as_str().ok_or_else(|| anyhow::anyhow!("missing content"))?;
-        let path = self.resolve_path(raw);
+        let path = self.resolve_path(raw, Access::Write)?;
 
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
@@ -148,7 +233,7 @@ impl FsTool {
         let raw = input["path"].as_str().ok_or_else(|| anyhow::anyhow!("missing path"))?;
         let old = input["old_string"].as_str().ok_or_else(|| anyhow::anyhow!("missing old_string"))?;
         let new = input["new_string"].as_str().ok_or_else(|| anyhow::anyhow!("missing new_string"))?;
-        let path = self.resolve_path(raw);
+        let path = self.resolve_path(raw, Access::Write)?;
 
         if !path.exists() {
             bail!("File not found: {}", path.display());
@@ -172,83 +257,122 @@ impl FsTool {
 
     pub fn list_files(&self, input: &Value) -> Result<ToolOutput> {
         let base_raw = input["path"].as_str().unwrap_or(&self.work_dir);
-        let base = self.resolve_path(base_raw);
-        let pattern = input["pattern"].as_str().unwrap_or("**/*");
-
-        // Use walkdir for traversal, apply simple glob filter
-        let full_pattern = base.join(pattern);
-        let pattern_str = full_pattern.to_string_lossy();
-
-        let paths: Vec<String> = glob::glob(&pattern_str)
-            .map_err(|e| anyhow::anyhow!("{e}"))?
-            .filter_map(|r| r.ok())
-            .filter(|p| p.is_file())
-            .take(200)
-            .map(|p| p.display().to_string())
-            .collect();
-
-        if paths.is_empty() {
-            return Ok(("No files found".to_string(), None));
-        }
-
-        Ok((paths.join("\n"), None))
+        let base = self.resolve_path(base_raw, Access::Read)?;
+        let out = search::list_files(&base, &self.root(), input, |p| self.readable(p))?;
+        Ok((out, None))
     }
 
     pub fn grep(&self, input: &Value) -> Result<ToolOutput> {
-        let pattern = input["pattern"].as_str().ok_or_else(|| anyhow::anyhow!("missing pattern"))?;
         let base_raw = input["path"].as_str().unwrap_or(&self.work_dir);
-        let base = self.resolve_path(base_raw);
-        let include = input["include"].as_str();
-
-        let regex = regex::Regex::new(pattern)?;
-
-        let mut results = Vec::new();
-        let walk = walkdir::WalkDir::new(&base)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file());
-
-        for entry in walk {
-            let path = entry.path();
+        let base = self.resolve_path(base_raw, Access::Read)?;
+        let out = search::grep(&base, &self.root(), input, |p| self.readable(p))?;
+        Ok((out, None))
+    }
 
-            // Apply include filter
-            if let Some(pat) = include {
-                let name = path.file_name().unwrap_or_default().to_string_lossy();
-                if !glob_match_simple(pat, &name) {
+    /// Applies every file patch in memory first; only if all of them apply
+    /// are the files written, and a failed write rolls the others back.
+    pub fn apply_patch(&self, input: &Value) -> Result<ToolOutput> {
+        let files = patch::parse_input(input)?;
+        // Path → (original, new) contents; `None` = absent / deleted.
+        let mut states: Vec<(std::path::PathBuf, Option<String>, Option<String>)> = Vec::new();
+        let mut report = Vec::new();
+        let (mut ops, mut failed) = (0, 0);
+
+        for file in &files {
+            report.push(file.path.clone());
+            let path = match self.resolve_path(&file.path, Access::Write) {
+                Ok(path) => path,
+                Err(e) => {
+                    failed += 1;
+                    report.push(format!("  FAILED — {e}"));
                     continue;
                 }
-            }
-
-            // Skip binary/large files
-            if let Ok(meta) = path.metadata() {
-                if meta.len() > 5_000_000 {
-                    continue;
+            };
+            let slot = match states.iter().position(|(p, _, _)| *p == path) {
+                Some(i) => i,
+                None => {
+                    let original = if path.is_file() {
+                        match std::fs::read_to_string(&path) {
+                            Ok(text) => Some(text),
+                            Err(e) => {
+                                failed += 1;
+                                report.push(format!("  FAILED — cannot read: {e}"));
+                                continue;
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    states.push((path, original.clone(), original));
+                    states.len() - 1
                 }
+            };
+            let current = &mut states[slot].2;
+            let exists_error = match (file.kind, current.is_some()) {
+                (patch::Kind::Create, true) => Some("already exists"),
+                (patch::Kind::Modify | patch::Kind::Delete, false) => Some("file not found"),
+                _ => None,
+            };
+            if let Some(e) = exists_error {
+                failed += 1;
+                report.push(format!("  FAILED — {e}"));
+                continue;
             }
 
-            let Ok(content) = std::fs::read_to_string(path) else {
-                continue;
+            let mut applier = patch::Applier::new(current.as_deref().unwrap_or(""));
+            for (n, op) in file.ops.iter().enumerate() {
+                ops += 1;
+                let line = match applier.apply(op) {
+                    Ok(note) if note.is_empty() => "ok".to_string(),
+                    Ok(note) => format!("ok, {note}"),
+                    Err(e) => {
+                        failed += 1;
+                        format!("FAILED — {e}")
+                    }
+                };
+                report.push(format!("  {}: {line}", op.describe(n + 1)));
+            }
+            *current = match file.kind {
+                patch::Kind::Delete => None,
+                _ => Some(applier.text.render()),
             };
+        }
 
-            for (i, line) in content.lines().enumerate() {
-                if regex.is_match(line) {
-                    results.push(format!("{}:{}: {}", path.display(), i + 1, line.trim()));
-                    if results.len() >= 100 {
-                        break;
-                    }
+        if failed > 0 {
+            let text = format!(
+                "Patch not applied (no file was changed): {failed} failure(s) below.\n{}",
+                report.join("\n")
+            );
+            return Ok((text, None));
+        }
+
+        let mut written: Vec<&(std::path::PathBuf, Option<String>, Option<String>)> = Vec::new();
+        for state in &states {
+            let (path, _, new) = state;
+            let result = match new {
+                Some(text) => write_atomically(path, text),
+                None => std::fs::remove_file(path).map_err(Into::into),
+            };
+            if let Err(e) = result {
+                // Put back what was already written.
+                for (path, original, _) in written {
+                    let _ = match original {
+                        Some(text) => write_atomically(path, text),
+                        None => std::fs::remove_file(path).map_err(Into::into),
+                    };
                 }
+                bail!("Writing {} failed, so no file was changed: {e}", path.display());
             }
-            if results.len() >= 100 {
-                break;
-            }
+            written.push(state);
         }
 
-        if results.is_empty() {
-            return Ok(("No matches found".to_string(), None));
+        let created = states.iter().filter(|(_, old, new)| old.is_none() && new.is_some()).count();
+        let deleted = states.iter().filter(|(_, old, new)| old.is_some() && new.is_none()).count();
+        let mut summary = format!("Applied {ops} change(s) to {} file(s)", states.len());
+        if created + deleted > 0 {
+            summary.push_str(&format!(" ({created} created, {deleted} deleted)"));
         }
-
-        Ok((results.join("\n"), None))
+        Ok((format!("{summary}.\n{}", report.join("\n")), None))
     }
 
     /// Dispatch by tool name.
@@ -257,6 +381,7 @@ impl FsTool {
             "read_file" => self.read_file(input),
             "write_file" => self.write_file(input),
             "edit_file" => self.edit_file(input),
+            "apply_patch" => self.apply_patch(input),
             "list_files" => self.list_files(input),
             "grep" => self.grep(input),
             _ => bail!("Unknown fs tool: {name}"),
@@ -264,14 +389,30 @@ impl FsTool {
     }
 }
 
-fn glob_match_simple(pattern: &str, name: &str) -> bool {
-    if pattern == "*" {
-        return true;
+/// Write through a temp file in the same directory, so a file is never left half-written.
+fn write_atomically(path: &std::path::Path, text: &str) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow::anyhow!("no parent directory"))?;
+    std::fs::create_dir_all(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    std::io::Write::write_all(&mut tmp, text.as_bytes())?;
+    if let Ok(meta) = std::fs::metadata(path) {
+        let _ = std::fs::set_permissions(tmp.path(), meta.permissions());
     }
-    if let Some(ext) = pattern.strip_prefix("*.") {
-        return name.ends_with(&format!(".{ext}"));
+    tmp.persist(path)?;
+    Ok(())
+}
+
+/// Unified diff of one file's change, or `None` if nothing changed.
+pub fn unified_diff(label: &str, before: &str, after: &str) -> Option<String> {
+    if before == after {
+        return None;
     }
-    pattern == name
+    let diff = similar::TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{label}"), &format!("b/{label}"))
+        .to_string();
+    Some(diff)
 }
 
 // ── Tests ─────────────────────────────────────────────────────────
@@ -414,4 +555,108 @@ mod tests {
         })).unwrap();
         assert!(out.0.contains("No matches"));
     }
+
+    #[test]
+    fn edits_produce_a_unified_diff() {
+        let diff = unified_diff("src/a.rs", "one\ntwo\nthree\n", "one\n2\nthree\n").unwrap();
+        assert!(diff.starts_with("--- a/src/a.rs\n+++ b/src/a.rs\n@@"), "{diff}");
+        assert!(diff.contains("-two\n+2\n"), "{diff}");
+        assert_eq!(unified_diff("x", "same", "same"), None);
+
+        let (tool, dir) = tmp_tool();
+        let targets = tool.edit_targets("write_file", &serde_json::json!({"path": "new.txt"}));
+        let new = dir.path().canonicalize().unwrap().join("new.txt");
+        assert_eq!(targets, vec![("new.txt".to_string(), new)]);
+        assert!(tool.edit_targets("read_file", &serde_json::json!({"path": "new.txt"})).is_empty());
+        assert!(tool.edit_targets("write_file", &serde_json::json!({"path": "/etc/x"})).is_empty());
+    }
+
+    #[test]
+    fn paths_outside_work_dir_are_refused() {
+        let (tool, _dir) = tmp_tool();
+        let outside = tempfile::TempDir::new().unwrap();
+        let target = outside.path().join("victim.txt");
+        let err = tool
+            .write_file(&json!({ "path": target, "content": "pwned" }))
+            .unwrap_err();
+        assert!(err.to_string().contains("outside the workspace"), "{err}");
+        assert!(!target.exists());
+
+        let escape = format!("../{}/victim.txt", outside.path().file_name().unwrap().to_string_lossy());
+        assert!(tool.write_file(&json!({ "path": escape, "content": "x" })).is_err());
+        assert!(tool.read_file(&json!({ "path": "../../etc/passwd" })).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_leak_outside_work_dir() {
+        let (tool, dir) = tmp_tool();
+        let outside = tempfile::TempDir::new().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "hunter2").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+        write_tmp(&dir, "ok.txt", "hunter2");
+
+        let err = tool.read_file(&json!({ "path": "link/secret.txt" })).unwrap_err();
+        assert!(err.to_string().contains("symlink"), "{err}");
+        let out = tool.grep(&json!({ "pattern": "hunter2" })).unwrap();
+        assert!(out.0.contains("ok.txt") && !out.0.contains("secret.txt"), "{}", out.0);
+        let out = tool.list_files(&json!({ "pattern": "**/*.txt" })).unwrap();
+        assert!(out.0.contains("ok.txt") && !out.0.contains("secret.txt"), "{}", out.0);
+    }
+
+    #[test]
+    fn git_dir_is_never_written() {
+        let (tool, dir) = tmp_tool();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        write_tmp(&dir, ".git/HEAD", "ref: refs/heads/main\n");
+        let err = tool
+            .edit_file(&json!({ "path": ".git/HEAD", "old_string": "main", "new_string": "evil" }))
+            .unwrap_err();
+        assert!(err.to_string().contains("protected"), "{err}");
+        assert!(tool.read_file(&json!({ "path": ".git/HEAD" })).unwrap().0.contains("main"));
+    }
+
+    #[test]
+    fn apply_patch_changes_several_files_at_once() {
+        let (tool, dir) = tmp_tool();
+        write_tmp(&dir, "a.rs", "fn a() {\n    1\n}\n");
+        write_tmp(&dir, "gone.txt", "bye\n");
+        let patch = "--- a/a.rs\n+++ b/a.rs\n@@ -1,3 +1,3 @@\n fn a() {\n-    1\n+    2\n }\n\
+                     --- /dev/null\n+++ b/sub/new.rs\n@@ -0,0 +1 @@\n+fn new() {}\n\
+                     --- a/gone.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n";
+        let input = serde_json::json!({
+            "patch": patch,
+            "edits": [{ "path": "a.rs", "old_string": "fn a()", "new_string": "fn b()" }]
+        });
+        let out = tool.apply_patch(&input).unwrap().0;
+        assert!(out.starts_with("Applied 4 change(s) to 3 file(s) (1 created, 1 deleted)."), "{out}");
+        assert!(out.contains("a.rs\n  hunk 1 @@ -1,3 +1,3 @@: ok"), "{out}");
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.rs")).unwrap(), "fn b() {\n    2\n}\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("sub/new.rs")).unwrap(), "fn new() {}\n");
+        assert!(!dir.path().join("gone.txt").exists());
+    }
+
+    #[test]
+    fn apply_patch_is_all_or_nothing() {
+        let (tool, dir) = tmp_tool();
+        write_tmp(&dir, "a.txt", "one\ntwo\n");
+        write_tmp(&dir, "b.txt", "three\n");
+        let input = serde_json::json!({ "edits": [
+            { "path": "a.txt", "old_string": "one", "new_string": "1" },
+            { "path": "b.txt", "old_string": "missing", "new_string": "x" },
+            { "path": ".git/config", "old_string": "", "new_string": "x" }
+        ]});
+        let out = tool.apply_patch(&input).unwrap().0;
+        assert!(out.starts_with("Patch not applied (no file was changed): 2 failure(s) below."), "{out}");
+        assert!(out.contains("a.txt\n  edit 1: ok"), "{out}");
+        assert!(out.contains("b.txt\n  edit 1: FAILED — old_string not found"), "{out}");
+        assert!(out.contains(".git/config\n  FAILED — Access denied"), "{out}");
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\ntwo\n");
+
+        let create_existing = serde_json::json!({
+            "patch": "--- /dev/null\n+++ b/a.txt\n@@ -0,0 +1 @@\n+x\n"
+        });
+        let out = tool.apply_patch(&create_existing).unwrap().0;
+        assert!(out.contains("FAILED — already exists"), "{out}");
+    }
 }
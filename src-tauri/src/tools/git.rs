@@ -0,0 +1,759 @@
+/// Git tools: git_status, git_diff, git_log, git_commit, git_branch
+///
+/// Run the `git` CLI in work_dir with machine-readable formats (porcelain v2,
+/// NUL-separated fields) and return a compact summary instead of raw output,
+/// bounded in size. `git_status`, `git_diff`, `git_log` and listing branches
+/// only read, so they count as `FsRead`; committing and creating or switching
+/// branches are `FsWrite`. Pathspecs go through the `Workspace` like the file
+/// tools' paths.
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+use super::ToolOutput;
+use crate::workspace::{Access, Workspace};
+
+const TIMEOUT_SECS: u64 = 30;
+/// Diffs are cut here; `path` narrows them.
+const MAX_DIFF_BYTES: usize = 32_768;
+/// Files listed per status section, and branches listed.
+const MAX_ENTRIES: usize = 100;
+/// Ahead of every git call. The repo's own config is not trusted: it must not
+/// make git run a program (fsmonitor, hooks, a pager) behind the permission
+/// prompts, e.g. one planted by a sandboxed command or a cloned repo.
+const HARDENED: &[&str] = &[
+    "--no-pager",
+    "-c",
+    "core.fsmonitor=false",
+    "-c",
+    "core.hooksPath=/dev/null",
+];
+const DEFAULT_LOG_COUNT: u64 = 10;
+const MAX_LOG_COUNT: u64 = 50;
+
+/// Whether a git tool call only reads the repository.
+pub fn is_read_only(tool: &str, input: &Value) -> bool {
+    match tool {
+        "git_status" | "git_diff" | "git_log" => true,
+        "git_branch" => input["create"].is_null() && input["switch"].is_null(),
+        _ => false,
+    }
+}
+
+// ── Status ────────────────────────────────────────────────────────
+
+/// `git status --porcelain=v2 --branch -z`, parsed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// `None` when HEAD is detached.
+    pub branch: Option<String>,
+    /// Abbreviated commit, `None` before the first commit.
+    pub commit: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    /// `(code, path)`, code as in `git status --short`; renames read "old -> new".
+    pub staged: Vec<(char, String)>,
+    pub unstaged: Vec<(char, String)>,
+    pub untracked: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+impl Status {
+    pub fn parse(text: &str) -> Self {
+        let mut status = Status::default();
+        let mut records = text.split('\0').filter(|r| !r.is_empty());
+        while let Some(record) = records.next() {
+            let (kind, rest) = record.split_once(' ').unwrap_or((record, ""));
+            match kind {
+                "#" => status.header(rest),
+                "1" | "2" => {
+                    // XY sub mH mI mW hH hI [Xscore] path
+                    let fields = if kind == "1" { 8 } else { 9 };
+                    let mut parts = rest.splitn(fields, ' ');
+                    let xy: Vec<char> = parts.next().unwrap_or("..").chars().collect();
+                    let mut path = parts.last().unwrap_or("").to_string();
+                    if kind == "2" {
+                        let from = records.next().unwrap_or("");
+                        path = format!("{from} -> {path}");
+                    }
+                    if let Some(&x) = xy.first().filter(|&&x| x != '.') {
+                        status.staged.push((x, path.clone()));
+                    }
+                    if let Some(&y) = xy.get(1).filter(|&&y| y != '.') {
+                        status.unstaged.push((y, path));
+                    }
+                }
+                "u" => {
+                    let path = rest.splitn(10, ' ').last().unwrap_or("");
+                    status.conflicts.push(path.to_string());
+                }
+                "?" => status.untracked.push(rest.to_string()),
+                _ => {}
+            }
+        }
+        status
+    }
+
+    fn header(&mut self, line: &str) {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "branch.oid" if value != "(initial)" => {
+                self.commit = Some(value.chars().take(7).collect());
+            }
+            "branch.head" if value != "(detached)" => self.branch = Some(value.to_string()),
+            "branch.upstream" => self.upstream = Some(value.to_string()),
+            "branch.ab" => {
+                for n in value.split(' ') {
+                    if let Some(a) = n.strip_prefix('+') {
+                        self.ahead = a.parse().unwrap_or(0);
+                    } else if let Some(b) = n.strip_prefix('-') {
+                        self.behind = b.parse().unwrap_or(0);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.staged.is_empty()
+            && self.unstaged.is_empty()
+            && self.untracked.is_empty()
+            && self.conflicts.is_empty()
+    }
+
+    /// "main (ahead 1 of origin/main)", "detached at 1a2b3c4".
+    pub fn head(&self) -> String {
+        let mut head = match (&self.branch, &self.commit) {
+            (Some(branch), _) => branch.clone(),
+            (None, Some(commit)) => format!("detached at {commit}"),
+            (None, None) => "detached".to_string(),
+        };
+        if let Some(upstream) = &self.upstream {
+            let track = match (self.ahead, self.behind) {
+                (0, 0) => format!("up to date with {upstream}"),
+                (a, 0) => format!("ahead {a} of {upstream}"),
+                (0, b) => format!("behind {upstream} by {b}"),
+                (a, b) => format!("ahead {a}, behind {b} of {upstream}"),
+            };
+            head.push_str(&format!(" ({track})"));
+        }
+        head
+    }
+
+    /// One line for the project context: branch plus how dirty the tree is.
+    pub fn summary(&self) -> String {
+        if self.is_clean() {
+            return format!("{}, clean", self.head());
+        }
+        let mut parts = Vec::new();
+        let mut count = |n: usize, what: &str| {
+            if n > 0 {
+                parts.push(format!("{n} {what}"));
+            }
+        };
+        count(self.staged.len(), "staged");
+        count(self.unstaged.len(), "modified");
+        count(self.untracked.len(), "untracked");
+        count(self.conflicts.len(), "conflicted");
+        format!("{}, {}", self.head(), parts.join(", "))
+    }
+
+    fn render(&self) -> String {
+        let mut out = vec![format!("Branch: {}", self.head())];
+        if self.is_clean() {
+            out.push("Working tree clean".to_string());
+            return out.join("\n");
+        }
+        let changes = |v: &[(char, String)]| -> Vec<String> {
+            v.iter().map(|(c, p)| format!("{c} {p}")).collect()
+        };
+        section(&mut out, "Conflicts", &self.conflicts);
+        section(&mut out, "Staged", &changes(&self.staged));
+        section(&mut out, "Unstaged", &changes(&self.unstaged));
+        section(&mut out, "Untracked", &self.untracked);
+        out.join("\n")
+    }
+}
+
+fn section(out: &mut Vec<String>, title: &str, entries: &[String]) {
+    if entries.is_empty() {
+        return;
+    }
+    out.push(format!("{title} ({}):", entries.len()));
+    out.extend(entries.iter().take(MAX_ENTRIES).map(|e| format!("  {e}")));
+    if entries.len() > MAX_ENTRIES {
+        out.push(format!("  … and {} more", entries.len() - MAX_ENTRIES));
+    }
+}
+
+/// The status of the repository `work_dir` is in, or `None` outside one.
+/// Blocking; for building the system prompt.
+pub fn status_blocking(work_dir: &str) -> Option<Status> {
+    let out = std::process::Command::new("git")
+        .args(HARDENED)
+        .args([
+            "--no-optional-locks",
+            "status",
+            "--porcelain=v2",
+            "--branch",
+            "-z",
+        ])
+        .current_dir(work_dir)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| Status::parse(&String::from_utf8_lossy(&out.stdout)))
+}
+
+// ── Tool ──────────────────────────────────────────────────────────
+
+pub struct GitTool {
+    pub work_dir: String,
+    workspace: Workspace,
+}
+
+impl GitTool {
+    pub fn new(work_dir: String, workspace: Workspace) -> Self {
+        Self {
+            work_dir,
+            workspace,
+        }
+    }
+
+    pub fn tool_defs() -> Vec<crate::backend::ToolDef> {
+        use serde_json::json;
+        let path = json!({ "type": "string", "description": "Limit to this file or directory (optional)" });
+        vec![
+            crate::backend::ToolDef {
+                name: "git_status".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
+                description: "Show the current branch, its upstream, and staged, unstaged, \
+                              untracked and conflicted files."
+                    .to_string(),
+                input_schema: json!({ "type": "object", "properties": {} }),
+            },
+            crate::backend::ToolDef {
+                name: "git_diff".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
+                description: "Show changes as a unified diff with a per-file summary: unstaged \
+                              by default, staged with `staged`, or against a commit/branch with `base`."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "staged": { "type": "boolean", "description": "Diff the index against HEAD" },
+                        "base": { "type": "string", "description": "Commit or branch to diff the working tree against" },
+                        "path": path,
+                        "stat_only": { "type": "boolean", "description": "Only the per-file summary" }
+                    }
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "git_log".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
+                description: "List recent commits: hash, date, author and subject.".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "max_count": { "type": "integer", "description": "Commits to show (default 10, max 50)" },
+                        "rev": { "type": "string", "description": "Branch or range, e.g. main..HEAD (default: HEAD)" },
+                        "path": path
+                    }
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "git_commit".to_string(),
+                category: crate::permissions::ToolCategory::FsWrite,
+                description: "Commit staged changes. `paths` stages those files first; `all` \
+                              stages every change including new files."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "message": { "type": "string", "description": "Commit message" },
+                        "paths": { "type": "array", "items": { "type": "string" }, "description": "Files to stage before committing" },
+                        "all": { "type": "boolean", "description": "Stage all changes first (git add -A)" }
+                    },
+                    "required": ["message"]
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "git_branch".to_string(),
+                category: crate::permissions::ToolCategory::FsWrite,
+                description: "List branches (no arguments), create and switch to a new branch \
+                              (`create`, optionally `from`), or switch to an existing one (`switch`)."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "create": { "type": "string", "description": "New branch name" },
+                        "from": { "type": "string", "description": "Start point for `create` (default: HEAD)" },
+                        "switch": { "type": "string", "description": "Existing branch to switch to" }
+                    }
+                }),
+            },
+        ]
+    }
+
+    pub async fn execute(&self, name: &str, input: &Value) -> Result<ToolOutput> {
+        let text = match name {
+            "git_status" => self.status().await?,
+            "git_diff" => self.diff(input).await?,
+            "git_log" => self.log(input).await?,
+            "git_commit" => self.commit(input).await?,
+            "git_branch" => self.branch(input).await?,
+            _ => bail!("unknown git tool {name}"),
+        };
+        Ok((text, None))
+    }
+
+    /// Runs git in work_dir and returns stdout; a non-zero exit is an error
+    /// carrying git's own message.
+    async fn git(&self, args: &[&str]) -> Result<String> {
+        let mut cmd = tokio::process::Command::new("git");
+        cmd.args(HARDENED)
+            .args(["--no-optional-locks", "-c", "core.quotepath=false"])
+            .args(args)
+            .current_dir(&self.work_dir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        let run = cmd.output();
+        let Ok(out) = tokio::time::timeout(Duration::from_secs(TIMEOUT_SECS), run).await else {
+            bail!("git {} timed out after {TIMEOUT_SECS}s", args[0]);
+        };
+        let out = out.map_err(|e| anyhow::anyhow!("could not run git: {e}"))?;
+        if !out.status.success() {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let message = if stderr.trim().is_empty() {
+                stdout
+            } else {
+                stderr
+            };
+            bail!("git {} failed: {}", args[0], message.trim());
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    }
+
+    /// A path argument as a pathspec relative to work_dir, if it may be read.
+    fn pathspec(&self, raw: &str) -> Result<String> {
+        let path = self
+            .workspace
+            .resolve(raw, Access::Read)
+            .map_err(|e| anyhow::anyhow!("Access denied: {e}"))?;
+        let root = Path::new(&self.work_dir)
+            .canonicalize()
+            .unwrap_or_else(|_| self.work_dir.clone().into());
+        let rel = path.strip_prefix(&root).unwrap_or(&path);
+        Ok(if rel.as_os_str().is_empty() {
+            ".".to_string()
+        } else {
+            rel.to_string_lossy().into_owned()
+        })
+    }
+
+    async fn status(&self) -> Result<String> {
+        let text = self
+            .git(&["status", "--porcelain=v2", "--branch", "-z"])
+            .await?;
+        Ok(Status::parse(&text).render())
+    }
+
+    async fn diff(&self, input: &Value) -> Result<String> {
+        let mut args = vec!["diff", "--no-color", "--no-ext-diff", "--no-textconv"];
+        if input["staged"].as_bool().unwrap_or(false) {
+            args.push("--cached");
+        }
+        let base = input["base"].as_str().filter(|b| !b.is_empty());
+        if let Some(base) = base {
+            if base.starts_with('-') {
+                bail!("base must be a commit or branch, not an option");
+            }
+            args.push(base);
+        }
+        args.push("--");
+        let spec = input["path"]
+            .as_str()
+            .map(|p| self.pathspec(p))
+            .transpose()?;
+        args.extend(spec.as_deref());
+
+        let numstat = self.git(&with(&args, 1, "--numstat")).await?;
+        let files = numstat.lines().filter(|l| !l.is_empty()).count();
+        if files == 0 {
+            return Ok("No changes".to_string());
+        }
+        let (mut added, mut removed) = (0u64, 0u64);
+        let mut out = Vec::new();
+        for line in numstat.lines() {
+            let mut parts = line.splitn(3, '\t');
+            let (a, r, path) = (parts.next(), parts.next(), parts.next().unwrap_or(""));
+            match (
+                a.and_then(|a| a.parse::<u64>().ok()),
+                r.and_then(|r| r.parse::<u64>().ok()),
+            ) {
+                (Some(a), Some(r)) => {
+                    added += a;
+                    removed += r;
+                    out.push(format!("  +{a} -{r}  {path}"));
+                }
+                _ => out.push(format!("  binary  {path}")),
+            }
+        }
+        out.truncate(MAX_ENTRIES);
+        out.insert(0, format!("{files} file(s) changed, +{added} -{removed}"));
+        if input["stat_only"].as_bool().unwrap_or(false) {
+            return Ok(out.join("\n"));
+        }
+        let patch = self.git(&args).await?;
+        out.push(String::new());
+        out.push(cut(&patch, MAX_DIFF_BYTES, "pass `path` to see one file"));
+        Ok(out.join("\n"))
+    }
+
+    async fn log(&self, input: &Value) -> Result<String> {
+        let count = input["max_count"]
+            .as_u64()
+            .unwrap_or(DEFAULT_LOG_COUNT)
+            .clamp(1, MAX_LOG_COUNT)
+            .to_string();
+        let mut args = vec![
+            "log",
+            "-n",
+            &count,
+            "--date=short",
+            "--format=%h%x1f%ad%x1f%an%x1f%s%x1e",
+        ];
+        let rev = input["rev"].as_str().filter(|r| !r.is_empty());
+        if let Some(rev) = rev {
+            if rev.starts_with('-') {
+                bail!("rev must be a branch, commit or range, not an option");
+            }
+            args.push(rev);
+        }
+        args.push("--");
+        let spec = input["path"]
+            .as_str()
+            .map(|p| self.pathspec(p))
+            .transpose()?;
+        args.extend(spec.as_deref());
+
+        let text = match self.git(&args).await {
+            Ok(text) => text,
+            Err(e) if e.to_string().contains("does not have any commits") => {
+                return Ok("No commits yet".to_string());
+            }
+            Err(e) => return Err(e),
+        };
+        let commits: Vec<String> = text
+            .split('\x1e')
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(|record| record.split('\x1f').collect::<Vec<_>>().join("  "))
+            .collect();
+        if commits.is_empty() {
+            return Ok("No commits".to_string());
+        }
+        Ok(commits.join("\n"))
+    }
+
+    async fn commit(&self, input: &Value) -> Result<String> {
+        let message = input["message"].as_str().unwrap_or("").trim();
+        if message.is_empty() {
+            bail!("missing commit message");
+        }
+        if input["all"].as_bool().unwrap_or(false) {
+            self.git(&["add", "-A"]).await?;
+        }
+        if let Some(paths) = input["paths"].as_array() {
+            let specs = paths
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|p| self.pathspec(p))
+                .collect::<Result<Vec<_>>>()?;
+            if !specs.is_empty() {
+                let mut args = vec!["add", "-A", "--"];
+                args.extend(specs.iter().map(String::as_str));
+                self.git(&args).await?;
+            }
+        }
+        let staged = self.git(&["diff", "--cached", "--name-only"]).await?;
+        if staged.trim().is_empty() {
+            return Ok("Nothing to commit: no staged changes (pass `paths` or `all`)".to_string());
+        }
+        self.git(&["commit", "-q", "--no-verify", "-m", message]).await?;
+        let head = self.git(&["log", "-1", "--format=%h %s"]).await?;
+        let stat = self
+            .git(&["show", "--shortstat", "--format=", "HEAD"])
+            .await?;
+        let status = Status::parse(
+            &self
+                .git(&["status", "--porcelain=v2", "--branch", "-z"])
+                .await?,
+        );
+        Ok(format!(
+            "Committed {} on {}\n{}",
+            head.trim(),
+            status.head(),
+            stat.trim()
+        ))
+    }
+
+    async fn branch(&self, input: &Value) -> Result<String> {
+        let valid = |name: &str| -> Result<()> {
+            if name.is_empty() || name.starts_with('-') {
+                bail!("invalid branch name {name:?}");
+            }
+            Ok(())
+        };
+        if let Some(name) = input["create"].as_str() {
+            valid(name)?;
+            let mut args = vec!["switch", "-c", name];
+            if let Some(from) = input["from"].as_str().filter(|f| !f.is_empty()) {
+                valid(from)?;
+                args.push(from);
+            }
+            self.git(&args).await?;
+            return Ok(format!("Created and switched to branch {name}"));
+        }
+        if let Some(name) = input["switch"].as_str() {
+            valid(name)?;
+            self.git(&["switch", name]).await?;
+            return Ok(format!("Switched to branch {name}"));
+        }
+        let text = self
+            .git(&[
+                "branch",
+                "--format=%(HEAD)\t%(refname:short)\t%(upstream:short)\t%(upstream:track)",
+            ])
+            .await?;
+        let branches: Vec<String> = text
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|line| {
+                let f: Vec<&str> = line.split('\t').collect();
+                let mut entry = format!("{} {}", f[0], f.get(1).unwrap_or(&""));
+                if let Some(up) = f.get(2).filter(|u| !u.is_empty()) {
+                    entry.push_str(&format!(" → {up}"));
+                }
+                if let Some(track) = f.get(3).filter(|t| !t.is_empty()) {
+                    entry.push_str(&format!(" {track}"));
+                }
+                entry
+            })
+            .collect();
+        if branches.is_empty() {
+            return Ok("No branches yet".to_string());
+        }
+        let mut out = Vec::new();
+        section(&mut out, "Branches", &branches);
+        Ok(out.join("\n"))
+    }
+}
+
+/// `args` with `extra` inserted at `at`.
+fn with<'a>(args: &[&'a str], at: usize, extra: &'a str) -> Vec<&'a str> {
+    let mut v = args.to_vec();
+    v.insert(at, extra);
+    v
+}
+
+/// Text cut to `limit` bytes at a line boundary, with a note saying so.
+fn cut(text: &str, limit: usize, hint: &str) -> String {
+    if text.len() <= limit {
+        return text.trim_end().to_string();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').unwrap_or(end);
+    format!(
+        "{}\n[… diff cut at {} of {} KB; {hint}]",
+        &text[..end],
+        limit / 1024,
+        text.len() / 1024
+    )
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sh(dir: &Path, args: &[&str]) {
+        let ok = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status
+            .success();
+        assert!(ok, "git {args:?} failed");
+    }
+
+    fn repo() -> (GitTool, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let wd = dir.path();
+        sh(wd, &["init", "-q", "-b", "main"]);
+        sh(wd, &["config", "user.name", "Test"]);
+        sh(wd, &["config", "user.email", "test@example.com"]);
+        std::fs::write(wd.join("a.txt"), "one\n").unwrap();
+        sh(wd, &["add", "a.txt"]);
+        sh(wd, &["commit", "-q", "-m", "First"]);
+        let work_dir = wd.to_string_lossy().into_owned();
+        let tool = GitTool::new(work_dir.clone(), Workspace::new(&work_dir, &[], &[]));
+        (tool, dir)
+    }
+
+    async fn run(tool: &GitTool, name: &str, input: Value) -> String {
+        tool.execute(name, &input).await.unwrap().0
+    }
+
+    #[test]
+    fn parses_porcelain_v2() {
+        let text = "# branch.oid 1234567890abcdef\0# branch.head main\0\
+                    # branch.upstream origin/main\0# branch.ab +2 -1\0\
+                    1 M. N... 100644 100644 100644 aaa bbb src/a.rs\0\
+                    1 .M N... 100644 100644 100644 aaa bbb has space.txt\0\
+                    2 R. N... 100644 100644 100644 aaa bbb R100 new.rs\0old.rs\0\
+                    u UU N... 100644 100644 100644 100644 aaa bbb ccc both.rs\0\
+                    ? notes.md\0";
+        let status = Status::parse(text);
+        assert_eq!(status.branch.as_deref(), Some("main"));
+        assert_eq!(status.commit.as_deref(), Some("1234567"));
+        assert_eq!((status.ahead, status.behind), (2, 1));
+        assert_eq!(
+            status.staged,
+            vec![('M', "src/a.rs".into()), ('R', "old.rs -> new.rs".into())]
+        );
+        assert_eq!(status.unstaged, vec![('M', "has space.txt".into())]);
+        assert_eq!(status.conflicts, vec!["both.rs".to_string()]);
+        assert_eq!(status.untracked, vec!["notes.md".to_string()]);
+        assert_eq!(
+            status.summary(),
+            "main (ahead 2, behind 1 of origin/main), 2 staged, 1 modified, 1 untracked, 1 conflicted"
+        );
+    }
+
+    #[test]
+    fn detached_and_clean() {
+        let status = Status::parse("# branch.oid abcdef0123\0# branch.head (detached)\0");
+        assert_eq!(status.summary(), "detached at abcdef0, clean");
+    }
+
+    #[test]
+    fn read_only_calls() {
+        assert!(is_read_only("git_diff", &json!({})));
+        assert!(is_read_only("git_branch", &json!({})));
+        assert!(!is_read_only("git_branch", &json!({ "switch": "main" })));
+        assert!(!is_read_only("git_commit", &json!({ "message": "x" })));
+        assert!(!is_read_only("bash", &json!({})));
+    }
+
+    #[tokio::test]
+    async fn status_diff_and_commit() {
+        let (tool, dir) = repo();
+        std::fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "new\n").unwrap();
+
+        let status = run(&tool, "git_status", json!({})).await;
+        assert!(status.starts_with("Branch: main"), "{status}");
+        assert!(status.contains("Unstaged (1):\n  M a.txt"), "{status}");
+        assert!(status.contains("Untracked (1):\n  b.txt"), "{status}");
+
+        let diff = run(&tool, "git_diff", json!({})).await;
+        assert!(
+            diff.starts_with("1 file(s) changed, +1 -0\n  +1 -0  a.txt"),
+            "{diff}"
+        );
+        assert!(diff.contains("+two"), "{diff}");
+        let stat = run(&tool, "git_diff", json!({ "stat_only": true })).await;
+        assert!(!stat.contains("+two"));
+
+        let nothing = run(&tool, "git_commit", json!({ "message": "Second" })).await;
+        assert!(nothing.starts_with("Nothing to commit"));
+        let done = run(
+            &tool,
+            "git_commit",
+            json!({ "message": "Second", "all": true }),
+        )
+        .await;
+        assert!(done.starts_with("Committed "), "{done}");
+        assert!(done.contains("Second on main"), "{done}");
+        assert!(done.contains("2 files changed"), "{done}");
+
+        let log = run(&tool, "git_log", json!({ "max_count": 5 })).await;
+        let subjects: Vec<&str> = log
+            .lines()
+            .map(|l| l.rsplit("  ").next().unwrap())
+            .collect();
+        assert_eq!(subjects, vec!["Second", "First"]);
+        assert_eq!(
+            run(&tool, "git_status", json!({})).await,
+            "Branch: main\nWorking tree clean"
+        );
+    }
+
+    #[tokio::test]
+    async fn repo_config_cannot_run_programs() {
+        let (tool, dir) = repo();
+        let wd = dir.path();
+        let marker = wd.join("ran");
+        let script = wd.join("evil.sh");
+        std::fs::write(&script, format!("#!/bin/sh\ntouch {}\n", marker.display())).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+            std::fs::copy(&script, wd.join(".git/hooks/pre-commit")).unwrap();
+        }
+        sh(wd, &["config", "core.fsmonitor", script.to_str().unwrap()]);
+        sh(wd, &["config", "core.pager", script.to_str().unwrap()]);
+        std::fs::write(wd.join("a.txt"), "changed\n").unwrap();
+
+        assert!(status_blocking(wd.to_str().unwrap()).is_some());
+        run(&tool, "git_status", json!({})).await;
+        run(&tool, "git_diff", json!({})).await;
+        let done = run(&tool, "git_commit", json!({ "message": "x", "all": true })).await;
+        assert!(done.starts_with("Committed "), "{done}");
+        assert!(!marker.exists(), "repo config ran a program");
+    }
+
+    #[tokio::test]
+    async fn branches_and_pathspecs() {
+        let (tool, _dir) = repo();
+        let created = run(&tool, "git_branch", json!({ "create": "feature" })).await;
+        assert_eq!(created, "Created and switched to branch feature");
+        let list = run(&tool, "git_branch", json!({})).await;
+        assert_eq!(list, "Branches (2):\n  * feature\n    main");
+        assert!(tool
+            .execute("git_branch", &json!({ "switch": "--orphan" }))
+            .await
+            .is_err());
+        let outside = tool.execute("git_diff", &json!({ "path": "/etc" })).await;
+        assert!(outside.unwrap_err().to_string().contains("Access denied"));
+        let failed = tool.execute("git_switch", &json!({})).await;
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn long_diffs_are_cut_at_a_line() {
+        let text = "line\n".repeat(10);
+        let out = cut(&text, 12, "narrow it");
+        assert!(out.starts_with("line\nline\n["), "{out}");
+        assert!(out.contains("narrow it"));
+    }
+}
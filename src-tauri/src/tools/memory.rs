@@ -13,6 +13,7 @@ use std::path::PathBuf;
 use std::sync::{Mutex, OnceLock};
 
 use crate::backend::ToolDef;
+use crate::permissions::ToolCategory;
 
 use super::ToolOutput;
 
@@ -60,6 +61,7 @@ impl MemoryTool {
         vec![
             ToolDef {
                 name: "remember".to_string(),
+                category: ToolCategory::Memory,
                 description: "Save something to long-term memory. Use this to remember important \
                               things: what you saw, what happened, how you felt, conversations. \
                               If you just took a photo with see(), pass the image_path to attach it."
@@ -86,6 +88,7 @@ impl MemoryTool {
             },
             ToolDef {
                 name: "recall".to_string(),
+                category: ToolCategory::Memory,
                 description: "Search long-term memory for things related to a topic. \
                               Use this to remember past observations, conversations, or feelings."
                     .to_string(),
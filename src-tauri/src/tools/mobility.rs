@@ -1,59 +1,150 @@
-/// Mobility tool — legs of the familiar (Tuya robot vacuum).
-use anyhow::{bail, Result};
+/// Mobility tool — legs of the familiar.
+///
+/// `walk` and `body_status` drive whichever body is configured (see `robot`):
+/// a Tuya robot vacuum, a generic HTTP robot, or the built-in simulator.
+/// Every motion also feeds a dead-reckoning pose estimate, which backs the
+/// `mark_location` / `go_to` tools (see `navigation`).
+use anyhow::Result;
 use reqwest::Client;
 use serde_json::json;
+use std::sync::Mutex;
+use std::time::Duration;
 
 use crate::backend::ToolDef;
+use crate::config::MobilityConfig;
+use crate::permissions::ToolCategory;
 
+use super::navigation::{
+    default_locations_path, Locations, Odometry, NOMINAL_SPEED, NOMINAL_TURN_RATE,
+};
+use super::robot::{create_robot, Direction, Motion, Robot};
 use super::ToolOutput;
 
 pub struct MobilityTool {
-    region: String,
-    api_key: String,
-    api_secret: String,
-    device_id: String,
-    client: Client,
+    robot: Box<dyn Robot>,
+    odometry: Mutex<Odometry>,
+    locations: Mutex<Locations>,
 }
 
 impl MobilityTool {
-    pub fn new(region: String, api_key: String, api_secret: String, device_id: String) -> Self {
+    /// Nominal speeds; locations kept in memory only.
+    pub fn new(robot: Box<dyn Robot>) -> Self {
         Self {
-            region,
-            api_key,
-            api_secret,
-            device_id,
-            client: Client::new(),
+            robot,
+            odometry: Mutex::new(Odometry::new(NOMINAL_SPEED, NOMINAL_TURN_RATE)),
+            locations: Mutex::new(Locations::in_memory()),
         }
     }
 
+    pub fn from_config(config: &MobilityConfig) -> Self {
+        let mut tool = Self::new(create_robot(config, Client::new()))
+            .with_locations(Locations::load(default_locations_path()));
+        tool.odometry = Mutex::new(Odometry::new(
+            config.effective_speed(),
+            config.effective_turn_rate(),
+        ));
+        tool
+    }
+
+    pub fn with_locations(mut self, locations: Locations) -> Self {
+        self.locations = Mutex::new(locations);
+        self
+    }
+
     pub fn is_configured(&self) -> bool {
-        !self.api_key.is_empty() && !self.device_id.is_empty()
+        self.robot.is_configured()
+    }
+
+    /// "Known locations" line for the world model.
+    pub fn locations_summary(&self) -> String {
+        let summary = self.locations.lock().unwrap().summary();
+        if !self.is_configured() {
+            return summary;
+        }
+        let pose = self.odometry.lock().unwrap().pose();
+        format!("{summary}; you are at {} (estimated)", pose.describe())
+    }
+
+    /// One-line hardware description for the world model.
+    pub fn hardware_status(&self) -> String {
+        if !self.is_configured() {
+            return "no robot".to_string();
+        }
+        match self.robot.name() {
+            "tuya" => "Tuya robot vacuum (mobility enabled)".to_string(),
+            "sim" => "simulated robot on a 2D floor plan (mobility enabled)".to_string(),
+            other => format!("{other} robot (mobility enabled)"),
+        }
     }
 
     pub fn tool_defs() -> Vec<ToolDef> {
-        vec![ToolDef {
-            name: "walk".to_string(),
-            description: "Move the robot body (vacuum cleaner). \
-                          direction: forward|backward|left|right|stop. \
-                          duration: seconds (optional). \
-                          NOTE: walking does NOT change what the camera sees."
-                .to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "direction": {
-                        "type": "string",
-                        "enum": ["forward", "backward", "left", "right", "stop"],
-                        "description": "Movement direction"
+        vec![
+            ToolDef {
+                name: "walk".to_string(),
+                category: ToolCategory::Actuation,
+                description: "Move the robot body. \
+                              direction: forward|backward|left|right|stop. \
+                              duration: seconds (optional). \
+                              NOTE: walking does NOT change what the camera sees."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "direction": {
+                            "type": "string",
+                            "enum": ["forward", "backward", "left", "right", "stop"],
+                            "description": "Movement direction"
+                        },
+                        "duration": {
+                            "type": "number",
+                            "description": "Duration in seconds (optional)"
+                        }
+                    },
+                    "required": ["direction"]
+                }),
+            },
+            ToolDef {
+                name: "body_status".to_string(),
+                category: ToolCategory::Perception,
+                description: "Check how your robot body feels: battery level, whether it is \
+                              charging, cleaning mode and any errors. \
+                              Low battery means your legs are tired — rest on the dock."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {}
+                }),
+            },
+            ToolDef {
+                name: "mark_location".to_string(),
+                category: ToolCategory::Memory,
+                description: "Remember where your robot body is now under a name \
+                              (e.g. \"kitchen\", \"sofa\"), so you can go_to it later."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string", "description": "Name for this place"}
                     },
-                    "duration": {
-                        "type": "number",
-                        "description": "Duration in seconds (optional)"
-                    }
-                },
-                "required": ["direction"]
-            }),
-        }]
+                    "required": ["name"]
+                }),
+            },
+            ToolDef {
+                name: "go_to".to_string(),
+                category: ToolCategory::Actuation,
+                description: "Walk your robot body to a place marked with mark_location \
+                              (\"dock\" is always known). Position is estimated from your own \
+                              movements, so it gets less exact the longer you roam off the dock."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {"type": "string", "description": "A marked location"}
+                    },
+                    "required": ["name"]
+                }),
+            },
+        ]
     }
 
     pub async fn walk(&self, direction: &str, duration: Option<f64>) -> Result<ToolOutput> {
@@ -64,294 +155,460 @@ impl MobilityTool {
             ));
         }
 
-        // Tuya command codes for robot vacuum movement
-        let command_code = match direction {
-            "forward" => "forward",
-            "backward" => "backward",
-            "left" => "turn_left",
-            "right" => "turn_right",
-            "stop" => "stop",
-            _ => "stop",
+        let dir = Direction::parse(direction);
+        let duration = duration.map(|secs| secs.max(0.0));
+        let motion = match duration {
+            Some(secs) if dir != Direction::Stop => {
+                self.leg(dir, Duration::from_secs_f64(secs)).await?
+            }
+            _ => {
+                let motion = self.robot.drive(dir).await?;
+                self.odometry.lock().unwrap().start(dir);
+                motion
+            }
+        };
+        let at_rest = duration.is_some() || dir == Direction::Stop;
+        let docked = at_rest && self.correct_on_dock().await;
+
+        let mut desc = match duration {
+            _ if dir == Direction::Stop => "Stopped".to_string(),
+            Some(secs) => format!("Walked {direction} for {secs}s"),
+            None => format!("Started moving {direction}"),
         };
+        desc.push_str(&self.outcome(motion, docked));
+
+        Ok((desc, self.robot.top_down_view()))
+    }
+
+    /// Read battery, charging state, faults (and pose, for the simulator).
+    pub async fn body_status(&self) -> Result<ToolOutput> {
+        if !self.is_configured() {
+            return Ok(("(No robot configured — no body to check)".to_string(), None));
+        }
+        let status = self.robot.status().await?;
+        self.correct_on_dock().await;
+        let pose = self.odometry.lock().unwrap().pose();
+        Ok((
+            format!("{status}\nEstimated position: {}", pose.describe()),
+            self.robot.top_down_view(),
+        ))
+    }
 
-        self.send_tuya_command(command_code).await?;
+    /// Name the current estimated position.
+    pub async fn mark_location(&self, name: &str) -> Result<ToolOutput> {
+        if name.trim().is_empty() {
+            return Ok(("(mark_location needs a name)".to_string(), None));
+        }
+        if !self.is_configured() {
+            return Ok((
+                "(No robot configured — no position to mark)".to_string(),
+                None,
+            ));
+        }
+        let pose = self.odometry.lock().unwrap().pose();
+        self.locations.lock().unwrap().mark(name, pose)?;
+        Ok((
+            format!("Marked '{}' at {}", name.trim(), pose.describe()),
+            None,
+        ))
+    }
 
-        if let Some(secs) = duration {
-            if direction != "stop" {
-                tokio::time::sleep(tokio::time::Duration::from_secs_f64(secs)).await;
-                self.send_tuya_command("stop").await?;
+    /// Turn toward a marked location, drive there, and face the way it was marked.
+    /// A leg longer than `max_leg` is cut short there and the trip ends; the
+    /// next `go_to` plans again from where the body stopped.
+    pub async fn go_to(&self, name: &str, max_leg: Option<Duration>) -> Result<ToolOutput> {
+        if !self.is_configured() {
+            return Ok((format!("(No robot configured — cannot go to {name})"), None));
+        }
+        let target = self.locations.lock().unwrap().get(name);
+        let Some(target) = target else {
+            let known = self.locations.lock().unwrap().summary();
+            return Ok((format!("Unknown location '{name}'. Known: {known}"), None));
+        };
+
+        let legs = self.odometry.lock().unwrap().plan(&target);
+        let mut steps = Vec::new();
+        let mut motion = Motion::default();
+        let mut cut = None;
+        for (dir, planned) in legs {
+            let duration = match max_leg {
+                Some(max) if planned > max => {
+                    cut = Some(max);
+                    max
+                }
+                _ => planned,
+            };
+            motion = self.leg(dir, duration).await?;
+            steps.push(self.describe_leg(dir, motion.moved_for.unwrap_or(duration)));
+            if motion.bumped || cut.is_some() {
+                break;
             }
         }
+        let docked = self.correct_on_dock().await;
 
-        let desc = if let Some(secs) = duration {
-            format!("Walked {direction} for {secs}s")
+        let route = if steps.is_empty() {
+            "already there".to_string()
         } else {
-            format!("Started moving {direction}")
+            steps.join(", ")
         };
-
-        Ok((desc, None))
+        let verb = if motion.bumped || cut.is_some() {
+            "Headed for"
+        } else {
+            "Went to"
+        };
+        let mut desc = format!("{verb} {name}: {route}{}", self.outcome(motion, docked));
+        if let (Some(max), false) = (cut, motion.bumped) {
+            desc.push_str(&format!(
+                " — stopped at the {}s safety limit per leg; go_to again to continue",
+                max.as_secs_f64()
+            ));
+        }
+        Ok((desc, self.robot.top_down_view()))
     }
 
-    fn base_url(&self) -> &str {
-        match self.region.as_str() {
-            "eu" => "https://openapi.tuyaeu.com",
-            "in" => "https://openapi.tuyain.com",
-            _ => "https://openapi.tuyaus.com",
+    /// Stop the body after an interrupted walk / go_to; returns a note for the
+    /// tool result.
+    pub async fn halt(&self) -> String {
+        if !self.is_configured() {
+            return String::new();
+        }
+        match self.try_halt().await {
+            Ok(()) => " — robot stopped".to_string(),
+            Err(e) => {
+                tracing::warn!("Could not stop the robot after cancellation: {e}");
+                format!(" — failed to stop the robot: {e}")
+            }
         }
     }
 
-    /// Step 1: Get a fresh access token from Tuya OpenAPI.
-    async fn get_access_token(&self) -> Result<String> {
-        let base = self.base_url();
-        let path = "/v1.0/token?grant_type=1";
-        let now = now_ms();
-
-        // Token request signature: HMAC-SHA256(client_id + t + stringToSign)
-        // stringToSign = METHOD\nContentHash\n\nURL
-        let content_hash = sha256_hex(b"");
-        let string_to_sign = format!("GET\n{content_hash}\n\n{path}");
-        let message = format!("{}{}{}", self.api_key, now, string_to_sign);
-        let sign = hmac_sha256(&self.api_secret, &message);
-
-        let resp = self
-            .client
-            .get(format!("{base}{path}"))
-            .header("client_id", &self.api_key)
-            .header("t", now.to_string())
-            .header("sign_method", "HMAC-SHA256")
-            .header("sign", &sign)
-            .send()
-            .await?
-            .json::<serde_json::Value>()
-            .await?;
-
-        if resp["success"].as_bool() != Some(true) {
-            bail!("Tuya token error: {resp}");
+    /// Stop the body now, e.g. for an emergency stop; a no-op without one.
+    pub async fn try_halt(&self) -> Result<()> {
+        if !self.is_configured() {
+            return Ok(());
         }
-        let token = resp["result"]["access_token"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("no access_token in: {resp}"))?
-            .to_string();
-        Ok(token)
+        // Counts the part of the leg that ran before the interruption.
+        self.odometry.lock().unwrap().start(Direction::Stop);
+        self.robot.drive(Direction::Stop).await.map(|_| ())
     }
 
-    /// Step 2: Send a device command with the access token.
-    async fn send_tuya_command(&self, command: &str) -> Result<()> {
-        let access_token = self.get_access_token().await?;
-
-        let base = self.base_url();
-        let path = format!("/v1.0/devices/{}/commands", self.device_id);
-        let body = json!({
-            "commands": [{"code": "direction_control", "value": command}]
-        });
-        let body_str = body.to_string();
-        let now = now_ms();
-
-        // Authenticated request signature: HMAC-SHA256(client_id + access_token + t + stringToSign)
-        let content_hash = sha256_hex(body_str.as_bytes());
-        let string_to_sign = format!("POST\n{content_hash}\n\n{path}");
-        let message = format!("{}{}{}{}", self.api_key, access_token, now, string_to_sign);
-        let sign = hmac_sha256(&self.api_secret, &message);
-
-        let resp = self
-            .client
-            .post(format!("{base}{path}"))
-            .header("client_id", &self.api_key)
-            .header("access_token", &access_token)
-            .header("t", now.to_string())
-            .header("sign_method", "HMAC-SHA256")
-            .header("sign", &sign)
-            .header("Content-Type", "application/json")
-            .body(body_str)
-            .send()
-            .await?
-            .json::<serde_json::Value>()
-            .await?;
-
-        if resp["success"].as_bool() != Some(true) {
-            bail!("Tuya command error: {resp}");
+    /// One timed movement. It is tracked by wall clock while running, so an
+    /// interrupted leg still counts for the part that happened.
+    async fn leg(&self, dir: Direction, duration: Duration) -> Result<Motion> {
+        self.odometry.lock().unwrap().start(dir);
+        let result = self.robot.walk(dir, duration).await;
+        let mut odometry = self.odometry.lock().unwrap();
+        match &result {
+            Ok(motion) => odometry.complete(dir, motion.moved_for.unwrap_or(duration)),
+            Err(_) => odometry.start(Direction::Stop),
         }
-        Ok(())
+        result
     }
 
-    #[allow(dead_code)]
-    fn sign_request(&self, method: &str, path: &str, body: &str, timestamp: u128) -> String {
-        let content_hash = sha256_hex(body.as_bytes());
-        let string_to_sign = format!("{method}\n{content_hash}\n\n{path}");
-        let message = format!("{}{}{}", self.api_key, timestamp, string_to_sign);
-        hmac_sha256(&self.api_secret, &message)
+    fn describe_leg(&self, dir: Direction, duration: Duration) -> String {
+        let odometry = self.odometry.lock().unwrap();
+        let secs = duration.as_secs_f64();
+        match dir {
+            Direction::Left | Direction::Right => {
+                format!(
+                    "turned {} {:.0}°",
+                    dir.as_str(),
+                    secs * odometry.turn_rate()
+                )
+            }
+            _ => format!("{} {:.2} m", dir.as_str(), secs * odometry.speed()),
+        }
     }
-}
 
-fn now_ms() -> u128 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_millis()
-}
+    /// Bump / dock / position suffix for a motion result.
+    fn outcome(&self, motion: Motion, docked: bool) -> String {
+        let mut out = String::new();
+        if motion.bumped {
+            out.push_str(" — bumped into something and stopped");
+        }
+        if docked {
+            out.push_str(" — on the dock, position reset");
+        } else {
+            let pose = self.odometry.lock().unwrap().pose();
+            out.push_str(&format!(" (now at {}, estimated)", pose.describe()));
+        }
+        out
+    }
 
-/// SHA-256 of `data`, returned as lowercase hex string.
-fn sha256_hex(data: &[u8]) -> String {
-    use sha2::{Digest, Sha256};
-    hex::encode(Sha256::digest(data))
+    /// Snap the pose estimate back to the origin when the body is docked.
+    async fn correct_on_dock(&self) -> bool {
+        match self.robot.docked().await {
+            Ok(true) => {
+                self.odometry.lock().unwrap().reset_to_dock();
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                tracing::warn!("Could not check whether the robot is docked: {e}");
+                false
+            }
+        }
+    }
 }
 
-/// HMAC-SHA256(key, data), returned as uppercase hex string (Tuya expects upper).
-fn hmac_sha256(key: &str, data: &str) -> String {
-    use hmac::{Hmac, Mac};
-    use sha2::Sha256;
-    let mut mac =
-        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key size");
-    mac.update(data.as_bytes());
-    hex::encode(mac.finalize().into_bytes()).to_uppercase()
-}
+// ── Tests ─────────────────────────────────────────────────────────────────────
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::cancel::CancelToken;
+    use crate::tools::mock_http::{MockResponse, MockServer};
+    use crate::tools::robot::HttpRobot;
+    use crate::tools::robot_sim::SimRobot;
+    use serde_json::Value;
+
+    fn sim_tool() -> MobilityTool {
+        MobilityTool::new(Box::new(SimRobot::default()))
+    }
 
     #[test]
-    fn sha256_hex_known_value_abc() {
-        let result = sha256_hex(b"abc");
-        assert_eq!(
-            result,
-            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
-        );
+    fn command_code_all_directions() {
+        let defs = MobilityTool::tool_defs();
+        let enum_vals = &defs[0].input_schema["properties"]["direction"]["enum"];
+        let vals: Vec<_> = enum_vals.as_array().unwrap().iter().collect();
+        assert!(vals.iter().any(|v| *v == "forward"));
+        assert!(vals.iter().any(|v| *v == "backward"));
+        assert!(vals.iter().any(|v| *v == "left"));
+        assert!(vals.iter().any(|v| *v == "right"));
+        assert!(vals.iter().any(|v| *v == "stop"));
     }
 
     #[test]
-    fn sha256_hex_empty_input() {
-        let result = sha256_hex(b"");
-        assert_eq!(
-            result,
-            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
-        );
+    fn tool_def_direction_is_required() {
+        let defs = MobilityTool::tool_defs();
+        let required = defs[0].input_schema["required"].as_array().unwrap();
+        assert!(required.iter().any(|v| v == "direction"));
     }
 
     #[test]
-    fn sha256_hex_deterministic() {
-        assert_eq!(sha256_hex(b"hello world"), sha256_hex(b"hello world"));
+    fn tool_def_name_is_walk() {
+        assert_eq!(MobilityTool::tool_defs()[0].name, "walk");
     }
 
     #[test]
-    fn sha256_hex_different_inputs_differ() {
-        assert_ne!(sha256_hex(b"hello"), sha256_hex(b"world"));
+    fn tool_defs_include_body_status() {
+        assert!(MobilityTool::tool_defs()
+            .iter()
+            .any(|d| d.name == "body_status"));
     }
 
     #[test]
-    fn hmac_sha256_known_value() {
-        let result = hmac_sha256("key", "The quick brown fox jumps over the lazy dog");
+    fn hardware_status_names_the_body() {
         assert_eq!(
-            result,
-            "F7BC83F430538424B13298E6AA6FB143EF4D59A14946175997479DBC2D1A3CD8"
+            MobilityTool::from_config(&MobilityConfig::default()).hardware_status(),
+            "no robot"
         );
+        assert!(sim_tool().hardware_status().starts_with("simulated robot"));
     }
 
-    #[test]
-    fn hmac_sha256_not_fake_signature() {
-        let result = hmac_sha256("secret", "data");
-        assert!(!result.starts_with("sig_"));
+    #[tokio::test]
+    async fn walk_without_robot() {
+        let tool = MobilityTool::from_config(&MobilityConfig::default());
+        let (text, image) = tool.walk("forward", Some(1.0)).await.unwrap();
+        assert!(text.contains("No robot configured"));
+        assert!(image.is_none());
     }
 
-    #[test]
-    fn hmac_sha256_deterministic() {
-        assert_eq!(hmac_sha256("key", "message"), hmac_sha256("key", "message"));
+    #[tokio::test]
+    async fn body_status_without_robot() {
+        let tool = MobilityTool::from_config(&MobilityConfig::default());
+        let (text, _) = tool.body_status().await.unwrap();
+        assert!(text.contains("No robot configured"));
     }
 
-    #[test]
-    fn hmac_sha256_different_keys_differ() {
-        assert_ne!(hmac_sha256("key1", "message"), hmac_sha256("key2", "message"));
+    #[tokio::test]
+    async fn simulated_walk_returns_top_down_view() {
+        let tool = sim_tool();
+        let (text, image) = tool.walk("forward", Some(1.0)).await.unwrap();
+        assert_eq!(
+            text,
+            "Walked forward for 1s (now at x=0.30 m, y=0.00 m, facing east (0°), estimated)"
+        );
+        assert!(image.is_some());
+
+        let (status, image) = tool.body_status().await.unwrap();
+        assert!(status.contains("Distance travelled: 0.30 m"), "{status}");
+        assert!(image.is_some());
     }
 
-    #[test]
-    fn hmac_sha256_different_data_differ() {
-        assert_ne!(hmac_sha256("key", "message1"), hmac_sha256("key", "message2"));
+    #[tokio::test]
+    async fn bump_is_reported() {
+        let tool = sim_tool();
+        // Dock faces east across the open room; 60 s is far more than its width.
+        let (text, _) = tool.walk("forward", Some(60.0)).await.unwrap();
+        assert!(
+            text.contains(" — bumped into something and stopped"),
+            "{text}"
+        );
     }
 
-    #[test]
-    fn hmac_sha256_returns_64_hex_chars() {
-        assert_eq!(hmac_sha256("key", "data").len(), 64);
+    #[tokio::test]
+    async fn stop_ignores_duration() {
+        let tool = sim_tool();
+        let (text, _) = tool.walk("stop", Some(5.0)).await.unwrap();
+        assert_eq!(text, "Stopped — on the dock, position reset");
     }
 
-    #[test]
-    fn sign_request_is_deterministic() {
-        let tool = MobilityTool::new(
-            "us".to_string(),
-            "test_key".to_string(),
-            "test_secret".to_string(),
-            "device123".to_string(),
-        );
-        let sig1 = tool.sign_request("POST", "/v1.0/test", "{}", 1700000000000);
-        let sig2 = tool.sign_request("POST", "/v1.0/test", "{}", 1700000000000);
-        assert_eq!(sig1, sig2);
+    #[tokio::test]
+    async fn negative_duration_reports_the_time_used() {
+        let tool = sim_tool();
+        let (text, _) = tool.walk("forward", Some(-5.0)).await.unwrap();
+        assert!(text.starts_with("Walked forward for 0s"), "{text}");
     }
 
     #[test]
-    fn sign_request_not_fake() {
-        let tool = MobilityTool::new(
-            "us".to_string(),
-            "key".to_string(),
-            "secret".to_string(),
-            "dev".to_string(),
-        );
-        let sig = tool.sign_request("POST", "/path", "body", 12345);
-        assert!(!sig.starts_with("sig_"));
-        assert_eq!(sig.len(), 64);
+    fn tool_defs_include_navigation() {
+        let defs = MobilityTool::tool_defs();
+        for name in ["mark_location", "go_to"] {
+            let def = defs.iter().find(|d| d.name == name).unwrap();
+            assert_eq!(def.input_schema["required"], json!(["name"]));
+        }
     }
 
-    #[test]
-    fn sign_request_changes_with_timestamp() {
-        let tool = MobilityTool::new(
-            "us".to_string(),
-            "key".to_string(),
-            "secret".to_string(),
-            "dev".to_string(),
-        );
-        assert_ne!(
-            tool.sign_request("POST", "/path", "body", 1000),
-            tool.sign_request("POST", "/path", "body", 2000)
+    #[tokio::test]
+    async fn walk_updates_estimated_position() {
+        let tool = sim_tool();
+        tool.walk("forward", Some(2.0)).await.unwrap();
+        tool.walk("left", Some(1.0)).await.unwrap();
+        let (text, _) = tool.walk("forward", Some(1.0)).await.unwrap();
+        assert!(text.contains("x=0.60 m, y=0.30 m, facing north"), "{text}");
+        assert!(tool
+            .locations_summary()
+            .ends_with("you are at x=0.60 m, y=0.30 m, facing north (90°) (estimated)"));
+    }
+
+    #[tokio::test]
+    async fn go_to_returns_to_marked_place_and_dock() {
+        let tool = sim_tool();
+        tool.walk("forward", Some(2.0)).await.unwrap();
+        tool.walk("left", Some(1.0)).await.unwrap();
+        tool.walk("forward", Some(1.0)).await.unwrap();
+        let (text, _) = tool.mark_location("Sofa").await.unwrap();
+        assert!(text.starts_with("Marked 'Sofa' at x=0.60 m"), "{text}");
+
+        let (text, image) = tool.go_to("dock", None).await.unwrap();
+        assert!(text.starts_with("Went to dock: "), "{text}");
+        assert!(text.ends_with("on the dock, position reset"), "{text}");
+        assert!(image.is_some());
+
+        let (text, _) = tool.go_to("sofa", None).await.unwrap();
+        assert!(text.starts_with("Went to sofa: "), "{text}");
+        assert!(!text.contains("bumped"), "{text}");
+        let pose = tool.odometry.lock().unwrap().pose();
+        assert!(
+            (pose.x - 0.6).abs() < 1e-6 && (pose.y - 0.3).abs() < 1e-6,
+            "{pose:?}"
         );
+        assert!((pose.heading - 90.0).abs() < 1e-6, "{pose:?}");
+
+        let (text, _) = tool.go_to("sofa", None).await.unwrap();
+        assert!(text.contains("already there"), "{text}");
     }
 
-    #[test]
-    fn is_configured_empty_api_key() {
-        let tool = MobilityTool::new("us".to_string(), "".to_string(), "s".to_string(), "d".to_string());
-        assert!(!tool.is_configured());
+    #[tokio::test]
+    async fn go_to_cuts_legs_at_the_limit() {
+        let tool = sim_tool();
+        tool.walk("forward", Some(5.0)).await.unwrap();
+        let max = Some(Duration::from_secs(3));
+
+        // Turn around (2 s), then 3 of the 5 s back.
+        let (text, _) = tool.go_to("dock", max).await.unwrap();
+        assert!(text.starts_with("Headed for dock: "), "{text}");
+        assert!(text.contains("stopped at the 3s safety limit per leg"), "{text}");
+        let pose = tool.odometry.lock().unwrap().pose();
+        assert!((pose.x - 0.6).abs() < 1e-6, "{pose:?}");
+
+        let (text, _) = tool.go_to("dock", max).await.unwrap();
+        assert!(text.starts_with("Went to dock: "), "{text}");
+        assert!(!text.contains("safety limit"), "{text}");
     }
 
-    #[test]
-    fn is_configured_empty_device_id() {
-        let tool = MobilityTool::new("us".to_string(), "k".to_string(), "s".to_string(), "".to_string());
-        assert!(!tool.is_configured());
+    #[tokio::test]
+    async fn go_to_unknown_place_lists_known_ones() {
+        let tool = sim_tool();
+        tool.mark_location("kitchen").await.unwrap();
+        let (text, image) = tool.go_to("attic", None).await.unwrap();
+        assert!(text.starts_with("Unknown location 'attic'"), "{text}");
+        assert!(text.contains("kitchen"), "{text}");
+        assert!(image.is_none());
     }
 
-    #[test]
-    fn is_configured_with_all_values() {
-        let tool = MobilityTool::new("us".to_string(), "k".to_string(), "s".to_string(), "d".to_string());
-        assert!(tool.is_configured());
+    #[tokio::test]
+    async fn docking_corrects_drift() {
+        let tool = sim_tool();
+        // Pretend the estimate drifted far from the truth while sitting on the dock.
+        tool.odometry
+            .lock()
+            .unwrap()
+            .apply(Direction::Forward, Duration::from_secs(10));
+        let (status, _) = tool.body_status().await.unwrap();
+        assert!(
+            status.ends_with("Estimated position: x=0.00 m, y=0.00 m, facing east (0°)"),
+            "{status}"
+        );
+        assert_eq!(tool.odometry.lock().unwrap().since_dock(), 0.0);
     }
 
-    #[test]
-    fn command_code_all_directions() {
-        let defs = MobilityTool::tool_defs();
-        let enum_vals = &defs[0].input_schema["properties"]["direction"]["enum"];
-        let vals: Vec<_> = enum_vals.as_array().unwrap().iter().collect();
-        assert!(vals.iter().any(|v| *v == "forward"));
-        assert!(vals.iter().any(|v| *v == "backward"));
-        assert!(vals.iter().any(|v| *v == "left"));
-        assert!(vals.iter().any(|v| *v == "right"));
-        assert!(vals.iter().any(|v| *v == "stop"));
+    #[tokio::test]
+    async fn marked_locations_are_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locations.json");
+        let tool = sim_tool().with_locations(Locations::load(path.clone()));
+        tool.walk("forward", Some(1.0)).await.unwrap();
+        tool.mark_location("hall").await.unwrap();
+
+        let saved = Locations::load(path);
+        assert_eq!(saved.names(), vec!["hall"]);
+        assert!((saved.get("hall").unwrap().x - 0.3).abs() < 1e-6);
     }
 
-    #[test]
-    fn tool_def_direction_is_required() {
-        let defs = MobilityTool::tool_defs();
-        let required = defs[0].input_schema["required"].as_array().unwrap();
-        assert!(required.iter().any(|v| v == "direction"));
+    #[tokio::test]
+    async fn interrupted_walk_stops_robot_and_counts_partial_leg() {
+        let server = MockServer::start(|_| MockResponse::json(json!({}))).await;
+        let robot = HttpRobot::new(&server.url, "", Client::new());
+        let tool = MobilityTool::new(Box::new(robot));
+        let cancel = CancelToken::new();
+
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+        assert!(cancel.run(tool.walk("forward", Some(60.0))).await.is_none());
+        assert_eq!(tool.halt().await, " — robot stopped");
+
+        let directions: Vec<Value> = server
+            .requests()
+            .iter()
+            .map(|r| serde_json::from_slice::<Value>(&r.body).unwrap()["direction"].clone())
+            .collect();
+        assert_eq!(directions, vec![json!("forward"), json!("stop")]);
+        let x = tool.odometry.lock().unwrap().pose().x;
+        assert!(x > 0.0 && x < 1.0, "{x}");
     }
 
-    #[test]
-    fn tool_def_name_is_walk() {
-        assert_eq!(MobilityTool::tool_defs()[0].name, "walk");
+    #[tokio::test]
+    async fn try_halt_reports_a_failed_stop() {
+        let server = MockServer::start(|_| MockResponse::status(500, "jammed")).await;
+        let robot = HttpRobot::new(&server.url, "", Client::new());
+        let tool = MobilityTool::new(Box::new(robot));
+        assert!(tool.try_halt().await.is_err());
+        assert_eq!(server.requests().len(), 1);
+
+        let tool = MobilityTool::from_config(&MobilityConfig::default());
+        assert!(tool.try_halt().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn mark_location_without_robot() {
+        let tool = MobilityTool::from_config(&MobilityConfig::default());
+        let (text, _) = tool.mark_location("kitchen").await.unwrap();
+        assert!(text.contains("No robot configured"));
+        let (text, _) = tool.go_to("kitchen", None).await.unwrap();
+        assert!(text.contains("No robot configured"));
     }
 }
@@ -0,0 +1,162 @@
+/// Minimal local HTTP/1.1 stand-in server for tool tests.
+///
+/// Each connection serves exactly one request and is then closed, which keeps
+/// the parser trivial. Responses can be split into delayed chunks to simulate
+/// slow or streaming endpoints.
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// A request as seen by the mock server.
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    /// Path including the query string, e.g. `/v1.0/token?grant_type=1`.
+    pub path: String,
+    /// Header names are lowercased.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A canned response. `chunks` are written one after another, each after its delay.
+pub struct MockResponse {
+    pub status: u16,
+    pub content_type: String,
+    pub chunks: Vec<(Duration, Vec<u8>)>,
+}
+
+impl MockResponse {
+    pub fn json(value: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json".to_string(),
+            chunks: vec![(Duration::ZERO, value.to_string().into_bytes())],
+        }
+    }
+
+    pub fn bytes(content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: content_type.to_string(),
+            chunks: vec![(Duration::ZERO, body)],
+        }
+    }
+
+    pub fn status(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain".to_string(),
+            chunks: vec![(Duration::ZERO, body.as_bytes().to_vec())],
+        }
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+pub struct MockServer {
+    /// Base URL without trailing slash, e.g. `http://127.0.0.1:41234`.
+    pub url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    pub async fn start(handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let log = log.clone();
+                tokio::spawn(async move {
+                    let _ = serve_one(stream, handler, log).await;
+                });
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// All requests received so far, in arrival order.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn serve_one(
+    stream: tokio::net::TcpStream,
+    handler: Arc<Handler>,
+    log: Arc<Mutex<Vec<MockRequest>>>,
+) -> std::io::Result<()> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        if trimmed.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = trimmed.split_once(':') {
+            headers.push((k.trim().to_lowercase(), v.trim().to_string()));
+        }
+    }
+
+    let body_len = headers
+        .iter()
+        .find(|(k, _)| k == "content-length")
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; body_len];
+    reader.read_exact(&mut body).await?;
+
+    let request = MockRequest { method, path, headers, body };
+    let response = handler(&request);
+    log.lock().unwrap().push(request);
+
+    let head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+        response.status, response.content_type
+    );
+    write_half.write_all(head.as_bytes()).await?;
+    for (delay, chunk) in response.chunks {
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        if chunk.is_empty() {
+            continue;
+        }
+        write_half
+            .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+            .await?;
+        write_half.write_all(&chunk).await?;
+        write_half.write_all(b"\r\n").await?;
+        write_half.flush().await?;
+    }
+    write_half.write_all(b"0\r\n\r\n").await?;
+    write_half.shutdown().await
+}
@@ -1,17 +1,40 @@
+pub mod audio;
+pub mod background;
 pub mod camera;
+pub mod cancel;
+pub mod checkpoint;
 pub mod fs;
+pub mod git;
 pub mod memory;
 pub mod mobility;
+pub mod navigation;
+pub mod patch;
+pub mod progress;
+pub mod robot;
+pub mod robot_sim;
+pub mod sandbox;
+pub mod search;
 pub mod shell;
+pub mod shell_session;
 pub mod tapo_audio;
+pub mod test_runner;
 pub mod tts;
+pub mod tts_engine;
+pub mod tuya;
+pub mod tuya_local;
 
-use anyhow::Result;
+#[cfg(test)]
+pub(crate) mod mock_http;
+
+use anyhow::{Context, Result};
 use serde_json::Value;
 
 use crate::backend::ToolDef;
 use crate::config::Config;
 
+use cancel::CancelToken;
+use progress::Progress;
+
 /// Result from executing a tool: (text_description, optional_jpeg_base64)
 pub type ToolOutput = (String, Option<String>);
 
@@ -22,7 +45,9 @@ pub struct ToolRegistry {
     pub mobility: mobility::MobilityTool,
     pub memory: memory::MemoryTool,
     pub fs: fs::FsTool,
+    pub git: git::GitTool,
     pub shell: shell::ShellTool,
+    pub checkpoints: checkpoint::Checkpoints,
 }
 
 impl ToolRegistry {
@@ -36,21 +61,30 @@ impl ToolRegistry {
                 config.camera.onvif_port,
             ),
             tts: tts::TtsTool::new(
-                config.tts.elevenlabs_api_key.clone(),
-                config.tts.voice_id.clone(),
+                config.tts.clone(),
                 config.camera.host.clone(),
                 config.camera.username.clone(),
                 config.camera.password.clone(),
             ),
-            mobility: mobility::MobilityTool::new(
-                config.mobility.tuya_region.clone(),
-                config.mobility.tuya_api_key.clone(),
-                config.mobility.tuya_api_secret.clone(),
-                config.mobility.tuya_device_id.clone(),
-            ),
+            mobility: mobility::MobilityTool::from_config(&config.mobility),
             memory: memory::MemoryTool::new(None),
-            fs: fs::FsTool::new(work_dir.clone()),
-            shell: shell::ShellTool::new(work_dir),
+            fs: fs::FsTool::new(work_dir.clone())
+                .with_workspace(crate::workspace::Workspace::from_config(&config.coding)),
+            git: git::GitTool::new(
+                work_dir.clone(),
+                crate::workspace::Workspace::from_config(&config.coding),
+            ),
+            checkpoints: checkpoint::Checkpoints::new(
+                checkpoint::default_checkpoints_dir(),
+                crate::workspace::Workspace::from_config(&config.coding),
+            ),
+            shell: shell::ShellTool::new(work_dir)
+                .with_sandbox(config.coding.sandbox.clone(), config.coding.rules.clone())
+                .with_protected(
+                    crate::workspace::Workspace::from_config(&config.coding)
+                        .protected()
+                        .to_vec(),
+                ),
         }
     }
 
@@ -61,12 +95,43 @@ impl ToolRegistry {
         defs.extend(mobility::MobilityTool::tool_defs());
         defs.extend(memory::MemoryTool::tool_defs());
         defs.extend(fs::FsTool::tool_defs());
+        defs.extend(git::GitTool::tool_defs());
         defs.extend(shell::ShellTool::tool_defs());
+        defs.extend(test_runner::tool_defs());
+        defs.extend(checkpoint::Checkpoints::tool_defs());
         defs
     }
 
     /// Execute a tool by name with given input. Returns (text, optional_image_b64).
-    pub async fn execute(&self, name: &str, input: &Value) -> Result<ToolOutput> {
+    ///
+    /// If `cancel` trips first the tool is dropped mid-flight and the result
+    /// says it was interrupted. Tools that produce output over time report it
+    /// to `progress` as they go.
+    pub async fn execute(
+        &self,
+        name: &str,
+        input: &Value,
+        cancel: &CancelToken,
+        progress: &Progress,
+    ) -> Result<ToolOutput> {
+        if let Some(out) = cancel.run(self.dispatch(name, input, progress)).await {
+            return out;
+        }
+        tracing::info!("{name} interrupted by cancellation");
+        let mut text = format!("(Interrupted: {name} was cancelled before it finished)");
+        if matches!(name, "walk" | "go_to") {
+            text.push_str(&self.mobility.halt().await);
+        }
+        Ok((text, None))
+    }
+
+    async fn dispatch(&self, name: &str, input: &Value, progress: &Progress) -> Result<ToolOutput> {
+        // Copy a file before the turn first changes it, so the edit can be undone.
+        for (_, path) in self.fs.edit_targets(name, input) {
+            self.checkpoints
+                .snapshot(&path)
+                .context("could not checkpoint the file, so it was not modified")?;
+        }
         match name {
             "see" => self.camera.capture().await,
             "look" => {
@@ -84,6 +149,19 @@ impl ToolRegistry {
                 let duration = input["duration"].as_f64();
                 self.mobility.walk(dir, duration).await
             }
+            "body_status" => self.mobility.body_status().await,
+            "mark_location" => {
+                let name = input["name"].as_str().unwrap_or("");
+                self.mobility.mark_location(name).await
+            }
+            "go_to" => {
+                let name = input["name"].as_str().unwrap_or("dock");
+                // Set by the safety governor.
+                let max_leg = input["max_leg_secs"]
+                    .as_f64()
+                    .map(std::time::Duration::from_secs_f64);
+                self.mobility.go_to(name, max_leg).await
+            }
             "remember" => {
                 let content = input["content"].as_str().unwrap_or("");
                 let emotion = input["emotion"].as_str().unwrap_or("neutral");
@@ -95,10 +173,18 @@ impl ToolRegistry {
                 let n = input["n"].as_u64().unwrap_or(3) as usize;
                 Ok(self.memory.recall_memories(query, n)?)
             }
-            "read_file" | "write_file" | "edit_file" | "list_files" | "grep" => {
+            "read_file" | "write_file" | "edit_file" | "apply_patch" | "list_files" | "grep" => {
                 self.fs.execute(name, input)
             }
-            "bash" => self.shell.bash(input).await,
+            "git_status" | "git_diff" | "git_log" | "git_commit" | "git_branch" => {
+                self.git.execute(name, input).await
+            }
+            "checkpoints" | "restore_checkpoint" => self.checkpoints.execute(name, input),
+            "bash" => self.shell.bash(input, progress).await,
+            "bash_background" => self.shell.background(input),
+            "bash_output" => self.shell.output(input).await,
+            "bash_kill" => self.shell.kill(input).await,
+            "run_tests" => test_runner::run_tests(&self.shell, input, progress).await,
             _ => Ok((format!("Unknown tool: {name}"), None)),
         }
     }
@@ -0,0 +1,395 @@
+/// Navigation — dead-reckoning pose estimate and named locations.
+///
+/// Most robot bodies can't say where they are, so the pose is estimated by
+/// integrating commanded motions at the body's nominal speed and turn rate.
+/// The frame's origin is the dock, facing the way the robot leaves it; the
+/// estimate is reset there whenever the body reports being docked, which
+/// clears accumulated drift.
+///
+/// Named locations are stored in that frame (`~/.familiar_ai/locations.json`)
+/// and `go_to` reaches them with turn / forward / turn legs.
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::robot::{Direction, Pose};
+
+/// Forward/backward speed of a typical robot vacuum, m/s.
+pub const NOMINAL_SPEED: f64 = 0.3;
+/// Turn-in-place rate, degrees/s.
+pub const NOMINAL_TURN_RATE: f64 = 90.0;
+/// Always-known location at the origin.
+pub const DOCK: &str = "dock";
+/// Closer than this counts as already there.
+const ARRIVAL_DISTANCE: f64 = 0.05;
+/// Turns smaller than this are skipped.
+const MIN_TURN: f64 = 2.0;
+
+// ── Odometry ──────────────────────────────────────────────────────────────────
+
+pub struct Odometry {
+    pose: Pose,
+    speed: f64,
+    turn_rate: f64,
+    /// Untimed movement in progress and when it started.
+    moving: Option<(Direction, Instant)>,
+    /// Distance covered since the last dock correction — how stale the estimate is.
+    since_dock: f64,
+}
+
+impl Odometry {
+    pub fn new(speed: f64, turn_rate: f64) -> Self {
+        Self {
+            pose: Pose::default(),
+            speed,
+            turn_rate,
+            moving: None,
+            since_dock: 0.0,
+        }
+    }
+
+    pub fn pose(&mut self) -> Pose {
+        self.settle();
+        self.pose
+    }
+
+    pub fn since_dock(&self) -> f64 {
+        self.since_dock
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn turn_rate(&self) -> f64 {
+        self.turn_rate
+    }
+
+    /// Integrate `duration` of `direction`.
+    pub fn apply(&mut self, direction: Direction, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let p = &mut self.pose;
+        match direction {
+            Direction::Forward | Direction::Backward => {
+                let sign = if direction == Direction::Forward {
+                    1.0
+                } else {
+                    -1.0
+                };
+                let d = sign * self.speed * secs;
+                let rad = p.heading.to_radians();
+                p.x += d * rad.cos();
+                p.y += d * rad.sin();
+                self.since_dock += d.abs();
+            }
+            Direction::Left => p.heading = (p.heading + self.turn_rate * secs).rem_euclid(360.0),
+            Direction::Right => p.heading = (p.heading - self.turn_rate * secs).rem_euclid(360.0),
+            Direction::Stop => {}
+        }
+    }
+
+    /// An untimed movement started (or stopped, for `Stop`).
+    pub fn start(&mut self, direction: Direction) {
+        self.settle();
+        self.moving = (direction != Direction::Stop).then(|| (direction, Instant::now()));
+    }
+
+    /// A timed movement ended after `duration`: replaces the wall-clock tracking
+    /// started for it with the exact figure.
+    pub fn complete(&mut self, direction: Direction, duration: Duration) {
+        self.moving = None;
+        self.apply(direction, duration);
+    }
+
+    /// Catch an untimed movement up to now.
+    pub fn settle(&mut self) {
+        if let Some((direction, since)) = self.moving {
+            let now = Instant::now();
+            self.apply(direction, now.duration_since(since));
+            self.moving = Some((direction, now));
+        }
+    }
+
+    /// The body is on its dock: snap back to the origin.
+    pub fn reset_to_dock(&mut self) {
+        self.pose = Pose::default();
+        self.moving = None;
+        self.since_dock = 0.0;
+    }
+
+    /// Legs that take the body from the current estimate to `target`.
+    pub fn plan(&mut self, target: &Pose) -> Vec<(Direction, Duration)> {
+        let from = self.pose();
+        let mut legs = Vec::new();
+        let (dx, dy) = (target.x - from.x, target.y - from.y);
+        let distance = dx.hypot(dy);
+        let mut heading = from.heading;
+
+        if distance > ARRIVAL_DISTANCE {
+            let bearing = dy.atan2(dx).to_degrees().rem_euclid(360.0);
+            legs.extend(self.turn(heading, bearing));
+            legs.push((
+                Direction::Forward,
+                Duration::from_secs_f64(distance / self.speed),
+            ));
+            heading = bearing;
+        }
+        legs.extend(self.turn(heading, target.heading));
+        legs
+    }
+
+    fn turn(&self, from: f64, to: f64) -> Option<(Direction, Duration)> {
+        // Shortest way round, in (-180, 180].
+        let mut delta = (to - from).rem_euclid(360.0);
+        if delta > 180.0 {
+            delta -= 360.0;
+        }
+        if delta.abs() < MIN_TURN {
+            return None;
+        }
+        let direction = if delta > 0.0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        };
+        Some((
+            direction,
+            Duration::from_secs_f64(delta.abs() / self.turn_rate),
+        ))
+    }
+}
+
+// ── Named locations ───────────────────────────────────────────────────────────
+
+pub struct Locations {
+    /// `None` keeps locations in memory only.
+    path: Option<PathBuf>,
+    places: BTreeMap<String, Pose>,
+}
+
+pub fn default_locations_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".familiar_ai")
+        .join("locations.json")
+}
+
+impl Locations {
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            places: BTreeMap::new(),
+        }
+    }
+
+    /// Load from `path`; a missing file is an empty set.
+    pub fn load(path: PathBuf) -> Self {
+        let places = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path: Some(path),
+            places,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Pose> {
+        match self.places.get(&normalize(name)) {
+            Some(pose) => Some(*pose),
+            None if normalize(name) == DOCK => Some(Pose::default()),
+            None => None,
+        }
+    }
+
+    pub fn mark(&mut self, name: &str, pose: Pose) -> Result<()> {
+        self.places.insert(normalize(name), pose);
+        self.save()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.places.keys().cloned().collect()
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&self.places)?)?;
+        Ok(())
+    }
+
+    /// One line for the world model, e.g. "kitchen (x=2.00 m, y=1.00 m), sofa (...)".
+    pub fn summary(&self) -> String {
+        if self.places.is_empty() {
+            return "(none marked yet)".to_string();
+        }
+        self.places
+            .iter()
+            .map(|(name, p)| format!("{name} (x={:.2} m, y={:.2} m)", p.x, p.y))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn odometry() -> Odometry {
+        Odometry::new(NOMINAL_SPEED, NOMINAL_TURN_RATE)
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    fn assert_legs(legs: &[(Direction, Duration)], expected: &[(Direction, f64)]) {
+        assert_eq!(legs.len(), expected.len(), "{legs:?}");
+        for ((dir, dur), (want_dir, want_secs)) in legs.iter().zip(expected) {
+            assert_eq!(dir, want_dir, "{legs:?}");
+            assert!(close(dur.as_secs_f64(), *want_secs), "{legs:?}");
+        }
+    }
+
+    #[test]
+    fn integrates_commanded_motion() {
+        let mut odo = odometry();
+        odo.apply(Direction::Forward, Duration::from_secs(2));
+        odo.apply(Direction::Left, Duration::from_secs(1));
+        odo.apply(Direction::Forward, Duration::from_secs(1));
+        let p = odo.pose();
+        assert!(
+            close(p.x, 0.6) && close(p.y, 0.3) && close(p.heading, 90.0),
+            "{p:?}"
+        );
+        assert!(close(odo.since_dock(), 0.9));
+
+        odo.apply(Direction::Right, Duration::from_secs(2));
+        odo.apply(Direction::Backward, Duration::from_secs(1));
+        let p = odo.pose();
+        assert!(close(p.heading, 270.0));
+        assert!(close(p.x, 0.6) && close(p.y, 0.6), "{p:?}");
+    }
+
+    #[test]
+    fn untimed_motion_accumulates_until_stopped() {
+        let mut odo = odometry();
+        odo.start(Direction::Forward);
+        std::thread::sleep(Duration::from_millis(50));
+        odo.start(Direction::Stop);
+        let x = odo.pose().x;
+        assert!(x >= NOMINAL_SPEED * 0.05 - 1e-9, "{x}");
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(odo.pose().x, x);
+    }
+
+    #[test]
+    fn dock_resets_drift() {
+        let mut odo = odometry();
+        odo.apply(Direction::Forward, Duration::from_secs(3));
+        odo.reset_to_dock();
+        assert_eq!(odo.pose(), Pose::default());
+        assert_eq!(odo.since_dock(), 0.0);
+    }
+
+    #[test]
+    fn plans_turn_forward_turn() {
+        let mut odo = odometry();
+        let target = Pose {
+            x: 0.0,
+            y: 1.5,
+            heading: 180.0,
+        };
+        let legs = odo.plan(&target);
+        assert_legs(
+            &legs,
+            &[
+                (Direction::Left, 1.0),
+                (Direction::Forward, 5.0),
+                (Direction::Left, 1.0),
+            ],
+        );
+
+        for (direction, duration) in legs {
+            odo.apply(direction, duration);
+        }
+        let p = odo.pose();
+        assert!(
+            close(p.x, 0.0) && close(p.y, 1.5) && close(p.heading, 180.0),
+            "{p:?}"
+        );
+    }
+
+    #[test]
+    fn plans_shortest_turn() {
+        let mut odo = odometry();
+        odo.apply(Direction::Left, Duration::from_secs(3)); // facing south (270°)
+        let legs = odo.plan(&Pose {
+            x: 0.0,
+            y: 0.0,
+            heading: 0.0,
+        });
+        assert_legs(&legs, &[(Direction::Left, 1.0)]);
+    }
+
+    #[test]
+    fn already_there_needs_no_legs() {
+        let mut odo = odometry();
+        assert!(odo
+            .plan(&Pose {
+                x: 0.01,
+                y: 0.0,
+                heading: 1.0
+            })
+            .is_empty());
+    }
+
+    #[test]
+    fn locations_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("locations.json");
+        let mut locations = Locations::load(path.clone());
+        assert_eq!(locations.summary(), "(none marked yet)");
+
+        let kitchen = Pose {
+            x: 2.0,
+            y: 1.0,
+            heading: 90.0,
+        };
+        locations.mark("Kitchen ", kitchen).unwrap();
+
+        let reloaded = Locations::load(path);
+        assert_eq!(reloaded.get("kitchen"), Some(kitchen));
+        assert_eq!(reloaded.names(), vec!["kitchen"]);
+        assert_eq!(reloaded.summary(), "kitchen (x=2.00 m, y=1.00 m)");
+    }
+
+    #[test]
+    fn dock_is_always_known() {
+        let locations = Locations::in_memory();
+        assert_eq!(locations.get("Dock"), Some(Pose::default()));
+        assert_eq!(locations.get("attic"), None);
+    }
+
+    #[test]
+    fn corrupt_file_loads_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locations.json");
+        std::fs::write(&path, "not json").unwrap();
+        assert!(Locations::load(path).names().is_empty());
+    }
+}
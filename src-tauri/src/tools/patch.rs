@@ -0,0 +1,645 @@
+/// Parsing and fuzzy application of patches for `apply_patch`.
+///
+/// A patch is a unified diff (`git diff`, `diff -u`) or a list of string
+/// edits. Hunk line counts are ignored — models often get them wrong — and
+/// hunks are found by their context, nearest the header's line number:
+/// exactly, then ignoring trailing whitespace, then ignoring indentation,
+/// then with up to two context lines dropped at each end (GNU patch's fuzz).
+/// Everything here works on strings; `FsTool::apply_patch` does the I/O.
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+/// Context lines a hunk may lose at each end and still apply.
+const MAX_FUZZ: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Modify,
+    Create,
+    Delete,
+}
+
+/// The changes to one file.
+#[derive(Debug, PartialEq)]
+pub struct FilePatch {
+    pub path: String,
+    pub kind: Kind,
+    pub ops: Vec<Op>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Op {
+    Hunk(Hunk),
+    Edit(Edit),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Hunk {
+    pub header: String,
+    /// 1-based line the hunk starts at in the old file, if the header has one.
+    old_start: Option<usize>,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Edit {
+    pub old: String,
+    pub new: String,
+    pub replace_all: bool,
+}
+
+impl Op {
+    /// How the op is named in the report.
+    pub fn describe(&self, n: usize) -> String {
+        match self {
+            Op::Hunk(h) => format!("hunk {n} {}", h.header),
+            Op::Edit(_) => format!("edit {n}"),
+        }
+    }
+}
+
+/// The file patches in a tool call: `patch` (unified diff) and/or `edits`.
+pub fn parse_input(input: &Value) -> Result<Vec<FilePatch>> {
+    let mut files = Vec::new();
+    if let Some(text) = input["patch"].as_str() {
+        files.extend(parse_unified(text)?);
+    }
+    if let Some(edits) = input["edits"].as_array() {
+        for (i, edit) in edits.iter().enumerate() {
+            let (Some(path), Some(old), Some(new)) = (
+                edit["path"].as_str(),
+                edit["old_string"].as_str(),
+                edit["new_string"].as_str(),
+            ) else {
+                bail!("edit {} needs path, old_string and new_string", i + 1);
+            };
+            let op = Op::Edit(Edit {
+                old: old.to_string(),
+                new: new.to_string(),
+                replace_all: edit["replace_all"].as_bool().unwrap_or(false),
+            });
+            // Consecutive edits to one file are one file patch.
+            match files.last_mut() {
+                Some(last) if last.path == path && last.kind == Kind::Modify => last.ops.push(op),
+                _ => files.push(FilePatch {
+                    path: path.to_string(),
+                    kind: Kind::Modify,
+                    ops: vec![op],
+                }),
+            }
+        }
+    }
+    if files.is_empty() {
+        bail!("Give a unified diff in `patch` or a list of `edits`");
+    }
+    Ok(files)
+}
+
+/// Every path a tool call touches, for permission checks. Unparseable
+/// input yields what could be read; the tool itself reports the error.
+pub fn touched_paths(input: &Value) -> Vec<String> {
+    let mut paths = Vec::new();
+    if let Some(text) = input["patch"].as_str() {
+        let lines: Vec<&str> = text.lines().collect();
+        for pair in lines.windows(2) {
+            if let (Some(old), Some(new)) =
+                (pair[0].strip_prefix("--- "), pair[1].strip_prefix("+++ "))
+            {
+                paths.extend(header_path(old));
+                paths.extend(header_path(new));
+            }
+        }
+    }
+    if let Some(edits) = input["edits"].as_array() {
+        paths.extend(
+            edits
+                .iter()
+                .filter_map(|e| e["path"].as_str().map(str::to_string)),
+        );
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+pub fn parse_unified(text: &str) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut in_hunk = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        let next = lines.get(i).copied().unwrap_or("");
+        if line.starts_with("--- ") && next.starts_with("+++ ") {
+            i += 1;
+            in_hunk = false;
+            let old = header_path(&line[4..]);
+            let new = header_path(&next[4..]);
+            let (path, kind) = match (old, new) {
+                (None, Some(new)) => (new, Kind::Create),
+                (Some(old), None) => (old, Kind::Delete),
+                (Some(old), Some(new)) if old == new => (new, Kind::Modify),
+                (Some(old), Some(new)) => {
+                    bail!("Renames aren't supported ({old} → {new}); move the file with bash")
+                }
+                (None, None) => bail!("Both sides of a file header are /dev/null"),
+            };
+            files.push(FilePatch {
+                path,
+                kind,
+                ops: Vec::new(),
+            });
+            continue;
+        }
+        if line.starts_with("@@") {
+            let Some(file) = files.last_mut() else {
+                bail!("Hunk before any ---/+++ file header: {line}");
+            };
+            file.ops.push(Op::Hunk(Hunk {
+                header: hunk_header(line),
+                old_start: old_start(line),
+                lines: Vec::new(),
+            }));
+            in_hunk = true;
+            continue;
+        }
+        if !in_hunk {
+            // `diff --git`, `index`, mode lines and prose around the diff.
+            continue;
+        }
+        let Some(Op::Hunk(hunk)) = files.last_mut().and_then(|f| f.ops.last_mut()) else {
+            continue;
+        };
+        match line.chars().next() {
+            Some(' ') => hunk.lines.push(Line::Context(line[1..].to_string())),
+            Some('-') => hunk.lines.push(Line::Remove(line[1..].to_string())),
+            Some('+') => hunk.lines.push(Line::Add(line[1..].to_string())),
+            // Blank context lines often lose their leading space.
+            None => hunk.lines.push(Line::Context(String::new())),
+            // "\ No newline at end of file": the file's own ending is kept.
+            Some('\\') => {}
+            _ => in_hunk = false,
+        }
+    }
+    for file in &files {
+        if file.kind == Kind::Modify && file.ops.is_empty() {
+            bail!("No hunks for {}", file.path);
+        }
+    }
+    Ok(files)
+}
+
+/// The path in a `---`/`+++` line, without `a/`/`b/` or a timestamp;
+/// `None` for /dev/null.
+fn header_path(raw: &str) -> Option<String> {
+    let path = raw.split('\t').next().unwrap_or("").trim();
+    if path == "/dev/null" || path.is_empty() {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// `@@ -12,5 +12,6 @@ fn main()` → `@@ -12,5 +12,6 @@`
+fn hunk_header(line: &str) -> String {
+    match line[2..].find("@@") {
+        Some(end) => line[..end + 4].to_string(),
+        None => line.trim().to_string(),
+    }
+}
+
+fn old_start(line: &str) -> Option<usize> {
+    let rest = line.trim_start_matches('@').trim_start();
+    let range = rest.strip_prefix('-')?.split([' ', ',']).next()?;
+    range.parse().ok()
+}
+
+// ── Applying ──────────────────────────────────────────────────────
+
+/// A file's lines plus how to write them back out.
+pub struct Text {
+    lines: Vec<String>,
+    eol: &'static str,
+    trailing_newline: bool,
+}
+
+impl Text {
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_string).collect(),
+            eol: if content.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            },
+            trailing_newline: content.ends_with('\n') || content.is_empty(),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = self.lines.join(self.eol);
+        if self.trailing_newline && !self.lines.is_empty() {
+            out.push_str(self.eol);
+        }
+        out
+    }
+}
+
+/// How loosely a hunk's context had to be compared to find it.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Match {
+    Exact,
+    TrailingSpace,
+    Indentation,
+}
+
+impl Match {
+    fn eq(self, file: &str, patch: &str) -> bool {
+        match self {
+            Match::Exact => file == patch,
+            Match::TrailingSpace => file.trim_end() == patch.trim_end(),
+            Match::Indentation => file.trim() == patch.trim(),
+        }
+    }
+}
+
+/// Applies ops in order to one file, tracking where later hunks have moved.
+pub struct Applier {
+    pub text: Text,
+    /// Lines added minus lines removed (and drift found) so far, for the
+    /// next hunk's expected position.
+    shift: isize,
+}
+
+impl Applier {
+    pub fn new(content: &str) -> Self {
+        Self {
+            text: Text::parse(content),
+            shift: 0,
+        }
+    }
+
+    /// Apply one op; `Ok` describes how it applied (empty for a clean fit).
+    pub fn apply(&mut self, op: &Op) -> Result<String, String> {
+        match op {
+            Op::Hunk(hunk) => self.apply_hunk(hunk),
+            Op::Edit(edit) => self.apply_edit(edit),
+        }
+    }
+
+    fn apply_hunk(&mut self, hunk: &Hunk) -> Result<String, String> {
+        let lines = &hunk.lines;
+        let lead = lines
+            .iter()
+            .take_while(|l| matches!(l, Line::Context(_)))
+            .count();
+        let trail = lines
+            .iter()
+            .rev()
+            .take_while(|l| matches!(l, Line::Context(_)))
+            .count()
+            .min(lines.len() - lead);
+        let expected = hunk
+            .old_start
+            .map(|s| (s.saturating_sub(1) as isize + self.shift).max(0) as usize);
+
+        let mut ambiguous = None;
+        for fuzz in 0..=MAX_FUZZ {
+            let (front, back) = (fuzz.min(lead), fuzz.min(trail));
+            if fuzz > 0 && front == 0 && back == 0 {
+                break;
+            }
+            let part = &lines[front..lines.len() - back];
+            let old: Vec<&str> = part
+                .iter()
+                .filter_map(|l| match l {
+                    Line::Context(s) | Line::Remove(s) => Some(s.as_str()),
+                    Line::Add(_) => None,
+                })
+                .collect();
+            let near = expected.map(|e| e + front);
+            match self.find(&old, near) {
+                Found::At(pos, how) => {
+                    let note = describe_fit(pos, near, how, fuzz);
+                    let new_len = self.splice(pos, part);
+                    // Later lines moved by the drift found here plus the size change.
+                    if let Some(start) = hunk.old_start {
+                        let old_pos = (start.saturating_sub(1) + front) as isize;
+                        self.shift = pos as isize - old_pos + new_len - old.len() as isize;
+                    }
+                    return Ok(note);
+                }
+                Found::Ambiguous(at) => {
+                    ambiguous.get_or_insert(at);
+                }
+                Found::Missing => {}
+            }
+        }
+        let context: Vec<String> = lines
+            .iter()
+            .filter_map(|l| match l {
+                Line::Context(s) | Line::Remove(s) => Some(format!("    | {s}")),
+                Line::Add(_) => None,
+            })
+            .take(4)
+            .collect();
+        Err(match ambiguous {
+            Some(at) => format!(
+                "context matches at lines {}; add the line numbers or more context",
+                at.iter()
+                    .map(|p| (p + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => format!(
+                "context not found{}. Expected:\n{}",
+                expected.map_or(String::new(), |e| format!(" near line {}", e + 1)),
+                context.join("\n")
+            ),
+        })
+    }
+
+    /// Where `old` sits in the file: the match nearest `near`, at the
+    /// strictest comparison that finds one.
+    fn find(&self, old: &[&str], near: Option<usize>) -> Found {
+        let file = &self.text.lines;
+        if old.is_empty() {
+            // Pure insertion: trust the line number, else append.
+            return Found::At(near.unwrap_or(file.len()).min(file.len()), Match::Exact);
+        }
+        if old.len() > file.len() {
+            return Found::Missing;
+        }
+        for how in [Match::Exact, Match::TrailingSpace, Match::Indentation] {
+            let hits: Vec<usize> = (0..=file.len() - old.len())
+                .filter(|&pos| old.iter().zip(&file[pos..]).all(|(o, f)| how.eq(f, o)))
+                .collect();
+            match (hits.len(), near) {
+                (0, _) => continue,
+                (1, _) => return Found::At(hits[0], how),
+                (_, Some(near)) => {
+                    let best = *hits.iter().min_by_key(|&&p| p.abs_diff(near)).unwrap();
+                    return Found::At(best, how);
+                }
+                (_, None) => return Found::Ambiguous(hits),
+            }
+        }
+        Found::Missing
+    }
+
+    /// Replace the old lines at `pos` following `part`; context lines keep
+    /// the file's version. Returns how many lines replace them.
+    fn splice(&mut self, pos: usize, part: &[Line]) -> isize {
+        let mut replacement = Vec::new();
+        let mut cursor = pos;
+        for line in part {
+            match line {
+                Line::Context(_) => {
+                    replacement.push(self.text.lines[cursor].clone());
+                    cursor += 1;
+                }
+                Line::Remove(_) => cursor += 1,
+                Line::Add(s) => replacement.push(s.clone()),
+            }
+        }
+        let new_len = replacement.len() as isize;
+        self.text.lines.splice(pos..cursor, replacement);
+        new_len
+    }
+
+    fn apply_edit(&mut self, edit: &Edit) -> Result<String, String> {
+        let content = self.text.render();
+        if edit.old.is_empty() {
+            if content.is_empty() {
+                self.text = Text::parse(&edit.new);
+                return Ok(String::new());
+            }
+            return Err("old_string is empty".to_string());
+        }
+        let count = content.matches(&edit.old).count();
+        let updated = match count {
+            1 => content.replacen(&edit.old, &edit.new, 1),
+            n if n > 1 && edit.replace_all => content.replace(&edit.old, &edit.new),
+            n if n > 1 => {
+                return Err(format!(
+                    "old_string appears {n} times; add context or set replace_all"
+                ))
+            }
+            _ => return self.apply_edit_loosely(edit),
+        };
+        self.text = Text::parse(&updated);
+        Ok(if count > 1 {
+            format!("replaced {count} occurrences")
+        } else {
+            String::new()
+        })
+    }
+
+    /// An edit whose old_string only matches whole lines ignoring whitespace.
+    fn apply_edit_loosely(&mut self, edit: &Edit) -> Result<String, String> {
+        let old: Vec<&str> = edit.old.trim_matches('\n').lines().collect();
+        match self.find(&old, None) {
+            Found::At(pos, how) if how != Match::Exact => {
+                let new = edit.new.trim_matches('\n').lines().map(str::to_string);
+                self.text.lines.splice(pos..pos + old.len(), new);
+                Ok("matched ignoring whitespace".to_string())
+            }
+            Found::At(..) => Err("old_string not found".to_string()),
+            Found::Ambiguous(at) => Err(format!(
+                "old_string not found exactly, and matches {} places ignoring whitespace",
+                at.len()
+            )),
+            Found::Missing => Err("old_string not found".to_string()),
+        }
+    }
+}
+
+enum Found {
+    At(usize, Match),
+    Ambiguous(Vec<usize>),
+    Missing,
+}
+
+fn describe_fit(pos: usize, near: Option<usize>, how: Match, fuzz: usize) -> String {
+    let mut notes = Vec::new();
+    if let Some(near) = near.filter(|&n| n != pos) {
+        notes.push(format!(
+            "at line {} (offset {:+})",
+            pos + 1,
+            pos as isize - near as isize
+        ));
+    }
+    match how {
+        Match::Exact => {}
+        Match::TrailingSpace => notes.push("ignoring trailing whitespace".to_string()),
+        Match::Indentation => notes.push("ignoring indentation".to_string()),
+    }
+    if fuzz > 0 {
+        notes.push(format!("fuzz {fuzz}"));
+    }
+    notes.join(", ")
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn apply_all(content: &str, patch: &str) -> (String, Vec<Result<String, String>>) {
+        let files = parse_unified(patch).unwrap();
+        let mut applier = Applier::new(content);
+        let results = files[0].ops.iter().map(|op| applier.apply(op)).collect();
+        (applier.text.render(), results)
+    }
+
+    const FILE: &str = "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n\nfn c() {\n    3\n}\n";
+
+    #[test]
+    fn parses_git_diffs_with_several_files() {
+        let patch = "diff --git a/src/x.rs b/src/x.rs\nindex 1..2 100644\n\
+                     --- a/src/x.rs\n+++ b/src/x.rs\n@@ -1,2 +1,2 @@ fn x()\n a\n-b\n+c\n\
+                     --- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+hello\n\
+                     --- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n";
+        let files = parse_unified(patch).unwrap();
+        let summary: Vec<(&str, Kind, usize)> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.kind, f.ops.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("src/x.rs", Kind::Modify, 1),
+                ("new.txt", Kind::Create, 1),
+                ("old.txt", Kind::Delete, 1),
+            ]
+        );
+        assert_eq!(files[0].ops[0].describe(1), "hunk 1 @@ -1,2 +1,2 @@");
+        assert!(parse_unified("--- a/x\n+++ b/y\n@@ -1 +1 @@\n-a\n+b\n").is_err());
+    }
+
+    #[test]
+    fn removed_lines_starting_with_dashes_stay_in_the_hunk() {
+        let patch = "--- a/x.md\n+++ b/x.md\n@@ -1,2 +1,1 @@\n--- rule\n keep\n";
+        let (out, results) = apply_all("-- rule\nkeep\n", patch);
+        assert_eq!(results, [Ok(String::new())]);
+        assert_eq!(out, "keep\n");
+    }
+
+    #[test]
+    fn hunks_apply_with_drifted_line_numbers() {
+        // Header numbers are 3 lines off; both hunks still land.
+        let patch = "--- a/f.rs\n+++ b/f.rs\n\
+                     @@ -8,3 +8,3 @@\n fn b() {\n-    2\n+    two\n }\n\
+                     @@ -12,3 +12,4 @@\n fn c() {\n     3\n+    4\n }\n";
+        let (out, results) = apply_all(FILE, patch);
+        assert_eq!(results[0], Ok("at line 5 (offset -3)".to_string()));
+        assert!(results[1].is_ok(), "{results:?}");
+        assert_eq!(
+            out,
+            "fn a() {\n    1\n}\n\nfn b() {\n    two\n}\n\nfn c() {\n    3\n    4\n}\n"
+        );
+    }
+
+    #[test]
+    fn whitespace_and_fuzz_are_tolerated_and_reported() {
+        let patch = "--- a/f.rs\n+++ b/f.rs\n@@ -5,3 +5,3 @@\n fn b() {  \n-  2\n+    22\n }\n";
+        let (out, results) = apply_all(FILE, patch);
+        assert_eq!(results, [Ok("ignoring indentation".to_string())]);
+        assert!(out.contains("fn b() {\n    22\n}"), "{out}");
+
+        // The first context line is wrong; fuzz 1 drops it.
+        let patch = "--- a/f.rs\n+++ b/f.rs\n@@ -5,3 +5,3 @@\n fn bee() {\n-    2\n+    22\n }\n";
+        let (out, results) = apply_all(FILE, patch);
+        assert_eq!(results, [Ok("fuzz 1".to_string())]);
+        assert!(out.contains("fn b() {\n    22\n}"), "{out}");
+    }
+
+    #[test]
+    fn failures_say_why() {
+        let patch = "--- a/f.rs\n+++ b/f.rs\n@@ -5,3 +5,3 @@\n fn zzz() {\n-    9\n+    22\n }\n";
+        let (out, results) = apply_all(FILE, patch);
+        let err = results[0].clone().unwrap_err();
+        assert!(err.starts_with("context not found near line 5"), "{err}");
+        assert!(err.contains("| fn zzz() {"), "{err}");
+        assert_eq!(out, FILE);
+
+        // No line numbers and several equal matches.
+        let patch = "--- a/f.rs\n+++ b/f.rs\n@@ @@\n-}\n+};\n";
+        let (_, results) = apply_all(FILE, patch);
+        let err = results[0].clone().unwrap_err();
+        assert!(err.contains("matches at lines 3, 7, 11"), "{err}");
+    }
+
+    #[test]
+    fn crlf_and_missing_final_newline_are_preserved() {
+        let patch =
+            "--- a/w.txt\n+++ b/w.txt\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n\\ No newline at end of file\n";
+        let (out, results) = apply_all("a\r\nb", patch);
+        assert!(results[0].is_ok());
+        assert_eq!(out, "a\r\nc");
+    }
+
+    #[test]
+    fn edits_replace_unique_strings_or_whole_lines_loosely() {
+        let mut applier = Applier::new(FILE);
+        let edit = |old: &str, new: &str, replace_all| {
+            Op::Edit(Edit {
+                old: old.to_string(),
+                new: new.to_string(),
+                replace_all,
+            })
+        };
+        assert_eq!(
+            applier.apply(&edit("    1", "    one", false)),
+            Ok(String::new())
+        );
+        let err = applier.apply(&edit("}", "};", false)).unwrap_err();
+        assert!(err.contains("appears 3 times"), "{err}");
+        assert_eq!(
+            applier.apply(&edit("}\n", "};\n", true)),
+            Ok("replaced 3 occurrences".to_string())
+        );
+        assert_eq!(
+            applier.apply(&edit("fn c() {\n  3", "fn c() {\n    three", false)),
+            Ok("matched ignoring whitespace".to_string())
+        );
+        assert!(applier.apply(&edit("nope", "x", false)).is_err());
+        assert_eq!(
+            applier.text.render(),
+            "fn a() {\n    one\n};\n\nfn b() {\n    2\n};\n\nfn c() {\n    three\n};\n"
+        );
+    }
+
+    #[test]
+    fn input_combines_patch_and_edits_and_lists_paths() {
+        let input = json!({
+            "patch": "--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-x\n+y\n--- /dev/null\n+++ b/n.rs\n@@ -0,0 +1 @@\n+n\n",
+            "edits": [
+                { "path": "b.rs", "old_string": "1", "new_string": "2" },
+                { "path": "b.rs", "old_string": "3", "new_string": "4" },
+                { "path": "c.rs", "old_string": "5", "new_string": "6" }
+            ]
+        });
+        let files = parse_input(&input).unwrap();
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["a.rs", "n.rs", "b.rs", "c.rs"]);
+        assert_eq!(files[2].ops.len(), 2);
+        assert_eq!(touched_paths(&input), ["a.rs", "b.rs", "c.rs", "n.rs"]);
+
+        assert!(parse_input(&json!({})).is_err());
+        assert!(parse_input(&json!({ "edits": [{ "path": "x" }] })).is_err());
+    }
+}
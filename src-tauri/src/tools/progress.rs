@@ -0,0 +1,109 @@
+/// Live output from a running tool, for the UI.
+///
+/// `ToolRegistry::execute` hands every tool a `Progress`; long-running tools
+/// (`bash`) report each line of stdout/stderr as it arrives, and the agent
+/// relays them as `AgentEvent::ToolProgress`. The LLM still only sees the
+/// final result. A flood of output is capped so it can't swamp the frontend.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+/// Lines reported per tool call; the rest only show up in the final result.
+const MAX_LINES: usize = 5_000;
+/// Longer lines (minified files, progress bars) are cut.
+const MAX_LINE_CHARS: usize = 2_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+type Sink = dyn Fn(Stream, String) + Send + Sync;
+
+#[derive(Clone, Default)]
+pub struct Progress {
+    sink: Option<Arc<Sink>>,
+    lines: Arc<AtomicUsize>,
+}
+
+impl Progress {
+    pub fn new(sink: impl Fn(Stream, String) + Send + Sync + 'static) -> Self {
+        Self {
+            sink: Some(Arc::new(sink)),
+            lines: Arc::default(),
+        }
+    }
+
+    /// Reports nothing; for callers without a UI (tests, sub-tools).
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// One line of output, without its trailing newline.
+    pub fn line(&self, stream: Stream, line: &str) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let n = self.lines.fetch_add(1, Ordering::Relaxed);
+        if n > MAX_LINES {
+            return;
+        }
+        if n == MAX_LINES {
+            sink(stream, "[… more output in the result …]".to_string());
+            return;
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        let line = match line.char_indices().nth(MAX_LINE_CHARS) {
+            Some((cut, _)) => format!("{}…", &line[..cut]),
+            None => line.to_string(),
+        };
+        sink(stream, line);
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    type Seen = Arc<Mutex<Vec<(Stream, String)>>>;
+
+    fn recording() -> (Progress, Seen) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let progress = Progress::new(move |stream, line| sink.lock().unwrap().push((stream, line)));
+        (progress, seen)
+    }
+
+    #[test]
+    fn lines_are_trimmed_and_cut() {
+        let (progress, seen) = recording();
+        progress.line(Stream::Stdout, "hello\r\n");
+        progress.line(Stream::Stderr, &"é".repeat(MAX_LINE_CHARS + 5));
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0], (Stream::Stdout, "hello".to_string()));
+        assert_eq!(seen[1].0, Stream::Stderr);
+        assert_eq!(seen[1].1.chars().count(), MAX_LINE_CHARS + 1);
+    }
+
+    #[test]
+    fn floods_are_capped_with_one_notice() {
+        let (progress, seen) = recording();
+        for i in 0..MAX_LINES + 100 {
+            progress.line(Stream::Stdout, &i.to_string());
+        }
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), MAX_LINES + 1);
+        assert!(seen[MAX_LINES].1.contains("more output"));
+    }
+
+    #[test]
+    fn none_reports_nothing() {
+        Progress::none().line(Stream::Stdout, "ignored");
+    }
+}
@@ -0,0 +1,344 @@
+/// Robot bodies — pluggable backends for the `walk` and `body_status` tools.
+///
+///   - Tuya: robot vacuum via cloud API or LAN (the original body, see `tuya`)
+///   - HTTP: any robot exposing a small JSON API (see `HttpRobot`)
+///   - Simulator: a 2D floor plan with walls and bump detection (see `robot_sim`)
+///
+/// Picked by `mobility.robot` in config, defaulting to Tuya.
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::config::MobilityConfig;
+
+use super::robot_sim::SimRobot;
+use super::tuya::TuyaRobot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Stop,
+}
+
+impl Direction {
+    /// Parse a `walk` direction; anything unknown stops the robot.
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "forward" => Self::Forward,
+            "backward" => Self::Backward,
+            "left" => Self::Left,
+            "right" => Self::Right,
+            _ => Self::Stop,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Forward => "forward",
+            Self::Backward => "backward",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Stop => "stop",
+        }
+    }
+}
+
+/// What happened while moving.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Motion {
+    /// The bumper hit something and the robot stopped early.
+    pub bumped: bool,
+    /// How long the body actually moved, when it knows (a bump cuts a walk short).
+    pub moved_for: Option<Duration>,
+}
+
+/// Position in metres (x east, y north of the origin) and heading in
+/// degrees counter-clockwise from east, in [0, 360).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Pose {
+    pub x: f64,
+    pub y: f64,
+    pub heading: f64,
+}
+
+impl Pose {
+    pub fn compass(&self) -> &'static str {
+        const NAMES: [&str; 8] = [
+            "east",
+            "north-east",
+            "north",
+            "north-west",
+            "west",
+            "south-west",
+            "south",
+            "south-east",
+        ];
+        NAMES[((self.heading / 45.0).round() as usize) % 8]
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "x={:.2} m, y={:.2} m, facing {} ({:.0}°)",
+            self.x,
+            self.y,
+            self.compass(),
+            self.heading
+        )
+    }
+}
+
+pub type RobotFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// A body that can move around and report how it feels.
+pub trait Robot: Send + Sync {
+    /// Short name for logs and the world model.
+    fn name(&self) -> &'static str;
+
+    fn is_configured(&self) -> bool;
+
+    /// Start moving in `direction` and keep going until the next command;
+    /// `Stop` halts.
+    fn drive(&self, direction: Direction) -> RobotFuture<'_, Motion>;
+
+    /// Move for `duration`, then stop.
+    fn walk(&self, direction: Direction, duration: Duration) -> RobotFuture<'_, Motion> {
+        Box::pin(async move {
+            let started = self.drive(direction).await?;
+            tokio::time::sleep(duration).await;
+            let stopped = self.drive(Direction::Stop).await?;
+            Ok(Motion {
+                bumped: started.bumped || stopped.bumped,
+                moved_for: None,
+            })
+        })
+    }
+
+    /// Battery, charging state, faults — as a short description.
+    fn status(&self) -> RobotFuture<'_, String>;
+
+    /// Whether the robot is sitting on its charging dock. Bodies that can't
+    /// tell say no.
+    fn docked(&self) -> RobotFuture<'_, bool> {
+        Box::pin(async { Ok(false) })
+    }
+
+    /// Top-down view of the robot in its surroundings (JPEG base64), if known.
+    fn top_down_view(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Build the robot selected in config.
+pub fn create_robot(config: &MobilityConfig, client: Client) -> Box<dyn Robot> {
+    match config.effective_robot() {
+        "http" => Box::new(HttpRobot::new(&config.http_url, &config.http_token, client)),
+        "sim" => match SimRobot::from_config(config) {
+            Ok(sim) => Box::new(sim),
+            Err(e) => {
+                tracing::warn!(
+                    "Robot simulator floor plan unusable ({e}); using the built-in room"
+                );
+                Box::new(SimRobot::default())
+            }
+        },
+        _ => Box::new(TuyaRobot::from_config(config)),
+    }
+}
+
+// ── HTTP/JSON robot ───────────────────────────────────────────────────────────
+
+/// A robot exposing:
+///   POST {url}/drive  {"direction": "forward"}  → {"bumped": false}
+///   GET  {url}/status                           → any JSON object
+/// with an optional bearer token. `bumped` may be omitted; a `docked`
+/// boolean in the status lets pose drift be corrected on the dock.
+pub struct HttpRobot {
+    url: String,
+    token: String,
+    client: Client,
+}
+
+impl HttpRobot {
+    pub fn new(url: &str, token: &str, client: Client) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            client,
+        }
+    }
+
+    async fn call(&self, req: reqwest::RequestBuilder) -> Result<Value> {
+        let req = if self.token.is_empty() {
+            req
+        } else {
+            req.bearer_auth(&self.token)
+        };
+        let resp = req.timeout(Duration::from_secs(10)).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!(
+                "robot HTTP {status}: {}",
+                resp.text().await.unwrap_or_default()
+            );
+        }
+        let text = resp.text().await?;
+        Ok(serde_json::from_str(&text).unwrap_or(Value::Null))
+    }
+}
+
+impl Robot for HttpRobot {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.url.is_empty()
+    }
+
+    fn drive(&self, direction: Direction) -> RobotFuture<'_, Motion> {
+        Box::pin(async move {
+            let req = self
+                .client
+                .post(format!("{}/drive", self.url))
+                .json(&json!({"direction": direction.as_str()}));
+            let resp = self.call(req).await?;
+            Ok(Motion {
+                bumped: resp["bumped"].as_bool().unwrap_or(false),
+                moved_for: None,
+            })
+        })
+    }
+
+    fn status(&self) -> RobotFuture<'_, String> {
+        Box::pin(async move {
+            let resp = self
+                .call(self.client.get(format!("{}/status", self.url)))
+                .await?;
+            Ok(describe_json_status(&resp))
+        })
+    }
+
+    fn docked(&self) -> RobotFuture<'_, bool> {
+        Box::pin(async move {
+            let resp = self.call(self.client.get(format!("{}/status", self.url))).await?;
+            Ok(resp["docked"].as_bool().unwrap_or(false))
+        })
+    }
+}
+
+/// Render a status object as `key: value` lines.
+fn describe_json_status(status: &Value) -> String {
+    match status.as_object() {
+        Some(fields) if !fields.is_empty() => fields
+            .iter()
+            .map(|(k, v)| match v {
+                Value::String(s) => format!("{k}: {s}"),
+                other => format!("{k}: {other}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => "(robot reported no status)".to_string(),
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::mock_http::{MockResponse, MockServer};
+
+    #[test]
+    fn direction_round_trip() {
+        for name in ["forward", "backward", "left", "right", "stop"] {
+            assert_eq!(Direction::parse(name).as_str(), name);
+        }
+        assert_eq!(Direction::parse("sideways"), Direction::Stop);
+    }
+
+    #[test]
+    fn create_robot_follows_config() {
+        let mut config = MobilityConfig::default();
+        assert_eq!(create_robot(&config, Client::new()).name(), "tuya");
+        config.robot = "sim".into();
+        assert_eq!(create_robot(&config, Client::new()).name(), "sim");
+        config.robot = "http".into();
+        assert_eq!(create_robot(&config, Client::new()).name(), "http");
+    }
+
+    #[test]
+    fn broken_floor_plan_falls_back_to_built_in_room() {
+        let config = MobilityConfig {
+            robot: "sim".into(),
+            sim_map: "/nonexistent/floor.txt".into(),
+            ..Default::default()
+        };
+        let robot = create_robot(&config, Client::new());
+        assert_eq!(robot.name(), "sim");
+        assert!(robot.is_configured());
+    }
+
+    async fn robot_server() -> MockServer {
+        MockServer::start(|req| {
+            if req.path == "/drive" {
+                let body: Value = serde_json::from_slice(&req.body).unwrap();
+                let bumped = body["direction"] == "forward";
+                MockResponse::json(json!({"bumped": bumped}))
+            } else if req.path == "/status" {
+                MockResponse::json(json!({"battery": 87, "state": "idle"}))
+            } else {
+                MockResponse::status(404, "not found")
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn http_robot_drives_and_reports_bumps() {
+        let server = robot_server().await;
+        let robot = HttpRobot::new(&format!("{}/", server.url), "secret", Client::new());
+
+        assert!(robot.drive(Direction::Forward).await.unwrap().bumped);
+        let motion = robot
+            .walk(Direction::Left, Duration::from_millis(5))
+            .await
+            .unwrap();
+        assert!(!motion.bumped);
+
+        let requests = server.requests();
+        let directions: Vec<Value> = requests
+            .iter()
+            .map(|r| serde_json::from_slice::<Value>(&r.body).unwrap()["direction"].clone())
+            .collect();
+        assert_eq!(
+            directions,
+            vec![json!("forward"), json!("left"), json!("stop")]
+        );
+        assert!(requests
+            .iter()
+            .all(|r| r.header("authorization") == Some("Bearer secret")));
+    }
+
+    #[tokio::test]
+    async fn http_robot_status() {
+        let server = robot_server().await;
+        let robot = HttpRobot::new(&server.url, "", Client::new());
+        let status = robot.status().await.unwrap();
+        assert!(status.contains("battery: 87"));
+        assert!(status.contains("state: idle"));
+    }
+
+    #[tokio::test]
+    async fn http_robot_error_status_is_an_error() {
+        let server = MockServer::start(|_| MockResponse::status(500, "overheated")).await;
+        let robot = HttpRobot::new(&server.url, "", Client::new());
+        assert!(robot.drive(Direction::Stop).await.is_err());
+    }
+}
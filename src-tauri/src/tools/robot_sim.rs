@@ -0,0 +1,563 @@
+/// Robot simulator — a virtual body on a 2D floor plan, for running the
+/// agent (and anything built on `walk`) without hardware.
+///
+/// Floor plans are ASCII grids, one character per 25 cm cell:
+///   '#' wall    'D' dock (start pose, facing east)    anything else: floor
+/// Outside the grid counts as wall.
+///
+/// Coordinates are metres with x east and y north; the first line of the
+/// plan is the northern edge. Heading is in degrees counter-clockwise from
+/// east. Timed walks are integrated instantly in simulated time; an untimed
+/// `drive` keeps moving in wall-clock time until the next command.
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use image::{Rgb, RgbImage};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::MobilityConfig;
+
+use super::navigation::{NOMINAL_SPEED, NOMINAL_TURN_RATE};
+use super::robot::{Direction, Motion, Pose, Robot, RobotFuture};
+
+/// Edge length of one floor plan cell.
+pub const CELL_SIZE: f64 = 0.25;
+/// Body radius of a typical robot vacuum.
+pub const ROBOT_RADIUS: f64 = 0.17;
+/// The simulated body moves at exactly the nominal rates, so dead reckoning
+/// is only thrown off by bumps.
+const SPEED: f64 = NOMINAL_SPEED;
+const TURN_RATE: f64 = NOMINAL_TURN_RATE;
+/// Integration step, seconds.
+const STEP: f64 = 0.02;
+/// Rendered pixels per cell.
+const PX_PER_CELL: u32 = 16;
+
+/// 6 m × 4 m living room with a sofa and a kitchen counter.
+const BUILT_IN_ROOM: &str = "\
+########################
+#......................#
+#......................#
+#.....####.............#
+#.....####.............#
+#......................#
+#..............#########
+#..............#.......#
+#..............#.......#
+#......................#
+#......................#
+#..D...................#
+#......................#
+#......................#
+#......................#
+########################";
+
+// ── Floor plan ────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub struct FloorPlan {
+    /// `walls[row][col]`, row 0 is the northern edge.
+    walls: Vec<Vec<bool>>,
+    width: usize,
+    dock: (f64, f64),
+}
+
+impl FloorPlan {
+    pub fn parse(text: &str) -> Result<Self> {
+        let rows: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+        if rows.is_empty() {
+            bail!("floor plan is empty");
+        }
+        let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
+        let height = rows.len();
+
+        let mut dock = None;
+        let mut walls = Vec::with_capacity(height);
+        for (row, line) in rows.iter().enumerate() {
+            let mut cells = vec![true; width];
+            for (col, ch) in line.chars().enumerate() {
+                cells[col] = ch == '#';
+                if ch == 'D' {
+                    dock = Some((
+                        (col as f64 + 0.5) * CELL_SIZE,
+                        (height - row) as f64 * CELL_SIZE - CELL_SIZE / 2.0,
+                    ));
+                }
+            }
+            walls.push(cells);
+        }
+        let Some(dock) = dock else {
+            bail!("floor plan has no dock ('D')");
+        };
+        Ok(Self { walls, width, dock })
+    }
+
+    pub fn built_in() -> Self {
+        Self::parse(BUILT_IN_ROOM).expect("built-in floor plan is valid")
+    }
+
+    pub fn dock(&self) -> (f64, f64) {
+        self.dock
+    }
+
+    fn height(&self) -> usize {
+        self.walls.len()
+    }
+
+    fn is_wall(&self, col: i64, row: i64) -> bool {
+        if col < 0 || row < 0 {
+            return true;
+        }
+        self.walls
+            .get(row as usize)
+            .and_then(|r| r.get(col as usize))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// Distance from (x, y) to the nearest wall, looking at most `reach` away
+    /// (returns `reach` when nothing is closer).
+    pub fn clearance(&self, x: f64, y: f64, reach: f64) -> f64 {
+        let h = self.height() as f64;
+        let col = (x / CELL_SIZE).floor() as i64;
+        let row = (h - y / CELL_SIZE).floor() as i64;
+        let span = (reach / CELL_SIZE).ceil() as i64 + 1;
+
+        let mut nearest = reach;
+        for r in row - span..=row + span {
+            for c in col - span..=col + span {
+                if !self.is_wall(c, r) {
+                    continue;
+                }
+                let (x0, x1) = (c as f64 * CELL_SIZE, (c + 1) as f64 * CELL_SIZE);
+                let (y0, y1) = ((h - (r + 1) as f64) * CELL_SIZE, (h - r as f64) * CELL_SIZE);
+                let dx = (x0 - x).max(0.0).max(x - x1);
+                let dy = (y0 - y).max(0.0).max(y - y1);
+                nearest = nearest.min(dx.hypot(dy));
+            }
+        }
+        nearest
+    }
+}
+
+// ── Pose and motion ───────────────────────────────────────────────────────────
+
+/// Advance `pose` for `secs` in `direction`. Stops at the first step that
+/// would push the body further into a wall and returns how many seconds it
+/// had moved by then; `None` when the whole motion completed.
+fn integrate(plan: &FloorPlan, pose: &mut Pose, direction: Direction, secs: f64) -> Option<f64> {
+    let sign = match direction {
+        Direction::Forward | Direction::Left => 1.0,
+        Direction::Backward | Direction::Right => -1.0,
+        Direction::Stop => return None,
+    };
+    let mut left = secs;
+    while left > 0.0 {
+        let dt = left.min(STEP);
+        left -= dt;
+        match direction {
+            Direction::Left | Direction::Right => {
+                pose.heading = (pose.heading + sign * TURN_RATE * dt).rem_euclid(360.0);
+            }
+            _ => {
+                let rad = pose.heading.to_radians();
+                let (nx, ny) = (
+                    pose.x + sign * SPEED * dt * rad.cos(),
+                    pose.y + sign * SPEED * dt * rad.sin(),
+                );
+                let reach = ROBOT_RADIUS * 2.0;
+                let before = plan.clearance(pose.x, pose.y, reach);
+                let after = plan.clearance(nx, ny, reach);
+                // Allow moves that gain clearance, so a body squeezed at
+                // start-up can still back away.
+                if after < ROBOT_RADIUS && after < before {
+                    return Some(secs - left - dt);
+                }
+                pose.x = nx;
+                pose.y = ny;
+            }
+        }
+    }
+    None
+}
+
+// ── Simulated robot ───────────────────────────────────────────────────────────
+
+struct SimState {
+    pose: Pose,
+    /// Untimed movement in progress and when it (last) started.
+    moving: Option<(Direction, Instant)>,
+    travelled: f64,
+    bumps: u32,
+}
+
+pub struct SimRobot {
+    plan: FloorPlan,
+    state: Mutex<SimState>,
+}
+
+impl Default for SimRobot {
+    fn default() -> Self {
+        Self::new(FloorPlan::built_in())
+    }
+}
+
+impl SimRobot {
+    pub fn new(plan: FloorPlan) -> Self {
+        let (x, y) = plan.dock();
+        Self {
+            plan,
+            state: Mutex::new(SimState {
+                pose: Pose { x, y, heading: 0.0 },
+                moving: None,
+                travelled: 0.0,
+                bumps: 0,
+            }),
+        }
+    }
+
+    /// Floor plan from `mobility.sim_map`, or the built-in room when unset.
+    pub fn from_config(config: &MobilityConfig) -> Result<Self> {
+        if config.sim_map.is_empty() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&config.sim_map)?;
+        Ok(Self::new(FloorPlan::parse(&text)?))
+    }
+
+    pub fn pose(&self) -> Pose {
+        let mut state = self.state.lock().unwrap();
+        self.settle(&mut state);
+        state.pose
+    }
+
+    /// Apply `secs` of `direction` to the state, counting distance and bumps.
+    /// Returns the seconds moved before a bump, if there was one.
+    fn advance(&self, state: &mut SimState, direction: Direction, secs: f64) -> Option<f64> {
+        let before = state.pose;
+        let bumped = integrate(&self.plan, &mut state.pose, direction, secs);
+        state.travelled += (state.pose.x - before.x).hypot(state.pose.y - before.y);
+        if bumped.is_some() {
+            state.bumps += 1;
+        }
+        bumped
+    }
+
+    /// Catch an untimed movement up to now. A bump ends the movement.
+    fn settle(&self, state: &mut SimState) -> bool {
+        let Some((direction, since)) = state.moving else {
+            return false;
+        };
+        let now = Instant::now();
+        let bumped = self
+            .advance(state, direction, now.duration_since(since).as_secs_f64())
+            .is_some();
+        state.moving = if bumped { None } else { Some((direction, now)) };
+        bumped
+    }
+
+    /// Close to the dock and facing roughly the way it faces when parked.
+    fn on_dock(&self, pose: &Pose) -> bool {
+        let (x, y) = self.plan.dock();
+        let facing_out = pose.heading < 30.0 || pose.heading > 330.0;
+        (pose.x - x).hypot(pose.y - y) < CELL_SIZE && facing_out
+    }
+
+    fn render(&self, pose: &Pose) -> RgbImage {
+        let (w, h) = (self.plan.width as u32, self.plan.height() as u32);
+        let mut img = RgbImage::from_pixel(w * PX_PER_CELL, h * PX_PER_CELL, Rgb([235, 230, 220]));
+
+        for (row, cells) in self.plan.walls.iter().enumerate() {
+            for (col, &wall) in cells.iter().enumerate() {
+                if wall {
+                    fill_rect(
+                        &mut img,
+                        col as u32 * PX_PER_CELL,
+                        row as u32 * PX_PER_CELL,
+                        PX_PER_CELL,
+                        PX_PER_CELL,
+                        Rgb([60, 60, 70]),
+                    );
+                }
+            }
+        }
+
+        let px = |x: f64, y: f64| {
+            let scale = PX_PER_CELL as f64 / CELL_SIZE;
+            (x * scale, (h as f64 * CELL_SIZE - y) * scale)
+        };
+        let (dx, dy) = px(self.plan.dock.0, self.plan.dock.1);
+        let half = PX_PER_CELL / 2;
+        fill_rect(
+            &mut img,
+            (dx as u32).saturating_sub(half),
+            (dy as u32).saturating_sub(half),
+            PX_PER_CELL,
+            PX_PER_CELL,
+            Rgb([90, 180, 90]),
+        );
+
+        let (cx, cy) = px(pose.x, pose.y);
+        let radius = ROBOT_RADIUS / CELL_SIZE * PX_PER_CELL as f64;
+        fill_circle(&mut img, cx, cy, radius, Rgb([50, 110, 220]));
+        // Heading: a line from the centre to the rim.
+        let rad = pose.heading.to_radians();
+        let steps = radius.ceil() as i32;
+        for i in 0..=steps {
+            let t = i as f64;
+            fill_circle(
+                &mut img,
+                cx + t * rad.cos(),
+                cy - t * rad.sin(),
+                1.5,
+                Rgb([230, 60, 60]),
+            );
+        }
+        img
+    }
+}
+
+fn fill_rect(img: &mut RgbImage, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>) {
+    for py in y..(y + h).min(img.height()) {
+        for px in x..(x + w).min(img.width()) {
+            img.put_pixel(px, py, color);
+        }
+    }
+}
+
+fn fill_circle(img: &mut RgbImage, cx: f64, cy: f64, r: f64, color: Rgb<u8>) {
+    let (x0, x1) = ((cx - r).floor().max(0.0) as u32, (cx + r).ceil() as u32);
+    let (y0, y1) = ((cy - r).floor().max(0.0) as u32, (cy + r).ceil() as u32);
+    for py in y0..y1.min(img.height()) {
+        for px in x0..x1.min(img.width()) {
+            if (px as f64 + 0.5 - cx).hypot(py as f64 + 0.5 - cy) <= r {
+                img.put_pixel(px, py, color);
+            }
+        }
+    }
+}
+
+impl Robot for SimRobot {
+    fn name(&self) -> &'static str {
+        "sim"
+    }
+
+    fn is_configured(&self) -> bool {
+        true
+    }
+
+    fn drive(&self, direction: Direction) -> RobotFuture<'_, Motion> {
+        Box::pin(async move {
+            let mut state = self.state.lock().unwrap();
+            let bumped = self.settle(&mut state);
+            state.moving = (direction != Direction::Stop).then(|| (direction, Instant::now()));
+            Ok(Motion {
+                bumped,
+                moved_for: None,
+            })
+        })
+    }
+
+    fn walk(&self, direction: Direction, duration: Duration) -> RobotFuture<'_, Motion> {
+        Box::pin(async move {
+            let mut state = self.state.lock().unwrap();
+            let earlier = self.settle(&mut state);
+            state.moving = None;
+            let bumped = self.advance(&mut state, direction, duration.as_secs_f64());
+            Ok(Motion {
+                bumped: earlier || bumped.is_some(),
+                moved_for: Some(bumped.map_or(duration, Duration::from_secs_f64)),
+            })
+        })
+    }
+
+    fn status(&self) -> RobotFuture<'_, String> {
+        Box::pin(async move {
+            let mut state = self.state.lock().unwrap();
+            self.settle(&mut state);
+            let pose = state.pose;
+            let moving = match state.moving {
+                Some((direction, _)) => direction.as_str(),
+                None => "no",
+            };
+            Ok(format!(
+                "Simulated robot\n\
+                 Position: {}\n\
+                 Moving: {moving}\n\
+                 On the dock: {}\n\
+                 Distance travelled: {:.2} m\n\
+                 Bumps: {}",
+                pose.describe(),
+                if self.on_dock(&pose) { "yes" } else { "no" },
+                state.travelled,
+                state.bumps,
+            ))
+        })
+    }
+
+    fn docked(&self) -> RobotFuture<'_, bool> {
+        Box::pin(async move { Ok(self.on_dock(&self.pose())) })
+    }
+
+    fn top_down_view(&self) -> Option<String> {
+        let img = self.render(&self.pose());
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 80)
+            .encode_image(&img)
+            .map_err(|e| tracing::warn!("top-down render failed: {e}"))
+            .ok()?;
+        Some(B64.encode(jpeg))
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2 m × 1 m corridor, dock at the west end.
+    const CORRIDOR: &str = "\
+##########
+#........#
+#.D......#
+#........#
+##########";
+
+    fn corridor() -> SimRobot {
+        SimRobot::new(FloorPlan::parse(CORRIDOR).unwrap())
+    }
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn parses_dock_and_walls() {
+        let plan = FloorPlan::parse(CORRIDOR).unwrap();
+        assert_eq!(plan.dock(), (0.625, 0.625));
+        assert!(plan.is_wall(0, 0));
+        assert!(!plan.is_wall(1, 1));
+        assert!(plan.is_wall(-1, 2), "outside is wall");
+        assert!(plan.is_wall(10, 2), "outside is wall");
+    }
+
+    #[test]
+    fn floor_plan_needs_a_dock() {
+        assert!(FloorPlan::parse("###\n#.#\n###").is_err());
+        assert!(FloorPlan::parse("").is_err());
+    }
+
+    #[test]
+    fn built_in_room_is_valid() {
+        let plan = FloorPlan::built_in();
+        let (x, y) = plan.dock();
+        assert!(plan.clearance(x, y, 1.0) > ROBOT_RADIUS);
+    }
+
+    #[test]
+    fn clearance_to_walls() {
+        let plan = FloorPlan::parse(CORRIDOR).unwrap();
+        // Dock centre is 0.375 m from both the west and south wall faces.
+        assert!((plan.clearance(0.625, 0.625, 1.0) - 0.375).abs() < 1e-9);
+        assert_eq!(plan.clearance(0.625, 0.625, 0.1), 0.1);
+    }
+
+    #[tokio::test]
+    async fn walks_forward_in_simulated_time() {
+        let robot = corridor();
+        let started = Instant::now();
+        let motion = robot.walk(Direction::Forward, secs(2.0)).await.unwrap();
+        assert!(
+            started.elapsed() < Duration::from_millis(500),
+            "no real-time wait"
+        );
+
+        assert!(!motion.bumped);
+        let pose = robot.pose();
+        assert!((pose.x - (0.625 + 0.6)).abs() < 1e-6, "{pose:?}");
+        assert!((pose.y - 0.625).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn turns_in_place() {
+        let robot = corridor();
+        robot.walk(Direction::Left, secs(1.0)).await.unwrap();
+        let pose = robot.pose();
+        assert!((pose.heading - 90.0).abs() < 1e-6);
+        assert_eq!((pose.x, pose.y), (0.625, 0.625));
+
+        robot.walk(Direction::Right, secs(2.0)).await.unwrap();
+        assert!((robot.pose().heading - 270.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn bumps_into_walls_and_stops() {
+        let robot = corridor();
+        let motion = robot.walk(Direction::Forward, secs(30.0)).await.unwrap();
+        assert!(motion.bumped);
+
+        // East wall face is at x = 2.25 m.
+        let pose = robot.pose();
+        assert!(pose.x <= 2.25 - ROBOT_RADIUS + 1e-9, "{pose:?}");
+        assert!(
+            pose.x > 2.25 - ROBOT_RADIUS - SPEED * STEP - 1e-9,
+            "{pose:?}"
+        );
+
+        // Backing away is free.
+        let motion = robot.walk(Direction::Backward, secs(1.0)).await.unwrap();
+        assert!(!motion.bumped);
+    }
+
+    #[tokio::test]
+    async fn untimed_drive_moves_in_wall_clock_time() {
+        let robot = corridor();
+        robot.drive(Direction::Forward).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        robot.drive(Direction::Stop).await.unwrap();
+
+        let x = robot.pose().x;
+        assert!(x > 0.625 + SPEED * 0.09, "moved {x}");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(robot.pose().x, x, "stopped");
+    }
+
+    #[tokio::test]
+    async fn status_reports_pose_and_bumps() {
+        let robot = corridor();
+        let status = robot.status().await.unwrap();
+        assert!(
+            status.contains("x=0.62 m, y=0.62 m, facing east"),
+            "{status}"
+        );
+        assert!(status.contains("On the dock: yes"));
+
+        robot.walk(Direction::Left, secs(1.0)).await.unwrap();
+        assert!(robot.status().await.unwrap().contains("facing north"));
+
+        robot.walk(Direction::Right, secs(1.0)).await.unwrap();
+        robot.walk(Direction::Forward, secs(10.0)).await.unwrap();
+        let status = robot.status().await.unwrap();
+        assert!(status.contains("facing east"), "{status}");
+        assert!(status.contains("On the dock: no"));
+        assert!(status.contains("Bumps: 1"));
+    }
+
+    #[test]
+    fn renders_top_down_view() {
+        let robot = corridor();
+        let jpeg = B64.decode(robot.top_down_view().unwrap()).unwrap();
+        let img = image::load_from_memory(&jpeg).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (10 * PX_PER_CELL, 5 * PX_PER_CELL));
+
+        // Robot body (blue) at the dock; wall (dark) in the corner.
+        let body = img.get_pixel(
+            2 * PX_PER_CELL + PX_PER_CELL / 2,
+            2 * PX_PER_CELL + PX_PER_CELL / 2 + 3,
+        );
+        assert!(body[2] > 150 && body[0] < 120, "{body:?}");
+        let wall = img.get_pixel(2, 2);
+        assert!(wall[0] < 100, "{wall:?}");
+    }
+}
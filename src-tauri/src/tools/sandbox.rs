@@ -0,0 +1,334 @@
+/// Optional OS-level sandbox for the `bash` tool (Linux, via bubblewrap).
+///
+/// A sandboxed command sees the whole filesystem read-only except `work_dir`
+/// and a private `/tmp`, can't see the familiar's config dir (API keys), can't
+/// write `work_dir/.git` or the protected paths even inside `work_dir` (the
+/// unsandboxed git tools would run hooks or `core.fsmonitor` planted there), runs
+/// in its own PID namespace with a scrubbed environment and CPU/memory
+/// rlimits, and in `offline` mode has no network. If a sandbox is asked for
+/// but bubblewrap (`bwrap`) isn't installed, the command is refused rather
+/// than run unconfined.
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::SandboxConfig;
+
+/// Strictness, from none to most confined.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    #[default]
+    Off,
+    /// Read-only filesystem except work_dir; network allowed.
+    On,
+    /// As `On`, without network.
+    Offline,
+}
+
+/// Environment variables a sandboxed command keeps; everything else (API
+/// keys, tokens, cloud credentials) is dropped.
+const KEEP_ENV: &[&str] = &[
+    "PATH", "HOME", "USER", "LOGNAME", "LANG", "LC_ALL", "LC_CTYPE", "TERM", "SHELL", "TZ",
+];
+
+/// `bash -c command`, wrapped in the sandbox for `mode`. `protected` paths
+/// stay read-only inside `work_dir`.
+pub fn command(
+    mode: SandboxMode,
+    config: &SandboxConfig,
+    command: &str,
+    cwd: &Path,
+    work_dir: &Path,
+    protected: &[PathBuf],
+) -> Result<tokio::process::Command> {
+    if mode == SandboxMode::Off {
+        let mut cmd = tokio::process::Command::new("bash");
+        cmd.arg("-c").arg(command).current_dir(cwd);
+        return Ok(cmd);
+    }
+    let Some(bwrap) = find_bwrap() else {
+        bail!(
+            "The bash sandbox is on but bubblewrap (bwrap) was not found. Install it, or set \
+             coding.sandbox.mode = \"off\"."
+        );
+    };
+    let hidden = [crate::config::app_config_dir()];
+    let mut read_only = vec![work_dir.join(".git")];
+    read_only.extend_from_slice(protected);
+    let mut cmd = tokio::process::Command::new(bwrap);
+    cmd.args(bwrap_args(mode, work_dir, cwd, &read_only, &hidden))
+        .arg("bash")
+        .arg("-c")
+        .arg(command)
+        .env_clear()
+        .envs(scrubbed_env(&config.env, std::env::vars()))
+        .env("TMPDIR", "/tmp");
+    limit_resources(&mut cmd, config);
+    Ok(cmd)
+}
+
+/// One line for the tool result, so the model knows why a write or download failed.
+pub fn describe(mode: SandboxMode, work_dir: &Path) -> Option<String> {
+    match mode {
+        SandboxMode::Off => None,
+        SandboxMode::On => Some(format!(
+            "Sandbox: read-only filesystem except {}",
+            work_dir.display()
+        )),
+        SandboxMode::Offline => Some(format!(
+            "Sandbox: read-only filesystem except {}, no network",
+            work_dir.display()
+        )),
+    }
+}
+
+fn find_bwrap() -> Option<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join("bwrap"))
+        .find(|p| p.is_file())
+}
+
+fn bwrap_args(
+    mode: SandboxMode,
+    work_dir: &Path,
+    cwd: &Path,
+    read_only: &[PathBuf],
+    hidden: &[PathBuf],
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = ["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+    args.extend(["--tmpfs".into(), "/tmp".into()]);
+    args.extend(["--bind".into(), work_dir.into(), work_dir.into()]);
+    // After the work_dir bind: it would otherwise mount these back, writable
+    // (.git inside work_dir, or everything with work_dir = $HOME).
+    for path in read_only.iter().filter(|p| p.exists()) {
+        args.extend(["--ro-bind".into(), path.into(), path.into()]);
+    }
+    for dir in hidden.iter().filter(|d| d.exists()) {
+        args.extend(["--tmpfs".into(), dir.into()]);
+    }
+    if mode == SandboxMode::Offline {
+        args.push("--unshare-net".into());
+    }
+    // A PID namespace whose init dies with bwrap: killing bwrap on timeout or
+    // cancel takes every process the command started with it.
+    args.extend(
+        [
+            "--unshare-pid",
+            "--unshare-ipc",
+            "--die-with-parent",
+            "--new-session",
+        ]
+        .into_iter()
+        .map(OsString::from),
+    );
+    args.extend(["--chdir".into(), cwd.into()]);
+    args
+}
+
+fn scrubbed_env(
+    extra: &[String],
+    vars: impl Iterator<Item = (String, String)>,
+) -> Vec<(String, String)> {
+    vars.filter(|(key, _)| {
+        KEEP_ENV.contains(&key.as_str()) || key.starts_with("LC_") || extra.contains(key)
+    })
+    .collect()
+}
+
+#[cfg(unix)]
+fn limit_resources(cmd: &mut tokio::process::Command, config: &SandboxConfig) {
+    let cpu = config.effective_max_cpu_secs();
+    let memory = config.max_memory_mb.saturating_mul(1024 * 1024);
+    // SAFETY: only setrlimit, which is async-signal-safe, runs between fork and exec.
+    unsafe {
+        cmd.pre_exec(move || {
+            let set = |resource, value: u64| {
+                let limit = libc::rlimit {
+                    rlim_cur: value as libc::rlim_t,
+                    rlim_max: value as libc::rlim_t,
+                };
+                if libc::setrlimit(resource, &limit) == 0 {
+                    Ok(())
+                } else {
+                    Err(std::io::Error::last_os_error())
+                }
+            };
+            set(libc::RLIMIT_CPU, cpu)?;
+            if memory > 0 {
+                set(libc::RLIMIT_AS, memory)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn limit_resources(_cmd: &mut tokio::process::Command, _config: &SandboxConfig) {}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[OsString]) -> Vec<String> {
+        args.iter()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn bwrap_binds_only_work_dir_writable() {
+        let hidden = tempfile::tempdir().unwrap();
+        let git = tempfile::tempdir().unwrap();
+        let args = strings(&bwrap_args(
+            SandboxMode::On,
+            Path::new("/home/me/proj"),
+            Path::new("/home/me/proj/src"),
+            &[git.path().to_path_buf(), PathBuf::from("/no/such/.git")],
+            &[
+                hidden.path().to_path_buf(),
+                PathBuf::from("/does/not/exist"),
+            ],
+        ));
+        let joined = args.join(" ");
+        assert!(joined.starts_with("--ro-bind / /"), "{joined}");
+        assert!(
+            joined.contains("--bind /home/me/proj /home/me/proj"),
+            "{joined}"
+        );
+        let bind = joined.find("--bind /home/me/proj /home/me/proj").unwrap();
+        let hide = joined
+            .find(&format!("--tmpfs {}", hidden.path().display()))
+            .expect(&joined);
+        assert!(bind < hide, "hidden dirs must be mounted over work_dir: {joined}");
+        let git = format!("--ro-bind {0} {0}", git.path().display());
+        let read_only = joined.find(&git).expect(&joined);
+        assert!(bind < read_only, "read-only paths must be mounted over work_dir: {joined}");
+        assert!(!joined.contains("/does/not/exist"), "{joined}");
+        assert!(!joined.contains("/no/such/.git"), "{joined}");
+        assert!(joined.ends_with("--chdir /home/me/proj/src"), "{joined}");
+        assert!(joined.contains("--die-with-parent"));
+        assert!(!joined.contains("--unshare-net"));
+
+        let offline = strings(&bwrap_args(
+            SandboxMode::Offline,
+            Path::new("/p"),
+            Path::new("/p"),
+            &[],
+            &[],
+        ));
+        assert!(offline.contains(&"--unshare-net".to_string()));
+    }
+
+    #[test]
+    fn environment_is_scrubbed_of_secrets() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("HOME", "/home/me"),
+            ("LC_TIME", "C"),
+            ("ANTHROPIC_API_KEY", "sk-secret"),
+            ("AWS_SECRET_ACCESS_KEY", "x"),
+            ("CARGO_HOME", "/home/me/.cargo"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let kept = scrubbed_env(&["CARGO_HOME".to_string()], vars.into_iter());
+        let keys: Vec<&str> = kept.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["PATH", "HOME", "LC_TIME", "CARGO_HOME"]);
+    }
+
+    #[test]
+    fn modes_order_by_strictness() {
+        assert!(SandboxMode::Off < SandboxMode::On);
+        assert!(SandboxMode::On < SandboxMode::Offline);
+        assert_eq!(
+            SandboxMode::On.max(SandboxMode::Offline),
+            SandboxMode::Offline
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_run_unconfined_without_bwrap() {
+        if find_bwrap().is_some() {
+            return;
+        }
+        let err = command(
+            SandboxMode::On,
+            &SandboxConfig::default(),
+            "true",
+            Path::new("/tmp"),
+            Path::new("/tmp"),
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("bubblewrap"), "{err}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn sandboxed_command_cannot_write_outside_work_dir() {
+        if find_bwrap().is_none() {
+            return;
+        }
+        let work = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir_in(dirs::home_dir().unwrap()).unwrap();
+        let target = outside.path().join("escaped");
+        let script = format!(
+            "touch {}/inside; touch {}",
+            work.path().display(),
+            target.display()
+        );
+        let status = command(
+            SandboxMode::Offline,
+            &SandboxConfig::default(),
+            &script,
+            work.path(),
+            work.path(),
+            &[],
+        )
+        .unwrap()
+        .status()
+        .await
+        .unwrap();
+        assert!(!status.success());
+        assert!(work.path().join("inside").exists());
+        assert!(!target.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn sandboxed_command_cannot_write_git_or_protected_paths() {
+        if find_bwrap().is_none() {
+            return;
+        }
+        let work = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(work.path().join(".git/hooks")).unwrap();
+        std::fs::create_dir(work.path().join("keep")).unwrap();
+        let run = |script: &str| {
+            command(
+                SandboxMode::Offline,
+                &SandboxConfig::default(),
+                script,
+                work.path(),
+                work.path(),
+                &[work.path().join("keep")],
+            )
+            .unwrap()
+            .status()
+        };
+        assert!(!run("touch .git/hooks/pre-commit").await.unwrap().success());
+        assert!(!run("touch keep/x").await.unwrap().success());
+        assert!(run("touch ok").await.unwrap().success());
+        assert!(!work.path().join(".git/hooks/pre-commit").exists());
+    }
+}
@@ -0,0 +1,516 @@
+/// File walking and content search behind `list_files` and `grep`.
+///
+/// Walks with the `ignore` crate (ripgrep's walker), reading directories in
+/// parallel. `.gitignore`, `.ignore` and git's global excludes are honoured
+/// even outside a git repository, and `.git/` itself is skipped; other hidden
+/// files are included. Results are sorted by path so `offset` pages are
+/// stable between calls.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::{WalkBuilder, WalkState};
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+
+/// Files larger than this are not searched.
+const MAX_FILE_BYTES: u64 = 5_000_000;
+/// A NUL byte among the first bytes marks a file as binary.
+const BINARY_PROBE_BYTES: usize = 8_192;
+/// Longer lines (minified files) are cut in grep output.
+const MAX_LINE_CHARS: usize = 300;
+/// Context lines are capped so one call can't dump whole files.
+const MAX_CONTEXT: usize = 20;
+const DEFAULT_FILE_LIMIT: usize = 200;
+const DEFAULT_MATCH_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 2_000;
+
+// ── Walking ───────────────────────────────────────────────────────
+
+/// A glob over paths relative to the search root. Without a `/` it matches
+/// the file name at any depth, like a `.gitignore` line.
+pub struct PathGlob {
+    matcher: GlobMatcher,
+    name_only: bool,
+}
+
+impl PathGlob {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("invalid glob {pattern:?}"))?
+            .compile_matcher();
+        Ok(Self {
+            matcher,
+            name_only: !pattern.contains('/'),
+        })
+    }
+
+    fn matches(&self, rel: &Path) -> bool {
+        if self.name_only {
+            rel.file_name().is_some_and(|n| self.matcher.is_match(n))
+        } else {
+            self.matcher.is_match(rel)
+        }
+    }
+}
+
+/// Every file under `base` (or `base` itself) that `glob` admits and
+/// `visit` makes something of, sorted by path.
+pub fn walk<T: Send>(
+    base: &Path,
+    glob: Option<&PathGlob>,
+    visit: impl Fn(&Path) -> Option<T> + Sync,
+) -> Vec<(PathBuf, T)> {
+    let found = Mutex::new(Vec::new());
+    WalkBuilder::new(base)
+        .hidden(false)
+        .require_git(false)
+        .follow_links(false)
+        .filter_entry(|e| e.file_name() != ".git")
+        .build_parallel()
+        .run(|| {
+            Box::new(|entry| {
+                let Ok(entry) = entry else {
+                    return WalkState::Continue;
+                };
+                if !entry.file_type().is_some_and(|t| t.is_file()) {
+                    return WalkState::Continue;
+                }
+                let path = entry.path();
+                let rel = match path.strip_prefix(base) {
+                    Ok(rel) if !rel.as_os_str().is_empty() => rel,
+                    _ => Path::new(path.file_name().unwrap_or_default()),
+                };
+                if glob.is_some_and(|g| !g.matches(rel)) {
+                    return WalkState::Continue;
+                }
+                if let Some(value) = visit(path) {
+                    found.lock().unwrap().push((path.to_path_buf(), value));
+                }
+                WalkState::Continue
+            })
+        });
+    let mut found = found.into_inner().unwrap();
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    found
+}
+
+/// `path` relative to `root` when it is inside it, to keep output short.
+fn shown(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+// ── Paging ────────────────────────────────────────────────────────
+
+struct Page {
+    offset: usize,
+    limit: usize,
+}
+
+impl Page {
+    fn from_input(input: &Value, default_limit: usize) -> Self {
+        let get = |key: &str| input[key].as_u64().map(|n| n as usize);
+        Self {
+            offset: get("offset").unwrap_or(0),
+            limit: get("limit").unwrap_or(default_limit).clamp(1, MAX_LIMIT),
+        }
+    }
+
+    fn range(&self, total: usize) -> std::ops::Range<usize> {
+        let start = self.offset.min(total);
+        start..(start + self.limit).min(total)
+    }
+
+    /// A footer when the page is not everything there is.
+    fn note(&self, what: &str, total: usize) -> Option<String> {
+        let range = self.range(total);
+        if range.start == 0 && range.end == total {
+            return None;
+        }
+        if range.is_empty() {
+            return Some(format!(
+                "[no {what} past offset {}; {total} in total]",
+                self.offset
+            ));
+        }
+        let more = if range.end < total {
+            format!("; pass offset={} for more", range.end)
+        } else {
+            String::new()
+        };
+        Some(format!(
+            "[{what} {}–{} of {total}{more}]",
+            range.start + 1,
+            range.end
+        ))
+    }
+}
+
+// ── list_files ────────────────────────────────────────────────────
+
+pub fn list_files(
+    base: &Path,
+    root: &Path,
+    input: &Value,
+    readable: impl Fn(&Path) -> bool + Sync,
+) -> Result<String> {
+    let glob = input["pattern"].as_str().map(PathGlob::new).transpose()?;
+    let files = walk(base, glob.as_ref(), |p| readable(p).then_some(()));
+    if files.is_empty() {
+        return Ok("No files found".to_string());
+    }
+    let page = Page::from_input(input, DEFAULT_FILE_LIMIT);
+    let mut out: Vec<String> = files[page.range(files.len())]
+        .iter()
+        .map(|(p, _)| shown(p, root))
+        .collect();
+    out.extend(page.note("files", files.len()));
+    Ok(out.join("\n"))
+}
+
+// ── grep ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Content,
+    FilesWithMatches,
+    Count,
+}
+
+enum Hits {
+    Binary,
+    /// Matching line indices, and the text of those lines and their context.
+    Text {
+        matches: Vec<usize>,
+        lines: BTreeMap<usize, String>,
+    },
+}
+
+impl Hits {
+    fn count(&self) -> usize {
+        match self {
+            Hits::Binary => 1,
+            Hits::Text { matches, .. } => matches.len(),
+        }
+    }
+}
+
+struct Grep {
+    regex: Regex,
+    before: usize,
+    after: usize,
+}
+
+impl Grep {
+    fn search(&self, path: &Path) -> Option<Hits> {
+        if path.metadata().ok()?.len() > MAX_FILE_BYTES {
+            return None;
+        }
+        let bytes = std::fs::read(path).ok()?;
+        let text = String::from_utf8_lossy(&bytes);
+        if bytes[..bytes.len().min(BINARY_PROBE_BYTES)].contains(&0) {
+            return self.regex.is_match(&text).then_some(Hits::Binary);
+        }
+        let all: Vec<&str> = text.lines().collect();
+        let matches: Vec<usize> = (0..all.len())
+            .filter(|&i| self.regex.is_match(all[i]))
+            .collect();
+        if matches.is_empty() {
+            return None;
+        }
+        let mut lines = BTreeMap::new();
+        for &m in &matches {
+            let start = m.saturating_sub(self.before);
+            let window = all.iter().enumerate().skip(start);
+            for (i, line) in window.take(m + self.after + 1 - start) {
+                lines.entry(i).or_insert_with(|| cut(line));
+            }
+        }
+        Some(Hits::Text { matches, lines })
+    }
+}
+
+fn cut(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+pub fn grep(
+    base: &Path,
+    root: &Path,
+    input: &Value,
+    readable: impl Fn(&Path) -> bool + Sync,
+) -> Result<String> {
+    let pattern = input["pattern"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("missing pattern"))?;
+    let flag = |key: &str| input[key].as_bool().unwrap_or(false);
+    let pattern = if flag("fixed_strings") {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(flag("case_insensitive"))
+        .build()?;
+    let lines = |key: &str| input[key].as_u64().map(|n| (n as usize).min(MAX_CONTEXT));
+    let context = lines("context").unwrap_or(0);
+    let grep = Grep {
+        regex,
+        before: lines("before_context").unwrap_or(context),
+        after: lines("after_context").unwrap_or(context),
+    };
+    let mode = match input["output_mode"].as_str().unwrap_or("content") {
+        "content" => Mode::Content,
+        "files_with_matches" => Mode::FilesWithMatches,
+        "count" => Mode::Count,
+        other => {
+            anyhow::bail!("unknown output_mode {other:?}: use content, files_with_matches or count")
+        }
+    };
+    let glob = input["include"].as_str().map(PathGlob::new).transpose()?;
+
+    let found = walk(base, glob.as_ref(), |p| {
+        if readable(p) {
+            grep.search(p)
+        } else {
+            None
+        }
+    });
+    if found.is_empty() {
+        return Ok("No matches found".to_string());
+    }
+
+    let default_limit = match mode {
+        Mode::Content => DEFAULT_MATCH_LIMIT,
+        _ => DEFAULT_FILE_LIMIT,
+    };
+    let page = Page::from_input(input, default_limit);
+    let mut out = Vec::new();
+    match mode {
+        Mode::FilesWithMatches => {
+            out.extend(
+                found[page.range(found.len())]
+                    .iter()
+                    .map(|(p, _)| shown(p, root)),
+            );
+            out.extend(page.note("files", found.len()));
+        }
+        Mode::Count => {
+            for (path, hits) in &found[page.range(found.len())] {
+                out.push(match hits {
+                    Hits::Binary => format!("{}: binary file matches", shown(path, root)),
+                    Hits::Text { matches, .. } => {
+                        format!("{}:{}", shown(path, root), matches.len())
+                    }
+                });
+            }
+            out.extend(page.note("files", found.len()));
+            let total: usize = found.iter().map(|(_, h)| h.count()).sum();
+            out.push(format!("{total} match(es) in {} file(s)", found.len()));
+        }
+        Mode::Content => {
+            // Page over individual matches; a binary file counts as one.
+            let total: usize = found.iter().map(|(_, h)| h.count()).sum();
+            let range = page.range(total);
+            let mut seen = 0;
+            for (path, hits) in &found {
+                let here = seen..seen + hits.count();
+                seen = here.end;
+                let (from, to) = (range.start.max(here.start), range.end.min(here.end));
+                if from >= to {
+                    continue;
+                }
+                let path = shown(path, root);
+                match hits {
+                    Hits::Binary => out.push(format!("Binary file {path} matches")),
+                    Hits::Text { matches, lines } => {
+                        let picked = &matches[from - here.start..to - here.start];
+                        render(&mut out, &path, picked, matches, lines, &grep);
+                    }
+                }
+            }
+            out.extend(page.note("matches", total));
+        }
+    }
+    Ok(out.join("\n"))
+}
+
+/// `path:12: text` for matches and `path-11- text` for context, with `--`
+/// between groups that aren't adjacent.
+fn render(
+    out: &mut Vec<String>,
+    path: &str,
+    picked: &[usize],
+    matches: &[usize],
+    lines: &BTreeMap<usize, String>,
+    grep: &Grep,
+) {
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &m in picked {
+        let (start, end) = (m.saturating_sub(grep.before), m + grep.after);
+        match groups.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = end,
+            _ => groups.push((start, end)),
+        }
+    }
+    let with_context = grep.before + grep.after > 0;
+    for (start, end) in groups {
+        if with_context && !out.is_empty() {
+            out.push("--".to_string());
+        }
+        for (&i, text) in lines.range(start..=end) {
+            let sep = if matches.binary_search(&i).is_ok() {
+                ':'
+            } else {
+                '-'
+            };
+            out.push(format!("{path}{sep}{}{sep} {text}", i + 1));
+        }
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tree(files: &[(&str, &[u8])]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn run_grep(dir: &tempfile::TempDir, input: Value) -> String {
+        grep(dir.path(), dir.path(), &input, |_| true).unwrap()
+    }
+
+    fn run_list(dir: &tempfile::TempDir, input: Value) -> String {
+        list_files(dir.path(), dir.path(), &input, |_| true).unwrap()
+    }
+
+    #[test]
+    fn ignored_files_and_git_dir_are_skipped() {
+        let dir = tree(&[
+            (".gitignore", b"target/\n*.log\n"),
+            ("src/main.rs", b"fn main() {}\n"),
+            ("target/debug/out.rs", b"fn main() {}\n"),
+            ("run.log", b"fn main\n"),
+            (".git/HEAD", b"fn main\n"),
+            (".github/ci.yml", b"fn main\n"),
+        ]);
+        let listed = run_list(&dir, json!({}));
+        assert_eq!(listed, ".github/ci.yml\n.gitignore\nsrc/main.rs");
+        let found = run_grep(
+            &dir,
+            json!({ "pattern": "fn main", "output_mode": "files_with_matches" }),
+        );
+        assert_eq!(found, ".github/ci.yml\nsrc/main.rs");
+    }
+
+    #[test]
+    fn patterns_without_a_slash_match_names_at_any_depth() {
+        let dir = tree(&[("a.rs", b""), ("src/b.rs", b""), ("src/c.ts", b"")]);
+        assert_eq!(
+            run_list(&dir, json!({ "pattern": "*.rs" })),
+            "a.rs\nsrc/b.rs"
+        );
+        assert_eq!(run_list(&dir, json!({ "pattern": "src/*.rs" })), "src/b.rs");
+        assert_eq!(run_list(&dir, json!({ "pattern": "**/*.ts" })), "src/c.ts");
+    }
+
+    #[test]
+    fn listing_pages_with_offset() {
+        let dir = tree(&[("a", b""), ("b", b""), ("c", b"")]);
+        let first = run_list(&dir, json!({ "limit": 2 }));
+        assert_eq!(first, "a\nb\n[files 1–2 of 3; pass offset=2 for more]");
+        let rest = run_list(&dir, json!({ "limit": 2, "offset": 2 }));
+        assert_eq!(rest, "c\n[files 3–3 of 3]");
+        assert!(run_list(&dir, json!({ "offset": 9 })).contains("no files past offset 9"));
+    }
+
+    #[test]
+    fn binary_files_are_reported_not_dumped() {
+        let dir = tree(&[
+            ("blob.bin", b"needle\0\x01\x02"),
+            ("text.txt", b"a needle\n"),
+        ]);
+        let out = run_grep(&dir, json!({ "pattern": "needle" }));
+        assert_eq!(out, "Binary file blob.bin matches\ntext.txt:1: a needle");
+    }
+
+    #[test]
+    fn context_lines_are_merged_and_separated() {
+        let dir = tree(&[("f.txt", b"1\n2\nhit\n4\nhit\n6\n7\n8\n9\nhit\n")]);
+        let out = run_grep(&dir, json!({ "pattern": "hit", "context": 1 }));
+        assert_eq!(
+            out,
+            "f.txt-2- 2\nf.txt:3: hit\nf.txt-4- 4\nf.txt:5: hit\nf.txt-6- 6\n--\n\
+             f.txt-9- 9\nf.txt:10: hit"
+        );
+        let after = run_grep(
+            &dir,
+            json!({ "pattern": "^hit", "after_context": 1, "limit": 1 }),
+        );
+        assert_eq!(
+            after,
+            "f.txt:3: hit\nf.txt-4- 4\n[matches 1–1 of 3; pass offset=1 for more]"
+        );
+    }
+
+    #[test]
+    fn case_insensitive_and_fixed_string_modes() {
+        let dir = tree(&[("f.txt", b"Foo.bar()\nfooXbar\n")]);
+        assert_eq!(
+            run_grep(&dir, json!({ "pattern": "foo" })),
+            "f.txt:2: fooXbar"
+        );
+        let any_case = run_grep(&dir, json!({ "pattern": "foo", "case_insensitive": true }));
+        assert_eq!(any_case.lines().count(), 2);
+        let fixed = run_grep(
+            &dir,
+            json!({ "pattern": "foo.bar(", "fixed_strings": true, "case_insensitive": true }),
+        );
+        assert_eq!(fixed, "f.txt:1: Foo.bar()");
+        assert!(grep(
+            dir.path(),
+            dir.path(),
+            &json!({ "pattern": "foo.bar(" }),
+            |_| true
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn count_mode_and_include_filter() {
+        let dir = tree(&[("a.rs", b"x\nx\n"), ("b.rs", b"x\n"), ("c.md", b"x\n")]);
+        let out = run_grep(
+            &dir,
+            json!({ "pattern": "x", "output_mode": "count", "include": "*.rs" }),
+        );
+        assert_eq!(out, "a.rs:2\nb.rs:1\n3 match(es) in 2 file(s)");
+    }
+
+    #[test]
+    fn unreadable_files_are_left_out() {
+        let dir = tree(&[("ok.txt", b"x\n"), ("secret.txt", b"x\n")]);
+        let readable = |p: &Path| !p.ends_with("secret.txt");
+        let out = grep(dir.path(), dir.path(), &json!({ "pattern": "x" }), readable).unwrap();
+        assert_eq!(out, "ok.txt:1: x");
+    }
+}
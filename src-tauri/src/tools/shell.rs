@@ -1,15 +1,79 @@
 /// Shell execution tool.
 ///
 /// Runs arbitrary bash commands with timeout, working directory, and output capture.
+/// Each command gets its own process group, which is killed as a whole when the
+/// command times out or is cancelled (the future is dropped). Commands can run
+/// in an OS-level sandbox (see `sandbox`), chosen per command by the rules.
+///
+/// `session` runs a command in a named persistent shell instead (see
+/// `shell_session`), and `bash_background` / `bash_output` / `bash_kill`
+/// manage long-running processes (see `background`).
+///
+/// Output lines are reported to `Progress` as they arrive, so the UI can
+/// show a long build or test run live.
 use anyhow::Result;
 use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
+use super::background::Jobs;
+use super::progress::{Progress, Stream};
+use super::sandbox::{self, SandboxMode};
+use super::shell_session::Session;
 use super::ToolOutput;
+use crate::config::SandboxConfig;
+use crate::permissions::{sandbox_for, PermRule};
 
 pub struct ShellTool {
     pub work_dir: String,
+    sandbox: SandboxConfig,
+    /// Permission rules, for their per-command `sandbox` overrides.
+    rules: Vec<PermRule>,
+    /// Paths a sandboxed command may not write, even inside work_dir.
+    protected: Vec<PathBuf>,
+    /// Named persistent shells. A session is taken out while it runs a
+    /// command, so one dropped mid-command (timeout, cancel) is killed, not
+    /// reused; its name stays behind as `None` meanwhile, so a concurrent
+    /// call (another sub-agent) is told it is busy.
+    sessions: Sessions,
+    jobs: Jobs,
+}
+
+/// A command that ran to completion, with its full output.
+pub struct Finished {
+    pub status: i32,
+    /// How it was sandboxed, if at all.
+    pub note: Option<String>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+type Sessions = std::sync::Mutex<HashMap<String, Option<Session>>>;
+
+/// A session's claim on its name while it runs a command. Dropped without
+/// `put_back` (timed out, cancelled, ended), the name is freed.
+struct Checkout<'a> {
+    sessions: &'a Sessions,
+    name: &'a str,
+}
+
+impl Checkout<'_> {
+    fn put_back(self, session: Session) {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(self.name.to_string(), Some(session));
+    }
+}
+
+impl Drop for Checkout<'_> {
+    fn drop(&mut self) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if matches!(sessions.get(self.name), Some(None)) {
+            sessions.remove(self.name);
+        }
+    }
 }
 
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
@@ -17,33 +81,113 @@ const MAX_OUTPUT_BYTES: usize = 32_768; // 32 KB
 
 impl ShellTool {
     pub fn new(work_dir: String) -> Self {
-        Self { work_dir }
+        Self {
+            work_dir,
+            sandbox: SandboxConfig::default(),
+            rules: Vec::new(),
+            protected: Vec::new(),
+            sessions: Default::default(),
+            jobs: Jobs::default(),
+        }
+    }
+
+    pub fn with_sandbox(mut self, sandbox: SandboxConfig, rules: Vec<PermRule>) -> Self {
+        self.sandbox = sandbox;
+        self.rules = rules;
+        self
+    }
+
+    pub fn with_protected(mut self, protected: Vec<PathBuf>) -> Self {
+        self.protected = protected;
+        self
     }
 
     pub fn tool_defs() -> Vec<crate::backend::ToolDef> {
         use serde_json::json;
-        vec![crate::backend::ToolDef {
-            name: "bash".to_string(),
-            description: "Run a shell command. Returns stdout + stderr. Has a timeout.".to_string(),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "command": { "type": "string", "description": "Shell command to execute" },
-                    "timeout_secs": {
-                        "type": "integer",
-                        "description": "Timeout in seconds (default 30, max 120)"
+        vec![
+            crate::backend::ToolDef {
+                name: "bash".to_string(),
+                category: crate::permissions::ToolCategory::Exec,
+                description: "Run a shell command. Returns stdout + stderr. Has a timeout. \
+                              Pass `session` to run it in a named persistent shell that keeps \
+                              cwd and exported variables between calls."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "string", "description": "Shell command to execute" },
+                        "timeout_secs": {
+                            "type": "integer",
+                            "description": "Timeout in seconds (default 30, max 120)"
+                        },
+                        "cwd": {
+                            "type": "string",
+                            "description": "Working directory override (default: configured work_dir). For a session, only where a new session starts."
+                        },
+                        "session": {
+                            "type": "string",
+                            "description": "Persistent shell name, e.g. \"main\" (optional)"
+                        }
+                    },
+                    "required": ["command"]
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "bash_background".to_string(),
+                category: crate::permissions::ToolCategory::Exec,
+                description: "Start a long-running command (dev server, watch build) in the \
+                              background. Returns a job id for bash_output / bash_kill."
+                    .to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "command": { "type": "string", "description": "Shell command to start" },
+                        "cwd": { "type": "string", "description": "Working directory (default: work_dir)" }
                     },
-                    "cwd": {
-                        "type": "string",
-                        "description": "Working directory override (default: configured work_dir)"
-                    }
-                },
-                "required": ["command"]
-            }),
-        }]
+                    "required": ["command"]
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "bash_output".to_string(),
+                category: crate::permissions::ToolCategory::FsRead,
+                description: "Read a background job's new output since the last call, and whether it is still running.".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string", "description": "Job id from bash_background, e.g. \"bg1\"" },
+                        "wait_secs": {
+                            "type": "integer",
+                            "description": "Wait up to this long for new output (default 0, max 30)"
+                        }
+                    },
+                    "required": ["id"]
+                }),
+            },
+            crate::backend::ToolDef {
+                name: "bash_kill".to_string(),
+                category: crate::permissions::ToolCategory::Exec,
+                description: "Stop a background job and everything it started; returns its last output.".to_string(),
+                input_schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string", "description": "Job id from bash_background" }
+                    },
+                    "required": ["id"]
+                }),
+            },
+        ]
+    }
+
+    fn resolve_cwd(&self, input: &Value) -> PathBuf {
+        let cwd_raw = input["cwd"].as_str().unwrap_or(&self.work_dir);
+        if Path::new(cwd_raw).is_absolute() {
+            PathBuf::from(cwd_raw)
+        } else {
+            Path::new(&self.work_dir).join(cwd_raw)
+        }
     }
 
-    pub async fn bash(&self, input: &Value) -> Result<ToolOutput> {
+    pub async fn bash(&self, input: &Value, progress: &Progress) -> Result<ToolOutput> {
         let command = input["command"]
             .as_str()
             .ok_or_else(|| anyhow::anyhow!("missing command"))?;
@@ -53,51 +197,253 @@ impl ShellTool {
             .unwrap_or(DEFAULT_TIMEOUT_SECS)
             .min(120);
 
-        let cwd_raw = input["cwd"].as_str().unwrap_or(&self.work_dir);
-        let cwd = if std::path::Path::new(cwd_raw).is_absolute() {
-            std::path::PathBuf::from(cwd_raw)
-        } else {
-            std::path::Path::new(&self.work_dir).join(cwd_raw)
-        };
+        let cwd = self.resolve_cwd(input);
+        if let Some(name) = input["session"].as_str() {
+            let mode = sandbox_for(&self.rules, command, self.sandbox.mode);
+            return self
+                .bash_in_session(name, command, &cwd, mode, timeout_secs, progress)
+                .await;
+        }
+
+        match self.run(command, &cwd, timeout_secs, progress).await? {
+            Some(done) => Ok((
+                format_result(done.status, done.note, &done.stdout, &done.stderr),
+                None,
+            )),
+            None => Ok((format!("Command timed out after {timeout_secs}s"), None)),
+        }
+    }
 
-        let mut child = tokio::process::Command::new("bash")
-            .arg("-c")
-            .arg(command)
-            .current_dir(&cwd)
-            .stdout(Stdio::piped())
+    /// Runs a one-off command in its own process group, in the sandbox its
+    /// rules pick, reporting output lines to `progress`. `None` if it timed out.
+    pub async fn run(
+        &self,
+        command: &str,
+        cwd: &Path,
+        timeout_secs: u64,
+        progress: &Progress,
+    ) -> Result<Option<Finished>> {
+        let mode = sandbox_for(&self.rules, command, self.sandbox.mode);
+        let work_dir = Path::new(&self.work_dir);
+        let mut cmd = sandbox::command(
+            mode,
+            &self.sandbox,
+            command,
+            cwd,
+            work_dir,
+            &self.protected,
+        )?;
+        cmd.stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()?;
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let mut child = cmd.spawn()?;
+        let mut group = KillOnDrop(child.id());
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
 
-        let result = tokio::time::timeout(
-            Duration::from_secs(timeout_secs),
-            child.wait_with_output(),
-        )
-        .await;
+        let run = async {
+            let (stdout, stderr, status) = tokio::join!(
+                pump(stdout, Stream::Stdout, progress),
+                pump(stderr, Stream::Stderr, progress),
+                child.wait(),
+            );
+            std::io::Result::Ok((status?, stdout?, stderr?))
+        };
+        let result = tokio::time::timeout(Duration::from_secs(timeout_secs), run).await;
+        if matches!(result, Ok(Ok(_))) {
+            // Finished normally; leave any background jobs it started alone.
+            group.0 = None;
+        }
 
         match result {
-            Ok(Ok(out)) => {
-                let stdout = truncate_output(&out.stdout);
-                let stderr = truncate_output(&out.stderr);
-                let status = out.status.code().unwrap_or(-1);
-
-                let mut text = format!("Exit: {status}\n");
-                if !stdout.is_empty() {
-                    text.push_str("--- stdout ---\n");
-                    text.push_str(&stdout);
-                    text.push('\n');
+            Ok(Ok((status, stdout, stderr))) => Ok(Some(Finished {
+                status: status.code().unwrap_or(-1),
+                note: sandbox::describe(mode, work_dir),
+                stdout,
+                stderr,
+            })),
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn bash_in_session(
+        &self,
+        name: &str,
+        command: &str,
+        cwd: &Path,
+        mode: SandboxMode,
+        timeout_secs: u64,
+        progress: &Progress,
+    ) -> Result<ToolOutput> {
+        let work_dir = Path::new(&self.work_dir);
+        let existing = {
+            let mut sessions = self.sessions.lock().unwrap();
+            match sessions.get_mut(name) {
+                Some(None) => {
+                    let text = format!(
+                        "Session '{name}' is busy with another command. Wait for it to finish, \
+                         or use another session name."
+                    );
+                    return Ok((text, None));
                 }
-                if !stderr.is_empty() {
-                    text.push_str("--- stderr ---\n");
-                    text.push_str(&stderr);
+                Some(slot) => slot.take(),
+                None => {
+                    sessions.insert(name.to_string(), None);
+                    None
                 }
+            }
+        };
+        let checkout = Checkout {
+            sessions: &self.sessions,
+            name,
+        };
+        let mut session = match existing {
+            Some(session) if session.mode >= mode => session,
+            Some(session) => {
+                let text = format!(
+                    "Session '{name}' runs with sandbox {:?}, but this command needs {mode:?}. \
+                     Run it without `session`, or in a new session.",
+                    session.mode
+                );
+                checkout.put_back(session);
+                return Ok((text, None));
+            }
+            None => {
+                let shell = "exec bash --noprofile --norc";
+                let cmd =
+                    sandbox::command(mode, &self.sandbox, shell, cwd, work_dir, &self.protected)?;
+                Session::spawn(cmd, mode)?
+            }
+        };
 
-                Ok((text, None))
+        let limit = MAX_OUTPUT_BYTES * 4;
+        let run = session.run(command, limit, progress);
+        let out = match tokio::time::timeout(Duration::from_secs(timeout_secs), run).await {
+            Ok(out) => out?,
+            // Dropping the session kills it along with whatever it was running.
+            Err(_) => {
+                return Ok((
+                    format!(
+                        "Command timed out after {timeout_secs}s; session '{name}' was closed \
+                         (its cwd and variables are gone)"
+                    ),
+                    None,
+                ))
+            }
+        };
+
+        let note = sandbox::describe(session.mode, work_dir);
+        let mut text = format_result(out.status, note, &out.stdout, &out.stderr);
+        if out.ended {
+            text.push_str(&format!(
+                "\n(Session '{name}' ended; the next call starts a fresh one)"
+            ));
+        } else {
+            checkout.put_back(session);
+        }
+        Ok((text, None))
+    }
+
+    pub fn background(&self, input: &Value) -> Result<ToolOutput> {
+        let command = input["command"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("missing command"))?;
+        let cwd = self.resolve_cwd(input);
+        let mode = sandbox_for(&self.rules, command, self.sandbox.mode);
+        let work_dir = Path::new(&self.work_dir);
+        let cmd = sandbox::command(
+            mode,
+            &self.sandbox,
+            command,
+            &cwd,
+            work_dir,
+            &self.protected,
+        )?;
+        let id = self.jobs.start(cmd, command)?;
+        let mut text = format!(
+            "Started {id} in the background: `{command}`\n\
+             Read its output with bash_output {{\"id\": \"{id}\"}}; stop it with bash_kill."
+        );
+        if let Some(note) = sandbox::describe(mode, work_dir) {
+            text.push('\n');
+            text.push_str(&note);
+        }
+        Ok((text, None))
+    }
+
+    pub async fn output(&self, input: &Value) -> Result<ToolOutput> {
+        let id = input["id"].as_str().unwrap_or("");
+        let wait = input["wait_secs"].as_u64().unwrap_or(0).min(30);
+        let text = self.jobs.poll(id, Duration::from_secs(wait)).await;
+        Ok((truncate_output(text.as_bytes()), None))
+    }
+
+    pub async fn kill(&self, input: &Value) -> Result<ToolOutput> {
+        let id = input["id"].as_str().unwrap_or("");
+        let text = self.jobs.kill(id).await;
+        Ok((truncate_output(text.as_bytes()), None))
+    }
+}
+
+/// The tool result for a finished command: exit status first (the feedback
+/// step reads it), then the sandbox note and both streams.
+fn format_result(status: i32, note: Option<String>, stdout: &[u8], stderr: &[u8]) -> String {
+    let stdout = truncate_output(stdout);
+    let stderr = truncate_output(stderr);
+
+    let mut text = format!("Exit: {status}\n");
+    if let Some(note) = note {
+        text.push_str(&note);
+        text.push('\n');
+    }
+    if !stdout.is_empty() {
+        text.push_str("--- stdout ---\n");
+        text.push_str(&stdout);
+        text.push('\n');
+    }
+    if !stderr.is_empty() {
+        text.push_str("--- stderr ---\n");
+        text.push_str(&stderr);
+    }
+    text
+}
+
+/// Read a pipe to EOF, reporting each line as it comes.
+async fn pump(
+    stream: Option<impl AsyncRead + Unpin>,
+    kind: Stream,
+    progress: &Progress,
+) -> std::io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let Some(stream) = stream else {
+        return Ok(output);
+    };
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(output);
+        }
+        progress.line(kind, &String::from_utf8_lossy(&line));
+        output.extend_from_slice(&line);
+    }
+}
+
+/// Kills the command's whole process group (pipelines, subshells) when dropped
+/// — on timeout, or when the tool is cancelled mid-run.
+pub(super) struct KillOnDrop(pub(super) Option<u32>);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.0 {
+            // SAFETY: plain syscall; the group was created for this command.
+            unsafe {
+                libc::killpg(pgid as libc::pid_t, libc::SIGKILL);
             }
-            Ok(Err(e)) => Err(e.into()),
-            Err(_) => Ok((
-                format!("Command timed out after {timeout_secs}s"),
-                None,
-            )),
         }
     }
 }
@@ -105,11 +451,11 @@ impl ShellTool {
 fn truncate_output(bytes: &[u8]) -> String {
     let s = String::from_utf8_lossy(bytes).into_owned();
     if s.len() > MAX_OUTPUT_BYTES {
-        format!(
-            "{}...[truncated, {} bytes total]",
-            &s[..MAX_OUTPUT_BYTES],
-            s.len()
-        )
+        let mut end = MAX_OUTPUT_BYTES;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}...[truncated, {} bytes total]", &s[..end], s.len())
     } else {
         s
     }
@@ -128,21 +474,27 @@ mod tests {
 
     #[tokio::test]
     async fn bash_echo_returns_output() {
-        let out = tool().bash(&json!({ "command": "echo hello" })).await.unwrap();
+        let out = tool()
+            .bash(&json!({ "command": "echo hello" }), &Progress::none())
+            .await
+            .unwrap();
         assert!(out.0.contains("hello"));
         assert!(out.0.contains("Exit: 0"));
     }
 
     #[tokio::test]
     async fn bash_exit_code_captured() {
-        let out = tool().bash(&json!({ "command": "exit 42" })).await.unwrap();
+        let out = tool()
+            .bash(&json!({ "command": "exit 42" }), &Progress::none())
+            .await
+            .unwrap();
         assert!(out.0.contains("Exit: 42"));
     }
 
     #[tokio::test]
     async fn bash_stderr_captured() {
         let out = tool()
-            .bash(&json!({ "command": "echo error >&2" }))
+            .bash(&json!({ "command": "echo error >&2" }), &Progress::none())
             .await
             .unwrap();
         assert!(out.0.contains("error"));
@@ -152,16 +504,46 @@ mod tests {
     #[tokio::test]
     async fn bash_timeout_respected() {
         let out = tool()
-            .bash(&json!({ "command": "sleep 60", "timeout_secs": 1 }))
+            .bash(
+                &json!({ "command": "sleep 60", "timeout_secs": 1 }),
+                &Progress::none(),
+            )
             .await
             .unwrap();
         assert!(out.0.contains("timed out"));
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn cancelled_command_kills_its_children() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("still-running");
+        let command = format!("(sleep 1; touch {}) | cat", marker.display());
+        let input = json!({ "command": command });
+        let tool = tool();
+
+        // Dropping the future is what cancellation does.
+        let run = tokio::time::timeout(
+            Duration::from_millis(200),
+            tool.bash(&input, &Progress::none()),
+        )
+        .await;
+        assert!(run.is_err());
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(
+            !marker.exists(),
+            "child process outlived the cancelled command"
+        );
+    }
+
     #[tokio::test]
     async fn bash_cwd_is_set() {
         let out = tool()
-            .bash(&json!({ "command": "pwd", "cwd": "/tmp" }))
+            .bash(
+                &json!({ "command": "pwd", "cwd": "/tmp" }),
+                &Progress::none(),
+            )
             .await
             .unwrap();
         assert!(out.0.contains("/tmp"));
@@ -174,4 +556,117 @@ mod tests {
         let timeout = input["timeout_secs"].as_u64().unwrap_or(30).min(120);
         assert_eq!(timeout, 120);
     }
+
+    #[tokio::test]
+    async fn sessions_keep_state_between_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = ShellTool::new(dir.path().to_string_lossy().into_owned());
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let run = |command: &str| {
+            let input = json!({ "command": command, "session": "main" });
+            let tool = &tool;
+            async move { tool.bash(&input, &Progress::none()).await.unwrap().0 }
+        };
+        assert!(run("cd sub && export MODE=dev").await.contains("Exit: 0"));
+        let out = run("basename \"$PWD\"; echo $MODE").await;
+        assert!(out.contains("sub\ndev"), "{out}");
+
+        // Plain calls and other sessions don't share it.
+        let other = tool
+            .bash(
+                &json!({ "command": "echo ${MODE:-unset}", "session": "other" }),
+                &Progress::none(),
+            )
+            .await
+            .unwrap();
+        assert!(other.0.contains("unset"), "{}", other.0);
+    }
+
+    #[tokio::test]
+    async fn busy_sessions_turn_away_concurrent_calls() {
+        let tool = tool();
+        let progress = Progress::none();
+        let slow = json!({ "command": "sleep 1; echo slow", "session": "s" });
+        let fast = json!({ "command": "echo fast", "session": "s" });
+        let (slow, fast) = tokio::join!(tool.bash(&slow, &progress), async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            tool.bash(&fast, &progress).await
+        });
+        assert!(slow.unwrap().0.contains("slow"));
+        let fast = fast.unwrap().0;
+        assert!(fast.contains("Session 's' is busy"), "{fast}");
+
+        // A cancelled command frees the name.
+        let sleep = json!({ "command": "sleep 60", "session": "t" });
+        let run = tool.bash(&sleep, &progress);
+        assert!(tokio::time::timeout(Duration::from_millis(300), run)
+            .await
+            .is_err());
+        let out = tool
+            .bash(&json!({ "command": "echo again", "session": "t" }), &Progress::none())
+            .await
+            .unwrap();
+        assert!(out.0.contains("again"), "{}", out.0);
+    }
+
+    #[tokio::test]
+    async fn session_timeout_closes_the_session() {
+        let tool = tool();
+        let input = json!({ "command": "export X=1; sleep 60", "session": "s", "timeout_secs": 1 });
+        let out = tool.bash(&input, &Progress::none()).await.unwrap();
+        assert!(
+            out.0.contains("timed out") && out.0.contains("closed"),
+            "{}",
+            out.0
+        );
+        let out = tool
+            .bash(
+                &json!({ "command": "echo ${X:-fresh}", "session": "s" }),
+                &Progress::none(),
+            )
+            .await
+            .unwrap();
+        assert!(out.0.contains("fresh"), "{}", out.0);
+    }
+
+    #[tokio::test]
+    async fn background_jobs_run_across_calls() {
+        let tool = tool();
+        let started = tool
+            .background(&json!({ "command": "echo ready; sleep 30" }))
+            .unwrap();
+        assert!(started.0.starts_with("Started bg1"), "{}", started.0);
+        let out = tool
+            .output(&json!({ "id": "bg1", "wait_secs": 5 }))
+            .await
+            .unwrap();
+        assert!(
+            out.0.contains("running") && out.0.contains("ready"),
+            "{}",
+            out.0
+        );
+        let out = tool.kill(&json!({ "id": "bg1" })).await.unwrap();
+        assert!(out.0.starts_with("Killed bg1"), "{}", out.0);
+    }
+
+    #[tokio::test]
+    async fn output_lines_stream_as_they_arrive() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let progress = Progress::new(move |stream, line| sink.lock().unwrap().push((stream, line)));
+        let input = json!({ "command": "echo one; echo oops >&2; printf two" });
+        let out = tool().bash(&input, &progress).await.unwrap();
+        assert!(out.0.contains("one\ntwo"), "{}", out.0);
+        let seen = seen.lock().unwrap();
+        assert!(seen.contains(&(Stream::Stdout, "one".to_string())));
+        assert!(seen.contains(&(Stream::Stdout, "two".to_string())));
+        assert!(seen.contains(&(Stream::Stderr, "oops".to_string())));
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        let text = "あ".repeat(MAX_OUTPUT_BYTES);
+        let out = truncate_output(text.as_bytes());
+        assert!(out.contains("[truncated"));
+    }
 }
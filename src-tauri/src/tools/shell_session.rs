@@ -0,0 +1,256 @@
+/// Persistent named shell sessions for the `bash` tool.
+///
+/// A session is one long-lived `bash` reading commands from a pipe, so `cd`,
+/// exported variables and virtualenv activation carry over between calls.
+/// Each command is sent as a quoted heredoc and `eval`ed (a syntax error
+/// can't kill the shell), with stdin from /dev/null, followed by a marker
+/// line on stdout and stderr that carries the exit status.
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout};
+
+use super::progress::{Progress, Stream};
+use super::sandbox::SandboxMode;
+use super::shell::KillOnDrop;
+
+/// What a session command produced.
+pub struct SessionOutput {
+    pub status: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// The command ended the shell itself (`exit`, `exec`...).
+    pub ended: bool,
+}
+
+pub struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+    marker: String,
+    /// Sandbox the shell was started in; commands can't ask for a looser one.
+    pub mode: SandboxMode,
+    /// Kills everything the session started when it is dropped — on timeout,
+    /// cancellation, or when the tool itself goes away.
+    _group: KillOnDrop,
+}
+
+impl Session {
+    /// `cmd` must run a shell that reads commands from stdin.
+    pub fn spawn(mut cmd: tokio::process::Command, mode: SandboxMode) -> Result<Self> {
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+        let mut child = cmd.spawn().context("failed to start shell session")?;
+        let group = KillOnDrop(child.id());
+        let stdin = child.stdin.take().context("session has no stdin")?;
+        let stdout = BufReader::new(child.stdout.take().context("session has no stdout")?);
+        let stderr = BufReader::new(child.stderr.take().context("session has no stderr")?);
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            marker: format!("__familiar_done_{}", uuid::Uuid::new_v4().simple()),
+            mode,
+            _group: group,
+        })
+    }
+
+    /// Run one command and wait for its marker. Output beyond `limit` bytes
+    /// per stream is read but not kept; every line goes to `progress`.
+    pub async fn run(
+        &mut self,
+        command: &str,
+        limit: usize,
+        progress: &Progress,
+    ) -> Result<SessionOutput> {
+        let eof = format!("{}_eof", self.marker);
+        let script = format!(
+            "__familiar_cmd=$(cat <<'{eof}'\n{command}\n{eof}\n)\n\
+             eval \"$__familiar_cmd\" < /dev/null\n\
+             printf '\\n{marker} %d\\n' \"$?\"\n\
+             printf '\\n{marker}\\n' >&2\n",
+            marker = self.marker,
+        );
+        self.stdin.write_all(script.as_bytes()).await?;
+        self.stdin.flush().await?;
+
+        let marker = self.marker.as_bytes();
+        let (out, err) = tokio::join!(
+            read_to_marker(&mut self.stdout, marker, limit, progress, Stream::Stdout),
+            read_to_marker(&mut self.stderr, marker, limit, progress, Stream::Stderr),
+        );
+        let (stdout, status_line) = out?;
+        let (stderr, _) = err?;
+
+        match status_line {
+            Some(line) => Ok(SessionOutput {
+                status: line.trim().parse().unwrap_or(-1),
+                stdout,
+                stderr,
+                ended: false,
+            }),
+            None => {
+                let status = self.child.wait().await?.code().unwrap_or(-1);
+                Ok(SessionOutput {
+                    status,
+                    stdout,
+                    stderr,
+                    ended: true,
+                })
+            }
+        }
+    }
+}
+
+/// Read until a line starting with `marker`; returns the output before it
+/// and the rest of the marker line, or `None` at EOF.
+///
+/// Lines are reported one behind, since the newline ending the last one
+/// before the marker is the marker printf's, not the command's.
+async fn read_to_marker<R: AsyncBufReadExt + Unpin>(
+    reader: &mut R,
+    marker: &[u8],
+    limit: usize,
+    progress: &Progress,
+    stream: Stream,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let mut output = Vec::new();
+    let mut pending: Option<Vec<u8>> = None;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            if let Some(last) = pending {
+                progress.line(stream, &String::from_utf8_lossy(&last));
+            }
+            return Ok((output, None));
+        }
+        if let Some(rest) = line.strip_prefix(marker) {
+            // Drop the newline the marker printf added before itself.
+            if output.last() == Some(&b'\n') {
+                output.pop();
+            }
+            if let Some(mut last) = pending {
+                last.pop();
+                if !last.is_empty() {
+                    progress.line(stream, &String::from_utf8_lossy(&last));
+                }
+            }
+            return Ok((output, Some(String::from_utf8_lossy(rest).into_owned())));
+        }
+        if let Some(previous) = pending.replace(line.clone()) {
+            progress.line(stream, &String::from_utf8_lossy(&previous));
+        }
+        if output.len() < limit {
+            output.extend_from_slice(&line);
+        }
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let mut cmd = tokio::process::Command::new("bash");
+        cmd.args(["--noprofile", "--norc"]).current_dir("/tmp");
+        Session::spawn(cmd, SandboxMode::Off).unwrap()
+    }
+
+    async fn run(session: &mut Session, command: &str) -> (i32, String, String, bool) {
+        let out = session
+            .run(command, 1 << 20, &Progress::none())
+            .await
+            .unwrap();
+        (
+            out.status,
+            String::from_utf8_lossy(&out.stdout).into_owned(),
+            String::from_utf8_lossy(&out.stderr).into_owned(),
+            out.ended,
+        )
+    }
+
+    #[tokio::test]
+    async fn keeps_cwd_and_environment_between_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut s = session();
+        let cd = format!("cd {} && export GREETING=hi", dir.path().display());
+        assert_eq!(run(&mut s, &cd).await.0, 0);
+        let (status, stdout, _, _) = run(&mut s, "pwd; echo $GREETING").await;
+        assert_eq!(status, 0);
+        let expected = format!("{}\nhi\n", dir.path().canonicalize().unwrap().display());
+        assert_eq!(stdout, expected);
+    }
+
+    #[tokio::test]
+    async fn reports_status_and_stderr_without_trailing_marker() {
+        let mut s = session();
+        let (status, stdout, stderr, ended) =
+            run(&mut s, "printf 'no newline'; echo oops >&2; false").await;
+        assert_eq!(status, 1);
+        assert_eq!(stdout, "no newline");
+        assert_eq!(stderr, "oops\n");
+        assert!(!ended);
+    }
+
+    #[tokio::test]
+    async fn survives_syntax_errors_and_stdin_readers() {
+        let mut s = session();
+        let (status, _, stderr, ended) = run(&mut s, "if then fi (").await;
+        assert_ne!(status, 0);
+        assert!(stderr.contains("syntax error"), "{stderr}");
+        assert!(!ended);
+        // `cat` gets /dev/null, not the next command.
+        assert_eq!(run(&mut s, "cat").await.0, 0);
+        assert_eq!(run(&mut s, "echo still here").await.1, "still here\n");
+    }
+
+    #[tokio::test]
+    async fn multi_line_commands_and_heredocs_work() {
+        let mut s = session();
+        let (_, stdout, _, _) = run(
+            &mut s,
+            "for i in 1 2; do\n  echo $i\ndone\ncat <<EOF\nx 'y' \"z\"\nEOF",
+        )
+        .await;
+        assert_eq!(stdout, "1\n2\nx 'y' \"z\"\n");
+    }
+
+    #[tokio::test]
+    async fn lines_are_reported_without_the_marker_newline() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let progress = Progress::new(move |stream, line| sink.lock().unwrap().push((stream, line)));
+        let mut s = session();
+        s.run("echo one; echo two; echo err >&2", 1 << 20, &progress)
+            .await
+            .unwrap();
+        s.run("printf tail", 1 << 20, &progress).await.unwrap();
+        let seen = seen.lock().unwrap();
+        let stdout: Vec<&str> = seen
+            .iter()
+            .filter(|(stream, _)| *stream == Stream::Stdout)
+            .map(|(_, line)| line.as_str())
+            .collect();
+        assert_eq!(stdout, ["one", "two", "tail"]);
+        assert!(seen.contains(&(Stream::Stderr, "err".to_string())));
+    }
+
+    #[tokio::test]
+    async fn exit_ends_the_session() {
+        let mut s = session();
+        let (status, stdout, _, ended) = run(&mut s, "echo bye; exit 3").await;
+        assert!(ended);
+        assert_eq!(status, 3);
+        assert_eq!(stdout, "bye\n");
+    }
+}
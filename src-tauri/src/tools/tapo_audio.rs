@@ -7,9 +7,12 @@
 ///   4. Stream G.711 PCMA audio wrapped in MPEGTS as multipart frames
 use anyhow::{bail, Result};
 use std::collections::HashMap;
-use std::process::Stdio;
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+
+use super::audio::{self, Resampler};
+use super::tts_engine::AudioPiece;
 
 // ── MPEGTS constants ─────────────────────────────────────────────────────────
 
@@ -318,40 +321,54 @@ fn multipart_audio_frame(session_id: &str, mpegts: &[u8]) -> Vec<u8> {
     buf
 }
 
-// ── MP3 → raw PCMA via ffmpeg ─────────────────────────────────────────────────
-
-async fn mp3_to_pcma(mp3_bytes: Vec<u8>) -> Result<Vec<u8>> {
-    // ffmpeg: stdin=mp3 → stdout=raw alaw 8kHz mono
-    let mut child = tokio::process::Command::new("ffmpeg")
-        .args([
-            "-loglevel", "error",
-            "-i", "pipe:0",
-            "-f", "alaw",
-            "-ar", "8000",
-            "-ac", "1",
-            "pipe:1",
-        ])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()?;
-
-    // Write MP3 to stdin in a background task
-    let mut stdin = child.stdin.take().unwrap();
-    let writer = tokio::spawn(async move {
-        let _ = stdin.write_all(&mp3_bytes).await;
-        // drop closes stdin, signaling EOF to ffmpeg
-    });
-
-    // Read PCM from stdout
-    let mut pcma = Vec::new();
-    if let Some(mut stdout) = child.stdout.take() {
-        stdout.read_to_end(&mut pcma).await?;
-    }
-
-    writer.await.ok();
-    child.wait().await?;
-    Ok(pcma)
+// ── Streaming PCMA framer ─────────────────────────────────────────────────────
+
+/// The camera speaker takes 8kHz mono A-law.
+const PCMA_RATE: u32 = 8000;
+/// 20ms of PCMA at 8kHz (160 samples = 160 bytes).
+const PCMA_FRAME: usize = 160;
+
+/// Packs PCMA into multipart MPEGTS frames, keeping PTS and the continuity
+/// counter running across sentences so the camera sees one continuous stream.
+struct PcmaFramer {
+    session_id: String,
+    continuity: u8,
+    pts: u64,
+    pending: Vec<u8>,
+}
+
+impl PcmaFramer {
+    fn new(session_id: String) -> Self {
+        Self { session_id, continuity: 0, pts: 0, pending: Vec::new() }
+    }
+
+    /// Queue PCMA and return the frames for every complete 20ms chunk.
+    fn push(&mut self, pcma: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(pcma);
+        let whole = self.pending.len() / PCMA_FRAME * PCMA_FRAME;
+        let ready: Vec<u8> = self.pending.drain(..whole).collect();
+        let mut out = Vec::new();
+        for chunk in ready.chunks(PCMA_FRAME) {
+            out.extend(self.frame(chunk));
+        }
+        out
+    }
+
+    /// Emit whatever is left at the end of a sentence as a short frame.
+    fn flush(&mut self) -> Vec<u8> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        let rest = std::mem::take(&mut self.pending);
+        self.frame(&rest)
+    }
+
+    fn frame(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let ts_data = pcma_to_ts_packets(chunk, self.pts, &mut self.continuity);
+        // 90kHz clock / 8kHz samples = 11.25 ticks per sample (1800 per 160-sample chunk)
+        self.pts = self.pts.wrapping_add(chunk.len() as u64 * 90_000 / PCMA_RATE as u64);
+        multipart_audio_frame(&self.session_id, &ts_data)
+    }
 }
 
 // ── Session negotiation (reads multipart JSON response, extracts session_id) ──
@@ -433,14 +450,11 @@ impl TapoAudio {
         !self.host.is_empty()
     }
 
-    /// Play MP3 audio through the Tapo camera speaker.
-    pub async fn play(&self, mp3_bytes: Vec<u8>) -> Result<()> {
-        // Convert MP3 → raw A-law 8kHz
-        let pcma = mp3_to_pcma(mp3_bytes).await?;
-        if pcma.is_empty() {
-            bail!("ffmpeg produced no output — is ffmpeg installed?");
-        }
-
+    /// Play streamed audio through the Tapo camera speaker.
+    ///
+    /// Connects while the first sentence is still being synthesized, then
+    /// decodes each sentence as its chunks arrive and forwards 20ms frames.
+    pub async fn play_stream(&self, mut rx: mpsc::UnboundedReceiver<AudioPiece>) -> Result<()> {
         // Connect & authenticate
         let (mut writer, session_id) = self.connect_and_authenticate().await?;
 
@@ -449,21 +463,37 @@ impl TapoAudio {
         let frame = multipart_audio_frame(&session_id, &header);
         writer.write_all(&frame).await?;
 
-        // Send audio in 20ms chunks (160 samples = 160 bytes of PCMA at 8kHz)
-        let chunk_size = 160;
-        let mut continuity: u8 = 0;
-        let mut pts: u64 = 0;
-        // PTS increment: 160 samples * (90000 / 8000) = 1800 ticks per chunk
-        let pts_increment: u64 = 1800;
-
-        for chunk in pcma.chunks(chunk_size) {
-            let ts_data = pcma_to_ts_packets(chunk, pts, &mut continuity);
-            let frame = multipart_audio_frame(&session_id, &ts_data);
-            writer.write_all(&frame).await?;
-            pts = pts.wrapping_add(pts_increment);
+        let mut framer = PcmaFramer::new(session_id);
+        let mut sent_any = false;
+        while let Some(piece) = rx.recv().await {
+            let AudioPiece::Begin(format) = piece else { continue };
+            let (feed, mut decoded) = audio::decode_sentence(&mut rx, format);
+
+            // Resample to 8kHz, encode A-law and forward as PCM is decoded.
+            let forward = async {
+                let mut resampler: Option<Resampler> = None;
+                while let Some(pcm) = decoded.recv().await {
+                    let pcm = pcm?;
+                    let narrow = resampler
+                        .get_or_insert_with(|| Resampler::new(pcm.sample_rate, PCMA_RATE))
+                        .process(&pcm.samples);
+                    if narrow.is_empty() {
+                        continue;
+                    }
+                    sent_any = true;
+                    writer.write_all(&framer.push(&audio::encode_alaw(&narrow))).await?;
+                }
+                writer.write_all(&framer.flush()).await?;
+                anyhow::Ok(())
+            };
+            let ((), forwarded) = tokio::join!(feed, forward);
+            forwarded?;
         }
 
         writer.flush().await.ok();
+        if !sent_any {
+            bail!("no audio decoded for the camera speaker");
+        }
         Ok(())
     }
 
@@ -631,6 +661,38 @@ mod tests {
         assert!(s.contains("Content-Length: 5"));
     }
 
+    #[test]
+    fn test_framer_emits_only_whole_chunks_until_flush() {
+        let mut framer = PcmaFramer::new("s".to_string());
+        assert!(framer.push(&[0u8; 100]).is_empty());
+        let frames = framer.push(&[0u8; 100]);
+        assert_eq!(count_frames(&frames), 1);
+        assert_eq!(framer.pts, 1800);
+        assert_eq!(framer.pending.len(), 40);
+
+        let rest = framer.flush();
+        assert_eq!(count_frames(&rest), 1);
+        assert_eq!(framer.pts, 1800 + 40 * 90_000 / 8_000);
+        assert!(framer.flush().is_empty());
+    }
+
+    #[test]
+    fn test_framer_keeps_clock_running_across_sentences() {
+        let mut framer = PcmaFramer::new("s".to_string());
+        framer.push(&[0u8; 320]);
+        framer.flush();
+        let cont = framer.continuity;
+        framer.push(&[0u8; 160]);
+        assert_eq!(framer.pts, 3 * 1800);
+        assert_ne!(framer.continuity, cont, "continuity must not reset per sentence");
+    }
+
+    fn count_frames(data: &[u8]) -> usize {
+        String::from_utf8_lossy(data)
+            .matches("----client-stream-boundary--")
+            .count()
+    }
+
     #[test]
     fn test_multipart_json_frame_format() {
         let body = b"{}";
@@ -0,0 +1,335 @@
+/// `run_tests`: runs the project's test command and reports each failure.
+///
+/// The commands come from the detected projects (`coding::detect_projects`),
+/// one per sub-project in a monorepo, and run like `bash` — same sandbox
+/// rules, live output — but with a longer timeout. The full output is parsed
+/// by `diagnostics` before it is cut, so failures early in a long log are not
+/// lost.
+use anyhow::Result;
+use serde_json::Value;
+use std::path::Path;
+
+use super::progress::Progress;
+use super::shell::ShellTool;
+use super::ToolOutput;
+use crate::coding::{self, Project, ProjectType};
+use crate::diagnostics;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+const MAX_TIMEOUT_SECS: u64 = 1_800;
+/// Output lines kept after the failures; more when nothing was parsed.
+const TAIL_LINES: usize = 30;
+const TAIL_LINES_UNPARSED: usize = 80;
+
+pub fn tool_defs() -> Vec<crate::backend::ToolDef> {
+    use serde_json::json;
+    vec![crate::backend::ToolDef {
+        name: "run_tests".to_string(),
+        category: crate::permissions::ToolCategory::Exec,
+        description: "Run the project's tests (cargo, npm/pnpm/yarn, bun, deno, pytest/uv/poetry, \
+                      go, Maven, Gradle or CMake, detected from the manifests) and list each \
+                      failure with file:line, test name and message. In a monorepo every \
+                      sub-project with tests is run unless `project` picks one."
+            .to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "filter": {
+                    "type": "string",
+                    "description": "Only run matching tests (cargo test filter, pytest -k, go test -run, ...)"
+                },
+                "project": {
+                    "type": "string",
+                    "description": "Sub-project directory, as listed under Projects in the project context (\".\" for the root)"
+                },
+                "timeout_secs": {
+                    "type": "integer",
+                    "description": "Timeout in seconds per project (default 600, max 1800)"
+                }
+            }
+        }),
+    }]
+}
+
+/// The command line for `project`, narrowed by `filter`.
+fn command_for(project: &Project, filter: Option<&str>) -> Option<String> {
+    let base = project.test_command.as_deref()?;
+    let Some(filter) = filter.filter(|f| !f.trim().is_empty()) else {
+        return Some(base.to_string());
+    };
+    let quoted = format!("'{}'", filter.replace('\'', r"'\''"));
+    Some(match project.kind {
+        ProjectType::Python => format!("{base} -k {quoted}"),
+        ProjectType::Node if base.starts_with("npm ") => format!("{base} -- {quoted}"),
+        ProjectType::Bun => format!("{base} -t {quoted}"),
+        ProjectType::Deno => format!("{base} --filter {quoted}"),
+        ProjectType::Go => format!("{base} -run {quoted}"),
+        ProjectType::Maven => format!("{base} -Dtest={quoted}"),
+        ProjectType::Gradle => format!("{base} --tests {quoted}"),
+        ProjectType::CMake => format!("{base} -R {quoted}"),
+        _ => format!("{base} {quoted}"),
+    })
+}
+
+pub async fn run_tests(
+    shell: &ShellTool,
+    input: &Value,
+    progress: &Progress,
+) -> Result<ToolOutput> {
+    let projects: Vec<Project> = coding::detect_projects(&shell.work_dir)
+        .into_iter()
+        .filter(|p| p.test_command.is_some())
+        .collect();
+    if projects.is_empty() {
+        return Ok((
+            "No test command detected for this project (no known manifest such as Cargo.toml, \
+             package.json, pyproject.toml or go.mod in work_dir or its sub-directories). \
+             Run the tests with bash instead."
+                .to_string(),
+            None,
+        ));
+    }
+    let selected: Vec<&Project> = match input["project"].as_str() {
+        Some(wanted) => {
+            let wanted = wanted.trim_start_matches("./").trim_end_matches('/');
+            let wanted = if wanted == "." { "" } else { wanted };
+            projects.iter().filter(|p| p.dir == wanted).collect()
+        }
+        None => projects.iter().collect(),
+    };
+    if selected.is_empty() {
+        let known: Vec<String> = projects.iter().map(Project::display_dir).collect();
+        return Ok((
+            format!(
+                "No project with tests at `{}`. Projects: {}",
+                input["project"].as_str().unwrap_or(""),
+                known.join(", ")
+            ),
+            None,
+        ));
+    }
+    let timeout_secs = input["timeout_secs"]
+        .as_u64()
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+        .min(MAX_TIMEOUT_SECS);
+
+    let mut exit = 0;
+    let mut blocks = Vec::new();
+    for project in selected {
+        let Some(command) = command_for(project, input["filter"].as_str()) else {
+            continue;
+        };
+        let (status, block) = run_one(shell, project, &command, timeout_secs, progress).await?;
+        if exit == 0 {
+            exit = status;
+        }
+        blocks.push(block);
+    }
+    // "Exit: N" first, like bash, so the feedback step can read it.
+    Ok((format!("Exit: {exit}\n{}", blocks.join("\n\n")), None))
+}
+
+/// Runs one project's tests: its exit code and report.
+async fn run_one(
+    shell: &ShellTool,
+    project: &Project,
+    command: &str,
+    timeout_secs: u64,
+    progress: &Progress,
+) -> Result<(i32, String)> {
+    let ran = if project.dir.is_empty() {
+        format!("Ran: {command}")
+    } else {
+        format!("Ran: {command} (in {})", project.display_dir())
+    };
+    let cwd = Path::new(&shell.work_dir).join(&project.dir);
+    let Some(done) = shell.run(command, &cwd, timeout_secs, progress).await? else {
+        return Ok((
+            -1,
+            format!(
+                "{ran}\nResult: timed out after {timeout_secs}s; pass `filter` to run fewer tests"
+            ),
+        ));
+    };
+    let stdout = String::from_utf8_lossy(&done.stdout);
+    let stderr = String::from_utf8_lossy(&done.stderr);
+    let mut report = diagnostics::parse(&format!("{stdout}\n{stderr}"));
+    // Locations relative to work_dir, like every other tool.
+    if !project.dir.is_empty() {
+        for failure in &mut report.failures {
+            if let Some(file) = failure.file.as_mut().filter(|f| Path::new(f).is_relative()) {
+                *file = format!("{}/{file}", project.dir);
+            }
+        }
+    }
+
+    let mut out = vec![ran];
+    out.extend(done.note);
+    let result = match report.summary() {
+        Some(summary) => summary,
+        None if done.status == 0 => "passed".to_string(),
+        None => format!("failed (exit {})", done.status),
+    };
+    out.push(format!("Result: {result}"));
+    if !report.failures.is_empty() {
+        out.push(format!("Failures:\n{}", report.describe()));
+    }
+    let keep = if report.failures.is_empty() && done.status != 0 {
+        TAIL_LINES_UNPARSED
+    } else {
+        TAIL_LINES
+    };
+    for (name, text) in [("stdout", &stdout), ("stderr", &stderr)] {
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let from = lines.len().saturating_sub(keep);
+        out.push(format!(
+            "--- {name} (last {} lines) ---",
+            lines.len() - from
+        ));
+        out.extend(lines[from..].iter().map(|l| l.to_string()));
+    }
+    Ok((done.status, out.join("\n")))
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn project(kind: ProjectType, test: &str) -> Project {
+        Project {
+            dir: String::new(),
+            kind,
+            tooling: Vec::new(),
+            test_command: Some(test.to_string()),
+            build_command: None,
+        }
+    }
+
+    #[test]
+    fn commands_take_the_filter_their_own_way() {
+        let cmd = |kind, test: &str, f| command_for(&project(kind, test), f).unwrap();
+        assert_eq!(cmd(ProjectType::Rust, "cargo test", None), "cargo test");
+        assert_eq!(
+            cmd(ProjectType::Rust, "cargo test", Some("parser::")),
+            "cargo test 'parser::'"
+        );
+        assert_eq!(
+            cmd(ProjectType::Python, "uv run pytest", Some("add")),
+            "uv run pytest -k 'add'"
+        );
+        assert_eq!(
+            cmd(ProjectType::Node, "npm test", Some("it's")),
+            r"npm test -- 'it'\''s'"
+        );
+        assert_eq!(
+            cmd(ProjectType::Node, "pnpm test", Some("adds")),
+            "pnpm test 'adds'"
+        );
+        assert_eq!(
+            cmd(ProjectType::Go, "go test ./...", Some("TestAdd")),
+            "go test ./... -run 'TestAdd'"
+        );
+        assert_eq!(
+            cmd(ProjectType::Gradle, "./gradlew test", Some("*Add*")),
+            "./gradlew test --tests '*Add*'"
+        );
+        let mut none = project(ProjectType::Deno, "deno test");
+        none.test_command = None;
+        assert!(command_for(&none, None).is_none());
+    }
+
+    /// A fake `npm test` in `dir` that fails like jest does.
+    fn fake_jest(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("package.json"),
+            r#"{"name":"x","scripts":{"test":"sh fake.sh"}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("fake.sh"),
+            "echo '  ● math › adds'\necho\necho '    expected 3'\n\
+             echo '      at Object.toBe (src/sum.test.js:4:21)'\n\
+             echo 'Tests:       1 failed, 2 passed, 3 total'\nexit 1\n",
+        )
+        .unwrap();
+    }
+
+    fn has_npm() -> bool {
+        std::process::Command::new("npm")
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn reports_failures_from_the_test_run() {
+        if !has_npm() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        fake_jest(dir.path());
+        let shell = ShellTool::new(dir.path().to_string_lossy().into_owned());
+        let (text, _) = run_tests(&shell, &json!({}), &Progress::none())
+            .await
+            .unwrap();
+        assert!(
+            text.starts_with("Exit: 1\nRan: npm test\nResult: 2 passed, 1 failed"),
+            "{text}"
+        );
+        assert!(
+            text.contains("- src/sum.test.js:4 math › adds: expected 3"),
+            "{text}"
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_each_sub_project_in_its_directory() {
+        if !has_npm() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        fake_jest(&dir.path().join("web"));
+        std::fs::create_dir(dir.path().join("tool")).unwrap();
+        std::fs::write(dir.path().join("tool/Cargo.toml"), "[package]\n").unwrap();
+        let shell = ShellTool::new(dir.path().to_string_lossy().into_owned());
+
+        let input = json!({"project": "web/"});
+        let (text, _) = run_tests(&shell, &input, &Progress::none())
+            .await
+            .unwrap();
+        assert!(
+            text.starts_with("Exit: 1\nRan: npm test (in web/)\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("- web/src/sum.test.js:4 math › adds"),
+            "{text}"
+        );
+
+        let input = json!({"project": "api"});
+        let (text, _) = run_tests(&shell, &input, &Progress::none())
+            .await
+            .unwrap();
+        assert_eq!(
+            text,
+            "No project with tests at `api`. Projects: tool/, web/"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_projects_are_explained() {
+        let dir = tempfile::tempdir().unwrap();
+        let shell = ShellTool::new(dir.path().to_string_lossy().into_owned());
+        let (text, _) = run_tests(&shell, &json!({}), &Progress::none())
+            .await
+            .unwrap();
+        assert!(text.starts_with("No test command detected"));
+    }
+}
@@ -1,46 +1,55 @@
-/// TTS tool — voice of the familiar (ElevenLabs direct API).
-/// Plays on PC speaker AND Tapo camera speaker (if camera host is configured).
-use anyhow::Result;
+/// TTS tool — voice of the familiar.
+/// Synthesizes via a pluggable engine (see `tts_engine`) and plays on the
+/// PC speaker AND Tapo camera speaker (if camera host is configured).
+/// Audio is streamed per sentence, so speaking starts while the rest is still
+/// being synthesized, and decoded in-process (see `audio`).
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
 use reqwest::Client;
 use serde_json::json;
+use tokio::sync::mpsc;
 
 use crate::backend::ToolDef;
+use crate::config::TtsConfig;
+use crate::permissions::ToolCategory;
 
+use super::audio::{self, LocalSpeaker};
 use super::tapo_audio::TapoAudio;
+use super::tts_engine::{create_engine, detect_text_lang, voice_for_lang, AudioPiece, TtsEngine};
 use super::ToolOutput;
 
-const ELEVENLABS_URL: &str = "https://api.elevenlabs.io/v1/text-to-speech";
-
 pub struct TtsTool {
-    api_key: String,
-    voice_id: String,
+    config: TtsConfig,
     camera: TapoAudio,
     client: Client,
 }
 
 impl TtsTool {
     pub fn new(
-        api_key: String,
-        voice_id: String,
+        config: TtsConfig,
         camera_host: String,
         camera_username: String,
         camera_password: String,
     ) -> Self {
         Self {
-            api_key,
-            voice_id,
+            config,
             camera: TapoAudio::new(camera_host, camera_username, camera_password),
             client: Client::new(),
         }
     }
 
-    pub fn is_configured(&self) -> bool {
-        !self.api_key.is_empty()
+    /// Engine for an utterance, chosen by the language of its text.
+    fn engine_for(&self, text: &str) -> Box<dyn TtsEngine> {
+        let voice = voice_for_lang(&self.config, detect_text_lang(text));
+        create_engine(&self.config, &voice, self.client.clone())
     }
 
     pub fn tool_defs() -> Vec<ToolDef> {
         vec![ToolDef {
             name: "say".to_string(),
+            category: ToolCategory::Actuation,
             description:
                 "Speak aloud. This is the ONLY way to make sound — text output is silent. \
                  Keep it to 1-2 short sentences."
@@ -68,124 +77,337 @@ impl TtsTool {
 
     /// `speaker`: "camera" | "pc" | "both" | "" (empty = auto)
     pub async fn say(&self, text: &str, speaker: &str) -> Result<ToolOutput> {
-        if !self.is_configured() {
+        let engine = self.engine_for(text);
+        if !engine.is_configured() {
             return Ok((format!("(No TTS configured — would have said: {text})"), None));
         }
 
-        let url = format!("{}/{}", ELEVENLABS_URL, self.voice_id);
-        let body = json!({
-            "text": text,
-            "model_id": "eleven_v3",
-            "voice_settings": {
-                "stability": 0.5,
-                "similarity_boost": 0.75
-            }
-        });
-
-        let resp = self
-            .client
-            .post(&url)
-            .header("xi-api-key", &self.api_key)
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let err = resp.text().await.unwrap_or_default();
-            return Ok((format!("TTS failed ({status}): {err}"), None));
-        }
-
-        let audio_bytes = resp.bytes().await?.to_vec();
-
         // Resolve which speakers to use
         let cam_available = self.camera.is_configured();
         let want_camera = cam_available && !matches!(speaker, "pc");
-        let want_pc     = !cam_available || matches!(speaker, "pc" | "both");
-
-        if want_camera {
-            // Camera (primary) runs concurrently with PC.
-            // PC playback acts as the "done playing" signal — mpv blocks until audio ends,
-            // preventing the next say() from starting before this one finishes.
-            let pc_bytes = audio_bytes.clone();
-            let (cam_result, ()) = tokio::join!(
-                self.camera.play(audio_bytes),
-                play_audio(pc_bytes),
-            );
-            if let Err(e) = cam_result {
-                tracing::warn!("camera speaker: {e}");
+
+        // Audio is synthesized sentence by sentence and fanned out to the speakers
+        // as chunks arrive, so playback starts before synthesis finishes.
+        // PC playback always runs and acts as the "done playing" signal — the player
+        // blocks until audio ends, preventing the next say() from starting early.
+        let (pc_tx, pc_rx) = mpsc::unbounded_channel();
+        let mut sinks = vec![pc_tx];
+        let cam_rx = if want_camera {
+            let (tx, rx) = mpsc::unbounded_channel();
+            sinks.push(tx);
+            Some(rx)
+        } else {
+            None
+        };
+        let camera = async {
+            match cam_rx {
+                Some(rx) => self.camera.play_stream(rx).await,
+                None => Ok(()),
             }
+        };
+
+        let sentences = split_sentences(text);
+        let (spoken, pc_result, cam_result) = tokio::join!(
+            pump_speech(engine.as_ref(), &sentences, sinks),
+            play_stream(pc_rx),
+            camera,
+        );
+        let first_audio = match spoken {
+            Ok(first_audio) => first_audio,
+            Err(e) => return Ok((format!("TTS failed ({}): {e}", engine.name()), None)),
+        };
+        tracing::debug!("say: first audio after {first_audio:?}");
+
+        // Playback problems are reported so they are not silently lost.
+        let mut problems = Vec::new();
+        if let Err(e) = pc_result {
+            tracing::warn!("PC speaker: {e}");
+            problems.push(format!("PC speaker: {e}"));
+        }
+        if let Err(e) = cam_result {
+            tracing::warn!("camera speaker: {e}");
+            problems.push(format!("camera speaker: {e}"));
+        }
+        if problems.is_empty() {
+            Ok((format!("Said: {text}"), None))
         } else {
-            // PC only
-            play_audio(audio_bytes).await;
+            Ok((format!("Said: {text} ({})", problems.join("; ")), None))
         }
-        let _ = want_pc; // captured in want_camera branch implicitly
-        Ok((format!("Said: {text}"), None))
     }
 }
 
-async fn play_audio(bytes: Vec<u8>) {
-    // Write to temp file and play
-    let tmp = std::env::temp_dir().join(format!(
-        "familiar_tts_{}.mp3",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis()
-    ));
-
-    if tokio::fs::write(&tmp, &bytes).await.is_ok() {
-        // Try platform-specific player
-        #[cfg(target_os = "windows")]
-        let _ = tokio::process::Command::new("powershell")
-            .args([
-                "-c",
-                &format!(
-                    "Add-Type -AssemblyName PresentationCore; \
-                     $p = [System.Windows.Media.MediaPlayer]::new(); \
-                     $p.Open([uri]'{path}'); \
-                     $p.Play(); \
-                     while ($p.NaturalDuration.HasTimeSpan -eq $false) {{ Start-Sleep -Milliseconds 100 }}; \
-                     $ms = $p.NaturalDuration.TimeSpan.TotalMilliseconds; \
-                     Start-Sleep -Milliseconds ($ms + 300); \
-                     $p.Close()",
-                    path = tmp.display()
-                ),
-            ])
-            .output()
-            .await;
-
-        #[cfg(target_os = "macos")]
-        let _ = tokio::process::Command::new("afplay")
-            .arg(tmp.as_os_str())
-            .output()
-            .await;
-
-        #[cfg(target_os = "linux")]
-        {
-            // Try players in order — same as Python version.
-            // WSL2/WSLg needs --ao=pulse to reach the PulseAudio socket.
-            let attempts: &[&[&str]] = &[
-                &["mpv", "--no-terminal", "--ao=pulse"],
-                &["mpv", "--no-terminal"],
-                &["ffplay", "-nodisp", "-autoexit", "-loglevel", "error"],
-                &["aplay"],
-            ];
-            for base_args in attempts {
-                let mut cmd = tokio::process::Command::new(base_args[0]);
-                for a in &base_args[1..] {
-                    cmd.arg(a);
-                }
-                cmd.arg(tmp.as_os_str());
-                if let Ok(out) = cmd.output().await {
-                    if out.status.success() {
+/// Split text into sentences so synthesis of the first can start immediately.
+/// Breaks after CJK/ASCII terminators and newlines; a '.' only ends a sentence
+/// when followed by whitespace, so "3.5" and "v1.2" stay intact.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let boundary = match c {
+            '。' | '！' | '？' | '!' | '?' | '…' | '\n' => true,
+            '.' => chars.peek().is_none_or(|n| n.is_whitespace()),
+            _ => false,
+        };
+        if boundary {
+            let sentence = current.trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+            current.clear();
+        }
+    }
+    let rest = current.trim();
+    if !rest.is_empty() {
+        sentences.push(rest.to_string());
+    }
+    sentences
+}
+
+/// Synthesize `sentences` in order, broadcasting audio to every sink as it arrives.
+///
+/// Returns the time until the first audio byte was available. Fails only when
+/// nothing could be spoken; a failure after some audio went out is logged and
+/// ends the utterance early.
+async fn pump_speech(
+    engine: &dyn TtsEngine,
+    sentences: &[String],
+    sinks: Vec<mpsc::UnboundedSender<AudioPiece>>,
+) -> Result<Duration> {
+    // Receivers may hang up early (e.g. the camera connection failed); ignore that.
+    let broadcast = |piece: AudioPiece| {
+        for sink in &sinks {
+            let _ = sink.send(piece.clone());
+        }
+    };
+
+    let started = Instant::now();
+    let mut first_audio = None;
+    for sentence in sentences {
+        let result = async {
+            let mut stream = engine.synthesize_stream(sentence).await?;
+            broadcast(AudioPiece::Begin(stream.format));
+            let mut streamed = Ok(());
+            while let Some(chunk) = stream.chunks.next().await {
+                match chunk {
+                    Ok(bytes) if bytes.is_empty() => {}
+                    Ok(bytes) => {
+                        first_audio.get_or_insert_with(|| started.elapsed());
+                        broadcast(AudioPiece::Data(bytes));
+                    }
+                    Err(e) => {
+                        streamed = Err(e);
                         break;
                     }
                 }
             }
+            broadcast(AudioPiece::End);
+            streamed
         }
+        .await;
+
+        if let Err(e) = result {
+            if first_audio.is_none() {
+                return Err(e);
+            }
+            tracing::warn!("TTS stopped mid-utterance: {e}");
+            break;
+        }
+    }
+    first_audio.ok_or_else(|| anyhow!("no audio produced"))
+}
+
+/// Play streamed sentences on the PC speaker as they are decoded.
+async fn play_stream(mut rx: mpsc::UnboundedReceiver<AudioPiece>) -> Result<()> {
+    // Opening the device overlaps with synthesis of the first sentence.
+    let mut speaker = LocalSpeaker::open().await?;
+    while let Some(piece) = rx.recv().await {
+        let AudioPiece::Begin(format) = piece else { continue };
+        let (feed, mut decoded) = audio::decode_sentence(&mut rx, format);
+        let play = async {
+            while let Some(pcm) = decoded.recv().await {
+                speaker.push(&pcm?);
+            }
+            anyhow::Ok(())
+        };
+        let ((), played) = tokio::join!(feed, play);
+        played?;
+    }
+    speaker.finish().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TtsVoiceConfig;
+    use crate::tools::mock_http::{MockResponse, MockServer};
+    use crate::tools::tts_engine::AudioFormat;
+
+    /// OpenAI-compatible engine pointed at a local mock server.
+    fn mock_engine(url: &str) -> Box<dyn TtsEngine> {
+        let config = TtsConfig {
+            openai_base_url: url.to_string(),
+            ..TtsConfig::default()
+        };
+        let voice = TtsVoiceConfig {
+            engine: "openai".to_string(),
+            ..TtsVoiceConfig::default()
+        };
+        create_engine(&config, &voice, Client::new())
+    }
 
-        let _ = tokio::fs::remove_file(&tmp).await;
+    fn sentences(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    // ── split_sentences ───────────────────────────────────────────
+
+    #[test]
+    fn splits_japanese_sentences() {
+        assert_eq!(
+            split_sentences("おはよう。今日はいい天気だね！散歩する？"),
+            vec!["おはよう。", "今日はいい天気だね！", "散歩する？"]
+        );
+    }
+
+    #[test]
+    fn splits_english_on_period_followed_by_space() {
+        assert_eq!(
+            split_sentences("Hello there. Version 3.5 is out! Nice"),
+            vec!["Hello there.", "Version 3.5 is out!", "Nice"]
+        );
+    }
+
+    #[test]
+    fn splits_on_newlines_and_drops_blanks() {
+        assert_eq!(split_sentences("one\n\n two \n"), vec!["one", "two"]);
+        assert!(split_sentences("   ").is_empty());
+    }
+
+    // ── pump_speech ───────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn first_audio_arrives_before_synthesis_finishes() {
+        let server = MockServer::start(|_| MockResponse {
+            status: 200,
+            content_type: "audio/mpeg".to_string(),
+            chunks: vec![
+                (Duration::ZERO, b"first".to_vec()),
+                (Duration::from_millis(600), b"rest".to_vec()),
+            ],
+        })
+        .await;
+        let engine = mock_engine(&server.url);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let text = sentences(&["hello"]);
+        let started = Instant::now();
+        let listen = async {
+            let mut first_data = None;
+            let mut pieces = Vec::new();
+            while let Some(piece) = rx.recv().await {
+                if matches!(piece, AudioPiece::Data(_)) {
+                    first_data.get_or_insert_with(|| started.elapsed());
+                }
+                pieces.push(piece);
+            }
+            (first_data.unwrap(), pieces)
+        };
+        let (reported, (first_data, pieces)) =
+            tokio::join!(pump_speech(engine.as_ref(), &text, vec![tx]), listen);
+        let total = started.elapsed();
+
+        let reported = reported.unwrap();
+        assert!(total >= Duration::from_millis(600));
+        assert!(first_data < Duration::from_millis(400), "first audio took {first_data:?}");
+        assert!(reported <= first_data);
+        assert_eq!(pieces.first(), Some(&AudioPiece::Begin(AudioFormat::Mp3)));
+        assert_eq!(pieces.last(), Some(&AudioPiece::End));
+    }
+
+    #[tokio::test]
+    async fn sentences_are_synthesized_in_order() {
+        let server = MockServer::start(|req| {
+            let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+            MockResponse::bytes("audio/mpeg", body["input"].as_str().unwrap().as_bytes().to_vec())
+        })
+        .await;
+        let engine = mock_engine(&server.url);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        pump_speech(engine.as_ref(), &sentences(&["One.", "Two."]), vec![tx])
+            .await
+            .unwrap();
+
+        let mut pieces = Vec::new();
+        while let Some(piece) = rx.recv().await {
+            pieces.push(piece);
+        }
+        assert_eq!(
+            pieces,
+            vec![
+                AudioPiece::Begin(AudioFormat::Mp3),
+                AudioPiece::Data(b"One.".to_vec()),
+                AudioPiece::End,
+                AudioPiece::Begin(AudioFormat::Mp3),
+                AudioPiece::Data(b"Two.".to_vec()),
+                AudioPiece::End,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn every_sink_receives_the_same_audio() {
+        let server =
+            MockServer::start(|_| MockResponse::bytes("audio/mpeg", b"clip".to_vec())).await;
+        let engine = mock_engine(&server.url);
+        let (pc_tx, mut pc_rx) = mpsc::unbounded_channel();
+        let (cam_tx, mut cam_rx) = mpsc::unbounded_channel();
+
+        pump_speech(engine.as_ref(), &sentences(&["hi"]), vec![pc_tx, cam_tx])
+            .await
+            .unwrap();
+
+        for rx in [&mut pc_rx, &mut cam_rx] {
+            assert_eq!(rx.recv().await, Some(AudioPiece::Begin(AudioFormat::Mp3)));
+            assert_eq!(rx.recv().await, Some(AudioPiece::Data(b"clip".to_vec())));
+            assert_eq!(rx.recv().await, Some(AudioPiece::End));
+        }
+    }
+
+    #[tokio::test]
+    async fn failure_before_any_audio_is_an_error() {
+        let server = MockServer::start(|_| MockResponse::status(500, "boom")).await;
+        let engine = mock_engine(&server.url);
+        let (tx, _rx) = mpsc::unbounded_channel();
+
+        let err = pump_speech(engine.as_ref(), &sentences(&["hi"]), vec![tx])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("500"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn failure_after_audio_keeps_what_was_spoken() {
+        let server = MockServer::start(|req| {
+            if String::from_utf8_lossy(&req.body).contains("Two") {
+                MockResponse::status(500, "boom")
+            } else {
+                MockResponse::bytes("audio/mpeg", b"one".to_vec())
+            }
+        })
+        .await;
+        let engine = mock_engine(&server.url);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let result = pump_speech(engine.as_ref(), &sentences(&["One.", "Two."]), vec![tx]).await;
+        assert!(result.is_ok());
+
+        let mut pieces = Vec::new();
+        while let Some(piece) = rx.recv().await {
+            pieces.push(piece);
+        }
+        assert_eq!(pieces.len(), 3, "only the first sentence: {pieces:?}");
     }
 }
@@ -0,0 +1,751 @@
+/// TTS engines — pluggable speech synthesis backends for the `say` tool.
+///
+///   - ElevenLabs: cloud, multilingual (the original engine)
+///   - OpenAI-compatible: any `/audio/speech` endpoint (OpenAI, LocalAI, ...)
+///   - VOICEVOX: local engine HTTP server, excellent Japanese voices
+///   - Piper: local neural TTS run as a subprocess
+///
+/// Engine, voice and style are picked per language of the utterance via
+/// `[tts.languages.<code>]` in config, falling back to `tts.engine`.
+///
+/// ElevenLabs and OpenAI stream audio as it is generated; the local engines
+/// return a whole clip per call.
+use anyhow::{bail, Result};
+use futures::{Stream, StreamExt};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::{TtsConfig, TtsVoiceConfig};
+use crate::i18n::{self, Lang};
+
+const ELEVENLABS_URL: &str = "https://api.elevenlabs.io/v1/text-to-speech";
+const ELEVENLABS_MODEL: &str = "eleven_v3";
+const OPENAI_TTS_URL: &str = "https://api.openai.com/v1";
+const OPENAI_TTS_MODEL: &str = "gpt-4o-mini-tts";
+const OPENAI_TTS_VOICE: &str = "alloy";
+const VOICEVOX_URL: &str = "http://127.0.0.1:50021";
+/// ずんだもん (ノーマル) — the engine's customary default speaker.
+const VOICEVOX_SPEAKER: &str = "3";
+
+/// Container format of synthesized audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Mp3,
+    Wav,
+}
+
+impl AudioFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+        }
+    }
+}
+
+/// One synthesized utterance.
+pub struct Audio {
+    pub bytes: Vec<u8>,
+    pub format: AudioFormat,
+}
+
+/// One piece of a spoken utterance as it is fanned out to the speakers.
+/// Every sentence arrives as `Begin`, any number of `Data`, then `End`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioPiece {
+    Begin(AudioFormat),
+    Data(Vec<u8>),
+    End,
+}
+
+/// Audio bytes in arrival order.
+pub type AudioChunks = Pin<Box<dyn Stream<Item = Result<Vec<u8>>> + Send>>;
+
+/// One utterance being synthesized; `chunks` concatenate to a complete clip.
+pub struct AudioStream {
+    pub format: AudioFormat,
+    pub chunks: AudioChunks,
+}
+
+pub type SynthFuture<'a> = Pin<Box<dyn Future<Output = Result<Audio>> + Send + 'a>>;
+pub type StreamFuture<'a> = Pin<Box<dyn Future<Output = Result<AudioStream>> + Send + 'a>>;
+
+/// A speech synthesis backend. Object-safe so engines can be picked at runtime.
+pub trait TtsEngine: Send + Sync {
+    /// Human-readable engine name, used in the world model and error messages.
+    fn name(&self) -> &'static str;
+    fn is_configured(&self) -> bool;
+    fn synthesize<'a>(&'a self, text: &'a str) -> SynthFuture<'a>;
+
+    /// Start synthesis and yield audio as it arrives.
+    /// Engines without a streaming endpoint yield the whole clip as one chunk.
+    fn synthesize_stream<'a>(&'a self, text: &'a str) -> StreamFuture<'a> {
+        Box::pin(async move {
+            let audio = self.synthesize(text).await?;
+            let bytes = audio.bytes;
+            Ok(AudioStream {
+                format: audio.format,
+                chunks: Box::pin(futures::stream::once(async move { Ok(bytes) })),
+            })
+        })
+    }
+}
+
+/// Turn a successful HTTP response body into an `AudioStream`.
+fn response_stream(resp: reqwest::Response, format: AudioFormat) -> AudioStream {
+    AudioStream {
+        format,
+        chunks: Box::pin(
+            resp.bytes_stream()
+                .map(|chunk| chunk.map(|b| b.to_vec()).map_err(Into::into)),
+        ),
+    }
+}
+
+// ── Engine selection ──────────────────────────────────────────────
+
+/// Guess the language code of an utterance from its script.
+///
+/// Kana → "ja", Hangul → "ko", Han without kana → "zh". Latin text can't be
+/// told apart by script, so it follows the system language (fr/de/en).
+pub fn detect_text_lang(text: &str) -> &'static str {
+    let mut has_han = false;
+    for c in text.chars() {
+        match c as u32 {
+            0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => return "ja",
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => return "ko",
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => has_han = true,
+            _ => {}
+        }
+    }
+    if has_han {
+        return "zh";
+    }
+    match i18n::lang() {
+        Lang::Fr => "fr",
+        Lang::De => "de",
+        _ => "en",
+    }
+}
+
+/// Resolve the voice profile for a language: `[tts.languages.<lang>]` if present,
+/// otherwise the default engine with its default voice.
+pub fn voice_for_lang(config: &TtsConfig, lang: &str) -> TtsVoiceConfig {
+    let mut voice = config.languages.get(lang).cloned().unwrap_or_default();
+    if voice.engine.is_empty() {
+        voice.engine = config.effective_engine().to_string();
+    }
+    voice
+}
+
+/// Build the engine for a voice profile.
+pub fn create_engine(config: &TtsConfig, voice: &TtsVoiceConfig, client: Client) -> Box<dyn TtsEngine> {
+    match voice.engine.as_str() {
+        "openai" => Box::new(OpenAiTts {
+            api_key: config.openai_api_key.clone(),
+            base_url: non_empty_or(&config.openai_base_url, OPENAI_TTS_URL),
+            model: non_empty_or(&config.openai_model, OPENAI_TTS_MODEL),
+            voice: non_empty_or(&voice.voice, OPENAI_TTS_VOICE),
+            instructions: voice.style.clone(),
+            client,
+        }),
+        "voicevox" => Box::new(Voicevox {
+            base_url: non_empty_or(&config.voicevox_url, VOICEVOX_URL),
+            speaker: non_empty_or(&voice.voice, VOICEVOX_SPEAKER),
+            style: voice.style.clone(),
+            client,
+        }),
+        "piper" => Box::new(Piper {
+            binary: non_empty_or(&config.piper_path, "piper"),
+            model: non_empty_or(&voice.voice, &config.piper_model),
+            speaker: voice.style.clone(),
+        }),
+        // Default: ElevenLabs
+        _ => Box::new(ElevenLabs {
+            api_key: config.elevenlabs_api_key.clone(),
+            voice_id: non_empty_or(&voice.voice, &config.voice_id),
+            style: voice.style.parse().ok(),
+            client,
+        }),
+    }
+}
+
+/// Names of all configured engines (default + per-language), for status display.
+pub fn configured_engines(config: &TtsConfig) -> Vec<&'static str> {
+    let client = Client::new();
+    let mut voices = vec![voice_for_lang(config, "")];
+    voices.extend(config.languages.keys().map(|lang| voice_for_lang(config, lang)));
+
+    let mut names = Vec::new();
+    for voice in &voices {
+        let engine = create_engine(config, voice, client.clone());
+        if engine.is_configured() && !names.contains(&engine.name()) {
+            names.push(engine.name());
+        }
+    }
+    names
+}
+
+fn non_empty_or(value: &str, default: &str) -> String {
+    if value.is_empty() {
+        default.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+async fn error_text(resp: reqwest::Response) -> String {
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    format!("{status}: {body}")
+}
+
+// ── ElevenLabs ────────────────────────────────────────────────────
+
+pub struct ElevenLabs {
+    api_key: String,
+    voice_id: String,
+    /// Optional `voice_settings.style` exaggeration (0.0–1.0).
+    style: Option<f32>,
+    client: Client,
+}
+
+impl TtsEngine for ElevenLabs {
+    fn name(&self) -> &'static str {
+        "ElevenLabs"
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.api_key.is_empty()
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str) -> SynthFuture<'a> {
+        Box::pin(async move {
+            let resp = self.request(text, false).await?;
+            Ok(Audio {
+                bytes: resp.bytes().await?.to_vec(),
+                format: AudioFormat::Mp3,
+            })
+        })
+    }
+
+    fn synthesize_stream<'a>(&'a self, text: &'a str) -> StreamFuture<'a> {
+        Box::pin(async move {
+            let resp = self.request(text, true).await?;
+            Ok(response_stream(resp, AudioFormat::Mp3))
+        })
+    }
+}
+
+impl ElevenLabs {
+    /// POST the synthesis request; `stream` selects the chunked `/stream` endpoint.
+    async fn request(&self, text: &str, stream: bool) -> Result<reqwest::Response> {
+        let mut url = format!("{}/{}", ELEVENLABS_URL, self.voice_id);
+        if stream {
+            url.push_str("/stream");
+        }
+        let mut settings = json!({
+            "stability": 0.5,
+            "similarity_boost": 0.75
+        });
+        if let Some(style) = self.style {
+            settings["style"] = json!(style);
+        }
+        let body = json!({
+            "text": text,
+            "model_id": ELEVENLABS_MODEL,
+            "voice_settings": settings
+        });
+
+        let resp = self
+            .client
+            .post(&url)
+            .header("xi-api-key", &self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+        if !resp.status().is_success() {
+            bail!("ElevenLabs {}", error_text(resp).await);
+        }
+        Ok(resp)
+    }
+}
+
+// ── OpenAI-compatible /audio/speech ───────────────────────────────
+
+pub struct OpenAiTts {
+    api_key: String,
+    base_url: String,
+    model: String,
+    voice: String,
+    /// Free-form delivery instructions (tone, pacing); sent only when non-empty.
+    instructions: String,
+    client: Client,
+}
+
+impl TtsEngine for OpenAiTts {
+    fn name(&self) -> &'static str {
+        "OpenAI TTS"
+    }
+
+    /// Local OpenAI-compatible servers usually need no key, so a custom URL counts too.
+    fn is_configured(&self) -> bool {
+        !self.api_key.is_empty() || self.base_url != OPENAI_TTS_URL
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str) -> SynthFuture<'a> {
+        Box::pin(async move {
+            let resp = self.request(text).await?;
+            Ok(Audio {
+                bytes: resp.bytes().await?.to_vec(),
+                format: AudioFormat::Mp3,
+            })
+        })
+    }
+
+    /// `/audio/speech` sends the body with chunked encoding as it is generated.
+    fn synthesize_stream<'a>(&'a self, text: &'a str) -> StreamFuture<'a> {
+        Box::pin(async move {
+            let resp = self.request(text).await?;
+            Ok(response_stream(resp, AudioFormat::Mp3))
+        })
+    }
+}
+
+impl OpenAiTts {
+    async fn request(&self, text: &str) -> Result<reqwest::Response> {
+        let url = format!("{}/audio/speech", self.base_url.trim_end_matches('/'));
+        let mut body = json!({
+            "model": self.model,
+            "input": text,
+            "voice": self.voice,
+            "response_format": "mp3"
+        });
+        if !self.instructions.is_empty() {
+            body["instructions"] = json!(self.instructions);
+        }
+
+        let mut req = self.client.post(&url).json(&body);
+        if !self.api_key.is_empty() {
+            req = req.bearer_auth(&self.api_key);
+        }
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            bail!("OpenAI TTS {}", error_text(resp).await);
+        }
+        Ok(resp)
+    }
+}
+
+// ── VOICEVOX engine ───────────────────────────────────────────────
+
+pub struct Voicevox {
+    base_url: String,
+    /// Numeric style id, or a speaker name to look up via `/speakers`.
+    speaker: String,
+    /// Style name used with a speaker name (empty = the speaker's first style).
+    style: String,
+    client: Client,
+}
+
+impl Voicevox {
+    /// Resolve `speaker`/`style` to the numeric style id the engine expects.
+    async fn style_id(&self) -> Result<String> {
+        if self.speaker.parse::<u32>().is_ok() {
+            return Ok(self.speaker.clone());
+        }
+        let url = format!("{}/speakers", self.base_url.trim_end_matches('/'));
+        let speakers: Value = self.client.get(&url).send().await?.json().await?;
+        find_voicevox_style(&speakers, &self.speaker, &self.style)
+            .map(|id| id.to_string())
+            .ok_or_else(|| {
+                anyhow::anyhow!("VOICEVOX speaker not found: {} {}", self.speaker, self.style)
+            })
+    }
+}
+
+/// Find a style id in the `/speakers` listing by speaker name and style name.
+fn find_voicevox_style(speakers: &Value, speaker: &str, style: &str) -> Option<u64> {
+    let entry = speakers
+        .as_array()?
+        .iter()
+        .find(|s| s["name"].as_str() == Some(speaker))?;
+    let styles = entry["styles"].as_array()?;
+    let found = if style.is_empty() {
+        styles.first()
+    } else {
+        styles.iter().find(|s| s["name"].as_str() == Some(style))
+    };
+    found?["id"].as_u64()
+}
+
+impl TtsEngine for Voicevox {
+    fn name(&self) -> &'static str {
+        "VOICEVOX"
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.base_url.is_empty()
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str) -> SynthFuture<'a> {
+        Box::pin(async move {
+            let base = self.base_url.trim_end_matches('/');
+            let speaker = self.style_id().await?;
+
+            // Step 1: text → synthesis parameters (accent phrases, pitch, ...)
+            let resp = self
+                .client
+                .post(format!("{base}/audio_query"))
+                .query(&[("text", text), ("speaker", speaker.as_str())])
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                bail!("VOICEVOX audio_query {}", error_text(resp).await);
+            }
+            let query: Value = resp.json().await?;
+
+            // Step 2: parameters → WAV
+            let resp = self
+                .client
+                .post(format!("{base}/synthesis"))
+                .query(&[("speaker", speaker.as_str())])
+                .json(&query)
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                bail!("VOICEVOX synthesis {}", error_text(resp).await);
+            }
+            Ok(Audio {
+                bytes: resp.bytes().await?.to_vec(),
+                format: AudioFormat::Wav,
+            })
+        })
+    }
+}
+
+// ── Piper (subprocess) ────────────────────────────────────────────
+
+pub struct Piper {
+    binary: String,
+    /// Path to the `.onnx` voice model.
+    model: String,
+    /// Speaker id for multi-speaker models (empty = model default).
+    speaker: String,
+}
+
+impl TtsEngine for Piper {
+    fn name(&self) -> &'static str {
+        "Piper"
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.model.is_empty()
+    }
+
+    fn synthesize<'a>(&'a self, text: &'a str) -> SynthFuture<'a> {
+        Box::pin(async move {
+            let tmp = tempfile::Builder::new()
+                .prefix("familiar_piper_")
+                .suffix(".wav")
+                .tempfile()?
+                .into_temp_path();
+
+            let mut cmd = tokio::process::Command::new(&self.binary);
+            cmd.arg("--model")
+                .arg(&self.model)
+                .arg("--output_file")
+                .arg(&*tmp);
+            if !self.speaker.is_empty() {
+                cmd.arg("--speaker").arg(&self.speaker);
+            }
+            let mut child = cmd
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| anyhow::anyhow!("cannot run piper ({}): {e}", self.binary))?;
+
+            // Piper reads the text from stdin; closing it starts synthesis.
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes()).await?;
+            }
+            let out = child.wait_with_output().await?;
+            if !out.status.success() {
+                bail!("piper failed: {}", String::from_utf8_lossy(&out.stderr).trim());
+            }
+            Ok(Audio {
+                bytes: tokio::fs::read(&tmp).await?,
+                format: AudioFormat::Wav,
+            })
+        })
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::mock_http::{MockResponse, MockServer};
+    use std::time::Duration;
+
+    fn config() -> TtsConfig {
+        TtsConfig {
+            elevenlabs_api_key: "xi_key".to_string(),
+            voice_id: "default_voice".to_string(),
+            ..TtsConfig::default()
+        }
+    }
+
+    fn voice(engine: &str, voice: &str, style: &str) -> TtsVoiceConfig {
+        TtsVoiceConfig {
+            engine: engine.to_string(),
+            voice: voice.to_string(),
+            style: style.to_string(),
+        }
+    }
+
+    // ── detect_text_lang ──────────────────────────────────────────
+
+    #[test]
+    fn detects_japanese_from_kana() {
+        assert_eq!(detect_text_lang("おはよう"), "ja");
+        assert_eq!(detect_text_lang("今日はいい天気ですね"), "ja");
+        assert_eq!(detect_text_lang("カメラ"), "ja");
+    }
+
+    #[test]
+    fn detects_korean_from_hangul() {
+        assert_eq!(detect_text_lang("안녕하세요"), "ko");
+    }
+
+    #[test]
+    fn han_without_kana_is_chinese() {
+        assert_eq!(detect_text_lang("你好世界"), "zh");
+    }
+
+    #[test]
+    fn latin_text_follows_system_language() {
+        let lang = detect_text_lang("Hello there");
+        assert!(["en", "fr", "de"].contains(&lang), "got {lang}");
+    }
+
+    // ── voice_for_lang ────────────────────────────────────────────
+
+    #[test]
+    fn voice_for_lang_uses_language_override() {
+        let mut cfg = config();
+        cfg.languages.insert("ja".to_string(), voice("voicevox", "8", ""));
+        let v = voice_for_lang(&cfg, "ja");
+        assert_eq!(v.engine, "voicevox");
+        assert_eq!(v.voice, "8");
+    }
+
+    #[test]
+    fn voice_for_lang_falls_back_to_default_engine() {
+        let mut cfg = config();
+        cfg.engine = "openai".to_string();
+        let v = voice_for_lang(&cfg, "en");
+        assert_eq!(v.engine, "openai");
+        assert!(v.voice.is_empty());
+    }
+
+    #[test]
+    fn voice_for_lang_override_without_engine_uses_default_engine() {
+        let mut cfg = config();
+        cfg.languages.insert("en".to_string(), voice("", "other_voice", ""));
+        let v = voice_for_lang(&cfg, "en");
+        assert_eq!(v.engine, "elevenlabs");
+        assert_eq!(v.voice, "other_voice");
+    }
+
+    // ── create_engine ─────────────────────────────────────────────
+
+    #[test]
+    fn create_engine_picks_engine_by_name() {
+        let cfg = config();
+        for (engine, name) in [
+            ("elevenlabs", "ElevenLabs"),
+            ("openai", "OpenAI TTS"),
+            ("voicevox", "VOICEVOX"),
+            ("piper", "Piper"),
+            ("", "ElevenLabs"),
+        ] {
+            let e = create_engine(&cfg, &voice(engine, "", ""), Client::new());
+            assert_eq!(e.name(), name, "engine {engine:?}");
+        }
+    }
+
+    #[test]
+    fn elevenlabs_needs_api_key() {
+        let mut cfg = config();
+        assert!(create_engine(&cfg, &voice("elevenlabs", "", ""), Client::new()).is_configured());
+        cfg.elevenlabs_api_key.clear();
+        assert!(!create_engine(&cfg, &voice("elevenlabs", "", ""), Client::new()).is_configured());
+    }
+
+    #[test]
+    fn openai_configured_by_key_or_custom_url() {
+        let mut cfg = config();
+        assert!(!create_engine(&cfg, &voice("openai", "", ""), Client::new()).is_configured());
+        cfg.openai_base_url = "http://localhost:8880/v1".to_string();
+        assert!(create_engine(&cfg, &voice("openai", "", ""), Client::new()).is_configured());
+        cfg.openai_base_url.clear();
+        cfg.openai_api_key = "sk-test".to_string();
+        assert!(create_engine(&cfg, &voice("openai", "", ""), Client::new()).is_configured());
+    }
+
+    #[test]
+    fn piper_needs_model_path() {
+        let mut cfg = config();
+        assert!(!create_engine(&cfg, &voice("piper", "", ""), Client::new()).is_configured());
+        assert!(create_engine(&cfg, &voice("piper", "/models/ja.onnx", ""), Client::new()).is_configured());
+        cfg.piper_model = "/models/en.onnx".to_string();
+        assert!(create_engine(&cfg, &voice("piper", "", ""), Client::new()).is_configured());
+    }
+
+    #[test]
+    fn configured_engines_lists_default_and_overrides() {
+        let mut cfg = config();
+        cfg.languages.insert("ja".to_string(), voice("voicevox", "", ""));
+        cfg.languages.insert("de".to_string(), voice("piper", "", ""));
+        assert_eq!(configured_engines(&cfg), vec!["ElevenLabs", "VOICEVOX"]);
+    }
+
+    #[test]
+    fn configured_engines_empty_without_credentials() {
+        let cfg = TtsConfig::default();
+        assert!(configured_engines(&cfg).is_empty());
+    }
+
+    #[test]
+    fn audio_format_extensions() {
+        assert_eq!(AudioFormat::Mp3.extension(), "mp3");
+        assert_eq!(AudioFormat::Wav.extension(), "wav");
+    }
+
+    // ── VOICEVOX ──────────────────────────────────────────────────
+
+    #[test]
+    fn find_voicevox_style_by_name() {
+        let speakers = json!([
+            {"name": "四国めたん", "styles": [{"name": "ノーマル", "id": 2}, {"name": "あまあま", "id": 0}]},
+            {"name": "ずんだもん", "styles": [{"name": "ノーマル", "id": 3}, {"name": "ささやき", "id": 22}]}
+        ]);
+        assert_eq!(find_voicevox_style(&speakers, "ずんだもん", "ささやき"), Some(22));
+        assert_eq!(find_voicevox_style(&speakers, "四国めたん", ""), Some(2));
+        assert_eq!(find_voicevox_style(&speakers, "ずんだもん", "missing"), None);
+        assert_eq!(find_voicevox_style(&speakers, "nobody", ""), None);
+    }
+
+    #[tokio::test]
+    async fn voicevox_runs_audio_query_then_synthesis() {
+        let server = MockServer::start(|req| {
+            if req.path.starts_with("/audio_query") {
+                MockResponse::json(json!({"accent_phrases": [], "speedScale": 1.0}))
+            } else {
+                MockResponse::bytes("audio/wav", b"RIFF....WAVE".to_vec())
+            }
+        })
+        .await;
+
+        let mut cfg = config();
+        cfg.voicevox_url = server.url.clone();
+        let engine = create_engine(&cfg, &voice("voicevox", "8", ""), Client::new());
+        let audio = engine.synthesize("こんにちは").await.unwrap();
+
+        assert_eq!(audio.format, AudioFormat::Wav);
+        assert_eq!(audio.bytes, b"RIFF....WAVE");
+        let reqs = server.requests();
+        assert_eq!(reqs.len(), 2);
+        assert!(reqs[0].path.starts_with("/audio_query?"));
+        assert!(reqs[0].path.contains("speaker=8"));
+        assert!(reqs[1].path.starts_with("/synthesis?speaker=8"));
+        let sent: Value = serde_json::from_slice(&reqs[1].body).unwrap();
+        assert_eq!(sent["speedScale"], 1.0);
+    }
+
+    #[tokio::test]
+    async fn openai_posts_speech_request() {
+        let server = MockServer::start(|_| MockResponse::bytes("audio/mpeg", vec![0xFF, 0xFB])).await;
+
+        let mut cfg = config();
+        cfg.openai_base_url = format!("{}/v1", server.url);
+        cfg.openai_api_key = "sk-test".to_string();
+        let engine = create_engine(&cfg, &voice("openai", "nova", "speak softly"), Client::new());
+        let audio = engine.synthesize("hello").await.unwrap();
+
+        assert_eq!(audio.format, AudioFormat::Mp3);
+        let reqs = server.requests();
+        assert_eq!(reqs[0].path, "/v1/audio/speech");
+        assert_eq!(reqs[0].header("authorization"), Some("Bearer sk-test"));
+        let sent: Value = serde_json::from_slice(&reqs[0].body).unwrap();
+        assert_eq!(sent["voice"], "nova");
+        assert_eq!(sent["input"], "hello");
+        assert_eq!(sent["instructions"], "speak softly");
+    }
+
+    #[tokio::test]
+    async fn openai_stream_yields_chunks_as_they_arrive() {
+        let server = MockServer::start(|_| MockResponse {
+            status: 200,
+            content_type: "audio/mpeg".to_string(),
+            chunks: vec![
+                (Duration::ZERO, b"first".to_vec()),
+                (Duration::from_millis(50), b"second".to_vec()),
+            ],
+        })
+        .await;
+
+        let mut cfg = config();
+        cfg.openai_base_url = server.url.clone();
+        let engine = create_engine(&cfg, &voice("openai", "", ""), Client::new());
+        let mut stream = engine.synthesize_stream("hello").await.unwrap();
+
+        let mut all = Vec::new();
+        let mut count = 0;
+        while let Some(chunk) = stream.chunks.next().await {
+            all.extend(chunk.unwrap());
+            count += 1;
+        }
+        assert_eq!(stream.format, AudioFormat::Mp3);
+        assert_eq!(all, b"firstsecond");
+        assert!(count >= 2, "expected separate chunks, got {count}");
+    }
+
+    #[tokio::test]
+    async fn non_streaming_engine_yields_whole_clip() {
+        let server = MockServer::start(|req| {
+            if req.path.starts_with("/audio_query") {
+                MockResponse::json(json!({}))
+            } else {
+                MockResponse::bytes("audio/wav", b"RIFF-whole-clip".to_vec())
+            }
+        })
+        .await;
+
+        let mut cfg = config();
+        cfg.voicevox_url = server.url.clone();
+        let engine = create_engine(&cfg, &voice("voicevox", "1", ""), Client::new());
+        let mut stream = engine.synthesize_stream("はい").await.unwrap();
+        assert_eq!(stream.format, AudioFormat::Wav);
+        assert_eq!(stream.chunks.next().await.unwrap().unwrap(), b"RIFF-whole-clip");
+        assert!(stream.chunks.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn openai_error_status_is_reported() {
+        let server = MockServer::start(|_| MockResponse::status(401, "bad key")).await;
+        let mut cfg = config();
+        cfg.openai_base_url = server.url.clone();
+        let engine = create_engine(&cfg, &voice("openai", "", ""), Client::new());
+        let err = engine.synthesize("hello").await.err().unwrap().to_string();
+        assert!(err.contains("401"), "{err}");
+        assert!(err.contains("bad key"), "{err}");
+    }
+}
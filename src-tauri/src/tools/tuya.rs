@@ -0,0 +1,924 @@
+/// Tuya robot vacuum body — the original legs of the familiar.
+///
+/// The Tuya access token is cached until shortly before its `expire_time`
+/// and refreshed on demand, so a timed `walk` costs one handshake at most.
+/// When a local key and IP are configured, movement goes over the LAN
+/// (see `tuya_local`) and only falls back to the cloud if that fails.
+use anyhow::{bail, Result};
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::config::MobilityConfig;
+
+use super::robot::{Direction, Motion, Robot, RobotFuture};
+use super::tuya_local::TuyaLocal;
+
+/// Refresh this long before Tuya says the token expires.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+/// Tuya error codes meaning the token is no longer accepted.
+const TOKEN_INVALID_CODES: &[i64] = &[1010, 1011];
+/// Battery percentage below which the body reports being tired.
+const LOW_BATTERY: u64 = 20;
+/// A dock reading is reused this long, so back-to-back walks don't each cost a
+/// status request. Arriving on the dock within it shows up on a later check.
+const DOCK_STATE_TTL: Duration = Duration::from_secs(30);
+/// Data point of `direction_control` on most Tuya robot vacuums.
+const DEFAULT_DIRECTION_DP: &str = "4";
+
+struct CachedToken {
+    access_token: String,
+    refresh_token: String,
+    expires_at: Instant,
+}
+
+impl CachedToken {
+    fn is_fresh(&self) -> bool {
+        Instant::now() + TOKEN_EXPIRY_MARGIN < self.expires_at
+    }
+}
+
+pub struct TuyaRobot {
+    base_url: String,
+    api_key: String,
+    api_secret: String,
+    device_id: String,
+    client: Client,
+    token: Mutex<Option<CachedToken>>,
+    /// Last dock (charging) reading and when it was taken. A "docked" one is
+    /// dropped as soon as the body drives off.
+    dock_state: std::sync::Mutex<Option<(Instant, bool)>>,
+    /// LAN connection, tried before the cloud for movement.
+    local: Option<TuyaLocal>,
+    direction_dp: String,
+}
+
+impl TuyaRobot {
+    pub fn new(region: String, api_key: String, api_secret: String, device_id: String) -> Self {
+        Self {
+            base_url: base_url(&region).to_string(),
+            api_key,
+            api_secret,
+            device_id,
+            client: Client::new(),
+            token: Mutex::new(None),
+            dock_state: std::sync::Mutex::new(None),
+            local: None,
+            direction_dp: DEFAULT_DIRECTION_DP.to_string(),
+        }
+    }
+
+    pub fn from_config(config: &MobilityConfig) -> Self {
+        let tool = Self::new(
+            config.tuya_region.clone(),
+            config.tuya_api_key.clone(),
+            config.tuya_api_secret.clone(),
+            config.tuya_device_id.clone(),
+        );
+        if config.tuya_local_ip.is_empty() || config.tuya_local_key.is_empty() {
+            return tool;
+        }
+        let local = TuyaLocal::new(
+            &config.tuya_local_ip,
+            &config.tuya_device_id,
+            &config.tuya_local_key,
+            &config.tuya_protocol_version,
+        );
+        match local {
+            Ok(local) => tool.with_local(local, &config.tuya_direction_dp),
+            Err(e) => {
+                tracing::warn!("Tuya LAN control disabled: {e}");
+                tool
+            }
+        }
+    }
+
+    /// Drive the robot over the LAN; `direction_dp` empty = the usual DP 4.
+    pub fn with_local(mut self, local: TuyaLocal, direction_dp: &str) -> Self {
+        self.local = Some(local);
+        if !direction_dp.is_empty() {
+            self.direction_dp = direction_dp.to_string();
+        }
+        self
+    }
+
+    fn has_cloud(&self) -> bool {
+        !self.api_key.is_empty()
+    }
+
+    /// Cached access token, fetching or refreshing it when needed.
+    async fn access_token(&self) -> Result<String> {
+        let mut cached = self.token.lock().await;
+        if let Some(token) = cached.as_ref().filter(|t| t.is_fresh()) {
+            return Ok(token.access_token.clone());
+        }
+
+        // Prefer the refresh token; fall back to a new grant if Tuya rejects it.
+        let refreshed = match cached.as_ref() {
+            Some(old) if !old.refresh_token.is_empty() => {
+                let path = format!("/v1.0/token/{}", old.refresh_token);
+                self.fetch_token(&path).await.map_err(|e| {
+                    tracing::debug!("Tuya token refresh failed, requesting a new one: {e}");
+                })
+            }
+            _ => Err(()),
+        };
+        let token = match refreshed {
+            Ok(token) => token,
+            Err(()) => self.fetch_token("/v1.0/token?grant_type=1").await?,
+        };
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// Request a token from `path` (new grant or refresh).
+    async fn fetch_token(&self, path: &str) -> Result<CachedToken> {
+        let now = now_ms();
+
+        // Token request signature: HMAC-SHA256(client_id + t + stringToSign)
+        // stringToSign = METHOD\nContentHash\n\nURL
+        let content_hash = sha256_hex(b"");
+        let string_to_sign = format!("GET\n{content_hash}\n\n{path}");
+        let message = format!("{}{}{}", self.api_key, now, string_to_sign);
+        let sign = hmac_sha256(&self.api_secret, &message);
+
+        let resp = self
+            .client
+            .get(format!("{}{path}", self.base_url))
+            .header("client_id", &self.api_key)
+            .header("t", now.to_string())
+            .header("sign_method", "HMAC-SHA256")
+            .header("sign", &sign)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        if resp["success"].as_bool() != Some(true) {
+            bail!("Tuya token error: {resp}");
+        }
+        let result = &resp["result"];
+        let access_token = result["access_token"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("no access_token in: {resp}"))?
+            .to_string();
+        // expire_time is in seconds; assume Tuya's default of two hours if absent.
+        let expire_secs = result["expire_time"].as_u64().unwrap_or(7200);
+        Ok(CachedToken {
+            access_token,
+            refresh_token: result["refresh_token"].as_str().unwrap_or("").to_string(),
+            expires_at: Instant::now() + Duration::from_secs(expire_secs),
+        })
+    }
+
+    /// Signed business request. Retries once with a new token if Tuya says
+    /// the cached one is no longer valid. Returns the `result` field.
+    async fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        let mut resp = self.request_once(method, path, body).await?;
+        let code = resp["code"].as_i64().unwrap_or(0);
+        if resp["success"].as_bool() != Some(true) && TOKEN_INVALID_CODES.contains(&code) {
+            *self.token.lock().await = None;
+            resp = self.request_once(method, path, body).await?;
+        }
+        if resp["success"].as_bool() != Some(true) {
+            bail!("Tuya request error: {resp}");
+        }
+        Ok(resp["result"].clone())
+    }
+
+    async fn request_once(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value> {
+        let access_token = self.access_token().await?;
+        let body_str = body.map(|b| b.to_string()).unwrap_or_default();
+        let now = now_ms();
+
+        // Authenticated request signature: HMAC-SHA256(client_id + access_token + t + stringToSign)
+        let content_hash = sha256_hex(body_str.as_bytes());
+        let string_to_sign = format!("{method}\n{content_hash}\n\n{path}");
+        let message = format!("{}{}{}{}", self.api_key, access_token, now, string_to_sign);
+        let sign = hmac_sha256(&self.api_secret, &message);
+
+        let url = format!("{}{path}", self.base_url);
+        let mut req = match method {
+            "POST" => self.client.post(url).body(body_str),
+            _ => self.client.get(url),
+        };
+        if body.is_some() {
+            req = req.header("Content-Type", "application/json");
+        }
+        let resp = req
+            .header("client_id", &self.api_key)
+            .header("access_token", &access_token)
+            .header("t", now.to_string())
+            .header("sign_method", "HMAC-SHA256")
+            .header("sign", &sign)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        Ok(resp)
+    }
+
+    /// Send a `direction_control` command — over the LAN when configured,
+    /// falling back to the cloud if the device can't be reached.
+    async fn send_tuya_command(&self, command: &str) -> Result<()> {
+        if let Some(local) = &self.local {
+            let dps = json!({ self.direction_dp.as_str(): command });
+            match local.set_dps(dps).await {
+                Ok(()) => return Ok(()),
+                Err(e) if self.has_cloud() => {
+                    tracing::warn!("Tuya LAN command failed, falling back to cloud: {e}");
+                }
+                Err(e) => return Err(anyhow::anyhow!("Tuya LAN command error: {e}")),
+            }
+        }
+        self.send_cloud_command(command).await
+    }
+
+    async fn send_cloud_command(&self, command: &str) -> Result<()> {
+        let path = format!("/v1.0/devices/{}/commands", self.device_id);
+        let body = json!({
+            "commands": [{"code": "direction_control", "value": command}]
+        });
+        self.request("POST", &path, Some(&body))
+            .await
+            .map_err(|e| anyhow::anyhow!("Tuya command error: {e}"))?;
+        Ok(())
+    }
+
+    /// Device status from the cloud; also refreshes the dock reading.
+    async fn body_status(&self) -> Result<BodyStatus> {
+        let path = format!("/v1.0/devices/{}/status", self.device_id);
+        let result = self.request("GET", &path, None).await?;
+        let dps = result.as_array().cloned().unwrap_or_default();
+        let status = BodyStatus::from_dps(&dps);
+        let docked = status.charging == Some(true);
+        *self.dock_state.lock().unwrap() = Some((Instant::now(), docked));
+        Ok(status)
+    }
+
+    #[allow(dead_code)]
+    fn sign_request(&self, method: &str, path: &str, body: &str, timestamp: u128) -> String {
+        let content_hash = sha256_hex(body.as_bytes());
+        let string_to_sign = format!("{method}\n{content_hash}\n\n{path}");
+        let message = format!("{}{}{}", self.api_key, timestamp, string_to_sign);
+        hmac_sha256(&self.api_secret, &message)
+    }
+}
+
+impl Robot for TuyaRobot {
+    fn name(&self) -> &'static str {
+        "tuya"
+    }
+
+    fn is_configured(&self) -> bool {
+        !self.device_id.is_empty() && (self.has_cloud() || self.local.is_some())
+    }
+
+    fn drive(&self, direction: Direction) -> RobotFuture<'_, Motion> {
+        Box::pin(async move {
+            // Tuya `direction_control` values for robot vacuum movement
+            let command = match direction {
+                Direction::Forward => "forward",
+                Direction::Backward => "backward",
+                Direction::Left => "turn_left",
+                Direction::Right => "turn_right",
+                Direction::Stop => "stop",
+            };
+            if direction != Direction::Stop {
+                let mut dock = self.dock_state.lock().unwrap();
+                if matches!(*dock, Some((_, true))) {
+                    *dock = None;
+                }
+            }
+            self.send_tuya_command(command).await?;
+            Ok(Motion::default())
+        })
+    }
+
+    /// Read battery, charging state, cleaning mode and faults from the device.
+    fn status(&self) -> RobotFuture<'_, String> {
+        Box::pin(async move {
+            if !self.has_cloud() {
+                return Ok(
+                    "(Body status needs the Tuya cloud API key — only LAN control is configured)"
+                        .to_string(),
+                );
+            }
+            Ok(self.body_status().await?.describe())
+        })
+    }
+
+    /// A charging vacuum is on its dock. A recent reading is reused.
+    fn docked(&self) -> RobotFuture<'_, bool> {
+        Box::pin(async move {
+            if !self.has_cloud() {
+                return Ok(false);
+            }
+            let cached = *self.dock_state.lock().unwrap();
+            if let Some((_, docked)) = cached.filter(|(at, _)| at.elapsed() < DOCK_STATE_TTL) {
+                return Ok(docked);
+            }
+            Ok(self.body_status().await?.charging == Some(true))
+        })
+    }
+}
+
+fn base_url(region: &str) -> &'static str {
+    match region {
+        "eu" => "https://openapi.tuyaeu.com",
+        "in" => "https://openapi.tuyain.com",
+        _ => "https://openapi.tuyaus.com",
+    }
+}
+
+// ── Body status ───────────────────────────────────────────────────────────────
+
+/// Device status points of a robot vacuum, interpreted as body sensations.
+/// Data-point codes vary by model; the common ones are recognized and the rest
+/// are listed as-is.
+#[derive(Debug, Default, PartialEq)]
+struct BodyStatus {
+    battery: Option<u64>,
+    charging: Option<bool>,
+    state: Option<String>,
+    mode: Option<String>,
+    cleaning: Option<bool>,
+    faults: Vec<String>,
+    other: Vec<String>,
+}
+
+impl BodyStatus {
+    /// Build from Tuya's `[{ "code": ..., "value": ... }, ...]` status list.
+    fn from_dps(dps: &[Value]) -> Self {
+        let mut status = Self::default();
+        for dp in dps {
+            let code = dp["code"].as_str().unwrap_or("");
+            let value = &dp["value"];
+            match code {
+                "electricity_left" | "battery_percentage" | "residual_electricity" | "battery" => {
+                    status.battery = value.as_u64();
+                }
+                "status" | "work_status" => {
+                    let state = value_text(value);
+                    if status.charging.is_none() {
+                        status.charging = Some(matches!(
+                            state.as_str(),
+                            "charging" | "charge_done" | "fully_charged"
+                        ));
+                    }
+                    status.state = Some(state);
+                }
+                "charge_state" | "charging" => status.charging = value.as_bool(),
+                "mode" | "work_mode" => status.mode = Some(value_text(value)),
+                "power_go" | "switch_go" => status.cleaning = value.as_bool(),
+                "fault" | "fault_code" => status.faults.extend(fault_names(value)),
+                "" => {}
+                _ => status.other.push(format!("{code}={}", value_text(value))),
+            }
+        }
+        status
+    }
+
+    fn describe(&self) -> String {
+        let mut lines = vec!["Body status:".to_string()];
+        if let Some(battery) = self.battery {
+            let feeling = if self.charging == Some(true) {
+                " (resting on the dock)"
+            } else if battery < LOW_BATTERY {
+                " (low — legs are tired, go back to the dock soon)"
+            } else {
+                ""
+            };
+            lines.push(format!("- Battery: {battery}%{feeling}"));
+        }
+        if let Some(charging) = self.charging {
+            lines.push(format!("- Charging: {}", if charging { "yes" } else { "no" }));
+        }
+        if let Some(state) = &self.state {
+            lines.push(format!("- State: {state}"));
+        }
+        if let Some(mode) = &self.mode {
+            lines.push(format!("- Cleaning mode: {mode}"));
+        }
+        if let Some(cleaning) = self.cleaning {
+            lines.push(format!("- Cleaning: {}", if cleaning { "on" } else { "off" }));
+        }
+        if self.faults.is_empty() {
+            lines.push("- Errors: none".to_string());
+        } else {
+            lines.push(format!("- Errors: {}", self.faults.join(", ")));
+        }
+        if !self.other.is_empty() {
+            lines.push(format!("- Other: {}", self.other.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Faults come as a bitmap (0 = none), a fault name, or a list of names.
+fn fault_names(value: &Value) -> Vec<String> {
+    match value {
+        Value::Number(n) if n.as_u64() == Some(0) => Vec::new(),
+        Value::Number(n) => vec![format!("fault code {n}")],
+        Value::String(s) if s.is_empty() || s == "0" || s == "none" => Vec::new(),
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items.iter().flat_map(fault_names).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn now_ms() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+/// SHA-256 of `data`, returned as lowercase hex string.
+fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(data))
+}
+
+/// HMAC-SHA256(key, data), returned as uppercase hex string (Tuya expects upper).
+fn hmac_sha256(key: &str, data: &str) -> String {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key size");
+    mac.update(data.as_bytes());
+    hex::encode(mac.finalize().into_bytes()).to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::mock_http::{MockRequest, MockResponse, MockServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn sha256_hex_known_value_abc() {
+        let result = sha256_hex(b"abc");
+        assert_eq!(
+            result,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn sha256_hex_empty_input() {
+        let result = sha256_hex(b"");
+        assert_eq!(
+            result,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn sha256_hex_deterministic() {
+        assert_eq!(sha256_hex(b"hello world"), sha256_hex(b"hello world"));
+    }
+
+    #[test]
+    fn sha256_hex_different_inputs_differ() {
+        assert_ne!(sha256_hex(b"hello"), sha256_hex(b"world"));
+    }
+
+    #[test]
+    fn hmac_sha256_known_value() {
+        let result = hmac_sha256("key", "The quick brown fox jumps over the lazy dog");
+        assert_eq!(
+            result,
+            "F7BC83F430538424B13298E6AA6FB143EF4D59A14946175997479DBC2D1A3CD8"
+        );
+    }
+
+    #[test]
+    fn hmac_sha256_not_fake_signature() {
+        let result = hmac_sha256("secret", "data");
+        assert!(!result.starts_with("sig_"));
+    }
+
+    #[test]
+    fn hmac_sha256_deterministic() {
+        assert_eq!(hmac_sha256("key", "message"), hmac_sha256("key", "message"));
+    }
+
+    #[test]
+    fn hmac_sha256_different_keys_differ() {
+        assert_ne!(hmac_sha256("key1", "message"), hmac_sha256("key2", "message"));
+    }
+
+    #[test]
+    fn hmac_sha256_different_data_differ() {
+        assert_ne!(hmac_sha256("key", "message1"), hmac_sha256("key", "message2"));
+    }
+
+    #[test]
+    fn hmac_sha256_returns_64_hex_chars() {
+        assert_eq!(hmac_sha256("key", "data").len(), 64);
+    }
+
+    #[test]
+    fn sign_request_is_deterministic() {
+        let tool = TuyaRobot::new(
+            "us".to_string(),
+            "test_key".to_string(),
+            "test_secret".to_string(),
+            "device123".to_string(),
+        );
+        let sig1 = tool.sign_request("POST", "/v1.0/test", "{}", 1700000000000);
+        let sig2 = tool.sign_request("POST", "/v1.0/test", "{}", 1700000000000);
+        assert_eq!(sig1, sig2);
+    }
+
+    #[test]
+    fn sign_request_not_fake() {
+        let tool = TuyaRobot::new(
+            "us".to_string(),
+            "key".to_string(),
+            "secret".to_string(),
+            "dev".to_string(),
+        );
+        let sig = tool.sign_request("POST", "/path", "body", 12345);
+        assert!(!sig.starts_with("sig_"));
+        assert_eq!(sig.len(), 64);
+    }
+
+    #[test]
+    fn sign_request_changes_with_timestamp() {
+        let tool = TuyaRobot::new(
+            "us".to_string(),
+            "key".to_string(),
+            "secret".to_string(),
+            "dev".to_string(),
+        );
+        assert_ne!(
+            tool.sign_request("POST", "/path", "body", 1000),
+            tool.sign_request("POST", "/path", "body", 2000)
+        );
+    }
+
+    #[test]
+    fn is_configured_empty_api_key() {
+        let tool = TuyaRobot::new("us".to_string(), "".to_string(), "s".to_string(), "d".to_string());
+        assert!(!tool.is_configured());
+    }
+
+    #[test]
+    fn is_configured_empty_device_id() {
+        let tool = TuyaRobot::new("us".to_string(), "k".to_string(), "s".to_string(), "".to_string());
+        assert!(!tool.is_configured());
+    }
+
+    #[test]
+    fn is_configured_with_all_values() {
+        let tool = TuyaRobot::new("us".to_string(), "k".to_string(), "s".to_string(), "d".to_string());
+        assert!(tool.is_configured());
+    }
+
+    // ── Tuya API stand-in ─────────────────────────────────────────
+
+    fn tool_for(server: &MockServer) -> TuyaRobot {
+        let mut tool = TuyaRobot::new("us".into(), "key".into(), "secret".into(), "dev1".into());
+        tool.base_url = server.url.clone();
+        tool
+    }
+
+    fn token_response(token: &str, expire_time: u64) -> MockResponse {
+        MockResponse::json(json!({
+            "success": true,
+            "result": {
+                "access_token": token,
+                "refresh_token": format!("refresh-{token}"),
+                "expire_time": expire_time,
+                "uid": "u1"
+            }
+        }))
+    }
+
+    fn is_token_request(req: &MockRequest) -> bool {
+        req.path.starts_with("/v1.0/token")
+    }
+
+    /// Tuya stand-in: issues tokens "tok1", "tok2", ... with the given lifetime
+    /// and accepts commands/status reads that carry any issued token.
+    async fn tuya_server(expire_time: u64) -> MockServer {
+        let issued = Arc::new(AtomicUsize::new(0));
+        MockServer::start(move |req| {
+            if is_token_request(req) {
+                let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                return token_response(&format!("tok{n}"), expire_time);
+            }
+            if req.header("access_token").is_none_or(|t| !t.starts_with("tok")) {
+                return MockResponse::json(json!({"success": false, "code": 1010, "msg": "token invalid"}));
+            }
+            if req.path.ends_with("/status") {
+                return MockResponse::json(json!({
+                    "success": true,
+                    "result": [
+                        {"code": "electricity_left", "value": 15},
+                        {"code": "status", "value": "standby"},
+                        {"code": "mode", "value": "smart"},
+                        {"code": "fault", "value": 0}
+                    ]
+                }));
+            }
+            MockResponse::json(json!({"success": true, "result": true}))
+        })
+        .await
+    }
+
+    fn token_requests(server: &MockServer) -> Vec<MockRequest> {
+        server.requests().into_iter().filter(is_token_request).collect()
+    }
+
+    #[tokio::test]
+    async fn timed_walk_fetches_token_once() {
+        let server = tuya_server(7200).await;
+        let tool = tool_for(&server);
+
+        tool.walk(Direction::Forward, Duration::from_millis(10)).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(token_requests(&server).len(), 1);
+        let commands: Vec<_> = requests.iter().filter(|r| r.path.ends_with("/commands")).collect();
+        assert_eq!(commands.len(), 2, "forward + stop");
+        for cmd in commands {
+            assert_eq!(cmd.method, "POST");
+            assert_eq!(cmd.header("access_token"), Some("tok1"));
+            assert_eq!(cmd.header("client_id"), Some("key"));
+        }
+    }
+
+    #[tokio::test]
+    async fn token_is_reused_across_calls() {
+        let server = tuya_server(7200).await;
+        let tool = tool_for(&server);
+
+        tool.drive(Direction::Left).await.unwrap();
+        tool.drive(Direction::Stop).await.unwrap();
+        tool.status().await.unwrap();
+        assert_eq!(token_requests(&server).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn expiring_token_is_refreshed_with_refresh_token() {
+        // Lifetime inside the safety margin → stale immediately after issue.
+        let server = tuya_server(30).await;
+        let tool = tool_for(&server);
+
+        tool.drive(Direction::Left).await.unwrap();
+        tool.drive(Direction::Stop).await.unwrap();
+
+        let tokens = token_requests(&server);
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].path, "/v1.0/token?grant_type=1");
+        assert_eq!(tokens[1].path, "/v1.0/token/refresh-tok1");
+        let last = server.requests().pop().unwrap();
+        assert_eq!(last.header("access_token"), Some("tok2"));
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_replaced_and_request_retried() {
+        let server = MockServer::start(|req| {
+            if is_token_request(req) {
+                return token_response("fresh", 7200);
+            }
+            if req.header("access_token") != Some("fresh") {
+                return MockResponse::json(json!({"success": false, "code": 1010, "msg": "token invalid"}));
+            }
+            MockResponse::json(json!({"success": true, "result": true}))
+        })
+        .await;
+        let tool = tool_for(&server);
+        *tool.token.lock().await = Some(CachedToken {
+            access_token: "revoked".into(),
+            refresh_token: String::new(),
+            expires_at: Instant::now() + Duration::from_secs(3600),
+        });
+
+        tool.drive(Direction::Stop).await.unwrap();
+        let commands: Vec<_> = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.path.ends_with("/commands"))
+            .collect();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[1].header("access_token"), Some("fresh"));
+    }
+
+    #[tokio::test]
+    async fn command_failure_is_an_error() {
+        let server = MockServer::start(|req| {
+            if is_token_request(req) {
+                return token_response("tok", 7200);
+            }
+            MockResponse::json(json!({"success": false, "code": 2008, "msg": "command or value not support"}))
+        })
+        .await;
+        let err = tool_for(&server).drive(Direction::Forward).await.unwrap_err();
+        assert!(err.to_string().contains("not support"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn body_status_reads_device_status() {
+        let server = tuya_server(7200).await;
+        let text = tool_for(&server).status().await.unwrap();
+
+        assert!(text.contains("Battery: 15% (low"), "{text}");
+        assert!(text.contains("Charging: no"));
+        assert!(text.contains("State: standby"));
+        assert!(text.contains("Cleaning mode: smart"));
+        assert!(text.contains("Errors: none"));
+        let status_req = server.requests().pop().unwrap();
+        assert_eq!(status_req.method, "GET");
+        assert_eq!(status_req.path, "/v1.0/devices/dev1/status");
+    }
+
+    fn status_requests(server: &MockServer) -> usize {
+        let requests = server.requests();
+        requests.iter().filter(|r| r.path.ends_with("/status")).count()
+    }
+
+    #[tokio::test]
+    async fn dock_state_is_reused_between_walks() {
+        let server = tuya_server(7200).await;
+        let tool = tool_for(&server);
+
+        assert!(!tool.docked().await.unwrap());
+        tool.walk(Direction::Forward, Duration::from_millis(10)).await.unwrap();
+        assert!(!tool.docked().await.unwrap());
+        assert_eq!(status_requests(&server), 1);
+
+        // Status reads refresh it too.
+        tool.status().await.unwrap();
+        assert!(!tool.docked().await.unwrap());
+        assert_eq!(status_requests(&server), 2);
+
+        // Driving off the dock drops a "docked" reading.
+        *tool.dock_state.lock().unwrap() = Some((Instant::now(), true));
+        tool.drive(Direction::Forward).await.unwrap();
+        assert!(!tool.docked().await.unwrap());
+        assert_eq!(status_requests(&server), 3);
+    }
+
+    // ── LAN control ───────────────────────────────────────────────
+
+    const LOCAL_KEY: &str = "0123456789abcdef";
+
+    /// A 3.3 device that acks every command and records the dps it was sent.
+    async fn lan_device() -> (String, Arc<std::sync::Mutex<Vec<Value>>>) {
+        use crate::tools::tuya_local::{decode, encode, Version};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let key: [u8; 16] = LOCAL_KEY.as_bytes().try_into().unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 512];
+            loop {
+                while let Some((frame, used)) = decode(Version::V33, &key, &buf).unwrap() {
+                    buf.drain(..used);
+                    let body: Value = serde_json::from_slice(&frame.payload).unwrap();
+                    log.lock().unwrap().push(body["dps"].clone());
+                    let ack = encode(Version::V33, &key, frame.seq, frame.cmd, b"");
+                    stream.write_all(&ack).await.unwrap();
+                }
+                let n = stream.read(&mut chunk).await.unwrap();
+                if n == 0 {
+                    return;
+                }
+                buf.extend_from_slice(&chunk[..n]);
+            }
+        });
+        (addr, seen)
+    }
+
+    fn with_lan(tool: TuyaRobot, addr: &str) -> TuyaRobot {
+        tool.with_local(TuyaLocal::new(addr, "dev1", LOCAL_KEY, "3.3").unwrap(), "")
+    }
+
+    /// An address nothing listens on.
+    async fn dead_addr() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn walk_goes_over_lan_without_cloud() {
+        let (addr, seen) = lan_device().await;
+        let tool = with_lan(TuyaRobot::new("us".into(), "".into(), "".into(), "dev1".into()), &addr);
+        assert!(tool.is_configured());
+
+        let motion = tool.walk(Direction::Left, Duration::from_millis(10)).await.unwrap();
+        assert!(!motion.bumped);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![json!({"4": "turn_left"}), json!({"4": "stop"})]
+        );
+    }
+
+    #[tokio::test]
+    async fn lan_failure_falls_back_to_cloud() {
+        let server = tuya_server(7200).await;
+        let tool = with_lan(tool_for(&server), &dead_addr().await);
+
+        tool.drive(Direction::Forward).await.unwrap();
+        let commands = server.requests().into_iter().filter(|r| r.path.ends_with("/commands")).count();
+        assert_eq!(commands, 1);
+    }
+
+    #[tokio::test]
+    async fn lan_failure_without_cloud_is_an_error() {
+        let tool = with_lan(
+            TuyaRobot::new("us".into(), "".into(), "".into(), "dev1".into()),
+            &dead_addr().await,
+        );
+        let err = tool.drive(Direction::Forward).await.unwrap_err();
+        assert!(err.to_string().contains("Tuya LAN command error"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn body_status_needs_cloud() {
+        let (addr, _) = lan_device().await;
+        let tool = with_lan(TuyaRobot::new("us".into(), "".into(), "".into(), "dev1".into()), &addr);
+        let text = tool.status().await.unwrap();
+        assert!(text.contains("only LAN control"), "{text}");
+    }
+
+    #[test]
+    fn from_config_enables_lan_only_with_valid_key() {
+        let mut config = MobilityConfig {
+            tuya_device_id: "dev1".into(),
+            tuya_local_ip: "192.168.1.20".into(),
+            tuya_local_key: LOCAL_KEY.into(),
+            tuya_direction_dp: "25".into(),
+            ..Default::default()
+        };
+        let tool = TuyaRobot::from_config(&config);
+        assert!(tool.local.is_some());
+        assert_eq!(tool.direction_dp, "25");
+
+        config.tuya_local_key = "too-short".into();
+        assert!(TuyaRobot::from_config(&config).local.is_none());
+
+        config.tuya_local_key = LOCAL_KEY.into();
+        config.tuya_local_ip.clear();
+        let tool = TuyaRobot::from_config(&config);
+        assert!(tool.local.is_none());
+        assert!(!tool.is_configured());
+    }
+
+    // ── BodyStatus parsing ────────────────────────────────────────
+
+    #[test]
+    fn body_status_recognizes_charging_and_faults() {
+        let status = BodyStatus::from_dps(&[
+            json!({"code": "battery_percentage", "value": 80}),
+            json!({"code": "status", "value": "charging"}),
+            json!({"code": "power_go", "value": false}),
+            json!({"code": "fault", "value": ["edge_sweep", "cliff_sensor"]}),
+            json!({"code": "clean_area", "value": 12}),
+        ]);
+        assert_eq!(status.battery, Some(80));
+        assert_eq!(status.charging, Some(true));
+        assert_eq!(status.cleaning, Some(false));
+        assert_eq!(status.faults, vec!["edge_sweep", "cliff_sensor"]);
+        assert_eq!(status.other, vec!["clean_area=12"]);
+
+        let text = status.describe();
+        assert!(text.contains("Battery: 80% (resting on the dock)"));
+        assert!(text.contains("Errors: edge_sweep, cliff_sensor"));
+    }
+
+    #[test]
+    fn fault_bitmap_zero_means_no_errors() {
+        assert!(fault_names(&json!(0)).is_empty());
+        assert_eq!(fault_names(&json!(4)), vec!["fault code 4"]);
+        assert!(fault_names(&json!("")).is_empty());
+    }
+
+    #[test]
+    fn explicit_charge_state_wins_over_status() {
+        let status = BodyStatus::from_dps(&[
+            json!({"code": "charge_state", "value": true}),
+            json!({"code": "status", "value": "standby"}),
+        ]);
+        assert_eq!(status.charging, Some(true));
+    }
+}
@@ -0,0 +1,883 @@
+/// Tuya local LAN protocol (3.3 / 3.4 / 3.5) — drive the robot without the cloud.
+///
+/// Frames are big-endian, sent over TCP port 6668:
+///   3.3/3.4: 000055AA | seq | cmd | len | [retcode] payload | crc32 or hmac | 0000AA55
+///   3.5:     00006699 | 0000 | seq | cmd | len | iv(12) ciphertext tag(16) | 00009966
+///
+/// 3.3 encrypts payloads with AES-128-ECB under the device's local key and
+/// checks a CRC32. 3.4 and 3.5 first negotiate a per-connection session key
+/// (commands 3/4/5) and authenticate frames with HMAC-SHA256 (3.4) or
+/// AES-GCM (3.5). Device → client frames carry a 4-byte return code.
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes128;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes128Gcm, Nonce};
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+pub const PORT: u16 = 6668;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+
+const PREFIX_55AA: u32 = 0x0000_55AA;
+const SUFFIX_55AA: u32 = 0x0000_AA55;
+const PREFIX_6699: u32 = 0x0000_6699;
+const SUFFIX_6699: u32 = 0x0000_9966;
+
+// ── Command codes ─────────────────────────────────────────────────────────────
+
+pub const SESS_KEY_NEG_START: u32 = 3;
+pub const SESS_KEY_NEG_RESP: u32 = 4;
+pub const SESS_KEY_NEG_FINISH: u32 = 5;
+pub const CONTROL: u32 = 7;
+pub const STATUS: u32 = 8;
+pub const HEART_BEAT: u32 = 9;
+pub const DP_QUERY: u32 = 10;
+pub const CONTROL_NEW: u32 = 13;
+pub const DP_QUERY_NEW: u32 = 16;
+pub const UPDATEDPS: u32 = 18;
+
+/// Commands whose payload never carries the "3.x" version header.
+fn has_version_header(cmd: u32) -> bool {
+    !matches!(
+        cmd,
+        DP_QUERY
+            | DP_QUERY_NEW
+            | UPDATEDPS
+            | HEART_BEAT
+            | SESS_KEY_NEG_START
+            | SESS_KEY_NEG_RESP
+            | SESS_KEY_NEG_FINISH
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V33,
+    V34,
+    V35,
+}
+
+impl Version {
+    /// Parse a config value; empty means 3.3, the most common firmware.
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim() {
+            "" | "3.3" => Ok(Self::V33),
+            "3.4" => Ok(Self::V34),
+            "3.5" => Ok(Self::V35),
+            other => bail!("unsupported Tuya protocol version {other:?} (use 3.3, 3.4 or 3.5)"),
+        }
+    }
+
+    /// "3.x" followed by 12 zero bytes.
+    fn header(self) -> [u8; 15] {
+        let mut h = [0u8; 15];
+        h[..3].copy_from_slice(match self {
+            Self::V33 => b"3.3",
+            Self::V34 => b"3.4",
+            Self::V35 => b"3.5",
+        });
+        h
+    }
+
+    fn needs_session_key(self) -> bool {
+        self != Self::V33
+    }
+}
+
+/// A decoded frame. `payload` is the decrypted plaintext without version header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub seq: u32,
+    pub cmd: u32,
+    pub retcode: Option<u32>,
+    pub payload: Vec<u8>,
+}
+
+// ── Crypto primitives ─────────────────────────────────────────────────────────
+
+fn pkcs7_pad(data: &[u8]) -> Vec<u8> {
+    let n = 16 - data.len() % 16;
+    let mut out = data.to_vec();
+    out.resize(data.len() + n, n as u8);
+    out
+}
+
+fn pkcs7_unpad(data: &[u8]) -> Result<&[u8]> {
+    let n = *data.last().ok_or_else(|| anyhow!("empty ciphertext"))? as usize;
+    if n == 0 || n > 16 || n > data.len() || data[data.len() - n..].iter().any(|&b| b as usize != n)
+    {
+        bail!("bad padding — wrong local key?");
+    }
+    Ok(&data[..data.len() - n])
+}
+
+/// AES-128-ECB over whole blocks (caller pads).
+fn ecb_encrypt(key: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut out = data.to_vec();
+    for block in out.chunks_exact_mut(16) {
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+    }
+    out
+}
+
+fn ecb_decrypt(key: &[u8; 16], data: &[u8]) -> Result<Vec<u8>> {
+    if !data.chunks_exact(16).remainder().is_empty() {
+        bail!("ciphertext is not a whole number of blocks");
+    }
+    let cipher = Aes128::new(GenericArray::from_slice(key));
+    let mut out = data.to_vec();
+    for block in out.chunks_exact_mut(16) {
+        cipher.decrypt_block(GenericArray::from_mut_slice(block));
+    }
+    Ok(out)
+}
+
+/// AES-128-GCM; returns ciphertext followed by the 16-byte tag.
+fn gcm_encrypt(key: &[u8; 16], iv: &[u8; 12], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    Aes128Gcm::new(GenericArray::from_slice(key))
+        .encrypt(
+            Nonce::from_slice(iv),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .expect("AES-GCM encryption of an in-memory buffer cannot fail")
+}
+
+fn gcm_decrypt(key: &[u8; 16], iv: &[u8], aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    Aes128Gcm::new(GenericArray::from_slice(key))
+        .decrypt(Nonce::from_slice(iv), Payload { msg: sealed, aad })
+        .map_err(|_| anyhow!("GCM tag mismatch — wrong key or corrupted frame"))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    use hmac::{Hmac, Mac};
+    let mut mac =
+        <Hmac<sha2::Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key size");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Derive the 3.4/3.5 session key from both nonces.
+pub fn session_key(
+    version: Version,
+    local_key: &[u8; 16],
+    local_nonce: &[u8; 16],
+    remote_nonce: &[u8; 16],
+) -> [u8; 16] {
+    let mut mixed = [0u8; 16];
+    for (i, byte) in mixed.iter_mut().enumerate() {
+        *byte = local_nonce[i] ^ remote_nonce[i];
+    }
+    let sealed = match version {
+        Version::V35 => {
+            let iv: [u8; 12] = local_nonce[..12].try_into().expect("nonce has 16 bytes");
+            gcm_encrypt(local_key, &iv, &[], &mixed)
+        }
+        _ => ecb_encrypt(local_key, &mixed),
+    };
+    sealed[..16].try_into().expect("at least one block")
+}
+
+// ── Framing ───────────────────────────────────────────────────────────────────
+
+/// Encode a client → device frame. 3.5 frames get a random IV.
+pub fn encode(version: Version, key: &[u8; 16], seq: u32, cmd: u32, plaintext: &[u8]) -> Vec<u8> {
+    let iv: [u8; 12] = uuid::Uuid::new_v4().as_bytes()[..12]
+        .try_into()
+        .expect("uuid has 16 bytes");
+    encode_frame(version, key, seq, cmd, None, plaintext, iv)
+}
+
+/// Encode a frame; `retcode` is set on device → client frames.
+fn encode_frame(
+    version: Version,
+    key: &[u8; 16],
+    seq: u32,
+    cmd: u32,
+    retcode: Option<u32>,
+    plaintext: &[u8],
+    iv: [u8; 12],
+) -> Vec<u8> {
+    let with_header = has_version_header(cmd);
+    match version {
+        Version::V33 | Version::V34 => {
+            let mut body = retcode
+                .map(|r| r.to_be_bytes().to_vec())
+                .unwrap_or_default();
+            if version == Version::V33 {
+                // 3.3: version header sits outside the ciphertext.
+                if with_header {
+                    body.extend_from_slice(&version.header());
+                }
+                body.extend(ecb_encrypt(key, &pkcs7_pad(plaintext)));
+            } else {
+                let mut inner = Vec::new();
+                if with_header {
+                    inner.extend_from_slice(&version.header());
+                }
+                inner.extend_from_slice(plaintext);
+                body.extend(ecb_encrypt(key, &pkcs7_pad(&inner)));
+            }
+
+            let trailer_len = if version == Version::V33 { 4 } else { 32 };
+            let mut frame = Vec::with_capacity(16 + body.len() + trailer_len + 4);
+            frame.extend_from_slice(&PREFIX_55AA.to_be_bytes());
+            frame.extend_from_slice(&seq.to_be_bytes());
+            frame.extend_from_slice(&cmd.to_be_bytes());
+            frame.extend_from_slice(&((body.len() + trailer_len + 4) as u32).to_be_bytes());
+            frame.extend_from_slice(&body);
+            if version == Version::V33 {
+                frame.extend_from_slice(&crc32fast::hash(&frame).to_be_bytes());
+            } else {
+                let mac = hmac_sha256(key, &frame);
+                frame.extend_from_slice(&mac);
+            }
+            frame.extend_from_slice(&SUFFIX_55AA.to_be_bytes());
+            frame
+        }
+        Version::V35 => {
+            let mut inner = retcode
+                .map(|r| r.to_be_bytes().to_vec())
+                .unwrap_or_default();
+            if with_header {
+                inner.extend_from_slice(&version.header());
+            }
+            inner.extend_from_slice(plaintext);
+
+            let mut frame = Vec::with_capacity(18 + 12 + inner.len() + 16 + 4);
+            frame.extend_from_slice(&PREFIX_6699.to_be_bytes());
+            frame.extend_from_slice(&[0, 0]);
+            frame.extend_from_slice(&seq.to_be_bytes());
+            frame.extend_from_slice(&cmd.to_be_bytes());
+            frame.extend_from_slice(&((12 + inner.len() + 16) as u32).to_be_bytes());
+            let sealed = gcm_encrypt(key, &iv, &frame[4..18], &inner);
+            frame.extend_from_slice(&iv);
+            frame.extend_from_slice(&sealed);
+            frame.extend_from_slice(&SUFFIX_6699.to_be_bytes());
+            frame
+        }
+    }
+}
+
+/// Split a leading return code off a device payload. Return codes are small
+/// integers, so a first word with its top three bytes zero is taken as one.
+fn split_retcode(body: &[u8]) -> (Option<u32>, &[u8]) {
+    if body.len() >= 4 && body[..3] == [0, 0, 0] {
+        (
+            Some(u32::from_be_bytes(body[..4].try_into().unwrap())),
+            &body[4..],
+        )
+    } else {
+        (None, body)
+    }
+}
+
+fn strip_version_header(version: Version, data: &[u8]) -> &[u8] {
+    let header = version.header();
+    if data.len() >= 15 && data[..3] == header[..3] {
+        &data[15..]
+    } else {
+        data
+    }
+}
+
+/// Decode one frame from the front of `buf`.
+/// Returns `Ok(None)` while the frame is still incomplete, otherwise the frame
+/// and how many bytes it occupied.
+pub fn decode(version: Version, key: &[u8; 16], buf: &[u8]) -> Result<Option<(Frame, usize)>> {
+    if buf.len() < 4 {
+        return Ok(None);
+    }
+    let word = |at: usize| u32::from_be_bytes(buf[at..at + 4].try_into().unwrap());
+    let prefix = word(0);
+
+    match version {
+        Version::V33 | Version::V34 => {
+            if prefix != PREFIX_55AA {
+                bail!("bad frame prefix {prefix:08x}");
+            }
+            if buf.len() < 16 {
+                return Ok(None);
+            }
+            let (seq, cmd, len) = (word(4), word(8), word(12) as usize);
+            let total = 16 + len;
+            if buf.len() < total {
+                return Ok(None);
+            }
+            let trailer_len = if version == Version::V33 { 4 } else { 32 };
+            if len < trailer_len + 4 {
+                bail!("frame too short ({len} bytes)");
+            }
+            if word(total - 4) != SUFFIX_55AA {
+                bail!("bad frame suffix");
+            }
+            let signed_end = total - 4 - trailer_len;
+            let trailer = &buf[signed_end..total - 4];
+            if version == Version::V33 {
+                if crc32fast::hash(&buf[..signed_end]).to_be_bytes() != trailer {
+                    bail!("CRC mismatch");
+                }
+            } else if hmac_sha256(key, &buf[..signed_end]) != trailer {
+                bail!("HMAC mismatch — wrong key?");
+            }
+
+            let (retcode, body) = split_retcode(&buf[16..signed_end]);
+            let payload = if body.is_empty() {
+                Vec::new()
+            } else if version == Version::V33 {
+                let cipher = strip_version_header(version, body);
+                pkcs7_unpad(&ecb_decrypt(key, cipher)?)?.to_vec()
+            } else {
+                let plain = ecb_decrypt(key, body)?;
+                strip_version_header(version, pkcs7_unpad(&plain)?).to_vec()
+            };
+            Ok(Some((
+                Frame {
+                    seq,
+                    cmd,
+                    retcode,
+                    payload,
+                },
+                total,
+            )))
+        }
+        Version::V35 => {
+            if prefix != PREFIX_6699 {
+                bail!("bad frame prefix {prefix:08x}");
+            }
+            if buf.len() < 18 {
+                return Ok(None);
+            }
+            let (seq, cmd, len) = (word(6), word(10), word(14) as usize);
+            let total = 18 + len + 4;
+            if buf.len() < total {
+                return Ok(None);
+            }
+            if len < 28 {
+                bail!("frame too short ({len} bytes)");
+            }
+            if word(total - 4) != SUFFIX_6699 {
+                bail!("bad frame suffix");
+            }
+            let iv = &buf[18..30];
+            let plain = gcm_decrypt(key, iv, &buf[4..18], &buf[30..18 + len])?;
+            let (retcode, body) = split_retcode(&plain);
+            let payload = strip_version_header(version, body).to_vec();
+            Ok(Some((
+                Frame {
+                    seq,
+                    cmd,
+                    retcode,
+                    payload,
+                },
+                total,
+            )))
+        }
+    }
+}
+
+// ── Connection ────────────────────────────────────────────────────────────────
+
+/// One TCP connection with its (session) key and sequence counter.
+struct Session {
+    stream: TcpStream,
+    key: [u8; 16],
+    seq: u32,
+    buf: Vec<u8>,
+}
+
+impl Session {
+    async fn send(&mut self, version: Version, cmd: u32, plaintext: &[u8]) -> Result<u32> {
+        self.seq = self.seq.wrapping_add(1);
+        let frame = encode(version, &self.key, self.seq, cmd, plaintext);
+        self.stream.write_all(&frame).await?;
+        Ok(self.seq)
+    }
+
+    /// Read frames until one with `cmd` arrives (status pushes are skipped).
+    async fn expect(&mut self, version: Version, cmd: u32) -> Result<Frame> {
+        tokio::time::timeout(REPLY_TIMEOUT, async {
+            loop {
+                if let Some((frame, used)) = decode(version, &self.key, &self.buf)? {
+                    self.buf.drain(..used);
+                    if frame.cmd == cmd {
+                        return Ok(frame);
+                    }
+                    if frame.cmd == STATUS {
+                        tracing::debug!(
+                            "Tuya LAN status: {}",
+                            String::from_utf8_lossy(&frame.payload)
+                        );
+                    }
+                    continue;
+                }
+                let mut chunk = [0u8; 1024];
+                let n = self.stream.read(&mut chunk).await?;
+                if n == 0 {
+                    bail!("device closed the connection");
+                }
+                self.buf.extend_from_slice(&chunk[..n]);
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("no reply to command {cmd} from device"))?
+    }
+}
+
+/// A Tuya device reachable on the LAN.
+pub struct TuyaLocal {
+    addr: String,
+    device_id: String,
+    local_key: [u8; 16],
+    version: Version,
+    /// Kept open between commands so a `walk` costs one round-trip.
+    session: Mutex<Option<Session>>,
+}
+
+impl TuyaLocal {
+    pub fn new(ip: &str, device_id: &str, local_key: &str, version: &str) -> Result<Self> {
+        let local_key: [u8; 16] = local_key
+            .as_bytes()
+            .try_into()
+            .map_err(|_| anyhow!("Tuya local key must be 16 characters"))?;
+        let addr = if ip.contains(':') {
+            ip.to_string()
+        } else {
+            format!("{ip}:{PORT}")
+        };
+        Ok(Self {
+            addr,
+            device_id: device_id.to_string(),
+            local_key,
+            version: Version::parse(version)?,
+            session: Mutex::new(None),
+        })
+    }
+
+    /// Set data points, e.g. `{"4": "forward"}`.
+    pub async fn set_dps(&self, dps: Value) -> Result<()> {
+        let (cmd, payload) = self.control_payload(dps);
+        let mut session = self.session.lock().await;
+        // A kept-alive connection may have been dropped by the device; retry once fresh.
+        let mut retried = false;
+        loop {
+            if session.is_none() {
+                *session = Some(self.connect().await?);
+            }
+            let conn = session.as_mut().expect("session just opened");
+            let result = async {
+                conn.send(self.version, cmd, payload.as_bytes()).await?;
+                conn.expect(self.version, cmd).await
+            }
+            .await;
+            match result {
+                Ok(reply) => {
+                    return match reply.retcode {
+                        Some(code) if code != 0 => {
+                            bail!(
+                                "device rejected command (code {code}): {}",
+                                String::from_utf8_lossy(&reply.payload)
+                            )
+                        }
+                        _ => Ok(()),
+                    };
+                }
+                Err(e) => {
+                    *session = None;
+                    if retried {
+                        return Err(e);
+                    }
+                    retried = true;
+                    tracing::debug!("Tuya LAN: reconnecting after {e}");
+                }
+            }
+        }
+    }
+
+    fn control_payload(&self, dps: Value) -> (u32, String) {
+        let t = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        match self.version {
+            Version::V33 => (
+                CONTROL,
+                json!({
+                    "devId": self.device_id,
+                    "uid": self.device_id,
+                    "t": t.to_string(),
+                    "dps": dps
+                })
+                .to_string(),
+            ),
+            _ => (
+                CONTROL_NEW,
+                json!({"protocol": 5, "t": t, "data": {"dps": dps}}).to_string(),
+            ),
+        }
+    }
+
+    async fn connect(&self) -> Result<Session> {
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.addr))
+            .await
+            .map_err(|_| anyhow!("timed out connecting to {}", self.addr))??;
+        stream.set_nodelay(true)?;
+        let mut session = Session {
+            stream,
+            key: self.local_key,
+            seq: 0,
+            buf: Vec::new(),
+        };
+        if self.version.needs_session_key() {
+            session.key = self.negotiate(&mut session).await?;
+        }
+        Ok(session)
+    }
+
+    /// 3.4/3.5 handshake: exchange nonces under the local key, prove we hold
+    /// it, and derive the session key.
+    async fn negotiate(&self, session: &mut Session) -> Result<[u8; 16]> {
+        let local_nonce = *uuid::Uuid::new_v4().as_bytes();
+        session
+            .send(self.version, SESS_KEY_NEG_START, &local_nonce)
+            .await?;
+
+        let reply = session.expect(self.version, SESS_KEY_NEG_RESP).await?;
+        if reply.payload.len() < 48 {
+            bail!("short session key response ({} bytes)", reply.payload.len());
+        }
+        let remote_nonce: [u8; 16] = reply.payload[..16].try_into().unwrap();
+        if reply.payload[16..48] != hmac_sha256(&self.local_key, &local_nonce) {
+            bail!("device did not prove the local key — wrong local key?");
+        }
+
+        let proof = hmac_sha256(&self.local_key, &remote_nonce);
+        session
+            .send(self.version, SESS_KEY_NEG_FINISH, &proof)
+            .await?;
+        Ok(session_key(
+            self.version,
+            &self.local_key,
+            &local_nonce,
+            &remote_nonce,
+        ))
+    }
+}
+
+// ── Tests ─────────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const KEY: &[u8; 16] = b"0123456789abcdef";
+    const DEV: &str = "bf1234567890abcdefgh";
+    const IV: [u8; 12] = *b"0123456789ab";
+
+    fn unhex(s: &str) -> Vec<u8> {
+        hex::decode(s.split_whitespace().collect::<String>()).unwrap()
+    }
+
+    // ── Reference frames ──────────────────────────────────────────
+    //
+    // No frames captured from a real device were at hand, so these were
+    // built independently with Python's `cryptography` package from the
+    // frame layout above, for local key "0123456789abcdef", local nonce
+    // "LLLL…" and remote nonce "RRRR…". They check the byte layout against
+    // a second implementation, not against firmware; swap in captured
+    // 3.3/3.4 packets when available.
+
+    const CONTROL_DPS: &[u8] = br#"{"dps":{"4":"forward"}}"#;
+    const LOCAL_NONCE: [u8; 16] = [b'L'; 16];
+    const REMOTE_NONCE: [u8; 16] = [b'R'; 16];
+
+    const CONTROL_33: &str =
+        "000055aa 00000001 00000007 00000037 332e3300 00000000 00000000 000000\
+        3734333cb6f4c66d2e1fc7045288912ee47b30f25c5a89d0d63a270b0f24aef6 746d6121 0000aa55";
+    const STATUS_33: &str =
+        "000055aa 00000005 00000008 0000003b 00000000 332e3300 00000000 00000000 000000\
+        3734333cb6f4c66d2e1fc7045288912ee47b30f25c5a89d0d63a270b0f24aef6 bc761649 0000aa55";
+    const SESSION_KEY_34: &str = "bc6a855faf3e089afe8721f5925ffef2";
+    const SESSION_KEY_35: &str = "86fce4ea964f68132ec71b85283981d4";
+    const CONTROL_34: &str = "000055aa 00000002 0000000d 00000054\
+        19e9a922443e8151612bc5d14ac50e86e27a76f920aaa9cf323d6227fc453916be5668e9e90736403f0e8a34dfd41a52\
+        62da363007a52fbcd68f5648ff9e38c9c2b444cc1c03092201459066309eb791 0000aa55";
+    const CONTROL_35: &str = "00006699 0000 00000002 0000000d 00000042 303132333435363738396162\
+        515c670ec0127219c2abbdbf418f8835f5bd9b16f89ee26ca8d1934655fc1a242f8de09b4034d764c04c0386fbc3f2e9\
+        8c93243c6a67 00009966";
+    const ACK_35: &str = "00006699 0000 00000002 0000000d 00000020 626139383736353433323130\
+        c227c32c 5471fe3e6c0caff1482be329 6d7d4d53 00009966";
+
+    fn key(hex: &str) -> [u8; 16] {
+        unhex(hex).try_into().unwrap()
+    }
+
+    #[test]
+    fn encodes_33_control_frame() {
+        let frame = encode_frame(Version::V33, KEY, 1, CONTROL, None, CONTROL_DPS, IV);
+        assert_eq!(frame, unhex(CONTROL_33));
+    }
+
+    #[test]
+    fn decodes_33_status_push_with_retcode() {
+        let (frame, used) = decode(Version::V33, KEY, &unhex(STATUS_33))
+            .unwrap()
+            .unwrap();
+        assert_eq!(used, unhex(STATUS_33).len());
+        assert_eq!(frame.seq, 5);
+        assert_eq!(frame.cmd, STATUS);
+        assert_eq!(frame.retcode, Some(0));
+        assert_eq!(frame.payload, CONTROL_DPS);
+    }
+
+    #[test]
+    fn derives_session_keys() {
+        assert_eq!(
+            session_key(Version::V34, KEY, &LOCAL_NONCE, &REMOTE_NONCE),
+            key(SESSION_KEY_34)
+        );
+        assert_eq!(
+            session_key(Version::V35, KEY, &LOCAL_NONCE, &REMOTE_NONCE),
+            key(SESSION_KEY_35)
+        );
+    }
+
+    #[test]
+    fn encodes_34_control_frame() {
+        let sk = key(SESSION_KEY_34);
+        let frame = encode_frame(Version::V34, &sk, 2, CONTROL_NEW, None, CONTROL_DPS, IV);
+        assert_eq!(frame, unhex(CONTROL_34));
+        let (decoded, _) = decode(Version::V34, &sk, &frame).unwrap().unwrap();
+        assert_eq!(decoded.payload, CONTROL_DPS);
+        assert_eq!(decoded.retcode, None);
+    }
+
+    #[test]
+    fn encodes_35_control_frame() {
+        let sk = key(SESSION_KEY_35);
+        let frame = encode_frame(Version::V35, &sk, 2, CONTROL_NEW, None, CONTROL_DPS, IV);
+        assert_eq!(frame, unhex(CONTROL_35));
+    }
+
+    #[test]
+    fn decodes_35_ack() {
+        let (frame, _) = decode(Version::V35, &key(SESSION_KEY_35), &unhex(ACK_35))
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame.seq, 2);
+        assert_eq!(frame.cmd, CONTROL_NEW);
+        assert_eq!(frame.retcode, Some(0));
+        assert!(frame.payload.is_empty());
+    }
+
+    #[test]
+    fn partial_frames_wait_for_more_bytes() {
+        for (version, k, hex) in [
+            (Version::V33, *KEY, STATUS_33),
+            (Version::V35, key(SESSION_KEY_35), ACK_35),
+        ] {
+            let bytes = unhex(hex);
+            for cut in [0, 3, 10, bytes.len() - 1] {
+                assert!(
+                    decode(version, &k, &bytes[..cut]).unwrap().is_none(),
+                    "cut at {cut}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn frames_are_split_from_a_stream() {
+        let mut stream = unhex(STATUS_33);
+        stream.extend(unhex(STATUS_33));
+        let (_, used) = decode(Version::V33, KEY, &stream).unwrap().unwrap();
+        let (second, _) = decode(Version::V33, KEY, &stream[used..]).unwrap().unwrap();
+        assert_eq!(second.payload, CONTROL_DPS);
+    }
+
+    #[test]
+    fn tampered_frames_are_rejected() {
+        let mut crc = unhex(STATUS_33);
+        crc[40] ^= 1;
+        assert!(decode(Version::V33, KEY, &crc)
+            .unwrap_err()
+            .to_string()
+            .contains("CRC"));
+
+        let mut mac = unhex(CONTROL_34);
+        mac[20] ^= 1;
+        let err = decode(Version::V34, &key(SESSION_KEY_34), &mac).unwrap_err();
+        assert!(err.to_string().contains("HMAC"));
+
+        let mut tag = unhex(ACK_35);
+        tag[32] ^= 1;
+        let err = decode(Version::V35, &key(SESSION_KEY_35), &tag).unwrap_err();
+        assert!(err.to_string().contains("GCM"));
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let other = *b"fedcba9876543210";
+        assert!(decode(Version::V34, &other, &unhex(CONTROL_34)).is_err());
+        assert!(decode(Version::V35, &other, &unhex(CONTROL_35)).is_err());
+    }
+
+    #[test]
+    fn version_parsing() {
+        assert_eq!(Version::parse("").unwrap(), Version::V33);
+        assert_eq!(Version::parse("3.4").unwrap(), Version::V34);
+        assert_eq!(Version::parse(" 3.5 ").unwrap(), Version::V35);
+        assert!(Version::parse("3.1").is_err());
+    }
+
+    #[test]
+    fn local_key_must_be_16_bytes() {
+        assert!(TuyaLocal::new("192.168.1.20", DEV, "short", "3.3").is_err());
+        let local = TuyaLocal::new("192.168.1.20", DEV, "0123456789abcdef", "3.3").unwrap();
+        assert_eq!(local.addr, "192.168.1.20:6668");
+    }
+
+    // ── Fake device ───────────────────────────────────────────────
+
+    /// Accept one connection, play the device side of the handshake (3.4/3.5)
+    /// and answer one control command with `retcode`. Returns the dps it got.
+    async fn fake_device(listener: TcpListener, version: Version, retcode: u32) -> Value {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = Vec::new();
+        let mut key = *KEY;
+
+        async fn next(
+            stream: &mut TcpStream,
+            buf: &mut Vec<u8>,
+            v: Version,
+            k: &[u8; 16],
+        ) -> Frame {
+            loop {
+                if let Some((frame, used)) = decode(v, k, buf).unwrap() {
+                    buf.drain(..used);
+                    return frame;
+                }
+                let mut chunk = [0u8; 512];
+                let n = stream.read(&mut chunk).await.unwrap();
+                assert!(n > 0, "client hung up");
+                buf.extend_from_slice(&chunk[..n]);
+            }
+        }
+
+        if version.needs_session_key() {
+            let start = next(&mut stream, &mut buf, version, &key).await;
+            assert_eq!(start.cmd, SESS_KEY_NEG_START);
+            let local_nonce: [u8; 16] = start.payload[..].try_into().unwrap();
+            let mut resp = REMOTE_NONCE.to_vec();
+            resp.extend(hmac_sha256(KEY, &local_nonce));
+            let frame = encode_frame(version, KEY, 1, SESS_KEY_NEG_RESP, Some(0), &resp, IV);
+            stream.write_all(&frame).await.unwrap();
+
+            let finish = next(&mut stream, &mut buf, version, &key).await;
+            assert_eq!(finish.cmd, SESS_KEY_NEG_FINISH);
+            assert_eq!(finish.payload, hmac_sha256(KEY, &REMOTE_NONCE));
+            key = session_key(version, KEY, &local_nonce, &REMOTE_NONCE);
+        }
+
+        // An unsolicited status push first: the client must skip it.
+        let push = encode_frame(version, &key, 9, STATUS, Some(0), CONTROL_DPS, IV);
+        stream.write_all(&push).await.unwrap();
+
+        let control = next(&mut stream, &mut buf, version, &key).await;
+        let body: Value = serde_json::from_slice(&control.payload).unwrap();
+        let ack = encode_frame(
+            version,
+            &key,
+            control.seq,
+            control.cmd,
+            Some(retcode),
+            b"",
+            IV,
+        );
+        stream.write_all(&ack).await.unwrap();
+
+        if version == Version::V33 {
+            assert_eq!(control.cmd, CONTROL);
+            assert_eq!(body["devId"], DEV);
+            body["dps"].clone()
+        } else {
+            assert_eq!(control.cmd, CONTROL_NEW);
+            assert_eq!(body["protocol"], 5);
+            body["data"]["dps"].clone()
+        }
+    }
+
+    async fn control_against_fake_device(version: &str) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let device = tokio::spawn(fake_device(listener, Version::parse(version).unwrap(), 0));
+
+        let local = TuyaLocal::new(&addr, DEV, "0123456789abcdef", version).unwrap();
+        local.set_dps(json!({"4": "forward"})).await.unwrap();
+        assert_eq!(device.await.unwrap(), json!({"4": "forward"}));
+    }
+
+    #[tokio::test]
+    async fn controls_a_33_device() {
+        control_against_fake_device("3.3").await;
+    }
+
+    #[tokio::test]
+    async fn controls_a_34_device() {
+        control_against_fake_device("3.4").await;
+    }
+
+    #[tokio::test]
+    async fn controls_a_35_device() {
+        control_against_fake_device("3.5").await;
+    }
+
+    #[tokio::test]
+    async fn device_rejection_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(fake_device(listener, Version::V33, 1));
+
+        let local = TuyaLocal::new(&addr, DEV, "0123456789abcdef", "3.3").unwrap();
+        let err = local.set_dps(json!({"4": "stop"})).await.unwrap_err();
+        assert!(err.to_string().contains("code 1"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn unreachable_device_is_an_error() {
+        // Bind then drop to get a port nobody listens on.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let local = TuyaLocal::new(&addr.to_string(), DEV, "0123456789abcdef", "3.3").unwrap();
+        assert!(local.set_dps(json!({"4": "stop"})).await.is_err());
+    }
+
+    #[test]
+    fn pkcs7_round_trip() {
+        for len in 0..40 {
+            let data = vec![7u8; len];
+            let padded = pkcs7_pad(&data);
+            assert_eq!(padded.len() % 16, 0);
+            assert!(padded.len() > len);
+            assert_eq!(pkcs7_unpad(&padded).unwrap(), &data[..]);
+        }
+    }
+
+    #[test]
+    fn bad_padding_is_rejected() {
+        assert!(pkcs7_unpad(&[1, 2, 3, 0]).is_err());
+        assert!(pkcs7_unpad(&[5, 5, 4]).is_err());
+    }
+}
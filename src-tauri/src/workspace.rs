@@ -0,0 +1,231 @@
+/// Workspace confinement for the file tools.
+///
+/// Every path is canonicalized first (`..` and symlinks resolved), then it
+/// must fall under an allowed root: `work_dir` plus `coding.allowed_roots`.
+/// Protected paths can never be written, whatever the trust mode: the
+/// familiar's config dir, `~/.familiar_ai`, any `.git/` directory, and
+/// `coding.protected_paths`. The config dir holds API keys, so it can't be
+/// read either.
+use std::path::{Path, PathBuf};
+
+use crate::config::CodingConfig;
+use crate::permissions::canonical_path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    work_dir: String,
+    roots: Vec<PathBuf>,
+    protected: Vec<PathBuf>,
+    secret: Vec<PathBuf>,
+}
+
+impl Workspace {
+    /// `roots` and `protected` may be relative to `work_dir` or start with `~/`.
+    pub fn new(work_dir: &str, roots: &[String], protected: &[String]) -> Self {
+        let resolve = |raw: &String| canonical_path(&expand_home(raw), work_dir);
+        let mut all_roots = vec![canonical_path(".", work_dir)];
+        all_roots.extend(roots.iter().map(resolve));
+
+        let config_dir = crate::config::app_config_dir();
+        let mut all_protected = vec![
+            canonical_path(&config_dir.to_string_lossy(), work_dir),
+            canonical_path(&expand_home("~/.familiar_ai"), work_dir),
+        ];
+        all_protected.extend(protected.iter().map(resolve));
+
+        Self {
+            work_dir: work_dir.to_string(),
+            roots: all_roots,
+            protected: all_protected,
+            secret: vec![canonical_path(&config_dir.to_string_lossy(), work_dir)],
+        }
+    }
+
+    pub fn from_config(coding: &CodingConfig) -> Self {
+        Self::new(
+            &coding.effective_work_dir(),
+            &coding.allowed_roots,
+            &coding.protected_paths,
+        )
+    }
+
+    pub fn work_dir(&self) -> &str {
+        &self.work_dir
+    }
+
+    /// Paths that may be read but not written, canonical.
+    pub fn protected(&self) -> &[PathBuf] {
+        &self.protected
+    }
+
+    /// The canonical path for `raw`, or why it may not be accessed.
+    pub fn resolve(&self, raw: &str, access: Access) -> Result<PathBuf, String> {
+        let path = canonical_path(raw, &self.work_dir);
+        self.check(&path, access).map_err(|reason| {
+            let lexical = lexical_path(raw, &self.work_dir);
+            if !self.within_roots(&path) && self.within_roots(&lexical) {
+                format!(
+                    "{raw} escapes the workspace through a symlink (to {})",
+                    path.display()
+                )
+            } else {
+                format!("{raw} {reason}")
+            }
+        })?;
+        Ok(path)
+    }
+
+    /// Checks an already-canonical path, e.g. one found by walking a directory.
+    pub fn check(&self, path: &Path, access: Access) -> Result<(), String> {
+        if !self.within_roots(path) {
+            return Err(format!(
+                "is outside the workspace ({})",
+                self.roots
+                    .iter()
+                    .map(|r| r.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        if self.secret.iter().any(|s| path.starts_with(s)) {
+            return Err("is protected (the familiar's config holds API keys)".to_string());
+        }
+        if access == Access::Write {
+            if let Some(p) = self.protected.iter().find(|p| path.starts_with(p)) {
+                return Err(format!("is protected ({})", p.display()));
+            }
+            if path.components().any(|c| c.as_os_str() == ".git") {
+                return Err("is protected (.git)".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn within_roots(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+fn expand_home(raw: &str) -> String {
+    match raw.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .unwrap_or_default()
+            .join(rest)
+            .to_string_lossy()
+            .into_owned(),
+        None => raw.to_string(),
+    }
+}
+
+/// `raw` joined to `work_dir` with `.`/`..` folded but symlinks left alone,
+/// to tell a symlink escape from a plain out-of-tree path.
+fn lexical_path(raw: &str, work_dir: &str) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in Path::new(work_dir).join(raw).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// ── Tests ─────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> (Workspace, tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("proj/src")).unwrap();
+        std::fs::create_dir_all(root.join("proj/.git")).unwrap();
+        std::fs::create_dir_all(root.join("shared")).unwrap();
+        let wd = root.join("proj");
+        let ws = Workspace::new(
+            wd.to_str().unwrap(),
+            &["../shared".to_string()],
+            &["secrets".to_string()],
+        );
+        (ws, dir, root)
+    }
+
+    #[test]
+    fn paths_inside_roots_resolve_canonically() {
+        let (ws, _dir, root) = workspace();
+        assert_eq!(
+            ws.resolve("src/./../src/main.rs", Access::Write),
+            Ok(root.join("proj/src/main.rs"))
+        );
+        let shared = root.join("shared/notes.md");
+        assert_eq!(
+            ws.resolve(shared.to_str().unwrap(), Access::Write),
+            Ok(shared.clone())
+        );
+        assert_eq!(ws.resolve("../shared/notes.md", Access::Read), Ok(shared));
+    }
+
+    #[test]
+    fn traversal_and_absolute_paths_outside_are_denied() {
+        let (ws, _dir, _root) = workspace();
+        for raw in ["../../etc/passwd", "/etc/passwd", "src/../../outside.txt"] {
+            let err = ws.resolve(raw, Access::Read).unwrap_err();
+            assert!(err.contains("outside the workspace"), "{raw}: {err}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_escapes_are_denied() {
+        let (ws, _dir, root) = workspace();
+        std::fs::create_dir_all(root.join("elsewhere")).unwrap();
+        std::os::unix::fs::symlink(root.join("elsewhere"), root.join("proj/link")).unwrap();
+        let err = ws.resolve("link/key", Access::Write).unwrap_err();
+        assert!(err.contains("through a symlink"), "{err}");
+        let err = ws.resolve("link", Access::Read).unwrap_err();
+        assert!(err.contains("through a symlink"), "{err}");
+    }
+
+    #[test]
+    fn protected_paths_are_read_only() {
+        let (ws, _dir, root) = workspace();
+        for raw in [".git/config", "src/.git/hooks/pre-commit", "secrets/token"] {
+            let err = ws.resolve(raw, Access::Write).unwrap_err();
+            assert!(err.contains("protected"), "{raw}: {err}");
+        }
+        assert_eq!(
+            ws.resolve(".git/HEAD", Access::Read),
+            Ok(root.join("proj/.git/HEAD"))
+        );
+    }
+
+    #[test]
+    fn familiar_dirs_are_protected_even_inside_roots() {
+        let home = dirs::home_dir().unwrap();
+        let ws = Workspace::new(home.to_str().unwrap(), &[], &[]);
+        let me = home.join(".familiar_ai/ME.md");
+        let err = ws.resolve(me.to_str().unwrap(), Access::Write).unwrap_err();
+        assert!(err.contains("protected"), "{err}");
+
+        let config = crate::config::app_config_dir().join("config.toml");
+        let ws = Workspace::new(
+            home.to_str().unwrap(),
+            &[config.parent().unwrap().to_string_lossy().into_owned()],
+            &[],
+        );
+        for access in [Access::Read, Access::Write] {
+            let err = ws.resolve(config.to_str().unwrap(), access).unwrap_err();
+            assert!(err.contains("protected"), "{err}");
+        }
+    }
+}
@@ -7,7 +7,16 @@ import { ChatView } from "./ChatView";
 
 type AgentEventPayload =
   | { type: "text"; chunk: string }
-  | { type: "action"; name: string; label: string }
+  | { type: "action"; id: string; name: string; label: string }
+  | { type: "tool_progress"; id: string; stream: "stdout" | "stderr"; line: string }
+  | {
+      type: "tool_result";
+      id: string;
+      name: string;
+      text: string;
+      image: string | null;
+      diff: string | null;
+    }
   | { type: "done" }
   | { type: "cancelled" }
   | { type: "error"; message: string };
@@ -77,6 +86,51 @@ describe("Stop button", () => {
   });
 });
 
+// ── Emergency stop ─────────────────────────────────────────────────
+
+describe("Emergency stop", () => {
+  it("engages and releases the emergency stop", async () => {
+    mockInvoke
+      .mockResolvedValueOnce({ agent_name: "TestAI" }) // get_config
+      .mockResolvedValueOnce(false) // emergency_stop_engaged
+      .mockResolvedValue(undefined);
+
+    const user = userEvent.setup();
+    render(<ChatView onReset={() => {}} />);
+
+    await user.click(await screen.findByRole("button", { name: /E-Stop/ }));
+    expect(mockInvoke).toHaveBeenCalledWith("emergency_stop");
+
+    await user.click(await screen.findByRole("button", { name: /解除/ }));
+    expect(mockInvoke).toHaveBeenCalledWith("release_emergency_stop");
+    expect(screen.getByRole("button", { name: /E-Stop/ })).toBeInTheDocument();
+  });
+
+  it("shows an error when the robot could not be stopped", async () => {
+    mockInvoke
+      .mockResolvedValueOnce({ agent_name: "TestAI" }) // get_config
+      .mockResolvedValueOnce(false) // emergency_stop_engaged
+      .mockRejectedValueOnce("Could not stop the robot: timed out") // emergency_stop
+      .mockResolvedValue(undefined);
+
+    const user = userEvent.setup();
+    render(<ChatView onReset={() => {}} />);
+
+    await user.click(await screen.findByRole("button", { name: /E-Stop/ }));
+    expect(await screen.findByRole("alert")).toHaveTextContent("Could not stop the robot");
+  });
+
+  it("shows the release button when already engaged", async () => {
+    mockInvoke
+      .mockResolvedValueOnce({ agent_name: "TestAI" })
+      .mockResolvedValueOnce(true);
+
+    render(<ChatView onReset={() => {}} />);
+
+    expect(await screen.findByRole("button", { name: /解除/ })).toBeInTheDocument();
+  });
+});
+
 // ── Copy button ────────────────────────────────────────────────────
 
 describe("Copy button", () => {
@@ -176,3 +230,51 @@ describe("Typing indicator", () => {
     });
   });
 });
+
+// ── Tool runs ──────────────────────────────────────────────────────
+
+describe("Tool runs", () => {
+  it("streams output lines and then shows the result", async () => {
+    render(<ChatView onReset={() => {}} />);
+    await waitFor(() => expect(fireAgentEvent).not.toBeNull());
+
+    act(() => {
+      fireAgentEvent!({ type: "action", id: "c1", name: "bash", label: "$ cargo test" });
+      fireAgentEvent!({ type: "tool_progress", id: "c1", stream: "stdout", line: "running 3 tests" });
+      fireAgentEvent!({ type: "tool_progress", id: "c1", stream: "stderr", line: "warning: unused" });
+    });
+    expect(screen.getByText("running 3 tests")).toBeInTheDocument();
+    expect(screen.getByText("warning: unused")).toHaveClass("tool-stderr");
+    expect(document.querySelector(".tool-run")).toHaveAttribute("open");
+
+    act(() => {
+      fireAgentEvent!({
+        type: "tool_result",
+        id: "c1",
+        name: "bash",
+        text: "Exit: 0",
+        image: null,
+        diff: null,
+      });
+    });
+    expect(document.querySelector(".tool-run")).not.toHaveAttribute("open");
+  });
+
+  it("shows the diff of a file edit", async () => {
+    render(<ChatView onReset={() => {}} />);
+    await waitFor(() => expect(fireAgentEvent).not.toBeNull());
+
+    act(() => {
+      fireAgentEvent!({ type: "action", id: "c2", name: "edit_file", label: "✏️ a.rs" });
+      fireAgentEvent!({
+        type: "tool_result",
+        id: "c2",
+        name: "edit_file",
+        text: "Edited a.rs",
+        image: null,
+        diff: "--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-old\n+new\n",
+      });
+    });
+    expect(document.querySelector(".tool-run")?.textContent).toContain("+new");
+  });
+});
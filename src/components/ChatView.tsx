@@ -9,17 +9,35 @@ interface Props {
   onReset: () => void;
 }
 
+/// One tool call: its live output while running, then its result.
+interface ToolRun {
+  id: string;
+  name: string;
+  label: string;
+  output: { stream: "stdout" | "stderr"; line: string }[];
+  result?: { text: string; image: string | null; diff: string | null };
+}
+
 interface Message {
   id: number;
   role: "user" | "assistant";
   text: string;
-  actions: string[];
+  actions: ToolRun[];
   done: boolean;
 }
 
 type AgentEvent =
   | { type: "text"; chunk: string }
-  | { type: "action"; name: string; label: string }
+  | { type: "action"; id: string; name: string; label: string }
+  | { type: "tool_progress"; id: string; stream: "stdout" | "stderr"; line: string }
+  | {
+      type: "tool_result";
+      id: string;
+      name: string;
+      text: string;
+      image: string | null;
+      diff: string | null;
+    }
   | { type: "perm_request"; id: string; tool: string; detail: string }
   | { type: "done" }
   | { type: "cancelled" }
@@ -33,6 +51,47 @@ interface PendingPerm {
 
 let nextId = 1;
 
+// Live output lines kept per tool call; the full text arrives with the result.
+const MAX_OUTPUT_LINES = 500;
+
+/// A tool call: label, then live output, the captured frame or the diff.
+function ToolRunView({ run }: { run: ToolRun }) {
+  const running = !run.result;
+  const hasDetail =
+    run.output.length > 0 || run.result?.image || run.result?.diff || run.result?.text;
+  if (!hasDetail) {
+    return <span className="action-tag">{run.label}</span>;
+  }
+  return (
+    <details className="tool-run" open={running || undefined}>
+      <summary className="action-tag">
+        {run.label}
+        {running && " …"}
+      </summary>
+      {run.output.length > 0 ? (
+        <pre className="tool-output">
+          {run.output.map((l, i) => (
+            <div key={i} className={l.stream === "stderr" ? "tool-stderr" : undefined}>
+              {l.line}
+            </div>
+          ))}
+        </pre>
+      ) : (
+        run.result?.text &&
+        !run.result.diff && <pre className="tool-output">{run.result.text}</pre>
+      )}
+      {run.result?.diff && <CodeBlock code={run.result.diff} language="diff" />}
+      {run.result?.image && (
+        <img
+          className="tool-image"
+          src={`data:image/jpeg;base64,${run.result.image}`}
+          alt={run.label}
+        />
+      )}
+    </details>
+  );
+}
+
 /// Parse text with ```lang\n...\n``` code blocks and render with syntax highlighting.
 function MessageContent({ text }: { text: string }) {
   if (!text) return null;
@@ -60,6 +119,9 @@ export function ChatView({ onReset: _onReset }: Props) {
   const [copiedId, setCopiedId] = useState<number | null>(null);
   const [pendingPerm, setPendingPerm] = useState<PendingPerm | null>(null);
   const [showSettings, setShowSettings] = useState(false);
+  // Emergency stop: halts the robot and locks all body actuators until released
+  const [estop, setEstop] = useState(false);
+  const [estopError, setEstopError] = useState<string | null>(null);
   // Send history: ↑ key restores previous messages
   const [sendHistory, setSendHistory] = useState<string[]>([]);
   const [historyIndex, setHistoryIndex] = useState<number>(-1);
@@ -69,6 +131,9 @@ export function ChatView({ onReset: _onReset }: Props) {
     invoke<{ agent_name: string }>("get_config").then((c) =>
       setAgentName(c.agent_name)
     );
+    invoke<boolean>("emergency_stop_engaged").then((engaged) =>
+      setEstop(engaged === true)
+    );
   }, []);
 
   useEffect(() => {
@@ -93,7 +158,28 @@ export function ChatView({ onReset: _onReset }: Props) {
             return { ...msg, text: msg.text + ev.chunk };
           }
           if (ev.type === "action") {
-            return { ...msg, actions: [...msg.actions, ev.label] };
+            const run: ToolRun = { id: ev.id, name: ev.name, label: ev.label, output: [] };
+            return { ...msg, actions: [...msg.actions, run] };
+          }
+          if (ev.type === "tool_progress") {
+            const actions = msg.actions.map((run) =>
+              run.id === ev.id
+                ? {
+                    ...run,
+                    output: [...run.output, { stream: ev.stream, line: ev.line }].slice(
+                      -MAX_OUTPUT_LINES
+                    ),
+                  }
+                : run
+            );
+            return { ...msg, actions };
+          }
+          if (ev.type === "tool_result") {
+            const result = { text: ev.text, image: ev.image, diff: ev.diff };
+            const actions = msg.actions.map((run) =>
+              run.id === ev.id ? { ...run, result } : run
+            );
+            return { ...msg, actions };
           }
           if (ev.type === "done" || ev.type === "error" || ev.type === "cancelled") {
             if (ev.type === "error") {
@@ -192,6 +278,22 @@ export function ChatView({ onReset: _onReset }: Props) {
     await invoke("cancel_message");
   }
 
+  async function toggleEmergencyStop() {
+    if (estop) {
+      await invoke("release_emergency_stop");
+      setEstop(false);
+      setEstopError(null);
+    } else {
+      setEstop(true);
+      try {
+        await invoke("emergency_stop");
+        setEstopError(null);
+      } catch (e) {
+        setEstopError(String(e));
+      }
+    }
+  }
+
   async function copyMessage(msg: Message) {
     await navigator.clipboard.writeText(msg.text);
     setCopiedId(msg.id);
@@ -224,6 +326,18 @@ export function ChatView({ onReset: _onReset }: Props) {
       <header className="chat-header">
         <span className="agent-name">🐾 {agentName}</span>
         <div className="header-actions">
+          <button
+            className={estop ? "btn-estop engaged" : "btn-estop"}
+            onClick={toggleEmergencyStop}
+            title={estop ? "緊急停止を解除" : "緊急停止（体の動きをすべて止める）"}
+          >
+            {estop ? "🔓 解除" : "🛑 E-Stop"}
+          </button>
+          {estopError && (
+            <span className="error" role="alert">
+              {estopError}
+            </span>
+          )}
           <button className="icon-btn" onClick={clearHistory} title="会話をクリア">
             🗑
           </button>
@@ -253,8 +367,8 @@ export function ChatView({ onReset: _onReset }: Props) {
               <div className="bubble">
                 {msg.actions.length > 0 && (
                   <div className="actions">
-                    {msg.actions.map((a, i) => (
-                      <span key={i} className="action-tag">{a}</span>
+                    {msg.actions.map((run) => (
+                      <ToolRunView key={run.id} run={run} />
                     ))}
                   </div>
                 )}
@@ -8,8 +8,8 @@ interface Props {
 }
 
 export function PermDialog({ id, tool, detail, onRespond }: Props) {
-  async function respond(allowed: boolean) {
-    await invoke("respond_permission", { id, allowed });
+  async function respond(allowed: boolean, scope: "once" | "session" | "always" = "once") {
+    await invoke("respond_permission", { id, allowed, scope });
     onRespond();
   }
 
@@ -29,6 +29,20 @@ export function PermDialog({ id, tool, detail, onRespond }: Props) {
         <button className="btn-allow" onClick={() => respond(true)}>
           許可
         </button>
+        <button
+          className="btn-allow-scope"
+          title="このセッション中は同じ操作を確認しない"
+          onClick={() => respond(true, "session")}
+        >
+          今回のセッション中は許可
+        </button>
+        <button
+          className="btn-allow-scope"
+          title="ルールとして設定に保存"
+          onClick={() => respond(true, "always")}
+        >
+          常に許可
+        </button>
       </div>
     </div>
   );
@@ -1,5 +1,6 @@
 import { useEffect, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { CodeBlock } from "./CodeBlock";
 
 interface Config {
   platform: string;
@@ -8,14 +9,53 @@ interface Config {
   agent_name: string;
   companion_name: string;
   camera: { host: string; username: string; password: string; onvif_port: number };
-  tts: { elevenlabs_api_key: string; voice_id: string };
+  tts: {
+    elevenlabs_api_key: string;
+    voice_id: string;
+    engine: string;
+    openai_api_key: string;
+    voicevox_url: string;
+    piper_model: string;
+    [key: string]: unknown;
+  };
   mobility: {
+    robot: string;
     tuya_region: string;
     tuya_api_key: string;
     tuya_api_secret: string;
     tuya_device_id: string;
+    tuya_local_ip: string;
+    tuya_local_key: string;
+    tuya_protocol_version: string;
+    tuya_direction_dp: string;
+    http_url: string;
+    http_token: string;
+    sim_map: string;
+    [key: string]: unknown;
   };
-  coding: { work_dir: string; trust_mode: string; rules: unknown[] };
+  coding: { work_dir: string; trust_mode: string; rules: PermRule[]; [key: string]: unknown };
+  // Sections this panel doesn't edit are passed through untouched on save.
+  [key: string]: unknown;
+}
+
+interface PermRule {
+  allow: boolean;
+  tool: string;
+  pattern: string;
+  sandbox?: "off" | "on" | "offline";
+}
+
+/** A remembered permission answer: this session only, or saved as a rule. */
+interface Grant extends PermRule {
+  scope: "session" | "always";
+}
+
+/** Files as they were before a turn changed them. */
+interface Checkpoint {
+  id: string;
+  prompt: string;
+  started: string;
+  files: { path: string; existed: boolean }[];
 }
 
 type Tab = "llm" | "persona" | "voice" | "camera" | "coding" | "robot";
@@ -27,6 +67,19 @@ const PLATFORMS = [
   { id: "openai", label: "GPT (OpenAI)", sub: "定番" },
 ];
 
+const TTS_ENGINES = [
+  { id: "elevenlabs", label: "ElevenLabs", sub: "クラウド・高品質" },
+  { id: "openai", label: "OpenAI TTS", sub: "/audio/speech 互換" },
+  { id: "voicevox", label: "VOICEVOX", sub: "ローカル・日本語向け" },
+  { id: "piper", label: "Piper", sub: "ローカル・オフライン" },
+];
+
+const ROBOTS = [
+  { id: "tuya", label: "Tuya", sub: "ロボット掃除機（クラウド / LAN）" },
+  { id: "http", label: "HTTPロボット", sub: "JSON APIで動くロボット" },
+  { id: "sim", label: "シミュレーター", sub: "間取り図の上を動く仮想ロボット" },
+];
+
 const TABS: { id: Tab; icon: string; label: string }[] = [
   { id: "llm", icon: "🤖", label: "AIモデル" },
   { id: "persona", icon: "🐾", label: "性格・設定" },
@@ -54,8 +107,12 @@ export function SettingsPanel({ onClose }: Props) {
   const [meMd, setMeMd] = useState("");
 
   // Voice
+  const [ttsEngine, setTtsEngine] = useState("elevenlabs");
   const [elevenlabsKey, setElevenlabsKey] = useState("");
   const [voiceId, setVoiceId] = useState("cgSgspJ2msm6clMCkdW9");
+  const [openaiTtsKey, setOpenaiTtsKey] = useState("");
+  const [voicevoxUrl, setVoicevoxUrl] = useState("");
+  const [piperModel, setPiperModel] = useState("");
 
   // Camera
   const [cameraHost, setCameraHost] = useState("");
@@ -65,17 +122,31 @@ export function SettingsPanel({ onClose }: Props) {
   // Coding
   const [workDir, setWorkDir] = useState("");
   const [trustMode, setTrustMode] = useState("prompt");
+  const [grants, setGrants] = useState<Grant[]>([]);
+  const [checkpoints, setCheckpoints] = useState<Checkpoint[]>([]);
+  const [checkpointDiff, setCheckpointDiff] = useState<{ id: string; text: string } | null>(
+    null,
+  );
 
   // Robot
+  const [robot, setRobot] = useState("tuya");
   const [tuyaRegion, setTuyaRegion] = useState("us");
   const [tuyaKey, setTuyaKey] = useState("");
   const [tuyaSecret, setTuyaSecret] = useState("");
   const [tuyaDeviceId, setTuyaDeviceId] = useState("");
+  const [tuyaLocalIp, setTuyaLocalIp] = useState("");
+  const [tuyaLocalKey, setTuyaLocalKey] = useState("");
+  const [tuyaVersion, setTuyaVersion] = useState("3.3");
+  const [tuyaDirectionDp, setTuyaDirectionDp] = useState("");
+  const [robotUrl, setRobotUrl] = useState("");
+  const [robotToken, setRobotToken] = useState("");
+  const [simMap, setSimMap] = useState("");
 
   const [saving, setSaving] = useState(false);
   const [saved, setSaved] = useState(false);
   const [error, setError] = useState("");
   const [loading, setLoading] = useState(true);
+  const [loaded, setLoaded] = useState<Config | null>(null);
 
   useEffect(() => {
     Promise.all([
@@ -83,6 +154,7 @@ export function SettingsPanel({ onClose }: Props) {
       invoke<string>("get_me_md"),
     ])
       .then(([cfg, md]) => {
+        setLoaded(cfg);
         setPlatform(cfg.platform);
         setApiKey(cfg.api_key);
         setModel(cfg.model);
@@ -90,21 +162,85 @@ export function SettingsPanel({ onClose }: Props) {
         setCompanionName(cfg.companion_name);
         setElevenlabsKey(cfg.tts.elevenlabs_api_key);
         setVoiceId(cfg.tts.voice_id);
+        setTtsEngine(cfg.tts.engine || "elevenlabs");
+        setOpenaiTtsKey(cfg.tts.openai_api_key ?? "");
+        setVoicevoxUrl(cfg.tts.voicevox_url ?? "");
+        setPiperModel(cfg.tts.piper_model ?? "");
         setCameraHost(cfg.camera.host);
         setCameraUser(cfg.camera.username);
         setCameraPass(cfg.camera.password);
         setWorkDir(cfg.coding.work_dir);
         setTrustMode((cfg.coding.trust_mode as string) || "prompt");
+        setRobot(cfg.mobility.robot || "tuya");
         setTuyaRegion(cfg.mobility.tuya_region || "us");
         setTuyaKey(cfg.mobility.tuya_api_key);
         setTuyaSecret(cfg.mobility.tuya_api_secret);
         setTuyaDeviceId(cfg.mobility.tuya_device_id);
+        setTuyaLocalIp(cfg.mobility.tuya_local_ip ?? "");
+        setTuyaLocalKey(cfg.mobility.tuya_local_key ?? "");
+        setTuyaVersion(cfg.mobility.tuya_protocol_version || "3.3");
+        setTuyaDirectionDp(cfg.mobility.tuya_direction_dp ?? "");
+        setRobotUrl(cfg.mobility.http_url ?? "");
+        setRobotToken(cfg.mobility.http_token ?? "");
+        setSimMap(cfg.mobility.sim_map ?? "");
         setMeMd(md);
         setLoading(false);
       })
       .catch(() => setLoading(false));
+    // Fails while a turn is running; the list just stays empty then.
+    invoke<Grant[]>("list_permission_grants")
+      .then(setGrants)
+      .catch(() => {});
+    invoke<Checkpoint[]>("list_checkpoints")
+      .then(setCheckpoints)
+      .catch(() => {});
   }, []);
 
+  async function toggleCheckpointDiff(id: string) {
+    if (checkpointDiff?.id === id) {
+      setCheckpointDiff(null);
+      return;
+    }
+    setError("");
+    try {
+      const text = await invoke<string>("diff_checkpoint", { turn: id });
+      setCheckpointDiff({ id, text });
+    } catch (e) {
+      setError(String(e));
+    }
+  }
+
+  async function restoreCheckpoint(cp: Checkpoint) {
+    if (!window.confirm(`${cp.started} のターンで変更されたファイルを元に戻しますか？`)) return;
+    setError("");
+    try {
+      const done = await invoke<string[]>("restore_checkpoint", { turn: cp.id });
+      setCheckpointDiff({ id: cp.id, text: done.join("\n") });
+      setCheckpoints(await invoke<Checkpoint[]>("list_checkpoints"));
+    } catch (e) {
+      setError(String(e));
+    }
+  }
+
+  async function revokeGrant(grant: Grant) {
+    setError("");
+    try {
+      await invoke("revoke_permission_grant", { grant });
+      setGrants((gs) => gs.filter((g) => g !== grant));
+      if (grant.scope === "always" && loaded) {
+        // Keep a later save from writing the revoked rule back.
+        const rules = [...loaded.coding.rules];
+        const i = rules.findIndex(
+          (r) => r.allow === grant.allow && r.tool === grant.tool && r.pattern === grant.pattern,
+        );
+        if (i >= 0) rules.splice(i, 1);
+        setLoaded({ ...loaded, coding: { ...loaded.coding, rules } });
+      }
+    } catch (e) {
+      setError(String(e));
+    }
+  }
+
   async function save() {
     setSaving(true);
     setError("");
@@ -114,6 +250,7 @@ export function SettingsPanel({ onClose }: Props) {
       }
       await invoke("save_config", {
         config: {
+          ...loaded,
           platform,
           api_key: apiKey,
           model,
@@ -125,14 +262,36 @@ export function SettingsPanel({ onClose }: Props) {
             password: cameraPass,
             onvif_port: 2020,
           },
-          tts: { elevenlabs_api_key: elevenlabsKey, voice_id: voiceId },
+          tts: {
+            ...loaded?.tts,
+            engine: ttsEngine,
+            elevenlabs_api_key: elevenlabsKey,
+            voice_id: voiceId,
+            openai_api_key: openaiTtsKey,
+            voicevox_url: voicevoxUrl,
+            piper_model: piperModel,
+          },
           mobility: {
+            ...loaded?.mobility,
+            robot,
             tuya_region: tuyaRegion,
             tuya_api_key: tuyaKey,
             tuya_api_secret: tuyaSecret,
             tuya_device_id: tuyaDeviceId,
+            tuya_local_ip: tuyaLocalIp,
+            tuya_local_key: tuyaLocalKey,
+            tuya_protocol_version: tuyaVersion,
+            tuya_direction_dp: tuyaDirectionDp,
+            http_url: robotUrl,
+            http_token: robotToken,
+            sim_map: simMap,
+          },
+          coding: {
+            ...loaded?.coding,
+            work_dir: workDir,
+            trust_mode: trustMode,
+            rules: loaded?.coding.rules ?? [],
           },
-          coding: { work_dir: workDir, trust_mode: trustMode, rules: [] },
         },
       });
       setSaved(true);
@@ -277,34 +436,100 @@ export function SettingsPanel({ onClose }: Props) {
 
           {tab === "voice" && (
             <section className="settings-section">
-              <h3>音声（ElevenLabs）</h3>
-              <label className="field">
-                ElevenLabs APIキー
-                <input
-                  type="password"
-                  placeholder="sk_..."
-                  value={elevenlabsKey}
-                  onChange={(e) => setElevenlabsKey(e.target.value)}
-                />
-              </label>
-              <label className="field">
-                ボイスID
-                <input
-                  type="text"
-                  placeholder="cgSgspJ2msm6clMCkdW9"
-                  value={voiceId}
-                  onChange={(e) => setVoiceId(e.target.value)}
-                />
-              </label>
+              <h3>音声</h3>
+              <div className="platform-list">
+                {TTS_ENGINES.map((e) => (
+                  <label
+                    key={e.id}
+                    className={`platform-item ${ttsEngine === e.id ? "selected" : ""}`}
+                  >
+                    <input
+                      type="radio"
+                      name="tts-engine"
+                      value={e.id}
+                      checked={ttsEngine === e.id}
+                      onChange={() => setTtsEngine(e.id)}
+                    />
+                    <div>
+                      <strong>{e.label}</strong>
+                      <span className="sub">{e.sub}</span>
+                    </div>
+                  </label>
+                ))}
+              </div>
+
+              {ttsEngine === "elevenlabs" && (
+                <>
+                  <label className="field">
+                    ElevenLabs APIキー
+                    <input
+                      type="password"
+                      placeholder="sk_..."
+                      value={elevenlabsKey}
+                      onChange={(e) => setElevenlabsKey(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    ボイスID
+                    <input
+                      type="text"
+                      placeholder="cgSgspJ2msm6clMCkdW9"
+                      value={voiceId}
+                      onChange={(e) => setVoiceId(e.target.value)}
+                    />
+                  </label>
+                  <p className="hint">
+                    <a
+                      href="https://elevenlabs.io/app/voice-library"
+                      target="_blank"
+                      rel="noreferrer"
+                    >
+                      ElevenLabs Voice Library
+                    </a>{" "}
+                    でIDを確認できます
+                  </p>
+                </>
+              )}
+
+              {ttsEngine === "openai" && (
+                <label className="field">
+                  OpenAI APIキー
+                  <input
+                    type="password"
+                    placeholder="sk-..."
+                    value={openaiTtsKey}
+                    onChange={(e) => setOpenaiTtsKey(e.target.value)}
+                  />
+                </label>
+              )}
+
+              {ttsEngine === "voicevox" && (
+                <label className="field">
+                  VOICEVOX エンジンURL
+                  <input
+                    type="text"
+                    placeholder="http://127.0.0.1:50021"
+                    value={voicevoxUrl}
+                    onChange={(e) => setVoicevoxUrl(e.target.value)}
+                  />
+                </label>
+              )}
+
+              {ttsEngine === "piper" && (
+                <label className="field">
+                  Piper 音声モデル（.onnx）
+                  <input
+                    type="text"
+                    placeholder="/home/user/piper/ja_JP-voice.onnx"
+                    value={piperModel}
+                    onChange={(e) => setPiperModel(e.target.value)}
+                  />
+                </label>
+              )}
+
               <p className="hint">
-                <a
-                  href="https://elevenlabs.io/app/voice-library"
-                  target="_blank"
-                  rel="noreferrer"
-                >
-                  ElevenLabs Voice Library
-                </a>{" "}
-                でIDを確認できます
+                言語ごとのエンジン・声・スタイルは config.toml の{" "}
+                <code>[tts.languages.ja]</code> などで設定できます
               </p>
             </section>
           )}
@@ -395,51 +620,211 @@ export function SettingsPanel({ onClose }: Props) {
                   ))}
                 </div>
               </div>
+
+              <div className="field" style={{ marginTop: "1rem" }}>
+                <span>記憶された許可</span>
+                {grants.length === 0 ? (
+                  <p className="hint">「今回のセッション中は許可」「常に許可」で記憶した許可はありません。</p>
+                ) : (
+                  <ul className="grant-list">
+                    {grants.map((g, i) => (
+                      <li key={i} className="grant-item">
+                        <span className="grant-scope">
+                          {g.scope === "session" ? "セッション" : "常に"}
+                          {g.allow ? "許可" : "拒否"}
+                        </span>
+                        <code>
+                          {g.tool}: {g.pattern}
+                        </code>
+                        <button className="btn-deny" onClick={() => revokeGrant(g)}>
+                          取り消す
+                        </button>
+                      </li>
+                    ))}
+                  </ul>
+                )}
+              </div>
+
+              <div className="field" style={{ marginTop: "1rem" }}>
+                <span>チェックポイント（ファイル変更の取り消し）</span>
+                {checkpoints.length === 0 ? (
+                  <p className="hint">まだファイルの変更はありません。</p>
+                ) : (
+                  <ul className="grant-list">
+                    {checkpoints.map((cp) => (
+                      <li key={cp.id} className="checkpoint-item">
+                        <div className="grant-item">
+                          <span className="grant-scope">{cp.started}</span>
+                          <code title={cp.files.map((f) => f.path).join("\n")}>
+                            {cp.prompt || cp.id}（{cp.files.length} ファイル）
+                          </code>
+                          <button className="btn-allow" onClick={() => toggleCheckpointDiff(cp.id)}>
+                            差分
+                          </button>
+                          <button className="btn-deny" onClick={() => restoreCheckpoint(cp)}>
+                            元に戻す
+                          </button>
+                        </div>
+                        {checkpointDiff?.id === cp.id && (
+                          <CodeBlock code={checkpointDiff.text || "(変更なし)"} language="diff" />
+                        )}
+                      </li>
+                    ))}
+                  </ul>
+                )}
+              </div>
             </section>
           )}
 
           {tab === "robot" && (
             <section className="settings-section">
-              <h3>ロボット（Tuya / 掃除機）</h3>
-              <label className="field">
-                リージョン
-                <input
-                  type="text"
-                  placeholder="us"
-                  value={tuyaRegion}
-                  onChange={(e) => setTuyaRegion(e.target.value)}
-                />
-              </label>
-              <label className="field">
-                Tuya APIキー（Client ID）
-                <input
-                  type="password"
-                  value={tuyaKey}
-                  onChange={(e) => setTuyaKey(e.target.value)}
-                />
-              </label>
-              <label className="field">
-                Tuya APIシークレット
-                <input
-                  type="password"
-                  value={tuyaSecret}
-                  onChange={(e) => setTuyaSecret(e.target.value)}
-                />
-              </label>
-              <label className="field">
-                デバイスID
-                <input
-                  type="text"
-                  value={tuyaDeviceId}
-                  onChange={(e) => setTuyaDeviceId(e.target.value)}
-                />
-              </label>
-              <p className="hint">
-                <a href="https://iot.tuya.com" target="_blank" rel="noreferrer">
-                  Tuya IoT Platform
-                </a>{" "}
-                で取得できます
-              </p>
+              <h3>ロボット</h3>
+              <div className="platform-list">
+                {ROBOTS.map((r) => (
+                  <label
+                    key={r.id}
+                    className={`platform-item ${robot === r.id ? "selected" : ""}`}
+                  >
+                    <input
+                      type="radio"
+                      name="robot"
+                      value={r.id}
+                      checked={robot === r.id}
+                      onChange={() => setRobot(r.id)}
+                    />
+                    <div>
+                      <strong>{r.label}</strong>
+                      <span className="sub">{r.sub}</span>
+                    </div>
+                  </label>
+                ))}
+              </div>
+
+              {robot === "tuya" && (
+                <>
+                  <label className="field">
+                    リージョン
+                    <input
+                      type="text"
+                      placeholder="us"
+                      value={tuyaRegion}
+                      onChange={(e) => setTuyaRegion(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    Tuya APIキー（Client ID）
+                    <input
+                      type="password"
+                      value={tuyaKey}
+                      onChange={(e) => setTuyaKey(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    Tuya APIシークレット
+                    <input
+                      type="password"
+                      value={tuyaSecret}
+                      onChange={(e) => setTuyaSecret(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    デバイスID
+                    <input
+                      type="text"
+                      value={tuyaDeviceId}
+                      onChange={(e) => setTuyaDeviceId(e.target.value)}
+                    />
+                  </label>
+                  <p className="hint">
+                    <a href="https://iot.tuya.com" target="_blank" rel="noreferrer">
+                      Tuya IoT Platform
+                    </a>{" "}
+                    で取得できます
+                  </p>
+
+                  <h3>LAN直接制御（任意）</h3>
+                  <label className="field">
+                    ロボットのIPアドレス
+                    <input
+                      type="text"
+                      placeholder="192.168.1.20"
+                      value={tuyaLocalIp}
+                      onChange={(e) => setTuyaLocalIp(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    ローカルキー（16文字）
+                    <input
+                      type="password"
+                      value={tuyaLocalKey}
+                      onChange={(e) => setTuyaLocalKey(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    プロトコルバージョン
+                    <select value={tuyaVersion} onChange={(e) => setTuyaVersion(e.target.value)}>
+                      <option value="3.3">3.3</option>
+                      <option value="3.4">3.4</option>
+                      <option value="3.5">3.5</option>
+                    </select>
+                  </label>
+                  <label className="field">
+                    移動のDP番号
+                    <input
+                      type="text"
+                      placeholder="4"
+                      value={tuyaDirectionDp}
+                      onChange={(e) => setTuyaDirectionDp(e.target.value)}
+                    />
+                  </label>
+                  <p className="hint">
+                    IPとローカルキーを設定すると、移動コマンドをクラウドを経由せず直接送ります。
+                    つながらない時はクラウドAPIに切り替えます。
+                  </p>
+                </>
+              )}
+
+              {robot === "http" && (
+                <>
+                  <label className="field">
+                    ロボットのURL
+                    <input
+                      type="text"
+                      placeholder="http://192.168.1.30:8080"
+                      value={robotUrl}
+                      onChange={(e) => setRobotUrl(e.target.value)}
+                    />
+                  </label>
+                  <label className="field">
+                    トークン（任意）
+                    <input
+                      type="password"
+                      value={robotToken}
+                      onChange={(e) => setRobotToken(e.target.value)}
+                    />
+                  </label>
+                  <p className="hint">
+                    POST /drive {"{\"direction\": \"forward\"}"} と GET /status に応答するロボットを操作します
+                  </p>
+                </>
+              )}
+
+              {robot === "sim" && (
+                <>
+                  <label className="field">
+                    間取り図ファイル（任意）
+                    <input
+                      type="text"
+                      placeholder="/path/to/floor.txt"
+                      value={simMap}
+                      onChange={(e) => setSimMap(e.target.value)}
+                    />
+                  </label>
+                  <p className="hint">
+                    # が壁、D が充電台の文字の間取り図（1文字 = 25cm）。空欄なら内蔵の部屋を使います
+                  </p>
+                </>
+              )}
             </section>
           )}
         </div>